@@ -0,0 +1,258 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! User-defined potentials, using a mathematical expression for the energy
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use caldyn::{Context, Expr};
+use log_once::warn_once;
+
+use crate::{AnglePotential, BondPotential, PairPotential, Potential};
+use crate::{AngleForces, AngleGeometry};
+use crate::math::integrate;
+
+/// Number of intervals used for the numerical integration of tail corrections
+const TAIL_INTERVALS: usize = 2000;
+
+/// Relative tolerance on the agreement between the numerical derivatives
+/// computed with two different steps
+const DERIVATIVE_TOLERANCE: f64 = 1e-5;
+
+/// Potential defined by an user-provided mathematical expression.
+///
+/// The expression is written in terms of a single `variable` (for example
+/// `r` for pair and bond potentials, or `theta` for angles) and any number of
+/// named parameters. Numbers in the expression are in internal units, while
+/// the parameters can be converted from any unit before creating the
+/// potential. The usual operators (`+`, `-`, `*`, `/`, and `^` for
+/// exponentiation) and functions (`exp`, `sqrt`, `sin`, `cos`, ...) are
+/// available.
+///
+/// The force is computed by differentiating the energy numerically, using a
+/// fourth order central finite difference. The derivative is computed with
+/// two different steps, and a warning is emitted if the two values do not
+/// agree, for example if the energy is not smooth. These potentials are
+/// evaluated by
+/// interpreting the expression, which is slower than the built-in potentials.
+/// For pair potentials, the [`TableComputation`] can be used to remove this
+/// overhead.
+///
+/// Tail corrections are computed by numerical integration, and are only
+/// meaningful if the energy decays faster than `r^-3`.
+///
+/// [`TableComputation`]: struct.TableComputation.html
+///
+/// # Examples
+///
+/// ```
+/// # use std::collections::BTreeMap;
+/// use lumol_core::energy::{CustomPotential, Potential};
+///
+/// let mut parameters = BTreeMap::new();
+/// parameters.insert(String::from("k"), 4.0);
+/// parameters.insert(String::from("r0"), 1.0);
+///
+/// let potential = CustomPotential::new("k / 2 * (r - r0)^2", "r", parameters).unwrap();
+/// assert_eq!(potential.energy(1.5), 0.5);
+/// assert!(f64::abs(potential.force(1.5) + 2.0) < 1e-8);
+/// ```
+#[derive(Clone)]
+pub struct CustomPotential {
+    /// Parsed energy expression
+    expr: Arc<Expr>,
+    /// Original energy expression
+    expression: String,
+    /// Name of the variable in the expression
+    variable: String,
+    /// Values of the parameters in the expression
+    parameters: BTreeMap<String, f64>,
+}
+
+impl CustomPotential {
+    /// Create a new custom potential from the energy `expression`, depending
+    /// on the given `variable` and `parameters`. This function returns an
+    /// error if the expression can not be parsed, or if it contains a name
+    /// which is neither the variable nor one of the parameters.
+    ///
+    /// # Panics
+    ///
+    /// If one of the parameters has the same name as the variable.
+    pub fn new(
+        expression: &str,
+        variable: &str,
+        parameters: BTreeMap<String, f64>,
+    ) -> Result<CustomPotential, caldyn::Error> {
+        assert!(
+            !parameters.contains_key(variable),
+            "the variable '{}' can not also be a parameter in custom potential", variable
+        );
+
+        let potential = CustomPotential {
+            expr: Arc::new(Expr::parse(expression)?),
+            expression: expression.into(),
+            variable: variable.into(),
+            parameters: parameters,
+        };
+        // Check that all the names in the expression are known
+        let _ = potential.evaluate(1.0)?;
+        return Ok(potential);
+    }
+
+    /// Get the energy expression of this potential
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Get the name of the variable in the expression
+    pub fn variable(&self) -> &str {
+        &self.variable
+    }
+
+    /// Evaluate the expression with the variable set to `x`
+    fn evaluate(&self, x: f64) -> Result<f64, caldyn::Error> {
+        let mut context = Context::new();
+        context.set_query(move |name| {
+            if name == self.variable {
+                Some(x)
+            } else {
+                self.parameters.get(name).cloned()
+            }
+        });
+        return self.expr.eval(&context);
+    }
+
+    /// Numerical integration of `r^n V(r)` between `cutoff` and infinity, for
+    /// a function `V` decaying fast enough. This uses the change of variable
+    /// `u = cutoff / r` and a Gauss-Legendre quadrature on `(0, 1]`, which
+    /// does not need the value of the integrand at `u = 0`.
+    fn tail_integral<F: Fn(f64) -> f64>(cutoff: f64, n: i32, function: F) -> f64 {
+        let integrand = |u: f64| {
+            let r = cutoff / u;
+            f64::powi(r, n) * function(r) * cutoff / (u * u)
+        };
+        let integral = integrate(integrand, 0.0, 1.0, TAIL_INTERVALS);
+
+        if integral.is_finite() {
+            return integral;
+        } else {
+            warn_once!("tail correction is not finite for custom potential, it will be set to zero");
+            return 0.0;
+        }
+    }
+}
+
+impl Potential for CustomPotential {
+    fn energy(&self, x: f64) -> f64 {
+        self.evaluate(x).expect("failed to evaluate custom potential")
+    }
+
+    fn force(&self, x: f64) -> f64 {
+        let h = 1e-4 * f64::max(f64::abs(x), 1.0);
+        let energy = |delta: f64| self.energy(x + delta);
+        let (minus_2, minus_1, minus_half) = (energy(-2.0 * h), energy(-h), energy(-0.5 * h));
+        let (plus_half, plus_1, plus_2) = (energy(0.5 * h), energy(h), energy(2.0 * h));
+
+        // Fourth order central finite differences with steps h and h / 2
+        let coarse = (-plus_2 + 8.0 * plus_1 - 8.0 * minus_1 + minus_2) / (12.0 * h);
+        let fine = (-plus_1 + 8.0 * plus_half - 8.0 * minus_half + minus_1) / (6.0 * h);
+
+        // Rounding errors in the finite differences are of the order of
+        // ε |V| / h, and should not trigger the warning
+        let rounding = 1e3 * f64::EPSILON * (f64::abs(plus_2) + f64::abs(minus_2)) / h;
+        if f64::abs(coarse - fine) > DERIVATIVE_TOLERANCE * f64::abs(fine) + rounding {
+            warn_once!(
+                "the numerical derivative of custom potential '{}' depends on the step, forces might be wrong",
+                self.expression
+            );
+        }
+        return -fine;
+    }
+}
+
+impl PairPotential for CustomPotential {
+    fn tail_energy(&self, cutoff: f64) -> f64 {
+        CustomPotential::tail_integral(cutoff, 2, |r| self.energy(r))
+    }
+
+    fn tail_virial(&self, cutoff: f64) -> f64 {
+        CustomPotential::tail_integral(cutoff, 3, |r| self.force(r))
+    }
+}
+
+impl BondPotential for CustomPotential {}
+impl AnglePotential for CustomPotential {
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {
+        self.energy(geometry.theta)
+    }
+
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+        AngleForces {
+            theta: self.force(geometry.theta),
+            r_ij: 0.0,
+            r_jk: 0.0,
+            r_ik: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LennardJones;
+    use approx::assert_relative_eq;
+
+    fn lennard_jones() -> CustomPotential {
+        let mut parameters = BTreeMap::new();
+        let _ = parameters.insert(String::from("sigma"), 2.0);
+        let _ = parameters.insert(String::from("epsilon"), 0.8);
+        let expression = "4 * epsilon * ((sigma / r)^12 - (sigma / r)^6)";
+        return CustomPotential::new(expression, "r", parameters).unwrap();
+    }
+
+    #[test]
+    fn energy_and_force() {
+        let custom = lennard_jones();
+        let lj = LennardJones { sigma: 2.0, epsilon: 0.8 };
+
+        for &r in &[1.9, 2.3, 3.0, 5.5] {
+            assert_relative_eq!(custom.energy(r), lj.energy(r), max_relative = 1e-12);
+            assert_relative_eq!(custom.force(r), lj.force(r), max_relative = 1e-7);
+        }
+    }
+
+    #[test]
+    fn tail_corrections() {
+        let custom = lennard_jones();
+        let lj = LennardJones { sigma: 2.0, epsilon: 0.8 };
+
+        assert_relative_eq!(custom.tail_energy(5.0), lj.tail_energy(5.0), max_relative = 1e-6);
+        assert_relative_eq!(custom.tail_virial(5.0), lj.tail_virial(5.0), max_relative = 1e-6);
+    }
+
+    #[test]
+    fn angle() {
+        let mut parameters = BTreeMap::new();
+        let _ = parameters.insert(String::from("k"), 3.0);
+        let custom = CustomPotential::new("k * (1 - cos(theta))", "theta", parameters).unwrap();
+
+        assert_relative_eq!(custom.energy(0.4), 3.0 * (1.0 - f64::cos(0.4)), max_relative = 1e-12);
+        assert_relative_eq!(custom.force(0.4), -3.0 * f64::sin(0.4), max_relative = 1e-8);
+    }
+
+    #[test]
+    fn errors() {
+        let parameters = BTreeMap::new();
+        assert!(CustomPotential::new("3 * r +", "r", parameters.clone()).is_err());
+        assert!(CustomPotential::new("k * r", "r", parameters.clone()).is_err());
+        assert!(CustomPotential::new("3 * x", "r", parameters).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "the variable 'r' can not also be a parameter in custom potential")]
+    fn variable_as_parameter() {
+        let mut parameters = BTreeMap::new();
+        let _ = parameters.insert(String::from("r"), 3.0);
+        let _ = CustomPotential::new("3 * r", "r", parameters);
+    }
+}
@@ -1,11 +1,13 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
 
-use crate::{AnglePotential, BondPotential, DihedralPotential, PairPotential};
-use crate::Potential;
+use crate::{AnglePotential, BondPotential, DihedralPotential, ImproperPotential, PairPotential};
+use crate::{AngleForces, AngleGeometry, Potential};
 
 use crate::math::erfc;
 
+use log_once::warn_once;
+
 use std::f64::consts::PI;
 
 /// No-op potential.
@@ -46,8 +48,22 @@ impl PairPotential for NullPotential {
     }
 }
 impl BondPotential for NullPotential {}
-impl AnglePotential for NullPotential {}
+impl AnglePotential for NullPotential {
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {
+        self.energy(geometry.theta)
+    }
+
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+        AngleForces {
+            theta: self.force(geometry.theta),
+            r_ij: 0.0,
+            r_jk: 0.0,
+            r_ik: 0.0,
+        }
+    }
+}
 impl DihedralPotential for NullPotential {}
+impl ImproperPotential for NullPotential {}
 
 /// Lennard-Jones potential.
 ///
@@ -156,8 +172,22 @@ impl PairPotential for Harmonic {
 }
 
 impl BondPotential for Harmonic {}
-impl AnglePotential for Harmonic {}
+impl AnglePotential for Harmonic {
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {
+        self.energy(geometry.theta)
+    }
+
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+        AngleForces {
+            theta: self.force(geometry.theta),
+            r_ij: 0.0,
+            r_jk: 0.0,
+            r_ik: 0.0,
+        }
+    }
+}
 impl DihedralPotential for Harmonic {}
+impl ImproperPotential for Harmonic {}
 
 /// Cosine harmonic potential.
 ///
@@ -206,13 +236,189 @@ impl Potential for CosineHarmonic {
     }
 }
 
-impl AnglePotential for CosineHarmonic {}
+impl AnglePotential for CosineHarmonic {
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {
+        self.energy(geometry.theta)
+    }
+
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+        AngleForces {
+            theta: self.force(geometry.theta),
+            r_ij: 0.0,
+            r_jk: 0.0,
+            r_ik: 0.0,
+        }
+    }
+}
 impl DihedralPotential for CosineHarmonic {}
 
+/// Urey-Bradley potential.
+///
+/// This potential is used for angles in the CHARMM force field. It combines an
+/// harmonic potential on the angle value with an harmonic potential on the
+/// distance between the first and last particles in the angle (the 1-3
+/// distance):
+///
+/// $$ V(\theta, r_{ik}) = \frac{1}{2} k (\theta - x_0)^2
+///                      + \frac{1}{2} k_{UB} (r_{ik} - r_{UB})^2 $$
+///
+/// where $x_0$ and $r_{UB}$ are the equilibrium values, and $k$ and $k_{UB}$
+/// the elastic constants.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{AnglePotential, AngleGeometry};
+/// # use lumol_core::energy::UreyBradley;
+/// let potential = UreyBradley { k: 100.0, x0: 2.0, k_ub: 50.0, r_ub: 2.5 };
+/// let geometry = AngleGeometry { theta: 3.0, r_ij: 1.0, r_jk: 1.0, r_ik: 3.5 };
+/// assert_eq!(potential.angle_energy(&geometry), 75.0);
+///
+/// let forces = potential.angle_forces(&geometry);
+/// assert_eq!(forces.theta, -100.0);
+/// assert_eq!(forces.r_ik, -50.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct UreyBradley {
+    /// Spring constant for the angle
+    pub k: f64,
+    /// Equilibrium value of the angle
+    pub x0: f64,
+    /// Spring constant for the 1-3 distance
+    pub k_ub: f64,
+    /// Equilibrium value of the 1-3 distance
+    pub r_ub: f64,
+}
+
+impl AnglePotential for UreyBradley {
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {
+        let dtheta = geometry.theta - self.x0;
+        let dr = geometry.r_ik - self.r_ub;
+        0.5 * self.k * dtheta * dtheta + 0.5 * self.k_ub * dr * dr
+    }
+
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+        AngleForces {
+            theta: self.k * (self.x0 - geometry.theta),
+            r_ij: 0.0,
+            r_jk: 0.0,
+            r_ik: self.k_ub * (self.r_ub - geometry.r_ik),
+        }
+    }
+}
+
+/// Bond-bond cross term potential.
+///
+/// This potential is used in class II force fields (COMPASS, PCFF, ...) to
+/// couple the lengths of the two bonds in an angle:
+///
+/// $$ V(r_{ij}, r_{jk}) = k (r_{ij} - r_1) (r_{jk} - r_2) $$
+///
+/// where $r_1$ and $r_2$ are the equilibrium bond lengths, and $k$ the
+/// coupling constant. This potential does not depend on the value of the
+/// angle.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{AnglePotential, AngleGeometry};
+/// # use lumol_core::energy::BondBond;
+/// let potential = BondBond { k: 10.0, r1: 1.0, r2: 1.5 };
+/// let geometry = AngleGeometry { theta: 2.0, r_ij: 1.5, r_jk: 2.0, r_ik: 3.0 };
+/// assert_eq!(potential.angle_energy(&geometry), 2.5);
+///
+/// let forces = potential.angle_forces(&geometry);
+/// assert_eq!(forces.r_ij, -5.0);
+/// assert_eq!(forces.r_jk, -5.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct BondBond {
+    /// Coupling constant
+    pub k: f64,
+    /// Equilibrium length of the bond between the first and second particles
+    pub r1: f64,
+    /// Equilibrium length of the bond between the second and third particles
+    pub r2: f64,
+}
+
+impl AnglePotential for BondBond {
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {
+        self.k * (geometry.r_ij - self.r1) * (geometry.r_jk - self.r2)
+    }
+
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+        AngleForces {
+            theta: 0.0,
+            r_ij: -self.k * (geometry.r_jk - self.r2),
+            r_jk: -self.k * (geometry.r_ij - self.r1),
+            r_ik: 0.0,
+        }
+    }
+}
+
+/// Bond-angle cross term potential.
+///
+/// This potential is used in class II force fields (COMPASS, PCFF, ...) to
+/// couple the value of an angle with the lengths of the two bonds forming
+/// this angle:
+///
+/// $$ V(\theta, r_{ij}, r_{jk}) = (\theta - \theta_0)
+///    \left[k_1 (r_{ij} - r_1) + k_2 (r_{jk} - r_2)\right] $$
+///
+/// where $\theta_0$, $r_1$ and $r_2$ are the equilibrium values, and $k_1$
+/// and $k_2$ the coupling constants.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{AnglePotential, AngleGeometry};
+/// # use lumol_core::energy::BondAngle;
+/// let potential = BondAngle { k1: 10.0, k2: 20.0, r1: 1.0, r2: 1.5, theta0: 2.0 };
+/// let geometry = AngleGeometry { theta: 2.5, r_ij: 1.5, r_jk: 2.0, r_ik: 3.0 };
+/// assert_eq!(potential.angle_energy(&geometry), 7.5);
+///
+/// let forces = potential.angle_forces(&geometry);
+/// assert_eq!(forces.theta, -15.0);
+/// assert_eq!(forces.r_ij, -5.0);
+/// assert_eq!(forces.r_jk, -10.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct BondAngle {
+    /// Coupling constant for the bond between the first and second particles
+    pub k1: f64,
+    /// Coupling constant for the bond between the second and third particles
+    pub k2: f64,
+    /// Equilibrium length of the bond between the first and second particles
+    pub r1: f64,
+    /// Equilibrium length of the bond between the second and third particles
+    pub r2: f64,
+    /// Equilibrium value of the angle
+    pub theta0: f64,
+}
+
+impl AnglePotential for BondAngle {
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {
+        let dtheta = geometry.theta - self.theta0;
+        let bonds = self.k1 * (geometry.r_ij - self.r1) + self.k2 * (geometry.r_jk - self.r2);
+        dtheta * bonds
+    }
+
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+        let dtheta = geometry.theta - self.theta0;
+        let bonds = self.k1 * (geometry.r_ij - self.r1) + self.k2 * (geometry.r_jk - self.r2);
+        AngleForces {
+            theta: -bonds,
+            r_ij: -self.k1 * dtheta,
+            r_jk: -self.k2 * dtheta,
+            r_ik: 0.0,
+        }
+    }
+}
+
 /// Torsion potential.
 ///
-/// This potential is intended for use with dihedral angles, using a custom
-/// periodicity and multiple minima.
+/// This potential is intended for use with dihedral angles and improper
+/// dihedral angles, using a custom periodicity and multiple minima.
 ///
 /// $$ V(x) = k (1 + \cos(n x - \delta))$$
 ///
@@ -256,6 +462,111 @@ impl Potential for Torsion {
 }
 
 impl DihedralPotential for Torsion {}
+impl ImproperPotential for Torsion {}
+
+/// Ryckaert-Bellemans potential.
+///
+/// This potential is intended for use with dihedral angles in alkane chains,
+/// as a polynomial in the cosine of the angle:
+///
+/// $$ V(\phi) = \sum_{n=0}^5 c_n \cos^n(\phi - \pi) $$
+///
+/// where $c_n$ are the energetic constants. Following the polymer convention,
+/// the angle $\phi - \pi$ is zero in the trans conformation.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::Potential;
+/// # use lumol_core::energy::RyckaertBellemans;
+/// # use std::f64::consts::PI;
+/// let potential = RyckaertBellemans { c: [9.28, 12.16, -13.12, -3.06, 26.24, -31.5] };
+/// assert!(potential.energy(PI).abs() < 1e-12);
+/// assert!(potential.force(PI).abs() < 1e-12);
+/// ```
+#[derive(Clone, Copy)]
+pub struct RyckaertBellemans {
+    /// Energetic constants, for the powers 0 to 5 of the cosine
+    pub c: [f64; 6],
+}
+
+impl Potential for RyckaertBellemans {
+    fn energy(&self, phi: f64) -> f64 {
+        let cos = f64::cos(phi - PI);
+        let mut energy = 0.0;
+        let mut cos_n = 1.0;
+        for c in &self.c {
+            energy += c * cos_n;
+            cos_n *= cos;
+        }
+        energy
+    }
+
+    fn force(&self, phi: f64) -> f64 {
+        let (sin, cos) = f64::sin_cos(phi - PI);
+        let mut force = 0.0;
+        let mut cos_n = 1.0;
+        for (n, c) in self.c.iter().enumerate().skip(1) {
+            force += (n as f64) * c * cos_n;
+            cos_n *= cos;
+        }
+        force * sin
+    }
+}
+
+impl DihedralPotential for RyckaertBellemans {}
+
+/// OPLS torsion potential.
+///
+/// This potential is intended for use with dihedral angles, as a Fourier
+/// series with four terms:
+///
+/// $$ V(\phi) = \frac{k_1}{2} (1 + \cos \phi) + \frac{k_2}{2} (1 - \cos 2\phi)
+///             + \frac{k_3}{2} (1 + \cos 3\phi) + \frac{k_4}{2} (1 - \cos 4\phi) $$
+///
+/// where $k_1$, $k_2$, $k_3$ and $k_4$ are the energetic constants.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::Potential;
+/// # use lumol_core::energy::OplsTorsion;
+/// # use std::f64::consts::PI;
+/// let potential = OplsTorsion { k1: 5.0, k2: -1.0, k3: 2.0, k4: 0.0 };
+/// assert_eq!(potential.energy(0.0), 7.0);
+/// assert!(potential.energy(PI).abs() < 1e-12);
+/// ```
+#[derive(Clone, Copy)]
+pub struct OplsTorsion {
+    /// Energetic constant of the first term
+    pub k1: f64,
+    /// Energetic constant of the second term
+    pub k2: f64,
+    /// Energetic constant of the third term
+    pub k3: f64,
+    /// Energetic constant of the fourth term
+    pub k4: f64,
+}
+
+impl Potential for OplsTorsion {
+    fn energy(&self, phi: f64) -> f64 {
+        let e1 = self.k1 * (1.0 + f64::cos(phi));
+        let e2 = self.k2 * (1.0 - f64::cos(2.0 * phi));
+        let e3 = self.k3 * (1.0 + f64::cos(3.0 * phi));
+        let e4 = self.k4 * (1.0 - f64::cos(4.0 * phi));
+        0.5 * (e1 + e2 + e3 + e4)
+    }
+
+    fn force(&self, phi: f64) -> f64 {
+        let f1 = self.k1 * f64::sin(phi);
+        let f2 = -2.0 * self.k2 * f64::sin(2.0 * phi);
+        let f3 = 3.0 * self.k3 * f64::sin(3.0 * phi);
+        let f4 = -4.0 * self.k4 * f64::sin(4.0 * phi);
+        0.5 * (f1 + f2 + f3 + f4)
+    }
+}
+
+impl DihedralPotential for OplsTorsion {}
 
 /// Buckingham potential.
 ///
@@ -433,7 +744,20 @@ impl PairPotential for Morse {
 }
 
 impl BondPotential for Morse {}
-impl AnglePotential for Morse {}
+impl AnglePotential for Morse {
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {
+        self.energy(geometry.theta)
+    }
+
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+        AngleForces {
+            theta: self.force(geometry.theta),
+            r_ij: 0.0,
+            r_jk: 0.0,
+            r_ik: 0.0,
+        }
+    }
+}
 impl DihedralPotential for Morse {}
 
 /// Gaussian potential.
@@ -595,12 +919,256 @@ impl PairPotential for Mie {
     }
 }
 
+/// Finitely extensible nonlinear elastic (FENE) bond potential.
+///
+/// $$ V(r) = - \frac{1}{2} k R_0^2 \ln \left[ 1 - \left(\frac r R_0 \right)^2 \right] $$
+///
+/// where $k$ is the spring constant, and $R_0$ the maximal extension of the
+/// bond. This potential is usually combined with the purely repulsive
+/// [`Wca`](struct.Wca.html) potential in bead-spring polymer models such as the
+/// Kremer-Grest model [Kremer1990].
+///
+/// The energy diverges when the bond length goes to the maximal extension.
+/// For bonds stretched beyond the maximal extension, the energy is infinite
+/// (so that Monte Carlo moves creating such bonds are rejected) and a warning
+/// is emitted. The force is infinite and attractive, and computing the forces
+/// of a system containing such a bond panics with an error naming the bond.
+/// This usually means that the time step is too large, or that the initial
+/// configuration is invalid.
+///
+/// [Kremer1990]: Kremer, K. & Grest, G. S. J. Chem. Phys. 92, 5057 (1990).
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::Potential;
+/// # use lumol_core::energy::Fene;
+/// let potential = Fene { k: 30.0, r0: 1.5 };
+/// assert_eq!(potential.energy(0.0), 0.0);
+/// assert_eq!(potential.energy(1.0), 19.837799940446516);
+///
+/// assert_eq!(potential.force(1.0), -54.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct Fene {
+    /// Spring constant
+    pub k: f64,
+    /// Maximal extension of the bond
+    pub r0: f64,
+}
+
+impl Potential for Fene {
+    fn energy(&self, r: f64) -> f64 {
+        if r >= self.r0 {
+            warn_once!("FENE bond overstretched beyond the maximal extension, energy is infinite");
+            return f64::INFINITY;
+        }
+        let x = r / self.r0;
+        -0.5 * self.k * self.r0 * self.r0 * f64::ln(1.0 - x * x)
+    }
+
+    fn force(&self, r: f64) -> f64 {
+        if r >= self.r0 {
+            // The forces computation panics with the name of the bond
+            return f64::NEG_INFINITY;
+        }
+        let x = r / self.r0;
+        -self.k * r / (1.0 - x * x)
+    }
+}
+
+impl BondPotential for Fene {}
+
+/// Weeks-Chandler-Andersen (WCA) potential.
+///
+/// This is the purely repulsive part of the Lennard-Jones potential, truncated
+/// at its minimum and shifted up to be zero there [Weeks1971]:
+///
+/// $$ V(r) = \begin{cases}
+///     4 \epsilon \left[ \left(\frac \sigma r \right)^{12} -
+///     \left(\frac \sigma r \right)^6 \right] + \epsilon & r < 2^{1/6} \sigma \\\\
+///     0 & r \geq 2^{1/6} \sigma
+/// \end{cases} $$
+///
+/// where $\sigma$ is the distance constant, and $\epsilon$ the energetic
+/// constant.
+///
+/// [Weeks1971]: Weeks, J. D., Chandler, D. & Andersen, H. C. J. Chem. Phys. 54, 5237 (1971).
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::Potential;
+/// # use lumol_core::energy::Wca;
+/// let potential = Wca { sigma: 2.0, epsilon: 10.0 };
+/// assert_eq!(potential.energy(2.0), 10.0);
+/// assert_eq!(potential.energy(3.0), 0.0);
+///
+/// assert_eq!(potential.force(2.0), 120.0);
+/// assert_eq!(potential.force(3.0), 0.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct Wca {
+    /// Distance constant of the potential
+    pub sigma: f64,
+    /// Energy constant of the potential
+    pub epsilon: f64,
+}
+
+impl Wca {
+    /// Get the distance at which the potential goes to zero, corresponding to
+    /// the minimum of the Lennard-Jones potential
+    fn range(&self) -> f64 {
+        f64::powf(2.0, 1.0 / 6.0) * self.sigma
+    }
+
+    /// Get the corresponding Lennard-Jones potential
+    fn lennard_jones(&self) -> LennardJones {
+        LennardJones {
+            sigma: self.sigma,
+            epsilon: self.epsilon,
+        }
+    }
+}
+
+impl Potential for Wca {
+    fn energy(&self, r: f64) -> f64 {
+        if r >= self.range() {
+            0.0
+        } else {
+            self.lennard_jones().energy(r) + self.epsilon
+        }
+    }
+
+    fn force(&self, r: f64) -> f64 {
+        if r >= self.range() {
+            0.0
+        } else {
+            self.lennard_jones().force(r)
+        }
+    }
+}
+
+impl PairPotential for Wca {
+    fn tail_energy(&self, cutoff: f64) -> f64 {
+        let range = self.range();
+        if cutoff >= range {
+            return 0.0;
+        }
+        let lj = self.lennard_jones();
+        let shift = self.epsilon * (range * range * range - cutoff * cutoff * cutoff) / 3.0;
+        return lj.tail_energy(cutoff) - lj.tail_energy(range) + shift;
+    }
+
+    fn tail_virial(&self, cutoff: f64) -> f64 {
+        let range = self.range();
+        if cutoff >= range {
+            return 0.0;
+        }
+        let lj = self.lennard_jones();
+        return lj.tail_virial(cutoff) - lj.tail_virial(range);
+    }
+}
+
+/// Cosine-squared attractive potential.
+///
+/// This is the attractive tail used by Cooke, Kremer and Deserno for
+/// solvent-free membrane models [Cooke2005], usually combined with the
+/// repulsive [`Wca`](struct.Wca.html) potential:
+///
+/// $$ V(r) = \begin{cases}
+///     -\epsilon & r < r_0 \\\\
+///     -\epsilon \cos^2 \left( \frac{\pi (r - r_0)}{2 w} \right) & r_0 \leq r < r_0 + w \\\\
+///     0 & r \geq r_0 + w
+/// \end{cases} $$
+///
+/// where $\epsilon$ is the depth of the potential, $r_0$ the distance at which
+/// the attraction starts to decay, and $w$ the width of the decay.
+///
+/// [Cooke2005]: Cooke, I. R., Kremer, K. & Deserno, M. Phys. Rev. E 72, 011506 (2005).
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::Potential;
+/// # use lumol_core::energy::CosineSquared;
+/// let potential = CosineSquared { epsilon: 2.0, r0: 1.0, width: 1.5 };
+/// assert_eq!(potential.energy(0.5), -2.0);
+/// assert_eq!(potential.energy(2.5), 0.0);
+///
+/// assert_eq!(potential.force(0.5), 0.0);
+/// assert!(potential.force(1.75) < 0.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct CosineSquared {
+    /// Depth of the potential
+    pub epsilon: f64,
+    /// Distance at which the potential starts to decay
+    pub r0: f64,
+    /// Width of the decaying region
+    pub width: f64,
+}
+
+impl CosineSquared {
+    /// Antiderivative of `r^2 V(r)` in the decaying region
+    fn r2_energy_antiderivative(&self, r: f64) -> f64 {
+        let k = PI / self.width;
+        let (sin, cos) = f64::sin_cos(k * (r - self.r0));
+        let integral = r * r * r / 3.0 + r * r * sin / k + 2.0 * r * cos / (k * k) - 2.0 * sin / (k * k * k);
+        return -0.5 * self.epsilon * integral;
+    }
+}
+
+impl Potential for CosineSquared {
+    fn energy(&self, r: f64) -> f64 {
+        if r < self.r0 {
+            -self.epsilon
+        } else if r < self.r0 + self.width {
+            let cos = f64::cos(PI * (r - self.r0) / (2.0 * self.width));
+            -self.epsilon * cos * cos
+        } else {
+            0.0
+        }
+    }
+
+    fn force(&self, r: f64) -> f64 {
+        if r < self.r0 || r >= self.r0 + self.width {
+            0.0
+        } else {
+            let factor = PI / (2.0 * self.width);
+            -self.epsilon * factor * f64::sin(PI * (r - self.r0) / self.width)
+        }
+    }
+}
+
+impl PairPotential for CosineSquared {
+    fn tail_energy(&self, cutoff: f64) -> f64 {
+        let end = self.r0 + self.width;
+        if cutoff >= end {
+            return 0.0;
+        }
+
+        let start = f64::max(cutoff, self.r0);
+        let mut tail = self.r2_energy_antiderivative(end) - self.r2_energy_antiderivative(start);
+        if cutoff < self.r0 {
+            tail -= self.epsilon * (self.r0 * self.r0 * self.r0 - cutoff * cutoff * cutoff) / 3.0;
+        }
+        return tail;
+    }
+
+    fn tail_virial(&self, cutoff: f64) -> f64 {
+        // Integrating by part, using the fact that the potential is zero
+        // after r0 + width
+        cutoff * cutoff * cutoff * self.energy(cutoff) + 3.0 * self.tail_energy(cutoff)
+    }
+}
+
 
 #[cfg(test)]
 #[allow(clippy::unreadable_literal)]
 mod tests {
     use super::*;
-    use crate::{PairPotential, Potential};
+    use crate::{AngleGeometry, AnglePotential, PairPotential, Potential};
     use approx::{assert_ulps_eq, assert_relative_eq};
 
     const EPS: f64 = 1e-9;
@@ -680,6 +1248,120 @@ mod tests {
         assert_relative_eq!((e0 - e1) / EPS, harmonic.force(2.3), epsilon = 1e-6);
     }
 
+    /// Check the generalized forces of an angle potential against finite
+    /// differences of the energy
+    fn check_angle_forces(potential: &dyn AnglePotential, geometry: AngleGeometry) {
+        let forces = potential.angle_forces(&geometry);
+        let e0 = potential.angle_energy(&geometry);
+
+        let mut moved = geometry;
+        moved.theta += EPS;
+        let e1 = potential.angle_energy(&moved);
+        assert_relative_eq!((e0 - e1) / EPS, forces.theta, epsilon = 1e-6);
+
+        let mut moved = geometry;
+        moved.r_ij += EPS;
+        let e1 = potential.angle_energy(&moved);
+        assert_relative_eq!((e0 - e1) / EPS, forces.r_ij, epsilon = 1e-6);
+
+        let mut moved = geometry;
+        moved.r_jk += EPS;
+        let e1 = potential.angle_energy(&moved);
+        assert_relative_eq!((e0 - e1) / EPS, forces.r_jk, epsilon = 1e-6);
+
+        let mut moved = geometry;
+        moved.r_ik += EPS;
+        let e1 = potential.angle_energy(&moved);
+        assert_relative_eq!((e0 - e1) / EPS, forces.r_ik, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn angle_geometry() {
+        let geometry = AngleGeometry {
+            theta: 1.9,
+            r_ij: 1.1,
+            r_jk: 1.4,
+            r_ik: 2.2,
+        };
+
+        // Potentials depending only on the angle value
+        let harmonic = Harmonic { k: 50.0, x0: 2.0 };
+        assert_eq!(harmonic.angle_energy(&geometry), harmonic.energy(1.9));
+        let forces = harmonic.angle_forces(&geometry);
+        assert_eq!(forces.theta, harmonic.force(1.9));
+        assert_eq!(forces.r_ij, 0.0);
+        assert_eq!(forces.r_jk, 0.0);
+        assert_eq!(forces.r_ik, 0.0);
+
+        let reversed = geometry.reversed();
+        assert_eq!(reversed.r_ij, 1.4);
+        assert_eq!(reversed.r_jk, 1.1);
+        assert_eq!(reversed.reversed(), geometry);
+    }
+
+    #[test]
+    fn urey_bradley() {
+        let potential = UreyBradley {
+            k: 50.0,
+            x0: 2.0,
+            k_ub: 30.0,
+            r_ub: 2.4,
+        };
+        let geometry = AngleGeometry {
+            theta: 2.0,
+            r_ij: 1.1,
+            r_jk: 1.4,
+            r_ik: 2.4,
+        };
+        assert_eq!(potential.angle_energy(&geometry), 0.0);
+
+        let geometry = AngleGeometry {
+            theta: 1.9,
+            r_ij: 1.1,
+            r_jk: 1.4,
+            r_ik: 2.2,
+        };
+        assert_ulps_eq!(potential.angle_energy(&geometry), 0.5 * 50.0 * 0.01 + 0.5 * 30.0 * 0.04, epsilon = 1e-12);
+        check_angle_forces(&potential, geometry);
+    }
+
+    #[test]
+    fn bond_bond() {
+        let potential = BondBond {
+            k: 25.0,
+            r1: 1.0,
+            r2: 1.5,
+        };
+        let geometry = AngleGeometry {
+            theta: 1.9,
+            r_ij: 1.1,
+            r_jk: 1.3,
+            r_ik: 2.2,
+        };
+        assert_ulps_eq!(potential.angle_energy(&geometry), 25.0 * 0.1 * -0.2, epsilon = 1e-12);
+        check_angle_forces(&potential, geometry);
+    }
+
+    #[test]
+    fn bond_angle() {
+        let potential = BondAngle {
+            k1: 25.0,
+            k2: 12.0,
+            r1: 1.0,
+            r2: 1.5,
+            theta0: 2.0,
+        };
+        let geometry = AngleGeometry {
+            theta: 2.2,
+            r_ij: 1.1,
+            r_jk: 1.3,
+            r_ik: 2.2,
+        };
+        let expected = 0.2 * (25.0 * 0.1 - 12.0 * 0.2);
+        assert_ulps_eq!(potential.angle_energy(&geometry), expected, epsilon = 1e-12);
+        check_angle_forces(&potential, geometry);
+    }
+
     #[test]
     fn torsion() {
         let torsion = Torsion {
@@ -698,6 +1380,41 @@ mod tests {
         assert_relative_eq!((e0 - e1) / EPS, torsion.force(4.0), epsilon = 1e-6);
     }
 
+    #[test]
+    fn ryckaert_bellemans() {
+        let potential = RyckaertBellemans {
+            c: [1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+        };
+        // trans conformation
+        assert_eq!(potential.energy(PI), 21.0);
+        // cis conformation
+        assert_eq!(potential.energy(0.0), -3.0);
+
+        let cos = f64::cos(1.1 - PI);
+        let energy = 1.0 + 2.0 * cos + 3.0 * cos.powi(2) + 4.0 * cos.powi(3) + 5.0 * cos.powi(4) + 6.0 * cos.powi(5);
+        assert_relative_eq!(potential.energy(1.1), energy, max_relative = 1e-12);
+
+        let e0 = potential.energy(2.0);
+        let e1 = potential.energy(2.0 + EPS);
+        assert_relative_eq!((e0 - e1) / EPS, potential.force(2.0), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn opls_torsion() {
+        let potential = OplsTorsion {
+            k1: 3.0,
+            k2: -2.0,
+            k3: 1.5,
+            k4: 0.5,
+        };
+        assert_ulps_eq!(potential.energy(0.0), 4.5);
+        assert_ulps_eq!(potential.energy(PI), 0.0, epsilon = 1e-12);
+
+        let e0 = potential.energy(2.0);
+        let e1 = potential.energy(2.0 + EPS);
+        assert_relative_eq!((e0 - e1) / EPS, potential.force(2.0), epsilon = 1e-6);
+    }
+
     #[test]
     fn buckingham() {
         let buckingham = Buckingham {
@@ -816,4 +1533,83 @@ mod tests {
         assert_eq!(mie.tail_energy(2.0), 0.0);
         assert_eq!(mie.tail_virial(2.0), 0.0);
     }
+
+    #[test]
+    fn fene() {
+        let fene = Fene { k: 30.0, r0: 1.5 };
+        assert_eq!(fene.energy(0.0), 0.0);
+        assert_eq!(fene.force(0.0), 0.0);
+        assert_ulps_eq!(fene.energy(1.0), 19.837799940446516);
+        assert_ulps_eq!(fene.force(1.0), -54.0);
+
+        let e0 = fene.energy(1.2);
+        let e1 = fene.energy(1.2 + EPS);
+        assert_relative_eq!((e0 - e1) / EPS, fene.force(1.2), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn fene_overstretched() {
+        let fene = Fene { k: 30.0, r0: 1.5 };
+        assert_eq!(fene.energy(1.5), f64::INFINITY);
+        assert_eq!(fene.energy(1.6), f64::INFINITY);
+        assert_eq!(fene.force(1.6), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn wca() {
+        let wca = Wca {
+            sigma: 2.0,
+            epsilon: 0.8,
+        };
+        let lj = LennardJones {
+            sigma: 2.0,
+            epsilon: 0.8,
+        };
+        assert_eq!(wca.energy(2.0), 0.8);
+        assert_eq!(wca.energy(1.8), lj.energy(1.8) + 0.8);
+        assert_eq!(wca.force(1.8), lj.force(1.8));
+
+        // The potential is continuous at its minimum
+        let range = f64::powf(2.0, 1.0 / 6.0) * 2.0;
+        assert!(wca.energy(range - 1e-9).abs() < 1e-9);
+        assert!(wca.force(range - 1e-9).abs() < 1e-6);
+        assert_eq!(wca.energy(2.5), 0.0);
+        assert_eq!(wca.force(2.5), 0.0);
+
+        assert_eq!(wca.tail_energy(2.5), 0.0);
+        assert_eq!(wca.tail_virial(2.5), 0.0);
+        assert_relative_eq!(wca.tail_energy(2.0), 0.223081599278052, max_relative = 1e-10);
+        assert_relative_eq!(wca.tail_virial(2.0), 7.06924479783416, max_relative = 1e-10);
+
+        let e0 = wca.energy(2.1);
+        let e1 = wca.energy(2.1 + EPS);
+        assert_relative_eq!((e0 - e1) / EPS, wca.force(2.1), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn cosine_squared() {
+        let potential = CosineSquared {
+            epsilon: 2.0,
+            r0: 1.0,
+            width: 1.5,
+        };
+        assert_eq!(potential.energy(0.5), -2.0);
+        assert_eq!(potential.force(0.5), 0.0);
+        assert_ulps_eq!(potential.energy(1.75), -1.0);
+        assert_eq!(potential.energy(2.5), 0.0);
+        assert_eq!(potential.force(2.5), 0.0);
+
+        assert_eq!(potential.tail_energy(2.5), 0.0);
+        assert_eq!(potential.tail_virial(2.5), 0.0);
+        assert_relative_eq!(potential.tail_energy(1.7), -1.19397362117984, max_relative = 1e-10);
+        assert_relative_eq!(potential.tail_virial(1.7), -9.00846920357351, max_relative = 1e-10);
+        assert_relative_eq!(potential.tail_energy(0.5), -3.86252469096651, max_relative = 1e-10);
+        assert_relative_eq!(potential.tail_virial(0.5), -11.8375740728995, max_relative = 1e-10);
+
+        for &r in &[1.2, 1.75, 2.3] {
+            let e0 = potential.energy(r);
+            let e1 = potential.energy(r + EPS);
+            assert_relative_eq!((e0 - e1) / EPS, potential.force(r), epsilon = 1e-6);
+        }
+    }
 }
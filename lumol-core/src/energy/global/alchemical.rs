@@ -0,0 +1,329 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use crate::{CoulombicPotential, PairRestriction};
+use crate::Configuration;
+use crate::{Matrix3, Vector3D};
+
+use super::{GlobalCache, GlobalPotential};
+
+/// Coupling of the electrostatic interactions of a molecule to an alchemical
+/// parameter `λ`.
+///
+/// This wraps another coulombic solver, and linearly scales the interactions
+/// between the charges of the alchemical molecule and the other charges in
+/// the system:
+///
+/// ```text
+/// U(λ) = λ U + (1 - λ) (U_molecule + U_others)
+/// ```
+///
+/// where `U` is the energy of the full system, `U_molecule` the energy of the
+/// alchemical molecule alone, and `U_others` the energy of the system without
+/// the charges of the alchemical molecule. All these energies are computed
+/// with the wrapped solver, which means this works with all coulombic solvers,
+/// including the ones using periodic boundary conditions.
+///
+/// The electrostatic energy is a quadratic function of the charges, so
+/// changing the sign of the charges in the alchemical molecule gives the
+/// energy `U_flipped = U_molecule + U_others - U_cross`, where `U_cross` is the
+/// interaction energy between the molecule and the other charges. The
+/// coupled energy is then computed with only two evaluations of the wrapped
+/// solver, as `U(λ) = (1 + λ) / 2 U + (1 - λ) / 2 U_flipped`.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{AlchemicalCoulomb, Wolf, GlobalPotential};
+/// # use lumol_core::{System, Molecule, Particle, UnitCell, Vector3D};
+/// let mut system = System::with_cell(UnitCell::cubic(20.0));
+/// let mut na = Particle::with_position("Na", Vector3D::new(0.0, 0.0, 0.0));
+/// na.charge = 1.0;
+/// let mut cl = Particle::with_position("Cl", Vector3D::new(2.5, 0.0, 0.0));
+/// cl.charge = -1.0;
+/// system.add_molecule(Molecule::new(na));
+/// system.add_molecule(Molecule::new(cl));
+///
+/// // Decouple the first molecule from the rest of the system
+/// let coulomb = AlchemicalCoulomb::new(Box::new(Wolf::new(8.0)), 0, 0.0);
+/// let wolf = Wolf::new(8.0);
+///
+/// let mut alone = system.clone();
+/// alone.particles_mut().charge[1] = 0.0;
+/// let mut others = system.clone();
+/// others.particles_mut().charge[0] = 0.0;
+///
+/// let expected = wolf.energy(&alone) + wolf.energy(&others);
+/// assert!(f64::abs(coulomb.energy(&system) - expected) < 1e-12);
+/// ```
+#[derive(Clone)]
+pub struct AlchemicalCoulomb {
+    /// Solver used for the full system
+    full: Box<dyn CoulombicPotential>,
+    /// Solver used for the system with flipped charges in the alchemical
+    /// molecule
+    flipped: Box<dyn CoulombicPotential>,
+    /// Index of the alchemical molecule
+    molecule_id: usize,
+    /// Coupling parameter
+    lambda: f64,
+}
+
+impl AlchemicalCoulomb {
+    /// Create a new `AlchemicalCoulomb` coupling the electrostatic
+    /// interactions of the molecule at index `molecule_id` with the rest of
+    /// the system. The interactions are computed with the given `solver`, and
+    /// scaled by the coupling parameter `lambda`, between 0 (decoupled) and 1
+    /// (fully coupled).
+    pub fn new(solver: Box<dyn CoulombicPotential>, molecule_id: usize, lambda: f64) -> AlchemicalCoulomb {
+        AlchemicalCoulomb {
+            flipped: solver.clone(),
+            full: solver,
+            molecule_id: molecule_id,
+            lambda: lambda,
+        }
+    }
+
+    /// Is the alchemical molecule fully coupled to the rest of the system? In
+    /// this case, the flipped configuration does not contribute.
+    fn fully_coupled(&self) -> bool {
+        self.lambda == 1.0
+    }
+
+    /// Get a copy of the configuration where the charges of the alchemical
+    /// molecule have the opposite sign.
+    fn flip(&self, configuration: &Configuration) -> Configuration {
+        let mut flipped = configuration.clone();
+        for i in configuration.molecule(self.molecule_id).indexes() {
+            flipped.particles_mut().charge[i] *= -1.0;
+        }
+        return flipped;
+    }
+
+    /// Combine the contributions of the full system and of the system with
+    /// flipped charges.
+    fn combine<T>(&self, full: T, flipped: T) -> T
+    where
+        T: std::ops::Add<Output = T> + std::ops::Mul<f64, Output = T>,
+    {
+        return full * (0.5 * (1.0 + self.lambda)) + flipped * (0.5 * (1.0 - self.lambda));
+    }
+}
+
+impl GlobalCache for AlchemicalCoulomb {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let full = self.full.move_molecule_cost(configuration, molecule_id, new_positions);
+        if self.fully_coupled() {
+            return full;
+        }
+        let flipped = self.flip(configuration);
+        return self.combine(full, self.flipped.move_molecule_cost(&flipped, molecule_id, new_positions));
+    }
+
+    fn update(&self) {
+        self.full.update();
+        if !self.fully_coupled() {
+            self.flipped.update();
+        }
+    }
+}
+
+impl GlobalPotential for AlchemicalCoulomb {
+    fn cutoff(&self) -> Option<f64> {
+        self.full.cutoff()
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        let full = self.full.energy(configuration);
+        if self.fully_coupled() {
+            return full;
+        }
+        let flipped = self.flip(configuration);
+        return self.combine(full, self.flipped.energy(&flipped));
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+        if self.fully_coupled() {
+            self.full.forces(configuration, forces);
+            return;
+        }
+
+        let mut full_forces = vec![Vector3D::zero(); configuration.size()];
+        self.full.forces(configuration, &mut full_forces);
+        let flipped = self.flip(configuration);
+        let mut flipped_forces = vec![Vector3D::zero(); configuration.size()];
+        self.flipped.forces(&flipped, &mut flipped_forces);
+
+        for (i, force) in forces.iter_mut().enumerate() {
+            *force += self.combine(full_forces[i], flipped_forces[i]);
+        }
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let full = self.full.atomic_virial(configuration);
+        if self.fully_coupled() {
+            return full;
+        }
+        let flipped = self.flip(configuration);
+        return self.combine(full, self.flipped.atomic_virial(&flipped));
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let full = self.full.molecular_virial(configuration);
+        if self.fully_coupled() {
+            return full;
+        }
+        let flipped = self.flip(configuration);
+        return self.combine(full, self.flipped.molecular_virial(&flipped));
+    }
+}
+
+impl CoulombicPotential for AlchemicalCoulomb {
+    fn set_restriction(&mut self, restriction: PairRestriction) {
+        self.full.set_restriction(restriction);
+        self.flipped.set_restriction(restriction);
+    }
+
+    fn lambda_derivative(&self, configuration: &Configuration) -> f64 {
+        // The derivative is the interaction energy between the alchemical
+        // molecule and the other charges
+        let flipped = self.flip(configuration);
+        return 0.5 * (self.full.energy(configuration) - self.flipped.energy(&flipped));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ewald, SharedEwald, System, Wolf};
+    use crate::utils::system_from_xyz;
+
+    use approx::assert_relative_eq;
+
+    fn testing_system() -> System {
+        let mut system = system_from_xyz(
+            "4
+            cell: 20.0
+            O 0.0 0.0 0.0
+            H 0.9 0.3 0.0
+            Na 3.0 0.5 -0.2
+            Cl 1.2 2.8 0.4
+            ",
+        );
+        let _ = system.add_bond(0, 1);
+        system.particles_mut().charge[0] = -0.4;
+        system.particles_mut().charge[1] = 0.4;
+        system.particles_mut().charge[2] = 1.0;
+        system.particles_mut().charge[3] = -1.0;
+        return system;
+    }
+
+    fn alchemical(lambda: f64) -> AlchemicalCoulomb {
+        let mut alchemical = AlchemicalCoulomb::new(Box::new(Wolf::new(8.0)), 0, lambda);
+        alchemical.set_restriction(PairRestriction::InterMolecular);
+        return alchemical;
+    }
+
+    #[test]
+    fn energy() {
+        let system = testing_system();
+        let mut wolf = Wolf::new(8.0);
+        wolf.set_restriction(PairRestriction::InterMolecular);
+
+        // Fully coupled
+        assert_relative_eq!(alchemical(1.0).energy(&system), wolf.energy(&system), epsilon = 1e-12);
+
+        // Fully decoupled: only the Na-Cl interaction remains
+        let mut others = system.clone();
+        others.particles_mut().charge[0] = 0.0;
+        others.particles_mut().charge[1] = 0.0;
+        assert_relative_eq!(alchemical(0.0).energy(&system), wolf.energy(&others), epsilon = 1e-12);
+
+        // Linear interpolation in between
+        let expected = 0.3 * wolf.energy(&system) + 0.7 * wolf.energy(&others);
+        assert_relative_eq!(alchemical(0.3).energy(&system), expected, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn ewald() {
+        let system = testing_system();
+        let mut ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+        ewald.set_restriction(PairRestriction::InterMolecular);
+
+        let mut alchemical = AlchemicalCoulomb::new(Box::new(ewald.clone()), 0, 0.3);
+        alchemical.set_restriction(PairRestriction::InterMolecular);
+
+        let mut molecule = system.clone();
+        molecule.particles_mut().charge[2] = 0.0;
+        molecule.particles_mut().charge[3] = 0.0;
+        let mut others = system.clone();
+        others.particles_mut().charge[0] = 0.0;
+        others.particles_mut().charge[1] = 0.0;
+
+        let decoupled = ewald.energy(&molecule) + ewald.energy(&others);
+        let expected = 0.3 * ewald.energy(&system) + 0.7 * decoupled;
+        assert_relative_eq!(alchemical.energy(&system), expected, max_relative = 1e-10);
+        assert_relative_eq!(
+            alchemical.lambda_derivative(&system),
+            ewald.energy(&system) - decoupled,
+            max_relative = 1e-10
+        );
+    }
+
+    #[test]
+    fn lambda_derivative() {
+        let system = testing_system();
+        let eps = 1e-6;
+        let finite_diff = (alchemical(0.3 + eps).energy(&system) - alchemical(0.3 - eps).energy(&system)) / (2.0 * eps);
+        assert_relative_eq!(alchemical(0.3).lambda_derivative(&system), finite_diff, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn forces() {
+        let mut system = testing_system();
+        let alchemical = alchemical(0.3);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        alchemical.forces(&system, &mut forces);
+
+        let eps = 1e-6;
+        for i in 0..system.size() {
+            for k in 0..3 {
+                system.particles_mut().position[i][k] += eps;
+                let e1 = alchemical.energy(&system);
+                system.particles_mut().position[i][k] -= 2.0 * eps;
+                let e2 = alchemical.energy(&system);
+                system.particles_mut().position[i][k] += eps;
+                assert_relative_eq!(-(e1 - e2) / (2.0 * eps), forces[i][k], epsilon = 1e-6);
+            }
+        }
+
+        let mut wolf = Wolf::new(8.0);
+        wolf.set_restriction(PairRestriction::InterMolecular);
+        let mut others = system.clone();
+        others.particles_mut().charge[0] = 0.0;
+        others.particles_mut().charge[1] = 0.0;
+        let expected = 0.3 * wolf.atomic_virial(&system) + 0.7 * wolf.atomic_virial(&others);
+        assert_relative_eq!(alchemical.atomic_virial(&system), expected, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn move_molecule() {
+        let mut system = testing_system();
+        let alchemical = alchemical(0.3);
+        let old_energy = alchemical.energy(&system);
+
+        let new_positions = &[Vector3D::new(2.0, 1.5, 1.0)];
+        let cost = alchemical.move_molecule_cost(&system, 2, new_positions);
+        alchemical.update();
+
+        system.particles_mut().position[2] = new_positions[0];
+        let new_energy = alchemical.energy(&system);
+        assert_relative_eq!(cost, new_energy - old_energy, epsilon = 1e-12);
+    }
+}
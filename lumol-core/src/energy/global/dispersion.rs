@@ -0,0 +1,803 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use rayon::prelude::*;
+
+use log_once::warn_once;
+
+use crate::math::erfc;
+use crate::{Configuration, UnitCell, CellShape};
+use crate::{Matrix3, Vector3D, Complex};
+use crate::PairRestriction;
+use crate::sys::NeighborList;
+
+use super::{GlobalCache, GlobalPotential};
+use super::fft::{fft3d, Direction};
+use super::pme::{bspline_moduli, wave_index, Spline};
+
+/// Ewald damping function for `r^-6` interactions:
+/// `g(b) = exp(-b²) (1 + b² + b⁴ / 2)`
+#[inline]
+fn damping(b: f64) -> f64 {
+    let b2 = b * b;
+    f64::exp(-b2) * (1.0 + b2 + 0.5 * b2 * b2)
+}
+
+/// Smooth particle-mesh Ewald summation for the long-range part of `r^-6`
+/// dispersion interactions (LJ-PME).
+///
+/// Each particle gets a dispersion coefficient `C6_i`, and the dispersion
+/// energy between particles `i` and `j` is `- C6_ij / r^6` using the
+/// geometric combination rule `C6_ij = √(C6_i C6_j)`. The pair potentials
+/// (for example Lennard-Jones) are still used for all the pairs inside the
+/// cutoff, and this potential adds the dispersion energy of all the pairs
+/// outside of the cutoff, including all the periodic images [Essmann1995]
+/// [Veld2007]. This removes the need for tail corrections, which assume an
+/// homogeneous fluid outside of the cutoff and are wrong for interfaces.
+///
+/// The cutoff of this potential must be the same as the cutoff of the pair
+/// potentials, which should not be shifted and should not use tail
+/// corrections. The energy computed by this potential is:
+///
+/// ```text
+/// E = Σ_{i<j, r_ij < r_c} C6_ij (1 - g(β r_ij)) / r_ij^6 + E_k + β^6 / 12 Σ_i C6_i
+/// g(b) = exp(-b²) (1 + b² + b⁴ / 2)
+/// ```
+///
+/// where `E_k` is the reciprocal space part of the Ewald summation, computed
+/// with B-spline interpolation on a grid as in the [`Pme`](struct.Pme.html)
+/// coulombic solver. The pairs inside the cutoff are found with a neighbor
+/// list. Pairs outside of the cutoff in the same molecule are scaled using
+/// the [`PairRestriction`], which should be the same restriction as the one
+/// used for the pair potentials: the reciprocal space contribution of these
+/// pairs is multiplied by the restriction scaling factor, or removed if the
+/// pair is excluded. Pairs inside the cutoff are already scaled by the pair
+/// potentials.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{DispersionPme, GlobalPotential, LennardJones, PairInteraction};
+/// # use lumol_core::sys::{Particle, Molecule, UnitCell, System};
+/// # use lumol_core::types::Vector3D;
+/// let mut system = System::with_cell(UnitCell::cubic(20.0));
+/// system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+/// system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(3.5, 0.0, 0.0))));
+///
+/// let lj = LennardJones{sigma: 3.4, epsilon: 1e-3};
+/// system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(Box::new(lj), 8.0));
+///
+/// // Long-range dispersion with the same cutoff as the pair potential, using
+/// // C6 = 4 ε σ^6 for argon
+/// let mut dispersion = DispersionPme::new(8.0, [16, 16, 16], 6, None);
+/// dispersion.set_c6("Ar", 4.0 * 1e-3 * f64::powi(3.4, 6));
+/// system.add_global_potential(Box::new(dispersion));
+///
+/// println!("energy is {}", system.potential_energy());
+/// ```
+///
+/// [`PairRestriction`]: enum.PairRestriction.html
+/// [Essmann1995]: Essmann, U. et al. J. Chem. Phys. 103, 8577 (1995).
+/// [Veld2007]: in 't Veld, P. J., Ismail, A. E. & Grest, G. S. J. Chem. Phys. 127, 144711 (2007).
+#[derive(Clone)]
+pub struct DispersionPme {
+    /// Splitting parameter between k-space and real space
+    alpha: f64,
+    /// Cutoff radius in real space
+    cutoff: f64,
+    /// Number of points in the grid along each direction
+    grid: [usize; 3],
+    /// Order of the B-spline interpolation
+    order: usize,
+    /// Squared modulus of the B-spline Euler exponential factors along each
+    /// direction
+    moduli: [Vec<f64>; 3],
+    /// Square root of the dispersion coefficients, indexed by particle name
+    coefficients: BTreeMap<String, f64>,
+    /// Restriction scheme
+    restriction: PairRestriction,
+    /// Neighbor list used for the pairs inside the cutoff in real space
+    neighbors: NeighborList,
+}
+
+impl DispersionPme {
+    /// Create a new LJ-PME summation using the given `cutoff` radius in real
+    /// space, a `grid` with the given number of points along each direction of
+    /// the unit cell, and a B-spline interpolation of the given `order`. If
+    /// `alpha` is None, then the default value of `π / cutoff` is used.
+    pub fn new<I: Into<Option<f64>>>(cutoff: f64, grid: [usize; 3], order: usize, alpha: I) -> DispersionPme {
+        let alpha = alpha.into().unwrap_or(PI / cutoff);
+        if cutoff < 0.0 {
+            panic!("the cutoff can not be negative in LJ-PME");
+        } else if alpha < 0.0 {
+            panic!("alpha can not be negative in LJ-PME");
+        } else if order < 3 {
+            panic!("the B-spline order must be at least 3 in LJ-PME");
+        } else if grid.iter().any(|&size| size < order) {
+            panic!("the grid must contain at least as many points as the B-spline order in LJ-PME");
+        }
+
+        DispersionPme {
+            alpha: alpha,
+            cutoff: cutoff,
+            grid: grid,
+            order: order,
+            moduli: [
+                bspline_moduli(grid[0], order),
+                bspline_moduli(grid[1], order),
+                bspline_moduli(grid[2], order),
+            ],
+            coefficients: BTreeMap::new(),
+            restriction: PairRestriction::None,
+            neighbors: NeighborList::default(),
+        }
+    }
+
+    /// Set the dispersion coefficient `c6` for all the particles with the
+    /// given `name`. Particles without dispersion coefficient do not take part
+    /// in this interaction.
+    pub fn set_c6(&mut self, name: &str, c6: f64) {
+        assert!(c6 >= 0.0, "the dispersion coefficient can not be negative in LJ-PME");
+        let _ = self.coefficients.insert(name.into(), f64::sqrt(c6));
+    }
+
+    /// Set the restriction scheme to use to `restriction`. All excluded pairs
+    /// are removed from the summation, and scaled pairs are scaled
+    /// accordingly.
+    ///
+    /// # Panics
+    ///
+    /// If `restriction` is `PairRestriction::IntraMolecular`, since this
+    /// would exclude all the long-range intermolecular pairs.
+    pub fn set_restriction(&mut self, restriction: PairRestriction) {
+        if restriction == PairRestriction::IntraMolecular {
+            panic!("intra-molecular restriction can not be used with LJ-PME");
+        }
+        self.restriction = restriction;
+    }
+
+    /// Get the number of points in the grid along each direction
+    pub fn grid(&self) -> [usize; 3] {
+        self.grid
+    }
+
+    /// Get the order of the B-spline interpolation
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Get the splitting parameter between k-space and real space
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    /// Get the square root of the dispersion coefficient of all particles in
+    /// the `configuration`
+    fn coefficients(&self, configuration: &Configuration) -> Vec<f64> {
+        configuration.particles().name.iter().map(|name| {
+            self.coefficients.get(name).cloned().unwrap_or(0.0)
+        }).collect()
+    }
+
+    /// Get the index in the flattened grid of the point `(i, j, k)`
+    #[inline]
+    fn grid_index(&self, i: usize, j: usize, k: usize) -> usize {
+        (i * self.grid[1] + j) * self.grid[2] + k
+    }
+
+    /// Compute the energetic and virial pre-factors for each point in
+    /// reciprocal space. The energetic pre-factor is `- π^3/2 β³ / (2 V)
+    /// |b(m)|² f(π m / β)`, with `f(b) = ((1 - 2 b²) exp(-b²) + 2 b³ √π
+    /// erfc(b)) / 3`.
+    fn influence(&self, cell: &UnitCell) -> (Vec<f64>, Vec<Matrix3>) {
+        if cell.shape() == CellShape::Infinite {
+            panic!("LJ-PME is not defined with infinite unit cell");
+        }
+        if self.cutoff > cell.lengths().min() / 2.0 {
+            warn_once!("The LJ-PME cutoff is too high for this unit cell, energy and forces might be wrong.");
+        }
+
+        let reciprocal = cell.matrix().inverse().transposed();
+        let prefactor = -f64::powf(PI, 1.5) * f64::powi(self.alpha, 3) / (2.0 * cell.volume());
+        let pi2_alpha2 = PI * PI / (self.alpha * self.alpha);
+
+        let [n0, n1, n2] = self.grid;
+        let mut energy = Vec::with_capacity(n0 * n1 * n2);
+        let mut virial = Vec::with_capacity(n0 * n1 * n2);
+        for i in 0..n0 {
+            for j in 0..n1 {
+                for k in 0..n2 {
+                    let index = Vector3D::new(wave_index(i, n0), wave_index(j, n1), wave_index(k, n2));
+                    let m = reciprocal * index;
+                    let b = f64::sqrt(pi2_alpha2 * m.norm2());
+                    let moduli = self.moduli[0][i] * self.moduli[1][j] * self.moduli[2][k];
+
+                    let exp = f64::exp(-b * b);
+                    let sqrt_pi_erfc = f64::sqrt(PI) * erfc(b);
+                    let f = ((1.0 - 2.0 * b * b) * exp + 2.0 * b * b * b * sqrt_pi_erfc) / 3.0;
+                    // f'(b) / b
+                    let df_b = 2.0 * (b * sqrt_pi_erfc - exp);
+
+                    let factor = prefactor * moduli;
+                    energy.push(factor * f);
+                    virial.push(factor * (f * Matrix3::one() + pi2_alpha2 * df_b * m.tensorial(&m)));
+                }
+            }
+        }
+        return (energy, virial);
+    }
+
+    /// Compute the Fourier transform of the interpolated dispersion
+    /// `coefficients` of the particles at `positions`.
+    fn rho(&self, cell: &UnitCell, positions: &[Vector3D], coefficients: &[f64]) -> Vec<Complex> {
+        let [n0, n1, n2] = self.grid;
+        let mut rho = vec![Complex::zero(); n0 * n1 * n2];
+        self.spread(cell, positions, coefficients, &mut rho);
+        fft3d(&mut rho, (n0, n1, n2), Direction::Forward);
+        return rho;
+    }
+
+    /// Compute the Fourier transform of the interpolated dispersion
+    /// coefficients changes while moving the molecule with the given
+    /// `molecule_id` to `new_positions`
+    fn delta_rho_move_rigid_molecules(
+        &self,
+        configuration: &Configuration,
+        coefficients: &[f64],
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> Vec<Complex> {
+        let molecule = configuration.molecule(molecule_id);
+        let positions = &configuration.particles().position[molecule.indexes()];
+        let coefficients = &coefficients[molecule.indexes()];
+        let opposite = coefficients.iter().map(|c| -c).collect::<Vec<_>>();
+
+        let [n0, n1, n2] = self.grid;
+        let mut delta = vec![Complex::zero(); n0 * n1 * n2];
+        self.spread(&configuration.cell, positions, &opposite, &mut delta);
+        self.spread(&configuration.cell, new_positions, coefficients, &mut delta);
+        fft3d(&mut delta, (n0, n1, n2), Direction::Forward);
+
+        return delta;
+    }
+
+    /// Spread the dispersion `coefficients` of the particles at `positions`
+    /// on the `grid`, using B-spline interpolation.
+    fn spread(&self, cell: &UnitCell, positions: &[Vector3D], coefficients: &[f64], grid: &mut [Complex]) {
+        for (position, &coefficient) in zip!(positions, coefficients) {
+            if coefficient == 0.0 {
+                continue;
+            }
+
+            let spline = Spline::new(cell, position, self.grid, self.order);
+            for (j0, &v0) in spline.values[0].iter().enumerate() {
+                let i = spline.point(0, j0, self.grid);
+                for (j1, &v1) in spline.values[1].iter().enumerate() {
+                    let j = spline.point(1, j1, self.grid);
+                    let c01 = coefficient * v0 * v1;
+                    for (j2, &v2) in spline.values[2].iter().enumerate() {
+                        let k = spline.point(2, j2, self.grid);
+                        grid[self.grid_index(i, j, k)] += Complex::cartesian(c01 * v2, 0.0);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Call `function(i, j, cij, rij)` for all the pairs `i, j` that need to
+    /// be corrected in real space, with `cij` the dispersion coefficient of the
+    /// pair (including scaling from the restrictions) and `rij` the distance
+    /// vector between the particles.
+    ///
+    /// The reciprocal space contribution of all pairs inside the cutoff is
+    /// removed, since these pairs are handled by the pair potentials. The
+    /// contribution of pairs in the same molecule outside of the cutoff is
+    /// scaled according to the restriction.
+    fn real_space_pairs<F>(&self, configuration: &Configuration, coefficients: &[f64], mut function: F)
+    where
+        F: FnMut(usize, usize, f64, Vector3D),
+    {
+        let neighbors = self.neighbors.neighbors(configuration, Some(self.cutoff));
+        for i in 0..configuration.size() {
+            if coefficients[i] == 0.0 {
+                continue;
+            }
+            for j in neighbors.of(i) {
+                let cij = coefficients[i] * coefficients[j];
+                if cij == 0.0 {
+                    continue;
+                }
+                let rij = configuration.nearest_image(i, j);
+                if rij.norm() < self.cutoff {
+                    function(i, j, cij, rij);
+                }
+            }
+        }
+
+        if self.restriction == PairRestriction::None {
+            return;
+        }
+
+        for molecule in configuration.molecules() {
+            for i in molecule.indexes() {
+                if coefficients[i] == 0.0 {
+                    continue;
+                }
+                for j in (i + 1)..molecule.indexes().end {
+                    let cij = coefficients[i] * coefficients[j];
+                    if cij == 0.0 {
+                        continue;
+                    }
+                    let rij = configuration.nearest_image(i, j);
+                    if rij.norm() < self.cutoff {
+                        continue;
+                    }
+                    let info = self.restriction.information(configuration.bond_path(i, j));
+                    let scaling = if info.excluded { 0.0 } else { info.scaling };
+                    if scaling < 1.0 {
+                        function(i, j, (1.0 - scaling) * cij, rij);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Real space energy for the pair with coefficient `cij` at distance `r`
+    #[inline]
+    fn real_space_energy_pair(&self, cij: f64, r: f64) -> f64 {
+        cij * (1.0 - damping(self.alpha * r)) / f64::powi(r, 6)
+    }
+
+    /// Real space force over the distance for the pair with coefficient
+    /// `cij` at distance `r`
+    #[inline]
+    fn real_space_force_pair(&self, cij: f64, r: f64) -> f64 {
+        let b = self.alpha * r;
+        let b6 = f64::powi(b, 6);
+        let derivative = (-6.0 * (1.0 - damping(b)) + b6 * f64::exp(-b * b)) / f64::powi(r, 7);
+        return -cij * derivative / r;
+    }
+
+    /// Real space part of the energy
+    fn real_space_energy(&self, configuration: &Configuration, coefficients: &[f64]) -> f64 {
+        let mut energy = 0.0;
+        self.real_space_pairs(configuration, coefficients, |_, _, cij, rij| {
+            energy += self.real_space_energy_pair(cij, rij.norm());
+        });
+        return energy;
+    }
+
+    /// Real space part of the forces
+    fn real_space_forces(&self, configuration: &Configuration, coefficients: &[f64], forces: &mut [Vector3D]) {
+        self.real_space_pairs(configuration, coefficients, |i, j, cij, rij| {
+            let force = self.real_space_force_pair(cij, rij.norm()) * rij;
+            forces[i] += force;
+            forces[j] -= force;
+        });
+    }
+
+    /// Real space part of the atomic virial
+    fn real_space_virial(&self, configuration: &Configuration, coefficients: &[f64]) -> Matrix3 {
+        let mut virial = Matrix3::zero();
+        self.real_space_pairs(configuration, coefficients, |_, _, cij, rij| {
+            let force = self.real_space_force_pair(cij, rij.norm()) * rij;
+            virial += force.tensorial(&rij);
+        });
+        return virial;
+    }
+
+    /// Real space part of the energy change when moving the molecule with
+    /// the given `molecule_id` to `new_positions`. The molecule is moved as a
+    /// rigid body, so only the pairs between a particle in this molecule and
+    /// a particle in another molecule change.
+    fn real_space_move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        coefficients: &[f64],
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let positions = configuration.particles().position;
+        let indexes = configuration.molecule(molecule_id).indexes();
+
+        let mut cost = 0.0;
+        for (i, part_i) in indexes.clone().enumerate() {
+            if coefficients[part_i] == 0.0 {
+                continue;
+            }
+            for part_j in (0..configuration.size()).filter(|part_j| !indexes.contains(part_j)) {
+                let cij = coefficients[part_i] * coefficients[part_j];
+                if cij == 0.0 {
+                    continue;
+                }
+
+                let old_r = configuration.distance(part_i, part_j);
+                if old_r < self.cutoff {
+                    cost -= self.real_space_energy_pair(cij, old_r);
+                }
+                let new_r = configuration.cell.distance(&new_positions[i], &positions[part_j]);
+                if new_r < self.cutoff {
+                    cost += self.real_space_energy_pair(cij, new_r);
+                }
+            }
+        }
+        return cost;
+    }
+
+    /// Self-interaction energy
+    fn self_energy(&self, coefficients: &[f64]) -> f64 {
+        let c2 = coefficients.iter().map(|c| c * c).sum::<f64>();
+        return f64::powi(self.alpha, 6) * c2 / 12.0;
+    }
+
+    /// Energy of the particles at `positions` with the given `coefficients`
+    /// in reciprocal space
+    fn k_space_energy(&self, cell: &UnitCell, positions: &[Vector3D], coefficients: &[f64]) -> f64 {
+        let (energy, _) = self.influence(cell);
+        let rho = self.rho(cell, positions, coefficients);
+        return energy.par_iter()
+            .zip_eq(&rho)
+            .map(|(factor, rho)| factor * rho.norm2())
+            .sum::<f64>();
+    }
+
+    /// Reciprocal space part of the energy change when moving the molecule
+    /// with the given `molecule_id` to `new_positions`
+    fn k_space_move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        coefficients: &[f64],
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let cell = &configuration.cell;
+        let (energy, _) = self.influence(cell);
+        let rho = self.rho(cell, configuration.particles().position, coefficients);
+        let delta_rho = self.delta_rho_move_rigid_molecules(
+            configuration, coefficients, molecule_id, new_positions
+        );
+
+        return energy.par_iter()
+            .zip_eq(&rho)
+            .zip_eq(&delta_rho)
+            .map(|((factor, &rho), &delta)| factor * ((rho + delta).norm2() - rho.norm2()))
+            .sum::<f64>();
+    }
+
+    /// Reciprocal space part of the forces
+    fn k_space_forces(&self, configuration: &Configuration, coefficients: &[f64], forces: &mut [Vector3D]) {
+        let cell = &configuration.cell;
+        let positions = configuration.particles().position;
+        let (energy, _) = self.influence(cell);
+        let rho = self.rho(cell, positions, coefficients);
+
+        // Convolution of the interpolated coefficients with the influence
+        // function
+        let [n0, n1, n2] = self.grid;
+        let mut potential = energy.par_iter()
+            .zip_eq(&rho)
+            .map(|(&factor, &rho)| 2.0 * factor * rho)
+            .collect::<Vec<_>>();
+        fft3d(&mut potential, (n0, n1, n2), Direction::Backward);
+
+        let inverse = cell.matrix().inverse();
+        let rows = [
+            Vector3D::new(inverse[0][0], inverse[0][1], inverse[0][2]),
+            Vector3D::new(inverse[1][0], inverse[1][1], inverse[1][2]),
+            Vector3D::new(inverse[2][0], inverse[2][1], inverse[2][2]),
+        ];
+
+        forces.par_iter_mut()
+            .zip_eq(positions.par_iter())
+            .zip_eq(coefficients.par_iter())
+            .for_each(|((force, position), &coefficient)| {
+                if coefficient == 0.0 {
+                    return;
+                }
+
+                let spline = Spline::new(cell, position, self.grid, self.order);
+                let mut gradient = [0.0; 3];
+                for j0 in 0..self.order {
+                    let i = spline.point(0, j0, self.grid);
+                    let (v0, d0) = (spline.values[0][j0], spline.derivatives[0][j0]);
+                    for j1 in 0..self.order {
+                        let j = spline.point(1, j1, self.grid);
+                        let (v1, d1) = (spline.values[1][j1], spline.derivatives[1][j1]);
+                        for j2 in 0..self.order {
+                            let k = spline.point(2, j2, self.grid);
+                            let (v2, d2) = (spline.values[2][j2], spline.derivatives[2][j2]);
+
+                            let phi = potential[self.grid_index(i, j, k)].real();
+                            gradient[0] += phi * d0 * v1 * v2;
+                            gradient[1] += phi * v0 * d1 * v2;
+                            gradient[2] += phi * v0 * v1 * d2;
+                        }
+                    }
+                }
+
+                let gradient = (n0 as f64 * gradient[0]) * rows[0]
+                             + (n1 as f64 * gradient[1]) * rows[1]
+                             + (n2 as f64 * gradient[2]) * rows[2];
+                *force -= coefficient * gradient;
+            });
+    }
+
+    /// Reciprocal space part of the atomic virial
+    fn k_space_virial(&self, configuration: &Configuration, coefficients: &[f64]) -> Matrix3 {
+        let cell = &configuration.cell;
+        let (_, virial) = self.influence(cell);
+        let rho = self.rho(cell, configuration.particles().position, coefficients);
+        return virial.par_iter()
+            .zip_eq(&rho)
+            .map(|(factor, rho)| rho.norm2() * factor)
+            .sum::<Matrix3>();
+    }
+}
+
+impl GlobalPotential for DispersionPme {
+    fn cutoff(&self) -> Option<f64> {
+        Some(self.cutoff)
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        let coefficients = self.coefficients(configuration);
+        let positions = configuration.particles().position;
+        let real = self.real_space_energy(configuration, &coefficients);
+        let kspace = self.k_space_energy(&configuration.cell, positions, &coefficients);
+        return real + kspace + self.self_energy(&coefficients);
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+        let coefficients = self.coefficients(configuration);
+        self.real_space_forces(configuration, &coefficients, forces);
+        self.k_space_forces(configuration, &coefficients, forces);
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let coefficients = self.coefficients(configuration);
+        let real = self.real_space_virial(configuration, &coefficients);
+        let kspace = self.k_space_virial(configuration, &coefficients);
+        return real + kspace;
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let atomic = self.atomic_virial(configuration);
+
+        let mut forces = vec![Vector3D::zero(); configuration.size()];
+        self.forces(configuration, &mut forces);
+
+        let positions = configuration.particles().position;
+        let mut correction = Matrix3::zero();
+        for molecule in configuration.molecules() {
+            let com = molecule.center_of_mass();
+            for i in molecule.indexes() {
+                let di = positions[i] - com;
+                correction += forces[i].tensorial(&di);
+            }
+        }
+
+        return atomic - correction;
+    }
+}
+
+impl GlobalCache for DispersionPme {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let coefficients = self.coefficients(configuration);
+        let real = self.real_space_move_molecule_cost(configuration, &coefficients, molecule_id, new_positions);
+        let kspace = self.k_space_move_molecule_cost(configuration, &coefficients, molecule_id, new_positions);
+        /* No self cost, the coefficients do not change */
+        return real + kspace;
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::System;
+    use crate::utils::system_from_xyz;
+
+    use approx::assert_relative_eq;
+
+    fn testing_system() -> System {
+        system_from_xyz(
+            "4
+            cell: 10.0
+            A 0.0 0.0 0.0
+            B 1.5 0.3 0.2
+            C 3.0 4.0 -1.0
+            D 6.0 2.0 5.0
+            ",
+        )
+    }
+
+    fn dispersion(cutoff: f64) -> DispersionPme {
+        let mut dispersion = DispersionPme::new(cutoff, [32, 32, 32], 6, None);
+        dispersion.set_c6("A", 1.0);
+        dispersion.set_c6("B", 4.0);
+        dispersion.set_c6("C", 2.25);
+        dispersion.set_c6("D", 0.49);
+        return dispersion;
+    }
+
+    fn scaled(system: &System, i: usize, eps: f64) -> System {
+        let mut system = system.clone();
+        let mut scaling = Matrix3::one();
+        scaling[i][i] += eps;
+        let old_cell = system.cell;
+        let new_cell = system.cell.scale(scaling);
+        for position in system.particles_mut().position {
+            *position = new_cell.cartesian(&old_cell.fractional(position));
+        }
+        system.cell = new_cell;
+        return system;
+    }
+
+    #[test]
+    fn energy() {
+        let system = testing_system();
+        let dispersion = dispersion(4.9);
+        let coefficients = dispersion.coefficients(&system);
+
+        // Dispersion energy of the pairs inside the cutoff
+        let mut pairs = 0.0;
+        for i in 0..system.size() {
+            for j in (i + 1)..system.size() {
+                let r = system.distance(i, j);
+                if r < 4.9 {
+                    pairs -= coefficients[i] * coefficients[j] / f64::powi(r, 6);
+                }
+            }
+        }
+
+        // Reference value from a direct lattice sum
+        let expected = -0.1492804;
+        assert_relative_eq!(pairs + dispersion.energy(&system), expected, max_relative = 1e-5);
+    }
+
+    #[test]
+    fn forces() {
+        let mut system = testing_system();
+        let dispersion = dispersion(4.0);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        dispersion.forces(&system, &mut forces);
+
+        let eps = 1e-6;
+        for i in 0..system.size() {
+            for k in 0..3 {
+                system.particles_mut().position[i][k] += eps;
+                let e1 = dispersion.energy(&system);
+                system.particles_mut().position[i][k] -= 2.0 * eps;
+                let e2 = dispersion.energy(&system);
+                system.particles_mut().position[i][k] += eps;
+                assert_relative_eq!(-(e1 - e2) / (2.0 * eps), forces[i][k], epsilon = 1e-7);
+            }
+        }
+    }
+
+    #[test]
+    fn atomic_virial() {
+        let system = testing_system();
+        let dispersion = dispersion(4.0);
+        let virial = dispersion.atomic_virial(&system);
+
+        // Only use diagonal deformations, which keep the cell orthorhombic
+        let eps = 1e-6;
+        for i in 0..3 {
+            let e1 = dispersion.energy(&scaled(&system, i, eps));
+            let e2 = dispersion.energy(&scaled(&system, i, -eps));
+            let finite_diff = -(e1 - e2) / (2.0 * eps);
+            assert_relative_eq!(virial[i][i], finite_diff, epsilon = 1e-7);
+        }
+    }
+
+    #[test]
+    fn restriction() {
+        let mut system = testing_system();
+        assert!(system.add_bond(0, 1).is_empty());
+        let mut dispersion = dispersion(4.0);
+        let without = dispersion.energy(&system);
+
+        // The excluded pair is inside the cutoff, so nothing changes
+        dispersion.set_restriction(PairRestriction::InterMolecular);
+        assert_relative_eq!(dispersion.energy(&system), without, max_relative = 1e-12);
+
+        // The excluded pair outside of the cutoff is removed from the
+        // long-range part
+        system.particles_mut().position[1] = Vector3D::new(4.5, 0.3, 0.2);
+        let mut unrestricted = dispersion.clone();
+        unrestricted.set_restriction(PairRestriction::None);
+        let r = system.distance(0, 1);
+        let expected = unrestricted.energy(&system) + 2.0 * (1.0 - damping(dispersion.alpha * r)) / f64::powi(r, 6);
+        assert_relative_eq!(dispersion.energy(&system), expected, max_relative = 1e-12);
+    }
+
+    #[test]
+    fn scaled_restriction() {
+        let mut system = testing_system();
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(1, 2).is_empty());
+        assert!(system.add_bond(2, 3).is_empty());
+        let mut dispersion = dispersion(4.0);
+        let coefficients = dispersion.coefficients(&system);
+        let unrestricted = dispersion.energy(&system);
+
+        // All the pairs except 0-1 are outside of the cutoff: 1-2 pairs and
+        // 1-3 pairs are excluded, and the 1-4 pair is scaled.
+        dispersion.set_restriction(PairRestriction::Scale14(0.3));
+        let mut expected = unrestricted;
+        for &(i, j, factor) in &[(1, 2, 1.0), (2, 3, 1.0), (0, 2, 1.0), (1, 3, 1.0), (0, 3, 0.7)] {
+            let r = system.distance(i, j);
+            assert!(r > 4.0);
+            let cij = coefficients[i] * coefficients[j];
+            expected += factor * cij * (1.0 - damping(dispersion.alpha * r)) / f64::powi(r, 6);
+        }
+        assert_relative_eq!(dispersion.energy(&system), expected, max_relative = 1e-12);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        dispersion.forces(&system, &mut forces);
+        let eps = 1e-6;
+        for i in 0..system.size() {
+            for k in 0..3 {
+                system.particles_mut().position[i][k] += eps;
+                let e1 = dispersion.energy(&system);
+                system.particles_mut().position[i][k] -= 2.0 * eps;
+                let e2 = dispersion.energy(&system);
+                system.particles_mut().position[i][k] += eps;
+                assert_relative_eq!(-(e1 - e2) / (2.0 * eps), forces[i][k], epsilon = 1e-7);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "intra-molecular restriction can not be used with LJ-PME")]
+    fn intra_molecular_restriction() {
+        let mut dispersion = dispersion(4.0);
+        dispersion.set_restriction(PairRestriction::IntraMolecular);
+    }
+
+    #[test]
+    fn move_molecule() {
+        let mut system = testing_system();
+        let dispersion = dispersion(4.0);
+        let old_energy = dispersion.energy(&system);
+
+        let new_positions = &[Vector3D::new(2.0, 2.5, 1.0)];
+        let cost = dispersion.move_molecule_cost(&system, 2, new_positions);
+
+        system.particles_mut().position[2] = new_positions[0];
+        assert_relative_eq!(cost, dispersion.energy(&system) - old_energy, max_relative = 1e-12);
+    }
+    #[test]
+    fn move_rigid_molecule() {
+        let mut system = testing_system();
+        assert!(system.add_bond(0, 1).is_empty());
+        let mut dispersion = dispersion(4.0);
+        dispersion.set_restriction(PairRestriction::InterMolecular);
+        let old_energy = dispersion.energy(&system);
+
+        let delta = Vector3D::new(1.2, -0.7, 2.1);
+        let new_positions = &[
+            system.particles().position[0] + delta,
+            system.particles().position[1] + delta,
+        ];
+        let molecule_id = system.molecule_id(0);
+        let cost = dispersion.move_molecule_cost(&system, molecule_id, new_positions);
+
+        system.particles_mut().position[0] = new_positions[0];
+        system.particles_mut().position[1] = new_positions[1];
+        assert_relative_eq!(cost, dispersion.energy(&system) - old_energy, max_relative = 1e-9);
+    }
+}
@@ -0,0 +1,274 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use crate::PairRestriction;
+use crate::Configuration;
+use crate::{Matrix3, Vector3D};
+
+use super::{CoulombicPotential, GlobalCache, GlobalPotential};
+use super::DampedCoulomb;
+
+/// Damped shifted force (DSF) method for coulombic interactions.
+///
+/// This is a pairwise method where the coulombic interaction is damped with a
+/// complementary error function, and both the energy and the force are
+/// shifted to go smoothly to zero at the cutoff [Fennell2006]. The pair
+/// energy is
+///
+/// ```text
+/// V(r) = q_i q_j / (4 π ε_0) [erfc(α r) / r - erfc(α r_c) / r_c + (r - r_c) f_c]
+/// f_c = erfc(α r_c) / r_c² + 2 α / √π exp(-α² r_c²) / r_c
+/// ```
+///
+/// Each charged particle also gets a self-interaction energy of `- q_i² /
+/// (4 π ε_0) (erfc(α r_c) / (2 r_c) + α / √π)`.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::sys::{Particle, Molecule, UnitCell, System};
+/// # use lumol_core::energy::DampedShiftedForce;
+/// # use lumol_core::types::Vector3D;
+/// // DSF with a cutoff of 12 A, and a damping parameter of 0.2 A^-1
+/// let dsf = DampedShiftedForce::new(12.0, 0.2);
+///
+/// // Setup a system containing a NaCl pair
+/// let mut system = System::with_cell(UnitCell::cubic(30.0));
+///
+/// let mut na = Particle::new("Na");
+/// na.charge = 1.0;
+/// na.position = Vector3D::new(0.0, 0.0, 0.0);
+///
+/// let mut cl = Particle::new("Cl");
+/// cl.charge = -1.0;
+/// cl.position = Vector3D::new(2.0, 0.0, 0.0);
+///
+/// system.add_molecule(Molecule::new(na));
+/// system.add_molecule(Molecule::new(cl));
+///
+/// // Use DSF for electrostatic interactions
+/// system.set_coulomb_potential(Box::new(dsf));
+///
+/// println!("energy is {}", system.potential_energy());
+/// ```
+///
+/// [Fennell2006]: Fennell, C. J. & Gezelter, J. D. J. Chem. Phys. 124, 234104 (2006).
+#[derive(Clone)]
+pub struct DampedShiftedForce {
+    /// Damped coulombic interactions with shifted forces
+    coulomb: DampedCoulomb,
+}
+
+impl DampedShiftedForce {
+    /// Create a new damped shifted force solver, using a cutoff of `cutoff`
+    /// and the damping parameter `alpha`. A value of `alpha = 0` gives an
+    /// undamped shifted force potential.
+    pub fn new(cutoff: f64, alpha: f64) -> DampedShiftedForce {
+        assert!(cutoff > 0.0, "Got a negative cutoff in damped shifted force");
+        assert!(alpha >= 0.0, "Got a negative alpha in damped shifted force");
+        DampedShiftedForce {
+            coulomb: DampedCoulomb::new(cutoff, alpha, true),
+        }
+    }
+
+    /// Get the damping parameter
+    pub fn alpha(&self) -> f64 {
+        self.coulomb.alpha
+    }
+}
+
+impl GlobalCache for DampedShiftedForce {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        self.coulomb.move_molecule_cost(configuration, molecule_id, new_positions)
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+impl GlobalPotential for DampedShiftedForce {
+    fn cutoff(&self) -> Option<f64> {
+        Some(self.coulomb.cutoff)
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        self.coulomb.energy(configuration)
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        self.coulomb.forces(configuration, forces);
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        self.coulomb.atomic_virial(configuration)
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        self.coulomb.molecular_virial(configuration)
+    }
+}
+
+impl CoulombicPotential for DampedShiftedForce {
+    fn set_restriction(&mut self, restriction: PairRestriction) {
+        self.coulomb.restriction = restriction;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use std::f64::consts::{PI, FRAC_2_SQRT_PI};
+
+    use crate::{System, Matrix3};
+    use crate::consts::FOUR_PI_EPSILON_0;
+    use crate::math::erfc;
+    use crate::GlobalPotential;
+    use crate::utils::system_from_xyz;
+
+    use approx::{assert_ulps_eq, assert_relative_eq};
+
+    pub fn testing_system() -> System {
+        let mut system = system_from_xyz(
+            "2
+            cell: 20.0
+            Cl 0.0 0.0 0.0
+            Na 1.5 0.0 0.0
+            ",
+        );
+        system.particles_mut().charge[0] = -1.0;
+        system.particles_mut().charge[1] = 1.0;
+        return system;
+    }
+
+    #[test]
+    fn energy() {
+        let system = testing_system();
+        let dsf = DampedShiftedForce::new(8.0, 0.2);
+
+        let alpha: f64 = 0.2;
+        let energy_constant = erfc(alpha * 8.0) / 8.0;
+        let force_constant = erfc(alpha * 8.0) / 64.0 + FRAC_2_SQRT_PI * alpha * f64::exp(-alpha * alpha * 64.0) / 8.0;
+        let pair = -(erfc(alpha * 1.5) / 1.5 - energy_constant + (1.5 - 8.0) * force_constant);
+        let self_energy = -2.0 * (0.5 * energy_constant + alpha / f64::sqrt(PI));
+        let expected = (pair + self_energy) / FOUR_PI_EPSILON_0;
+        assert_relative_eq!(dsf.energy(&system), expected, max_relative = 1e-12);
+    }
+
+    #[test]
+    fn cutoff() {
+        let mut system = testing_system();
+        let dsf = DampedShiftedForce::new(8.0, 0.2);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        // Both the energy and the forces go continuously to zero at the cutoff
+        system.particles_mut().position[1][0] = 8.0 - 1e-9;
+        let alpha: f64 = 0.2;
+        let energy_constant = erfc(alpha * 8.0) / 8.0;
+        let self_energy = -2.0 * (0.5 * energy_constant + alpha / f64::sqrt(PI)) / FOUR_PI_EPSILON_0;
+        assert!((dsf.energy(&system) - self_energy).abs() < 1e-9);
+        dsf.forces(&system, &mut forces);
+        assert!(forces[0].norm() < 1e-9);
+
+        system.particles_mut().position[1][0] = 8.5;
+        assert_relative_eq!(dsf.energy(&system), self_energy, max_relative = 1e-12);
+    }
+
+    #[test]
+    fn forces() {
+        let mut system = testing_system();
+        let dsf = DampedShiftedForce::new(8.0, 0.2);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        dsf.forces(&system, &mut forces);
+        let norm = (forces[0] + forces[1]).norm();
+        // Total force should be null
+        assert_ulps_eq!(norm, 0.0);
+
+        // Finite difference computation of the force
+        let e = dsf.energy(&system);
+        let eps = 1e-9;
+        system.particles_mut().position[0][0] += eps;
+
+        let e1 = dsf.energy(&system);
+        assert_relative_eq!((e - e1) / eps, forces[0][0], epsilon = 1e-6);
+    }
+
+    #[test]
+    fn atomic_virial() {
+        let system = testing_system();
+        let dsf = DampedShiftedForce::new(8.0, 0.2);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        dsf.forces(&system, &mut forces);
+        let force = forces[0][0];
+        let expected = Matrix3::new([[-force * 1.5, 0.0, 0.0], [0.0; 3], [0.0; 3]]);
+
+        assert_eq!(dsf.atomic_virial(&system), expected);
+    }
+
+    mod cache {
+        use super::*;
+        use crate::{CoulombicPotential, GlobalCache, GlobalPotential, PairRestriction};
+        use crate::System;
+        use crate::Vector3D;
+        use crate::utils::system_from_xyz;
+
+        pub fn testing_system() -> System {
+            let mut system = system_from_xyz(
+                "6
+                cell: 20.0
+                O  0.0  0.0  0.0
+                H -0.7 -0.7  0.3
+                H  0.3 -0.3 -0.8
+                O  2.0  2.0  0.0
+                H  1.3  1.3  0.3
+                H  2.3  1.7 -0.8
+                ",
+            );
+            assert!(system.add_bond(0, 1).is_empty());
+            assert!(system.add_bond(0, 2).is_empty());
+            assert!(system.add_bond(3, 4).is_empty());
+            assert!(system.add_bond(3, 5).is_empty());
+            assert!(system.molecules().count() == 2);
+
+            for particle in system.particles_mut() {
+                if particle.name == "O" {
+                    *particle.charge = -0.8476;
+                } else if particle.name == "H" {
+                    *particle.charge = 0.4238;
+                }
+            }
+            return system;
+        }
+
+        #[test]
+        #[allow(clippy::unreadable_literal)]
+        fn move_rigid_molecule() {
+            let mut system = testing_system();
+            let mut dsf = DampedShiftedForce::new(8.0, 0.2);
+            dsf.set_restriction(PairRestriction::InterMolecular);
+
+            let check = dsf.clone();
+
+            let old_energy = check.energy(&system);
+
+            let new_positions = &[
+                Vector3D::new(4.0, 0.0, -2.0),
+                Vector3D::new(3.010010191494968, 0.19045656166589708, -2.1166435218719863),
+                Vector3D::new(4.0761078062722484, -0.8995901989882638, -2.0703212322750546),
+            ];
+            let cost = dsf.move_molecule_cost(&system, 0, new_positions);
+
+            system.particles_mut().position[0] = new_positions[0];
+            system.particles_mut().position[1] = new_positions[1];
+            system.particles_mut().position[2] = new_positions[2];
+            let new_energy = check.energy(&system);
+            assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-12);
+        }
+    }
+}
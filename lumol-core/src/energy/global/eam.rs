@@ -0,0 +1,950 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::SplitWhitespace;
+
+use rayon::prelude::*;
+
+use crate::units;
+use crate::utils::ThreadLocalVec;
+use crate::sys::NeighborList;
+use crate::Configuration;
+use crate::{Matrix3, Vector3D};
+
+use super::{GlobalCache, GlobalPotential};
+
+/// Conversion factor from the effective charges `Z` in funcfl files to pair
+/// energies in eV: `φ(r) = 27.2 × 0.529 Z²(r) / r`. This is the value used by
+/// DYNAMO and LAMMPS, and the potentials in funcfl format are fitted with it.
+const FUNCFL_CHARGE_FACTOR: f64 = 27.2 * 0.529;
+
+/// A function tabulated on a regular grid starting at 0, and interpolated with
+/// cubic polynomials. This uses the same interpolation scheme as LAMMPS, in
+/// order to give the same results when using the same tabulated potentials.
+#[derive(Clone, Debug)]
+struct CubicTable {
+    /// Distance between two tabulated points
+    step: f64,
+    /// Polynomial coefficients for each interval, from the constant term to
+    /// the cubic term, in the reduced variable `x / step`.
+    coefficients: Vec<[f64; 4]>,
+}
+
+impl CubicTable {
+    /// Create a new table from the `values` at `0, step, 2 step, ...`. The
+    /// derivatives at each point are estimated with finite differences.
+    fn new(step: f64, values: &[f64]) -> CubicTable {
+        let n = values.len();
+        assert!(n >= 5, "CubicTable needs at least 5 points");
+
+        let mut derivatives = vec![0.0; n];
+        derivatives[0] = values[1] - values[0];
+        derivatives[1] = 0.5 * (values[2] - values[0]);
+        derivatives[n - 2] = 0.5 * (values[n - 1] - values[n - 3]);
+        derivatives[n - 1] = values[n - 1] - values[n - 2];
+        for i in 2..n - 2 {
+            derivatives[i] = ((values[i - 2] - values[i + 2]) + 8.0 * (values[i + 1] - values[i - 1])) / 12.0;
+        }
+
+        let coefficients = (0..n - 1).map(|i| {
+            let delta = values[i + 1] - values[i];
+            [
+                values[i],
+                derivatives[i],
+                3.0 * delta - 2.0 * derivatives[i] - derivatives[i + 1],
+                derivatives[i] + derivatives[i + 1] - 2.0 * delta,
+            ]
+        }).collect();
+
+        CubicTable {
+            step: step,
+            coefficients: coefficients,
+        }
+    }
+
+    /// Get the value and the derivative of the tabulated function at `x`.
+    /// Past the end of the table, the function is linearly extrapolated.
+    fn eval(&self, x: f64) -> (f64, f64) {
+        let reduced = x / self.step;
+        let last = self.coefficients.len() - 1;
+        let index = usize::min(reduced as usize, last);
+        let [c0, c1, c2, c3] = self.coefficients[index];
+
+        let t = reduced - index as f64;
+        if t > 1.0 {
+            let value = c0 + c1 + c2 + c3;
+            let derivative = (c1 + 2.0 * c2 + 3.0 * c3) / self.step;
+            return (value + derivative * (t - 1.0) * self.step, derivative);
+        }
+
+        let value = ((c3 * t + c2) * t + c1) * t + c0;
+        let derivative = ((3.0 * c3 * t + 2.0 * c2) * t + c1) / self.step;
+        return (value, derivative);
+    }
+}
+
+/// Embedded-atom method (EAM) potential for metals and alloys.
+///
+/// In the EAM formalism [Daw1984], the energy of the system is the sum of a
+/// pair potential and of the energy needed to embed each atom in the electronic
+/// density created by its neighbors:
+///
+/// $$ E = \sum_i F_i(\rho_i) + \frac 12 \sum_{i \neq j} \phi_{ij}(r_{ij}), $$
+///
+/// $$ \rho_i = \sum_{j \neq i} f_{ji}(r_{ij}). $$
+///
+/// The embedding function `F`, the density function `f` and the pair potential
+/// `φ` depend on the elements of the atoms. In the Finnis-Sinclair variant of
+/// the method [Finnis1984], the density function depends on the elements of
+/// both atoms, while it only depends on the element contributing the density
+/// for EAM.
+///
+/// These functions are read from tabulated files, using the same formats as
+/// [DYNAMO] and [LAMMPS]: funcfl for single element EAM potentials, setfl for
+/// multiple elements EAM potentials, and the Finnis-Sinclair variant of setfl.
+/// Energies in these files are expressed in eV, and distances in Angstroms.
+///
+/// The elements in the potential are matched with the particles names, and
+/// particles with another name do not interact with this potential. All the
+/// pairs of particles interact, including particles in the same molecule.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use lumol_core::energy::Eam;
+/// # use lumol_core::sys::System;
+/// let eam = Eam::read_setfl("CuNi.eam.alloy").expect("invalid potential file");
+///
+/// let mut system = System::new();
+/// system.add_global_potential(Box::new(eam));
+/// ```
+///
+/// [Daw1984]: Daw, M. S. & Baskes, M. I. Phys. Rev. B 29, 6443 (1984).
+/// [Finnis1984]: Finnis, M. W. & Sinclair, J. E. Phil. Mag. A 50, 45 (1984).
+/// [DYNAMO]: https://www.ctcms.nist.gov/potentials/
+/// [LAMMPS]: https://lammps.sandia.gov/doc/pair_eam.html
+#[derive(Clone, Debug)]
+pub struct Eam {
+    /// Names of the elements in this potential
+    elements: Vec<String>,
+    /// Cutoff radius of the density and pair functions
+    cutoff: f64,
+    /// Embedding function for each element
+    embedding: Vec<CubicTable>,
+    /// `density[a][b]` is the density created by an atom of element `a` at the
+    /// position of an atom of element `b`
+    density: Vec<Vec<CubicTable>>,
+    /// `r φ(r)` for each pair of elements
+    pair: Vec<Vec<CubicTable>>,
+    /// Neighbor list for the pairs closer than the cutoff
+    neighbors: NeighborList,
+}
+
+impl Eam {
+    /// Read a single element EAM potential in the DYNAMO funcfl format from the
+    /// file at `path`. The potential will apply to particles with the given
+    /// `element` name.
+    pub fn read_funcfl<P: AsRef<Path>>(path: P, element: &str) -> io::Result<Eam> {
+        let content = fs::read_to_string(path)?;
+        return Eam::parse_funcfl(&content, element);
+    }
+
+    /// Read an EAM potential in the DYNAMO setfl format from the file at
+    /// `path`. This format is also known as `eam/alloy` in LAMMPS.
+    pub fn read_setfl<P: AsRef<Path>>(path: P) -> io::Result<Eam> {
+        let content = fs::read_to_string(path)?;
+        return Eam::parse_setfl(&content, false);
+    }
+
+    /// Read a Finnis-Sinclair potential in the `eam/fs` format from LAMMPS
+    /// from the file at `path`.
+    pub fn read_finnis_sinclair<P: AsRef<Path>>(path: P) -> io::Result<Eam> {
+        let content = fs::read_to_string(path)?;
+        return Eam::parse_setfl(&content, true);
+    }
+
+    /// Get the names of the elements in this potential
+    pub fn elements(&self) -> &[String] {
+        &self.elements
+    }
+
+    fn parse_funcfl(content: &str, element: &str) -> io::Result<Eam> {
+        // Skip the comment line
+        let content = content.splitn(2, '\n').nth(1).unwrap_or("");
+        let mut tokens = Tokens::new(content);
+
+        // Atomic number, mass, lattice constant and lattice type are not used
+        tokens.skip(4, "element header")?;
+        let grid = Grid::read(&mut tokens)?;
+
+        let embedding = tokens.values(grid.nrho, "embedding function")?;
+        let charges = tokens.values(grid.nr, "effective charge function")?;
+        let density = tokens.values(grid.nr, "density function")?;
+
+        let pair = charges.iter().map(|z| FUNCFL_CHARGE_FACTOR * z * z).collect::<Vec<_>>();
+        return Ok(Eam::new(
+            vec![element.into()],
+            &grid,
+            vec![embedding],
+            vec![vec![density]],
+            vec![vec![pair]],
+        ));
+    }
+
+    fn parse_setfl(content: &str, finnis_sinclair: bool) -> io::Result<Eam> {
+        // Skip the three comment lines
+        let content = content.splitn(4, '\n').nth(3).unwrap_or("");
+        let (header, content) = match content.find('\n') {
+            Some(position) => content.split_at(position),
+            None => (content, ""),
+        };
+
+        let mut header = header.split_whitespace();
+        let count = header.next().ok_or(invalid_data("missing number of elements"))?;
+        let count = count.parse::<usize>().map_err(|_| invalid_data(
+            format!("could not parse '{}' as a number of elements", count)
+        ))?;
+        let elements = header.map(String::from).collect::<Vec<_>>();
+        if count == 0 || elements.len() != count {
+            return Err(invalid_data(format!(
+                "expected {} elements names, got {}", count, elements.len()
+            )));
+        }
+
+        let mut tokens = Tokens::new(content);
+        let grid = Grid::read(&mut tokens)?;
+
+        let mut embedding = Vec::new();
+        let mut density = Vec::new();
+        for name in &elements {
+            // Atomic number, mass, lattice constant and lattice type are not used
+            tokens.skip(4, "element header")?;
+            embedding.push(tokens.values(grid.nrho, &format!("embedding function for {}", name))?);
+            if finnis_sinclair {
+                let mut functions = Vec::new();
+                for other in &elements {
+                    let what = format!("density function for {}-{}", name, other);
+                    functions.push(tokens.values(grid.nr, &what)?);
+                }
+                density.push(functions);
+            } else {
+                let function = tokens.values(grid.nr, &format!("density function for {}", name))?;
+                density.push(vec![function; count]);
+            }
+        }
+
+        let mut pair = vec![Vec::new(); count];
+        for i in 0..count {
+            for j in 0..=i {
+                let what = format!("pair function for {}-{}", elements[i], elements[j]);
+                pair[i].push(tokens.values(grid.nr, &what)?);
+            }
+        }
+        // Fill the upper half of the pair functions matrix
+        for i in 0..count {
+            for j in i + 1..count {
+                let function = pair[j][i].clone();
+                pair[i].push(function);
+            }
+        }
+
+        if let Some(token) = tokens.iter.next() {
+            return Err(invalid_data(format!("unexpected data '{}' at the end of the file", token)));
+        }
+
+        return Ok(Eam::new(elements, &grid, embedding, density, pair));
+    }
+
+    /// Create a new potential from the tabulated values of the functions, with
+    /// energies in eV.
+    fn new(
+        elements: Vec<String>,
+        grid: &Grid,
+        embedding: Vec<Vec<f64>>,
+        density: Vec<Vec<Vec<f64>>>,
+        pair: Vec<Vec<Vec<f64>>>,
+    ) -> Eam {
+        let ev = units::from(1.0, "eV").expect("eV is a valid unit");
+        let to_internal = |values: Vec<f64>| values.into_iter().map(|value| value * ev).collect::<Vec<_>>();
+
+        Eam {
+            elements: elements,
+            cutoff: grid.cutoff,
+            embedding: embedding.into_iter().map(|values| {
+                CubicTable::new(grid.drho, &to_internal(values))
+            }).collect(),
+            density: density.into_iter().map(|functions| {
+                functions.iter().map(|values| CubicTable::new(grid.dr, values)).collect()
+            }).collect(),
+            pair: pair.into_iter().map(|functions| {
+                functions.into_iter().map(|values| CubicTable::new(grid.dr, &to_internal(values))).collect()
+            }).collect(),
+            neighbors: NeighborList::default(),
+        }
+    }
+
+    /// Get the element index for all the particles in the `configuration`,
+    /// or `None` for particles not interacting with this potential.
+    fn elements_indexes(&self, configuration: &Configuration) -> Vec<Option<usize>> {
+        configuration.particles().name.iter().map(|name| {
+            self.elements.iter().position(|element| element == name)
+        }).collect()
+    }
+
+    /// Get the density created by an atom of element `a` at the distance `r`
+    /// of an atom of element `b`.
+    #[inline]
+    fn density(&self, a: usize, b: usize, r: f64) -> f64 {
+        if r >= self.cutoff {
+            0.0
+        } else {
+            self.density[a][b].eval(r).0
+        }
+    }
+
+    /// Get the pair energy for atoms of element `a` and `b` at distance `r`
+    #[inline]
+    fn pair_energy(&self, a: usize, b: usize, r: f64) -> f64 {
+        if r >= self.cutoff {
+            0.0
+        } else {
+            self.pair[a][b].eval(r).0 / r
+        }
+    }
+
+    /// Compute the electronic density at all the particles
+    fn densities(&self, configuration: &Configuration, elements: &[Option<usize>]) -> Vec<f64> {
+        let natoms = configuration.size();
+        let neighbors = self.neighbors.neighbors(configuration, Some(self.cutoff));
+        // To avoid race conditions, each thread needs its own local densities
+        let thread_local_densities = ThreadLocalVec::with_size(natoms);
+
+        (0..natoms).into_par_iter().for_each(|i| {
+            let a = match elements[i] {
+                Some(a) => a,
+                None => return,
+            };
+
+            let mut densities = thread_local_densities.borrow_mut();
+            for j in neighbors.of(i) {
+                if let Some(b) = elements[j] {
+                    let r = configuration.distance(i, j);
+                    densities[i] += self.density(b, a, r);
+                    densities[j] += self.density(a, b, r);
+                }
+            }
+        });
+
+        let mut densities = vec![0.0; natoms];
+        thread_local_densities.sum_into(&mut densities);
+        return densities;
+    }
+
+    /// Get the derivative of the embedding energy for all the particles
+    fn embedding_derivatives(&self, configuration: &Configuration, elements: &[Option<usize>]) -> Vec<f64> {
+        let densities = self.densities(configuration, elements);
+        return densities.iter().zip(elements).map(|(&density, element)| {
+            element.map_or(0.0, |a| self.embedding[a].eval(density).1)
+        }).collect();
+    }
+
+    /// Compute the force acting on the particle `i` of element `a` because of
+    /// the particle `j` of element `b`, with `rij` the vector between them.
+    /// `fp_i` and `fp_j` are the derivatives of the embedding energy for `i`
+    /// and `j`.
+    #[inline]
+    fn force_pair(&self, (a, fp_i): (usize, f64), (b, fp_j): (usize, f64), rij: Vector3D) -> Vector3D {
+        let r = rij.norm();
+        if r >= self.cutoff {
+            return Vector3D::zero();
+        }
+
+        let (r_phi, r_phi_derivative) = self.pair[a][b].eval(r);
+        let phi_derivative = r_phi_derivative / r - r_phi / (r * r);
+        let density_i = self.density[b][a].eval(r).1;
+        let density_j = self.density[a][b].eval(r).1;
+
+        let derivative = phi_derivative + fp_i * density_i + fp_j * density_j;
+        return -derivative / r * rij;
+    }
+}
+
+impl GlobalCache for Eam {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let natoms = configuration.size();
+        let elements = self.elements_indexes(configuration);
+        let positions = configuration.particles().position;
+        let moved = configuration.molecule(molecule_id).indexes();
+        let new_position = |i: usize| {
+            if moved.contains(&i) {
+                new_positions[i - moved.start]
+            } else {
+                positions[i]
+            }
+        };
+
+        // Pair energy between the moved particles and all the other
+        // particles, and changes in the densities created by the move
+        let old_densities = self.densities(configuration, &elements);
+        let mut new_densities = old_densities.clone();
+        let mut cost = 0.0;
+        for i in moved.clone() {
+            let a = match elements[i] {
+                Some(a) => a,
+                None => continue,
+            };
+
+            for j in 0..natoms {
+                // Only count once the pairs inside the molecule
+                if i == j || (moved.contains(&j) && j < i) {
+                    continue;
+                }
+
+                if let Some(b) = elements[j] {
+                    let old_r = configuration.distance(i, j);
+                    let new_r = configuration.cell.distance(&new_position(i), &new_position(j));
+                    if old_r >= self.cutoff && new_r >= self.cutoff {
+                        continue;
+                    }
+                    cost += self.pair_energy(a, b, new_r) - self.pair_energy(a, b, old_r);
+                    new_densities[i] += self.density(b, a, new_r) - self.density(b, a, old_r);
+                    new_densities[j] += self.density(a, b, new_r) - self.density(a, b, old_r);
+                }
+            }
+        }
+
+        // Embedding energy for all the particles with a modified density
+        let mut embedding = 0.0;
+        for (i, element) in elements.iter().enumerate() {
+            if let Some(a) = *element {
+                if new_densities[i] != old_densities[i] {
+                    embedding += self.embedding[a].eval(new_densities[i]).0;
+                    embedding -= self.embedding[a].eval(old_densities[i]).0;
+                }
+            }
+        }
+
+        return cost + embedding;
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+impl GlobalPotential for Eam {
+    fn cutoff(&self) -> Option<f64> {
+        Some(self.cutoff)
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        let natoms = configuration.size();
+        let elements = self.elements_indexes(configuration);
+        let densities = self.densities(configuration, &elements);
+        let neighbors = self.neighbors.neighbors(configuration, Some(self.cutoff));
+
+        let energies = (0..natoms).into_par_iter().map(|i| {
+            let a = match elements[i] {
+                Some(a) => a,
+                None => return 0.0,
+            };
+
+            let mut energy = self.embedding[a].eval(densities[i]).0;
+            for j in neighbors.of(i) {
+                if let Some(b) = elements[j] {
+                    energy += self.pair_energy(a, b, configuration.distance(i, j));
+                }
+            }
+            return energy;
+        });
+        return energies.sum();
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+
+        let natoms = configuration.size();
+        let elements = self.elements_indexes(configuration);
+        let embedding = self.embedding_derivatives(configuration, &elements);
+        let neighbors = self.neighbors.neighbors(configuration, Some(self.cutoff));
+        // To avoid race conditions, each thread needs its own local forces Vec
+        let thread_local_forces = ThreadLocalVec::with_size(natoms);
+
+        (0..natoms).into_par_iter().for_each(|i| {
+            let a = match elements[i] {
+                Some(a) => a,
+                None => return,
+            };
+
+            // Get the thread local forces Vec
+            let mut forces = thread_local_forces.borrow_mut();
+            let mut force_i = Vector3D::zero();
+            for j in neighbors.of(i) {
+                if let Some(b) = elements[j] {
+                    let rij = configuration.nearest_image(i, j);
+                    let force = self.force_pair((a, embedding[i]), (b, embedding[j]), rij);
+                    force_i += force;
+                    forces[j] -= force;
+                }
+            }
+            forces[i] += force_i;
+        });
+
+        // At this point all the forces are computed, but the results are
+        // scattered across all thread local Vecs, here we gather them.
+        thread_local_forces.sum_into(forces);
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let natoms = configuration.size();
+        let elements = self.elements_indexes(configuration);
+        let embedding = self.embedding_derivatives(configuration, &elements);
+        let neighbors = self.neighbors.neighbors(configuration, Some(self.cutoff));
+
+        let virials = (0..natoms).into_par_iter().map(|i| {
+            let a = match elements[i] {
+                Some(a) => a,
+                None => return Matrix3::zero(),
+            };
+
+            let mut local_virial = Matrix3::zero();
+            for j in neighbors.of(i) {
+                if let Some(b) = elements[j] {
+                    let rij = configuration.nearest_image(i, j);
+                    let force = self.force_pair((a, embedding[i]), (b, embedding[j]), rij);
+                    local_virial += force.tensorial(&rij);
+                }
+            }
+            return local_virial;
+        });
+        return virials.sum();
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let atomic = self.atomic_virial(configuration);
+
+        let mut forces = vec![Vector3D::zero(); configuration.size()];
+        self.forces(configuration, &mut forces);
+
+        // Remove the contribution of the intra-molecular part of the forces
+        let positions = configuration.particles().position;
+        let mut correction = Matrix3::zero();
+        for molecule in configuration.molecules() {
+            let com = molecule.center_of_mass();
+            for i in molecule.indexes() {
+                correction += forces[i].tensorial(&(positions[i] - com));
+            }
+        }
+
+        return atomic - correction;
+    }
+}
+
+/// Definition of the regular grids used in EAM files
+struct Grid {
+    /// Number of points in the embedding functions
+    nrho: usize,
+    /// Spacing between points in the embedding functions
+    drho: f64,
+    /// Number of points in the density and pair functions
+    nr: usize,
+    /// Spacing between points in the density and pair functions
+    dr: f64,
+    /// Cutoff radius
+    cutoff: f64,
+}
+
+impl Grid {
+    fn read(tokens: &mut Tokens<'_>) -> io::Result<Grid> {
+        let nrho = tokens.integer("number of points in embedding functions")?;
+        let drho = tokens.number("spacing of embedding functions")?;
+        let nr = tokens.integer("number of points in density and pair functions")?;
+        let dr = tokens.number("spacing of density and pair functions")?;
+        let cutoff = tokens.number("cutoff")?;
+
+        if nrho < 5 || nr < 5 {
+            return Err(invalid_data("tabulated functions must contain at least 5 points"));
+        }
+        if drho <= 0.0 || dr <= 0.0 {
+            return Err(invalid_data("tabulated functions spacing must be positive"));
+        }
+        if cutoff <= 0.0 {
+            return Err(invalid_data("the cutoff must be positive"));
+        }
+
+        Ok(Grid {
+            nrho: nrho,
+            drho: drho,
+            nr: nr,
+            dr: dr,
+            cutoff: cutoff,
+        })
+    }
+}
+
+/// Iterator over the whitespace-separated tokens in EAM files
+struct Tokens<'a> {
+    iter: SplitWhitespace<'a>,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(content: &'a str) -> Tokens<'a> {
+        Tokens {
+            iter: content.split_whitespace(),
+        }
+    }
+
+    fn next(&mut self, what: &str) -> io::Result<&'a str> {
+        self.iter.next().ok_or(invalid_data(format!("unexpected end of file while reading {}", what)))
+    }
+
+    fn skip(&mut self, count: usize, what: &str) -> io::Result<()> {
+        for _ in 0..count {
+            let _ = self.next(what)?;
+        }
+        Ok(())
+    }
+
+    fn number(&mut self, what: &str) -> io::Result<f64> {
+        let token = self.next(what)?;
+        return token.parse().map_err(|_| invalid_data(
+            format!("could not parse '{}' as a number in {}", token, what)
+        ));
+    }
+
+    fn integer(&mut self, what: &str) -> io::Result<usize> {
+        let token = self.next(what)?;
+        return token.parse().map_err(|_| invalid_data(
+            format!("could not parse '{}' as an integer in {}", token, what)
+        ));
+    }
+
+    fn values(&mut self, count: usize, what: &str) -> io::Result<Vec<f64>> {
+        (0..count).map(|_| self.number(what)).collect()
+    }
+}
+
+fn invalid_data<S: Into<String>>(message: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{System, UnitCell, Molecule, Particle};
+    use crate::utils::system_from_xyz;
+
+    use approx::assert_relative_eq;
+
+    const CUTOFF: f64 = 5.0;
+    const NRHO: usize = 2000;
+    const DRHO: f64 = 0.005;
+    const NR: usize = 2000;
+    const DR: f64 = 0.0026;
+
+    /// Density created by an atom of element `a`, in eV
+    fn density(a: usize, r: f64) -> f64 {
+        let scale = [1.0, 0.8][a];
+        if r >= CUTOFF {
+            0.0
+        } else {
+            scale * f64::exp(-r) * (CUTOFF - r).powi(3)
+        }
+    }
+
+    fn embedding(a: usize, rho: f64) -> f64 {
+        let scale = [1.5, 2.0][a];
+        return -scale * f64::sqrt(rho) + 0.05 * rho * rho;
+    }
+
+    /// r φ(r) for elements `a` and `b`, in eV Å
+    fn r_phi(a: usize, b: usize, r: f64) -> f64 {
+        let depth = [[0.5, 0.4], [0.4, 0.3]][a][b];
+        if r >= CUTOFF {
+            0.0
+        } else {
+            let x = r - 2.5;
+            r * depth * (f64::exp(-2.0 * x) - 2.0 * f64::exp(-x)) * (CUTOFF - r).powi(2)
+        }
+    }
+
+    fn write_values<F: Fn(f64) -> f64>(content: &mut String, count: usize, step: f64, function: F) {
+        for i in 0..count {
+            content.push_str(&format!("{:e}\n", function(i as f64 * step)));
+        }
+    }
+
+    fn setfl(finnis_sinclair: bool) -> String {
+        let mut content = String::from("comment 1\ncomment 2\ncomment 3\n2 Cu Ni\n");
+        content.push_str(&format!("{} {} {} {} {}\n", NRHO, DRHO, NR, DR, CUTOFF));
+        for a in 0..2 {
+            content.push_str("29 63.546 3.615 fcc\n");
+            write_values(&mut content, NRHO, DRHO, |rho| embedding(a, rho));
+            if finnis_sinclair {
+                for _ in 0..2 {
+                    write_values(&mut content, NR, DR, |r| density(a, r));
+                }
+            } else {
+                write_values(&mut content, NR, DR, |r| density(a, r));
+            }
+        }
+        for a in 0..2 {
+            for b in 0..=a {
+                write_values(&mut content, NR, DR, |r| r_phi(a, b, r));
+            }
+        }
+        return content;
+    }
+
+    fn testing_system() -> System {
+        let mut system = system_from_xyz("4
+        cell: 10.0
+        Cu 0.0 0.0 0.0
+        Ni 2.5 0.1 0.0
+        Cu 0.2 2.4 0.3
+        Ar 1.0 1.0 1.0
+        ");
+        assert!(system.add_bond(0, 1).is_empty());
+        return system;
+    }
+
+    /// Compute the expected energy in eV directly from the analytical functions
+    fn expected_energy(system: &System) -> f64 {
+        let elements = system.particles().name.iter().map(|name| match &**name {
+            "Cu" => Some(0),
+            "Ni" => Some(1),
+            _ => None,
+        }).collect::<Vec<_>>();
+        let mut energy = 0.0;
+        for i in 0..system.size() {
+            let a = match elements[i] {
+                Some(a) => a,
+                None => continue,
+            };
+
+            let mut rho = 0.0;
+            for j in 0..system.size() {
+                if i == j {
+                    continue;
+                }
+                if let Some(b) = elements[j] {
+                    let r = system.distance(i, j);
+                    rho += density(b, r);
+                    energy += 0.5 * r_phi(a, b, r) / r;
+                }
+            }
+            energy += embedding(a, rho);
+        }
+        return energy;
+    }
+
+    #[test]
+    fn interpolation() {
+        let values = (0..20).map(|i| f64::sin(0.1 * i as f64)).collect::<Vec<_>>();
+        let table = CubicTable::new(0.1, &values);
+
+        // Exact at the tabulated points
+        for i in 0..20 {
+            assert_relative_eq!(table.eval(0.1 * i as f64).0, values[i], epsilon = 1e-12);
+        }
+
+        let (value, derivative) = table.eval(0.75);
+        assert_relative_eq!(value, f64::sin(0.75), epsilon = 1e-5);
+        assert_relative_eq!(derivative, f64::cos(0.75), epsilon = 1e-4);
+
+        // Linear extrapolation
+        let (last, last_derivative) = table.eval(1.9);
+        let (value, derivative) = table.eval(2.5);
+        assert_relative_eq!(derivative, last_derivative);
+        assert_relative_eq!(value, last + 0.6 * last_derivative, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn parse() {
+        let eam = Eam::parse_setfl(&setfl(false), false).unwrap();
+        assert_eq!(eam.elements(), &["Cu", "Ni"]);
+        assert_eq!(eam.cutoff(), Some(CUTOFF));
+
+        let eam = Eam::parse_setfl(&setfl(true), true).unwrap();
+        assert_eq!(eam.elements(), &["Cu", "Ni"]);
+
+        let mut content = String::from("comment\n29 63.546 3.615 fcc\n");
+        content.push_str(&format!("{} {} {} {} {}\n", NRHO, DRHO, NR, DR, CUTOFF));
+        write_values(&mut content, NRHO, DRHO, |rho| embedding(0, rho));
+        write_values(&mut content, NR, DR, |_| 1.0);
+        write_values(&mut content, NR, DR, |r| density(0, r));
+        let eam = Eam::parse_funcfl(&content, "Cu").unwrap();
+        assert_eq!(eam.elements(), &["Cu"]);
+
+        // pair potential from the effective charges
+        let ev = units::from(1.0, "eV").unwrap();
+        assert_relative_eq!(eam.pair_energy(0, 0, 2.0), 27.2 * 0.529 / 2.0 * ev, max_relative = 1e-9);
+    }
+
+    #[test]
+    fn errors() {
+        let error = Eam::parse_setfl("a\nb\nc\n2 Cu\n", false).unwrap_err();
+        assert_eq!(error.to_string(), "expected 2 elements names, got 1");
+
+        let error = Eam::parse_setfl("a\nb\nc\n1 Cu\n3 0.1 100 0.1 5.0\n", false).unwrap_err();
+        assert_eq!(error.to_string(), "tabulated functions must contain at least 5 points");
+
+        let error = Eam::parse_setfl("a\nb\nc\n1 Cu\n10 0.1 10 0.1 5.0\n29 63.546 3.615 fcc\n1 2 3", false).unwrap_err();
+        assert_eq!(error.to_string(), "unexpected end of file while reading embedding function for Cu");
+
+        let error = Eam::parse_funcfl("a\n29 63.546 3.615 fcc\n10 0.1 ten 0.1 5.0\n", "Cu").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "could not parse 'ten' as an integer in number of points in density and pair functions"
+        );
+
+        let mut content = setfl(false);
+        content.push_str("42\n");
+        let error = Eam::parse_setfl(&content, false).unwrap_err();
+        assert_eq!(error.to_string(), "unexpected data '42' at the end of the file");
+    }
+
+    #[test]
+    fn energy() {
+        let system = testing_system();
+        let ev = units::from(1.0, "eV").unwrap();
+        let expected = expected_energy(&system) * ev;
+
+        let eam = Eam::parse_setfl(&setfl(false), false).unwrap();
+        assert_relative_eq!(eam.energy(&system), expected, max_relative = 1e-6);
+
+        let eam = Eam::parse_setfl(&setfl(true), true).unwrap();
+        assert_relative_eq!(eam.energy(&system), expected, max_relative = 1e-6);
+    }
+
+    #[test]
+    fn neighbor_list() {
+        // Large enough system to use linked cells in the neighbor list
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        for i in 0..8 {
+            for j in 0..8 {
+                for k in 0..8 {
+                    let name = if (i + j + k) % 3 == 0 { "Ni" } else { "Cu" };
+                    let position = Vector3D::new(
+                        2.5 * i as f64 + 0.1 * f64::sin(k as f64),
+                        2.5 * j as f64 + 0.1 * f64::cos(i as f64),
+                        2.5 * k as f64 + 0.1 * f64::sin(j as f64),
+                    );
+                    system.add_molecule(Molecule::new(Particle::with_position(name, position)));
+                }
+            }
+        }
+
+        let ev = units::from(1.0, "eV").unwrap();
+        let expected = expected_energy(&system) * ev;
+        let eam = Eam::parse_setfl(&setfl(false), false).unwrap();
+        assert_relative_eq!(eam.energy(&system), expected, max_relative = 1e-6);
+
+        let old_energy = eam.energy(&system);
+        let new_positions = &[system.particles().position[42] + Vector3D::new(0.4, -0.3, 0.2)];
+        let cost = eam.move_molecule_cost(&system, 42, new_positions);
+        system.particles_mut().position[42] = new_positions[0];
+        assert_relative_eq!(cost, eam.energy(&system) - old_energy, max_relative = 1e-9);
+    }
+
+    #[test]
+    fn forces() {
+        let mut system = testing_system();
+        let eam = Eam::parse_setfl(&setfl(false), false).unwrap();
+
+        let ev = units::from(1.0, "eV").unwrap();
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        eam.forces(&system, &mut forces);
+
+        let total = forces[0] + forces[1] + forces[2];
+        assert_relative_eq!(total.norm() / ev, 0.0, epsilon = 1e-12);
+        assert_eq!(forces[3], Vector3D::zero());
+
+        let eps = 1e-9;
+        for i in 0..3 {
+            for k in 0..3 {
+                let e = eam.energy(&system) / ev;
+                system.particles_mut().position[i][k] += eps;
+                let e1 = eam.energy(&system) / ev;
+                system.particles_mut().position[i][k] -= eps;
+                assert_relative_eq!((e - e1) / eps, forces[i][k] / ev, epsilon = 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn atomic_virial() {
+        let mut system = testing_system();
+        let eam = Eam::parse_setfl(&setfl(false), false).unwrap();
+        let ev = units::from(1.0, "eV").unwrap();
+        let virial = eam.atomic_virial(&system) / ev;
+
+        let eps = 1e-9;
+        let mut finite_diff = Matrix3::zero();
+        for i in 0..3 {
+            for j in 0..3 {
+                let e = eam.energy(&system) / ev;
+
+                let mut scaling = Matrix3::one();
+                scaling[i][j] += eps;
+                let old_cell = system.cell;
+                let new_cell = system.cell.scale(scaling);
+                let old_positions = system.particles().position.to_vec();
+                for position in system.particles_mut().position {
+                    *position = new_cell.cartesian(&old_cell.fractional(&position));
+                }
+                system.cell = new_cell;
+                let e1 = eam.energy(&system) / ev;
+
+                system.cell = old_cell;
+                system.particles_mut().position.copy_from_slice(&old_positions);
+                finite_diff[i][j] = (e - e1) / eps;
+            }
+        }
+
+        assert_relative_eq!(virial, finite_diff, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn molecular_virial() {
+        let system = testing_system();
+        let eam = Eam::parse_setfl(&setfl(false), false).unwrap();
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        eam.forces(&system, &mut forces);
+
+        // Molecular virial from the total force on each molecule
+        let mut expected = Matrix3::zero();
+        for molecule in system.molecules() {
+            let mut force = Vector3D::zero();
+            for i in molecule.indexes() {
+                force += forces[i];
+            }
+            expected += force.tensorial(&molecule.center_of_mass());
+        }
+        assert_relative_eq!(eam.molecular_virial(&system), expected, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn move_molecule() {
+        let mut system = testing_system();
+        let eam = Eam::parse_setfl(&setfl(false), false).unwrap();
+        let old_energy = eam.energy(&system);
+
+        let new_positions = &[Vector3D::new(0.3, -0.2, 0.5), Vector3D::new(2.6, 0.4, 0.3)];
+        let cost = eam.move_molecule_cost(&system, 0, new_positions);
+
+        system.particles_mut().position[0] = new_positions[0];
+        system.particles_mut().position[1] = new_positions[1];
+        let new_energy = eam.energy(&system);
+        assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+    }
+}
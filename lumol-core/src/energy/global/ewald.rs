@@ -20,6 +20,8 @@ use crate::{PairRestriction, RestrictionInfo};
 use crate::utils::ThreadLocalVec;
 
 use super::{GlobalPotential, CoulombicPotential, GlobalCache};
+use super::{Polarization, DampedDipoles};
+use super::polarization::{DipolarField, Positions};
 
 /// 3D array with negative indexing on the first dimension, for use in Ewald
 /// phase factors.
@@ -75,6 +77,60 @@ impl IndexMut<(isize, usize, usize)> for Ewald3DArray {
     }
 }
 
+/// Error estimates for Ewald-like summations, used to select parameters
+/// giving a specific accuracy.
+pub(super) struct AccuracyEstimate {
+    /// Sum of the squared charges, divided by `4 π ε_0`
+    q2: f64,
+    /// Number of atoms in the system
+    natoms: f64,
+    /// Splitting parameter between k-space and real space
+    pub alpha: f64,
+}
+
+impl AccuracyEstimate {
+    /// Compute the value of `alpha` giving the requested `accuracy` in real
+    /// space with the given `cutoff` for this `configuration`.
+    pub fn new(cutoff: f64, accuracy: f64, configuration: &Configuration) -> AccuracyEstimate {
+        // Compute squared total charge
+        let mut q2 = 0.0;
+        for charge in configuration.particles().charge {
+            q2 += charge * charge;
+        }
+        q2 /= FOUR_PI_EPSILON_0;
+
+        let natoms = configuration.size() as f64;
+        let lengths = configuration.cell.lengths();
+        let alpha = accuracy * f64::sqrt(natoms * cutoff * lengths[0] * lengths[1] * lengths[2]) / (2.0 * q2);
+        let alpha = if alpha >= 1.0 {
+            (1.35 - 0.15 * f64::ln(accuracy)) / cutoff
+        } else {
+            f64::sqrt(-f64::ln(alpha)) / cutoff
+        };
+
+        AccuracyEstimate {
+            q2: q2,
+            natoms: natoms,
+            alpha: alpha,
+        }
+    }
+
+    /// Get the number of points to use in k-space along a direction of the
+    /// unit cell with the given `length` to reach the requested `accuracy`.
+    pub fn kmax(&self, length: f64, accuracy: f64) -> usize {
+        let error = |kmax| {
+            let arg: f64 = PI * kmax / (self.alpha * length);
+            FRAC_2_SQRT_PI * self.q2 * self.alpha / length / f64::sqrt(kmax * self.natoms) * f64::exp(-arg * arg)
+        };
+
+        let mut kmax = 1;
+        while error(kmax as f64) > accuracy {
+            kmax += 1;
+        }
+        return kmax;
+    }
+}
+
 /// Various parameters used by Ewald calculations.
 ///
 /// They are grouped in a struct for easier passing as function arguments.
@@ -194,6 +250,13 @@ fn compute_ewald_factors_triclinic(factors: &mut EwaldFactorVec, cell: &UnitCell
 /// one in real space, and the other in Fourier or k-space. For more information
 /// about this algorithm see [FS2002].
 ///
+/// By default, the summation uses tin-foil boundary conditions, see
+/// `Ewald::set_surface_dielectric` to change this. For systems with a net
+/// charge, the energy includes the interaction with a uniform neutralizing
+/// background. Induced point dipoles can be added with
+/// `Ewald::set_polarization`, in which case the charge-dipole and
+/// dipole-dipole interactions are also computed with Ewald summation.
+///
 /// [FS2002] Frenkel, D. & Smith, B. Understanding molecular simulation. (Academic press, 2002).
 ///
 /// # Examples
@@ -245,6 +308,18 @@ pub struct Ewald {
     field: Vec<Vector3D>,
     /// Guard for cache invalidation of `self.factors`
     previous_cell: Option<UnitCell>,
+    /// Unit cell used for the k-space part of the summation. This is the same
+    /// as the system unit cell, except for slab geometry where it is extended
+    /// along the z axis.
+    kspace_cell: UnitCell,
+    /// Extension factor of the unit cell along the z axis, if using the slab
+    /// correction for 2D-periodic systems
+    slab: Option<f64>,
+    /// Dielectric constant of the medium surrounding the system, or `None`
+    /// for tin-foil (conducting) boundary conditions
+    surface_dielectric: Option<f64>,
+    /// Induced dipoles polarization, if any
+    polarization: Option<Polarization>,
     /// Update the cached quantities
     updater: Option<Box<dyn Fn(&mut Ewald) + Sync + Send>>,
 }
@@ -259,6 +334,10 @@ impl Clone for Ewald {
             rho: self.rho.clone(),
             field: self.field.clone(),
             previous_cell: self.previous_cell,
+            kspace_cell: self.kspace_cell,
+            slab: self.slab,
+            surface_dielectric: self.surface_dielectric,
+            polarization: self.polarization.clone(),
             updater: None,
         }
     }
@@ -300,6 +379,10 @@ impl Ewald {
             rho: Vec::new(),
             field: Vec::new(),
             previous_cell: None,
+            kspace_cell: UnitCell::infinite(),
+            slab: None,
+            surface_dielectric: None,
+            polarization: None,
             updater: None,
         }
     }
@@ -318,36 +401,76 @@ impl Ewald {
             warn!("accuracy is bigger than 1 in Ewald::with_precision")
         }
 
-        // Compute squared total charge
-        let mut q2 = 0.0;
-        for charge in configuration.particles().charge {
-            q2 += charge * charge;
-        }
-        q2 /= FOUR_PI_EPSILON_0;
+        let estimate = AccuracyEstimate::new(cutoff, accuracy, configuration);
+        let alpha = estimate.alpha;
+        let kmax = estimate.kmax(configuration.cell.lengths().min(), accuracy);
 
-        let natoms = configuration.size() as f64;
-        let lengths = configuration.cell.lengths();
-        let alpha = accuracy * f64::sqrt(natoms * cutoff * lengths[0] * lengths[1] * lengths[2]) / (2.0 * q2);
-        let alpha = if alpha >= 1.0 {
-            (1.35 - 0.15 * f64::ln(accuracy)) / cutoff
-        } else {
-            f64::sqrt(-f64::ln(alpha)) / cutoff
-        };
+        info!("Setting Ewald summation parameters: cutoff = {}, alpha = {}, kmax = {}", cutoff, alpha, kmax);
 
-        let min_length = lengths.min();
-        let error = |kmax| {
-            let arg: f64 = PI * kmax / (alpha * min_length);
-            FRAC_2_SQRT_PI * q2 * alpha / min_length / f64::sqrt(kmax * natoms) * f64::exp(-arg * arg)
-        };
+        Ewald::new(cutoff, kmax, alpha)
+    }
 
-        let mut kmax = 1;
-        while error(kmax as f64) > accuracy {
-            kmax += 1;
+    /// Use the Yeh-Berkowitz correction for systems periodic only in the x
+    /// and y directions, with a slab geometry along the z axis [Yeh1999].
+    ///
+    /// The unit cell is extended by a factor of `extension` along the z axis
+    /// in k-space, to add an empty vacuum gap between the periodic images of
+    /// the slab. An `extension` of 1 means the system unit cell already
+    /// contains a large enough vacuum gap. Using `None` removes the slab
+    /// correction.
+    ///
+    /// The a and b vectors of the unit cell should be in the xy plane, and
+    /// the particles should not cross the z boundaries of the unit cell. The
+    /// real space part of the summation does not use periodic boundary
+    /// conditions along the z axis, so the vacuum gap in the system unit cell
+    /// can be smaller than the cutoff.
+    ///
+    /// [Yeh1999]: Yeh, I.-C. & Berkowitz, M. L. J. Chem. Phys. 111, 3155 (1999).
+    pub fn set_slab_correction(&mut self, extension: Option<f64>) {
+        if let Some(extension) = extension {
+            assert!(extension >= 1.0, "the slab extension factor must be at least 1 in Ewald");
         }
+        self.slab = extension;
+        // Force re-computation of the k-space factors
+        self.previous_cell = None;
+        self.clear_polarization_cache();
+    }
 
-        info!("Setting Ewald summation parameters: cutoff = {}, alpha = {}, kmax = {}", cutoff, alpha, kmax);
+    /// Set the `dielectric` constant of the medium surrounding the infinite
+    /// periodic system, adding the corresponding surface dipole term to the
+    /// energy [deLeeuw1980]. The default is to use tin-foil (conducting)
+    /// boundary conditions, corresponding to an infinite `dielectric`, where
+    /// this term vanishes. Use a `dielectric` of 1 for vacuum boundary
+    /// conditions.
+    ///
+    /// [deLeeuw1980]: de Leeuw, S. W. et al. Proc. R. Soc. Lond. A 373, 27 (1980).
+    pub fn set_surface_dielectric(&mut self, dielectric: f64) {
+        assert!(dielectric >= 1.0, "the surface dielectric constant must be at least 1 in Ewald");
+        if dielectric.is_infinite() {
+            self.surface_dielectric = None;
+        } else {
+            self.surface_dielectric = Some(dielectric);
+        }
+        self.clear_polarization_cache();
+    }
 
-        Ewald::new(cutoff, kmax, alpha)
+    /// Add induced point dipoles `polarization` to this Ewald summation.
+    pub fn set_polarization(&mut self, polarization: Polarization) {
+        self.polarization = Some(polarization);
+    }
+
+    /// Set the restriction scheme used for the real space part of the
+    /// summation.
+    pub(super) fn set_restriction(&mut self, restriction: PairRestriction) {
+        self.restriction = restriction;
+        self.clear_polarization_cache();
+    }
+
+    /// Forget about the induced dipoles computed with the previous parameters
+    fn clear_polarization_cache(&self) {
+        if let Some(ref polarization) = self.polarization {
+            polarization.clear_cache();
+        }
     }
 
     fn prepare(&mut self, cell: &UnitCell) {
@@ -359,6 +482,23 @@ impl Ewald {
         }
         self.previous_cell = Some(*cell);
 
+        self.kspace_cell = match self.slab {
+            Some(extension) => {
+                if cell.shape() == CellShape::Infinite {
+                    panic!("Ewald is not defined with infinite unit cell");
+                }
+                let matrix = cell.matrix();
+                if matrix[2][0] != 0.0 || matrix[2][1] != 0.0 {
+                    panic!("the a and b unit cell vectors must be in the xy plane to use Ewald slab correction");
+                }
+                let mut scaling = Matrix3::one();
+                scaling[2][2] = extension;
+                cell.scale(scaling)
+            }
+            None => *cell,
+        };
+        let cell = &self.kspace_cell;
+
         let max = cell.k_vector([1.0, 1.0, 1.0]).max() * self.parameters.kmax as f64;
         self.parameters.kmax2 = 1.0001 * max * max;
 
@@ -378,13 +518,48 @@ You can manually set alpha to a slightly higher value (current alpha is {})",
     }
 }
 
+/// Find the image of the `vector` in the `cell` when the system is only
+/// periodic along the x and y axes, as with the slab correction.
+#[inline]
+pub(super) fn slab_vector_image(cell: &UnitCell, vector: &mut Vector3D) {
+    if cell.is_infinite() {
+        return;
+    }
+    let mut fractional = cell.fractional(vector);
+    fractional[0] -= f64::round(fractional[0]);
+    fractional[1] -= f64::round(fractional[1]);
+    *vector = cell.cartesian(&fractional);
+}
+
 /// Real space part of the summation
 impl Ewald {
+    /// Find the image of the `vector` to use in the real space part of the
+    /// summation. With the slab correction, the system is only periodic along
+    /// the x and y axes, and the vector is not wrapped along the z axis.
+    #[inline]
+    fn real_space_image(&self, cell: &UnitCell, vector: &mut Vector3D) {
+        if self.slab.is_none() {
+            cell.vector_image(vector);
+        } else {
+            slab_vector_image(cell, vector);
+        }
+    }
+
+    /// Get the vector between the real space image of particle `j` with
+    /// respect to particle `i`
+    #[inline]
+    fn real_space_vector(&self, configuration: &Configuration, i: usize, j: usize) -> Vector3D {
+        let positions = configuration.particles().position;
+        let mut rij = positions[i] - positions[j];
+        self.real_space_image(&configuration.cell, &mut rij);
+        return rij;
+    }
+
     /// Get the real-space energy for one pair at distance `r` with charges `qi`
     /// and `qj` ; and with restriction information for this pair in `info`.
     #[allow(clippy::float_cmp)]  // checking info.scaling
     #[inline]
-    fn real_space_energy_pair(&self, info: RestrictionInfo, qiqj: f64, r: f64) -> f64 {
+    pub(super) fn real_space_energy_pair(&self, info: RestrictionInfo, qiqj: f64, r: f64) -> f64 {
         assert_eq!(info.scaling, 1.0, "Scaling restriction scheme using Ewald are not implemented");
         debug_assert!(!(r > self.rc && info.excluded), "excluded atoms are too far apart");
         if r > self.rc {
@@ -405,7 +580,7 @@ impl Ewald {
     /// `info`.
     #[allow(clippy::float_cmp)]  // checking info.scaling
     #[inline]
-    fn real_space_force_pair(&self, info: RestrictionInfo, qiqj: f64, r: f64) -> f64 {
+    pub(super) fn real_space_force_pair(&self, info: RestrictionInfo, qiqj: f64, r: f64) -> f64 {
         assert_eq!(info.scaling, 1.0, "Scaling restriction scheme using Ewald are not implemented");
         debug_assert!(!(r > self.rc && info.excluded), "excluded atoms are too far apart");
         if r > self.rc {
@@ -428,7 +603,7 @@ impl Ewald {
     }
 
     /// Real space contribution to the energy
-    fn real_space_energy(&self, configuration: &Configuration) -> f64 {
+    pub(super) fn real_space_energy(&self, configuration: &Configuration) -> f64 {
         let natoms = configuration.size();
         let charges = configuration.particles().charge;
 
@@ -448,7 +623,7 @@ impl Ewald {
                 let path = configuration.bond_path(i, j);
                 let info = self.restriction.information(path);
 
-                let r = configuration.distance(i, j);
+                let r = self.real_space_vector(configuration, i, j).norm();
                 local_energy += self.real_space_energy_pair(info, qi * qj, r);
             }
 
@@ -458,7 +633,7 @@ impl Ewald {
     }
 
     /// Real space contribution to the forces
-    fn real_space_forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+    pub(super) fn real_space_forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
         assert_eq!(forces.len(), configuration.size());
 
         let natoms = configuration.size();
@@ -486,7 +661,7 @@ impl Ewald {
                 let path = configuration.bond_path(i, j);
                 let info = self.restriction.information(path);
 
-                let rij = configuration.nearest_image(i, j);
+                let rij = self.real_space_vector(configuration, i, j);
                 let force = self.real_space_force_pair(info, qi * qj, rij.norm()) * rij;
                 force_i += force;
                 forces[j] -= force;
@@ -500,7 +675,7 @@ impl Ewald {
     }
 
     /// Real space contribution to the atomic virial
-    fn real_space_atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+    pub(super) fn real_space_atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
         let natoms = configuration.size();
         let charges = configuration.particles().charge;
 
@@ -520,7 +695,7 @@ impl Ewald {
                 let path = configuration.bond_path(i, j);
                 let info = self.restriction.information(path);
 
-                let rij = configuration.nearest_image(i, j);
+                let rij = self.real_space_vector(configuration, i, j);
                 let force = self.real_space_force_pair(info, qi * qj, rij.norm()) * rij;
                 local_virial += force.tensorial(&rij);
             }
@@ -530,7 +705,7 @@ impl Ewald {
     }
 
     /// Real space contribution to the molecular virial
-    fn real_space_molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+    pub(super) fn real_space_molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
         let charges = configuration.particles().charge;
         let virial = configuration.molecules().enumerate().par_bridge().map(|(i, molecule_i)| {
             let mut local_virial = Matrix3::zero();
@@ -539,7 +714,7 @@ impl Ewald {
             for molecule_j in configuration.molecules().skip(i + 1) {
                 let rj = molecule_j.center_of_mass();
                 let mut r_ij = ri - rj;
-                configuration.cell.vector_image(&mut r_ij);
+                self.real_space_image(&configuration.cell, &mut r_ij);
 
                 for part_a in molecule_i.indexes() {
                     let q_a = charges[part_a];
@@ -556,7 +731,7 @@ impl Ewald {
                         let path = configuration.bond_path(part_a, part_b);
                         let info = self.restriction.information(path);
 
-                        let r_ab = configuration.nearest_image(part_a, part_b);
+                        let r_ab = self.real_space_vector(configuration, part_a, part_b);
                         let force = self.real_space_force_pair(info, q_a * q_b, r_ab.norm()) * r_ab;
                         let w_ab = force.tensorial(&r_ab);
                         local_virial += w_ab * (r_ab * r_ij) / r_ab.norm2();
@@ -569,7 +744,7 @@ impl Ewald {
         return virial.sum();
      }
 
-     fn real_space_move_molecule_cost(
+     pub(super) fn real_space_move_molecule_cost(
          &self,
          configuration: &Configuration,
          molecule_id: usize,
@@ -597,8 +772,10 @@ impl Ewald {
                         continue;
                     }
 
-                    let old_r = configuration.distance(part_i, part_j);
-                    let new_r = configuration.cell.distance(&new_positions[i], &positions[part_j]);
+                    let old_r = self.real_space_vector(configuration, part_i, part_j).norm();
+                    let mut new_rij = new_positions[i] - positions[part_j];
+                    self.real_space_image(&configuration.cell, &mut new_rij);
+                    let new_r = new_rij.norm();
 
                     let path = configuration.bond_path(part_i, part_j);
                     let info = self.restriction.information(path);
@@ -616,7 +793,7 @@ impl Ewald {
 /// Self-interaction correction
 impl Ewald {
     /// Self-interaction contribution to the energy
-    fn self_energy(&self, configuration: &Configuration) -> f64 {
+    pub(super) fn self_energy(&self, configuration: &Configuration) -> f64 {
         let q2 = configuration.particles()
                               .charge
                               .iter()
@@ -638,27 +815,8 @@ impl Ewald {
 
         let positions = configuration.particles().position;
         let charges = configuration.particles().charge;
-
-        // do the k = -1, 0, 1 cases first
-        for spatial in 0..3 {
-            let mut k_idx = [0.0, 0.0, 0.0];
-            k_idx[spatial] = 1.0;
-            let k_vector = configuration.cell.k_vector(k_idx);
-            for i in 0..natoms {
-                self.eikr[(0, spatial, i)] = Complex::cartesian(1.0, 0.0);
-                self.eikr[(1, spatial, i)] = Complex::polar(1.0, k_vector * positions[i]);
-                self.eikr[(-1, spatial, i)] = self.eikr[(1, spatial, i)].conj();
-            }
-        }
-
-        // compute the other values of k by recursion
-        for spatial in 0..3 {
-            for k in 2..(self.kmax + 1) {
-                for i in 0..natoms {
-                    self.eikr[(k, spatial, i)] = self.eikr[(k - 1, spatial, i)] * self.eikr[(1, spatial, i)];
-                    self.eikr[(-k, spatial, i)] = self.eikr[(k, spatial, i)].conj();
-                }
-            }
+        for i in 0..natoms {
+            self.set_phases(i, positions[i]);
         }
 
         for &(ikx, iky, ikz) in &self.factors.index {
@@ -673,6 +831,28 @@ impl Ewald {
         }
     }
 
+    /// Compute the phase factors `e^{i k r}` of the particle `i` at the given
+    /// `position`, for all the k-vectors.
+    fn set_phases(&mut self, i: usize, position: Vector3D) {
+        // do the k = -1, 0, 1 cases first
+        for spatial in 0..3 {
+            let mut k_idx = [0.0, 0.0, 0.0];
+            k_idx[spatial] = 1.0;
+            let k_vector = self.kspace_cell.k_vector(k_idx);
+            self.eikr[(0, spatial, i)] = Complex::cartesian(1.0, 0.0);
+            self.eikr[(1, spatial, i)] = Complex::polar(1.0, k_vector * position);
+            self.eikr[(-1, spatial, i)] = self.eikr[(1, spatial, i)].conj();
+        }
+
+        // compute the other values of k by recursion
+        for spatial in 0..3 {
+            for k in 2..(self.kmax + 1) {
+                self.eikr[(k, spatial, i)] = self.eikr[(k - 1, spatial, i)] * self.eikr[(1, spatial, i)];
+                self.eikr[(-k, spatial, i)] = self.eikr[(k, spatial, i)].conj();
+            }
+        }
+    }
+
     /// k-space contribution to the energy
     fn k_space_energy(&mut self, configuration: &Configuration) -> f64 {
         self.eik_dot_r(configuration);
@@ -768,7 +948,7 @@ impl Ewald {
         for spatial in 0..3 {
             let mut k_idx = [0.0, 0.0, 0.0];
             k_idx[spatial] = 1.0;
-            let k_vector = configuration.cell.k_vector(k_idx);
+            let k_vector = self.kspace_cell.k_vector(k_idx);
             for i in 0..molecule.size() {
                 new_energy_ikr[(0, spatial, i)] = Complex::cartesian(1.0, 0.0);
                 new_energy_ikr[(1, spatial, i)] = Complex::polar(1.0, k_vector * new_positions[i]);
@@ -839,140 +1019,584 @@ impl Ewald {
     }
 }
 
-/// Thread-sade wrapper around Ewald implementing `CoulombicPotential`.
-///
-/// This wrapper allow to share a Ewald solver between threads (make it `Send
-/// + Sync`) while still using caching in Monte Carlo simulations (with
-/// interior mutability).
-pub struct SharedEwald(RwLock<Ewald>);
-
-impl SharedEwald {
-    /// Wrap `ewald` in a thread-safe structure.
-    ///
-    /// # Example
-    /// ```
-    /// # use lumol_core::energy::{Ewald, SharedEwald, CoulombicPotential};
-    /// let ewald = SharedEwald::new(Ewald::new(12.5, 10, None));
-    /// let boxed: Box<dyn CoulombicPotential> = Box::new(ewald);
-    /// ```
-    pub fn new(ewald: Ewald) -> SharedEwald {
-        SharedEwald(RwLock::new(ewald))
-    }
-
-    /// Get read access to the underlying Ewald solver
-    fn read(&self) -> RwLockReadGuard<'_, Ewald> {
-        // The lock should never be poisoned, because any panic will unwind
-        // and finish the simulation.
-        self.0.read().expect("Ewald lock is poisoned")
+/// Corrections for the boundary conditions and for non-neutral systems
+impl Ewald {
+    /// Get the total dipole of the `configuration`, using the particles
+    /// positions as-is.
+    fn dipole(configuration: &Configuration) -> Vector3D {
+        let positions = configuration.particles().position;
+        let charges = configuration.particles().charge;
+        let mut dipole = Vector3D::zero();
+        for (&charge, position) in zip!(charges, positions) {
+            dipole += charge * *position;
+        }
+        return dipole;
     }
 
-    /// Get write access to the underlying Ewald solver
-    fn write(&self) -> RwLockWriteGuard<'_, Ewald> {
-        // The lock should never be poisoned, because any panic will unwind
-        // and finish the simulation.
-        self.0.write().expect("Ewald lock is poisoned")
-    }
-}
+    /// Get the matrix `C` such that the energy of the dipolar corrections
+    /// (slab correction and surface dipole term) is `M · C · M / V`, where `M`
+    /// is the total dipole of the system and `V` the volume. This returns
+    /// `None` if there is no such correction.
+    fn dipolar_factor(&self) -> Option<Matrix3> {
+        if self.slab.is_none() && self.surface_dielectric.is_none() {
+            return None;
+        }
 
-impl Clone for SharedEwald {
-    fn clone(&self) -> SharedEwald {
-        SharedEwald::new(self.read().clone())
+        let mut factor = Matrix3::zero();
+        if self.slab.is_some() {
+            factor[2][2] += 2.0 * PI;
+        }
+        if let Some(dielectric) = self.surface_dielectric {
+            factor += 2.0 * PI / (2.0 * dielectric + 1.0) * Matrix3::one();
+        }
+        return Some(factor);
     }
-}
 
-impl GlobalPotential for SharedEwald {
-    fn cutoff(&self) -> Option<f64> {
-        Some(self.read().rc)
-    }
+    /// Dipolar corrections contribution to the energy
+    fn dipolar_energy(&self, configuration: &Configuration) -> f64 {
+        let factor = match self.dipolar_factor() {
+            Some(factor) => factor,
+            None => return 0.0,
+        };
 
-    fn energy(&self, configuration: &Configuration) -> f64 {
-        let mut ewald = self.write();
-        ewald.prepare(&configuration.cell);
-        let real = ewald.real_space_energy(configuration);
-        let self_e = ewald.self_energy(configuration);
-        let k_space = ewald.k_space_energy(configuration);
-        return real + self_e + k_space;
+        let dipole = Ewald::dipole(configuration);
+        let volume = self.kspace_cell.volume();
+        return dipole * (factor * dipole) / volume / FOUR_PI_EPSILON_0;
     }
 
-    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D])  {
+    /// Dipolar corrections contribution to the forces
+    fn dipolar_forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
         assert_eq!(forces.len(), configuration.size());
-        let mut ewald = self.write();
-        ewald.prepare(&configuration.cell);
+        let factor = match self.dipolar_factor() {
+            Some(factor) => factor,
+            None => return,
+        };
 
-        ewald.real_space_forces(configuration, forces);
-        // No self force
-        ewald.k_space_forces(configuration, forces);
+        let dipole = Ewald::dipole(configuration);
+        let field = 2.0 * (factor * dipole) / self.kspace_cell.volume() / FOUR_PI_EPSILON_0;
+        let charges = configuration.particles().charge;
+        for (force, &charge) in zip!(&mut *forces, charges) {
+            *force -= charge * field;
+        }
     }
 
-    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
-        let mut ewald = self.write();
-        ewald.prepare(&configuration.cell);
-        let real = ewald.real_space_atomic_virial(configuration);
-        // No self virial
-        let k_space = ewald.k_space_atomic_virial(configuration);
-        return real + k_space;
-    }
+    /// Dipolar corrections contribution to the atomic virial
+    fn dipolar_atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let factor = match self.dipolar_factor() {
+            Some(factor) => factor,
+            None => return Matrix3::zero(),
+        };
 
-    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
-        let mut ewald = self.write();
-        ewald.prepare(&configuration.cell);
-        let real = ewald.real_space_molecular_virial(configuration);
-        // No self virial
-        let k_space = ewald.k_space_molecular_virial(configuration);
-        return real + k_space;
+        let dipole = Ewald::dipole(configuration);
+        let volume = self.kspace_cell.volume();
+        let c_dipole = factor * dipole;
+        let energy = dipole * c_dipole / volume;
+
+        // Derivative of the energy with respect to the volume and to the
+        // positions, using a symmetric virial.
+        let positions = (c_dipole.tensorial(&dipole) + dipole.tensorial(&c_dipole)) / volume;
+        return (energy * Matrix3::one() - positions) / FOUR_PI_EPSILON_0;
     }
-}
 
-impl CoulombicPotential for SharedEwald {
-    fn set_restriction(&mut self, restriction: PairRestriction) {
-        self.write().restriction = restriction;
+    /// Dipolar corrections contribution to the molecular virial
+    fn dipolar_molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        if self.dipolar_factor().is_none() {
+            return Matrix3::zero();
+        }
+
+        let atomic = self.dipolar_atomic_virial(configuration);
+
+        let mut forces = vec![Vector3D::zero(); configuration.size()];
+        self.dipolar_forces(configuration, &mut forces);
+
+        let positions = configuration.particles().position;
+        let mut correction = Matrix3::zero();
+        for molecule in configuration.molecules() {
+            let com = molecule.center_of_mass();
+            for i in molecule.indexes() {
+                let di = positions[i] - com;
+                correction += forces[i].tensorial(&di);
+            }
+        }
+
+        return atomic - correction;
     }
-}
 
-impl GlobalCache for SharedEwald {
-    fn move_molecule_cost(
+    /// Dipolar corrections contribution to the energy cost of moving the
+    /// molecule with the given `molecule_id` to `new_positions`
+    fn dipolar_move_molecule_cost(
         &self,
         configuration: &Configuration,
         molecule_id: usize,
-        new_positions: &[Vector3D]
+        new_positions: &[Vector3D],
     ) -> f64 {
-        let mut ewald = self.write();
-        ewald.prepare(&configuration.cell);
-        let real = ewald.real_space_move_molecule_cost(configuration, molecule_id, new_positions);
-        /* No self cost */
-        let k_space = ewald.k_space_move_molecule_cost(configuration, molecule_id, new_positions);
-        return real + k_space;
-    }
+        let factor = match self.dipolar_factor() {
+            Some(factor) => factor,
+            None => return 0.0,
+        };
 
-    fn update(&self) {
-        let mut ewald = self.write();
-        if ewald.updater.is_some() {
-            let mut updater = None;
-            ::std::mem::swap(&mut updater, &mut ewald.updater);
-            let updater = updater.unwrap();
-            updater(&mut *ewald);
+        let old_dipole = Ewald::dipole(configuration);
+        let mut new_dipole = old_dipole;
+
+        let positions = configuration.particles().position;
+        let charges = configuration.particles().charge;
+        let molecule = configuration.molecule(molecule_id);
+        for (i, part_i) in molecule.indexes().enumerate() {
+            new_dipole += charges[part_i] * (new_positions[i] - positions[part_i]);
         }
+
+        let old_energy = old_dipole * (factor * old_dipole);
+        let new_energy = new_dipole * (factor * new_dipole);
+        return (new_energy - old_energy) / self.kspace_cell.volume() / FOUR_PI_EPSILON_0;
     }
-}
 
-#[cfg(test)]
-mod tests {
-    pub use super::*;
-    use crate::System;
-    use crate::utils::system_from_xyz;
+    /// Energy of the interaction of the charges with a uniform neutralizing
+    /// background, for systems with a net charge.
+    fn background_energy(&self, configuration: &Configuration) -> f64 {
+        let charge = configuration.particles().charge.iter().sum::<f64>();
+        let volume = self.kspace_cell.volume();
+        return -PI * charge * charge / (2.0 * volume * self.alpha * self.alpha) / FOUR_PI_EPSILON_0;
+    }
 
-    pub fn nacl_pair() -> System {
-        let mut system = system_from_xyz("2
-        cell: 20.0
-        Cl 0.0 0.0 0.0
-        Na 1.5 0.0 0.0
-        ");
-        system.particles_mut().charge[0] = -1.0;
-        system.particles_mut().charge[1] = 1.0;
-        return system;
+    /// Neutralizing background contribution to the virial. There is no
+    /// corresponding force, and the molecular and atomic virial are the same.
+    fn background_virial(&self, configuration: &Configuration) -> Matrix3 {
+        return self.background_energy(configuration) * Matrix3::one();
     }
+}
 
-    pub fn water() -> System {
+/// Induced dipoles contributions. All the functions in this block use the
+/// phase factors cached by `eik_dot_r`, which must be up to date with the
+/// configuration.
+impl Ewald {
+    /// Get the real space dipolar interactions corresponding to this Ewald
+    /// summation
+    fn damped_dipoles(&self) -> DampedDipoles<'_> {
+        let polarization = self.polarization.as_ref().expect("missing polarization in Ewald");
+        DampedDipoles {
+            alpha: self.alpha,
+            cutoff: self.rc,
+            restriction: self.restriction,
+            remove_excluded: true,
+            slab: self.slab.is_some(),
+            neighbors: polarization.neighbor_list(),
+        }
+    }
+
+    /// Get the cached phase factor `e^{i k r}` for the particle `i` and the
+    /// k-vector with the given `index`
+    #[inline]
+    fn phase(&self, (ikx, iky, ikz): (isize, isize, isize), i: usize) -> Complex {
+        self.eikr[(ikx, 0, i)] * self.eikr[(iky, 1, i)] * self.eikr[(ikz, 2, i)]
+    }
+
+    /// Get the k-vector with the given `index`
+    #[inline]
+    fn k_vector(&self, (ikx, iky, ikz): (isize, isize, isize)) -> Vector3D {
+        self.kspace_cell.k_vector([ikx as f64, iky as f64, ikz as f64])
+    }
+
+    /// Compute the Fourier transform of the `dipoles` (`\sum μ_i e^{i k r_i}`)
+    /// for all the k-vectors
+    fn dipoles_fourier(&self, dipoles: &[Vector3D]) -> Vec<[Complex; 3]> {
+        self.factors.index.par_iter().map(|&index| {
+            let mut fourier = [Complex::zero(); 3];
+            for (i, dipole) in dipoles.iter().enumerate() {
+                if dipole.norm2() == 0.0 {
+                    continue;
+                }
+                let phase = self.phase(index, i);
+                for (component, &value) in fourier.iter_mut().zip(dipole.iter()) {
+                    *component += phase * value;
+                }
+            }
+            fourier
+        }).collect()
+    }
+
+    /// Get the Fourier transform of the electrostatic density of the dipoles
+    /// (`i \vec k · \sum μ_i e^{i k r_i}`) from the `fourier` transform of the
+    /// dipoles at the given `k_vector`.
+    #[inline]
+    fn dipoles_rho(k_vector: Vector3D, fourier: &[Complex; 3]) -> Complex {
+        let k_dot = fourier[0] * k_vector[0] + fourier[1] * k_vector[1] + fourier[2] * k_vector[2];
+        return Complex::cartesian(-k_dot.imag(), k_dot.real());
+    }
+
+    /// Get the k-space electric field (without the `1 / (4 π ε_0)` factor)
+    /// created at all the particles by the Fourier transformed electrostatic
+    /// density `rho`.
+    fn k_space_field(&self, natoms: usize, rho: &[Complex]) -> Vec<Vector3D> {
+        let thread_local_field = ThreadLocalVec::with_size(natoms);
+        self.factors.index
+            .par_iter()
+            .zip_eq(&self.factors.field)
+            .zip_eq(rho)
+            .for_each(|((&index, factor), rho)| {
+                let mut field = thread_local_field.borrow_mut();
+                for i in 0..natoms {
+                    let partial = self.phase(index, i) * rho.conj();
+                    field[i] += partial.imag() * factor;
+                }
+            });
+
+        let mut field = vec![Vector3D::zero(); natoms];
+        thread_local_field.sum_into(&mut field);
+        return field;
+    }
+
+    /// Polarization contribution to the energy
+    fn polarization_energy(&self, configuration: &Configuration) -> f64 {
+        match self.polarization {
+            Some(ref polarization) => polarization.energy(self, configuration),
+            None => 0.0,
+        }
+    }
+
+    /// Polarization contribution to the forces
+    fn polarization_forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        if let Some(ref polarization) = self.polarization {
+            polarization.forces(self, configuration, forces);
+        }
+    }
+
+    /// Polarization contribution to the atomic virial
+    fn polarization_atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        match self.polarization {
+            Some(ref polarization) => polarization.atomic_virial(self, configuration),
+            None => Matrix3::zero(),
+        }
+    }
+
+    /// Polarization contribution to the molecular virial
+    fn polarization_molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        match self.polarization {
+            Some(ref polarization) => polarization.molecular_virial(self, configuration),
+            None => Matrix3::zero(),
+        }
+    }
+
+    /// Polarization contribution to the energy cost of moving the molecule
+    /// with the given `molecule_id` to `new_positions`. Contrary to the other
+    /// functions in this block, this one updates the cached phase factors.
+    fn polarization_move_molecule_cost(
+        &mut self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        if self.polarization.is_none() {
+            return 0.0;
+        }
+
+        self.eik_dot_r(configuration);
+        let old_energy = self.polarization_energy(configuration);
+
+        // Update the phase factors and the charges density for the moved
+        // molecule, without copying the configuration
+        let delta_rho = self.delta_rho_move_rigid_molecules(configuration, molecule_id, new_positions);
+        let old_rho = self.rho.clone();
+        for (rho, &delta) in zip!(&mut self.rho, &delta_rho) {
+            *rho += delta;
+        }
+        let indexes = configuration.molecule(molecule_id).indexes();
+        for (i, part_i) in indexes.clone().enumerate() {
+            self.set_phases(part_i, new_positions[i]);
+        }
+
+        let new_energy = {
+            let polarization = self.polarization.as_ref().expect("missing polarization in Ewald");
+            polarization.moved_energy(&*self, configuration, molecule_id, new_positions)
+        };
+
+        // Restore the cached values for the current configuration
+        let positions = configuration.particles().position;
+        for part_i in indexes {
+            self.set_phases(part_i, positions[part_i]);
+        }
+        self.rho = old_rho;
+
+        return new_energy - old_energy;
+    }
+}
+
+impl DipolarField for Ewald {
+    fn charges_field(&self, positions: &Positions<'_>) -> Vec<Vector3D> {
+        let natoms = positions.configuration.size();
+        let mut field = self.damped_dipoles().charges_field(positions);
+        let k_space = self.k_space_field(natoms, &self.rho);
+        for (field, k_space) in field.iter_mut().zip(k_space) {
+            *field += k_space;
+        }
+
+        if let Some(factor) = self.dipolar_factor() {
+            let charges = positions.configuration.particles().charge;
+            let dipole = (0..natoms).fold(Vector3D::zero(), |sum, i| sum + charges[i] * positions.get(i));
+            let correction = 2.0 * (factor * dipole) / self.kspace_cell.volume();
+            for field in &mut field {
+                *field -= correction;
+            }
+        }
+        return field;
+    }
+
+    fn dipoles_field(&self, positions: &Positions<'_>, dipoles: &[Vector3D]) -> Vec<Vector3D> {
+        let mut field = self.damped_dipoles().dipoles_field(positions, dipoles);
+
+        let rho = self.dipoles_fourier(dipoles)
+            .iter()
+            .zip(&self.factors.index)
+            .map(|(fourier, &index)| Ewald::dipoles_rho(self.k_vector(index), fourier))
+            .collect::<Vec<_>>();
+        let k_space = self.k_space_field(positions.configuration.size(), &rho);
+
+        // The self-interaction correction for dipoles depends on the dipoles
+        // values, and contributes to the field.
+        let self_factor = 2.0 / 3.0 * FRAC_2_SQRT_PI * self.alpha * self.alpha * self.alpha;
+        for ((field, k_space), &dipole) in field.iter_mut().zip(k_space).zip(dipoles) {
+            *field += k_space + self_factor * dipole;
+        }
+
+        if let Some(factor) = self.dipolar_factor() {
+            let total = dipoles.iter().fold(Vector3D::zero(), |sum, &dipole| sum + dipole);
+            let correction = 2.0 * (factor * total) / self.kspace_cell.volume();
+            for field in &mut field {
+                *field -= correction;
+            }
+        }
+        return field;
+    }
+
+    fn dipoles_forces(&self, positions: &Positions<'_>, dipoles: &[Vector3D]) -> Vec<Vector3D> {
+        let natoms = positions.configuration.size();
+        let charges = positions.configuration.particles().charge;
+        let mut forces = self.damped_dipoles().dipoles_forces(positions, dipoles);
+
+        let fourier = self.dipoles_fourier(dipoles);
+        let thread_local_forces = ThreadLocalVec::with_size(natoms);
+        self.factors.index
+            .par_iter()
+            .zip_eq(&self.factors.field)
+            .zip_eq(&self.rho)
+            .zip_eq(&fourier)
+            .for_each(|(((&index, factor), &rho), fourier)| {
+                let mut forces = thread_local_forces.borrow_mut();
+                let k_vector = self.k_vector(index);
+                let rho_dipoles = Ewald::dipoles_rho(k_vector, fourier);
+                let rho_total = rho + rho_dipoles;
+                for i in 0..natoms {
+                    let phase = self.phase(index, i);
+                    // Charges in the field of the dipoles, and dipoles in the
+                    // field gradient of both charges and dipoles
+                    let charge = charges[i] * (phase * rho_dipoles.conj()).imag();
+                    let dipole = (k_vector * dipoles[i]) * (phase * rho_total.conj()).real();
+                    forces[i] += (charge + dipole) * factor;
+                }
+            });
+        thread_local_forces.sum_into(&mut forces);
+
+        if let Some(factor) = self.dipolar_factor() {
+            let total = dipoles.iter().fold(Vector3D::zero(), |sum, &dipole| sum + dipole);
+            let field = 2.0 * (factor * total) / self.kspace_cell.volume();
+            for (force, &charge) in forces.iter_mut().zip(charges) {
+                *force -= charge * field;
+            }
+        }
+        return forces;
+    }
+
+    fn dipoles_virial(&self, positions: &Positions<'_>, dipoles: &[Vector3D]) -> Matrix3 {
+        let configuration = positions.configuration;
+        let mut virial = self.damped_dipoles().dipoles_virial(positions, dipoles);
+
+        let fourier = self.dipoles_fourier(dipoles);
+        virial += self.factors.index
+            .par_iter()
+            .zip_eq(&self.factors.energy)
+            .zip_eq(&self.factors.virial)
+            .zip_eq(&self.rho)
+            .zip_eq(&fourier)
+            .map(|((((&index, &energy), factor), &rho), fourier)| {
+                let k_vector = self.k_vector(index);
+                let rho_total = rho + Ewald::dipoles_rho(k_vector, fourier);
+                // The dipoles do not change with the cell, but their product
+                // with the k-vectors does.
+                let conj = rho_total.conj();
+                let gradient = Vector3D::new(
+                    (conj * fourier[0]).imag(),
+                    (conj * fourier[1]).imag(),
+                    (conj * fourier[2]).imag(),
+                );
+                let density = (rho_total.norm2() - rho.norm2()) * factor;
+                density - 2.0 * energy * k_vector.tensorial(&gradient)
+            })
+            .sum::<Matrix3>();
+
+        if let Some(factor) = self.dipolar_factor() {
+            let dipole = Ewald::dipole(configuration);
+            let total = dipoles.iter().fold(Vector3D::zero(), |sum, &dipole| sum + dipole);
+            let volume = self.kspace_cell.volume();
+            let c_total = factor * total;
+            let energy = (2.0 * dipole + total) * c_total / volume;
+            // Only the charges dipole changes with the cell. The virial is not
+            // symmetric, the asymmetry compensating the one from the dipolar
+            // interactions in real and k-space.
+            let positions = 2.0 * c_total.tensorial(&dipole) / volume;
+            virial += energy * Matrix3::one() - positions;
+        }
+        return virial;
+    }
+}
+
+/// Thread-sade wrapper around Ewald implementing `CoulombicPotential`.
+///
+/// This wrapper allow to share a Ewald solver between threads (make it `Send
+/// + Sync`) while still using caching in Monte Carlo simulations (with
+/// interior mutability).
+pub struct SharedEwald(RwLock<Ewald>);
+
+impl SharedEwald {
+    /// Wrap `ewald` in a thread-safe structure.
+    ///
+    /// # Example
+    /// ```
+    /// # use lumol_core::energy::{Ewald, SharedEwald, CoulombicPotential};
+    /// let ewald = SharedEwald::new(Ewald::new(12.5, 10, None));
+    /// let boxed: Box<dyn CoulombicPotential> = Box::new(ewald);
+    /// ```
+    pub fn new(ewald: Ewald) -> SharedEwald {
+        SharedEwald(RwLock::new(ewald))
+    }
+
+    /// Get read access to the underlying Ewald solver
+    fn read(&self) -> RwLockReadGuard<'_, Ewald> {
+        // The lock should never be poisoned, because any panic will unwind
+        // and finish the simulation.
+        self.0.read().expect("Ewald lock is poisoned")
+    }
+
+    /// Get write access to the underlying Ewald solver
+    fn write(&self) -> RwLockWriteGuard<'_, Ewald> {
+        // The lock should never be poisoned, because any panic will unwind
+        // and finish the simulation.
+        self.0.write().expect("Ewald lock is poisoned")
+    }
+}
+
+impl Clone for SharedEwald {
+    fn clone(&self) -> SharedEwald {
+        SharedEwald::new(self.read().clone())
+    }
+}
+
+impl GlobalPotential for SharedEwald {
+    fn cutoff(&self) -> Option<f64> {
+        Some(self.read().rc)
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        let mut ewald = self.write();
+        ewald.prepare(&configuration.cell);
+        let real = ewald.real_space_energy(configuration);
+        let self_e = ewald.self_energy(configuration);
+        let k_space = ewald.k_space_energy(configuration);
+        let polarization = ewald.polarization_energy(configuration);
+        let dipolar = ewald.dipolar_energy(configuration);
+        let background = ewald.background_energy(configuration);
+        return real + self_e + k_space + polarization + dipolar + background;
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D])  {
+        assert_eq!(forces.len(), configuration.size());
+        let mut ewald = self.write();
+        ewald.prepare(&configuration.cell);
+
+        ewald.real_space_forces(configuration, forces);
+        // No self force
+        ewald.k_space_forces(configuration, forces);
+        ewald.polarization_forces(configuration, forces);
+        ewald.dipolar_forces(configuration, forces);
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let mut ewald = self.write();
+        ewald.prepare(&configuration.cell);
+        let real = ewald.real_space_atomic_virial(configuration);
+        // No self virial
+        let k_space = ewald.k_space_atomic_virial(configuration);
+        let polarization = ewald.polarization_atomic_virial(configuration);
+        let dipolar = ewald.dipolar_atomic_virial(configuration);
+        let background = ewald.background_virial(configuration);
+        return real + k_space + polarization + dipolar + background;
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let mut ewald = self.write();
+        ewald.prepare(&configuration.cell);
+        let real = ewald.real_space_molecular_virial(configuration);
+        // No self virial
+        let k_space = ewald.k_space_molecular_virial(configuration);
+        let polarization = ewald.polarization_molecular_virial(configuration);
+        let dipolar = ewald.dipolar_molecular_virial(configuration);
+        let background = ewald.background_virial(configuration);
+        return real + k_space + polarization + dipolar + background;
+    }
+}
+
+impl CoulombicPotential for SharedEwald {
+    fn set_restriction(&mut self, restriction: PairRestriction) {
+        self.write().set_restriction(restriction);
+    }
+}
+
+impl GlobalCache for SharedEwald {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D]
+    ) -> f64 {
+        let mut ewald = self.write();
+        ewald.prepare(&configuration.cell);
+        // This must be called first, as it resets the cached phase factors
+        let polarization = ewald.polarization_move_molecule_cost(configuration, molecule_id, new_positions);
+        let real = ewald.real_space_move_molecule_cost(configuration, molecule_id, new_positions);
+        /* No self cost */
+        let k_space = ewald.k_space_move_molecule_cost(configuration, molecule_id, new_positions);
+        let dipolar = ewald.dipolar_move_molecule_cost(configuration, molecule_id, new_positions);
+        /* No background cost, the total charge and the volume are constant */
+        return real + k_space + polarization + dipolar;
+    }
+
+    fn update(&self) {
+        let mut ewald = self.write();
+        if ewald.updater.is_some() {
+            let mut updater = None;
+            ::std::mem::swap(&mut updater, &mut ewald.updater);
+            let updater = updater.unwrap();
+            updater(&mut *ewald);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::System;
+    use crate::utils::system_from_xyz;
+
+    pub fn nacl_pair() -> System {
+        let mut system = system_from_xyz("2
+        cell: 20.0
+        Cl 0.0 0.0 0.0
+        Na 1.5 0.0 0.0
+        ");
+        system.particles_mut().charge[0] = -1.0;
+        system.particles_mut().charge[1] = 1.0;
+        return system;
+    }
+
+    pub fn water() -> System {
         let mut system = system_from_xyz("3
         cell: 20.0
         O  0.0  0.0  0.0
@@ -1200,7 +1824,7 @@ mod tests {
 
         use approx::assert_relative_eq;
 
-        fn scale(system: &mut System, i: usize, j: usize, eps: f64) {
+        pub fn scale(system: &mut System, i: usize, j: usize, eps: f64) {
             let mut scaling = Matrix3::one();
             scaling[i][j] += eps;
             let old_cell = system.cell;
@@ -1376,6 +2000,517 @@ mod tests {
         );
     }
 
+    mod slab {
+        use super::*;
+        use crate::{GlobalPotential, GlobalCache, CoulombicPotential, Matrix3};
+        use crate::PairRestriction;
+
+        use approx::assert_relative_eq;
+
+        pub fn slab_system() -> System {
+            let mut system = system_from_xyz("6
+            cell: 20.0
+            H  0.3 -0.3  9.2
+            O  0.0  0.0 10.0
+            H -0.7 -0.7 10.3
+            H  2.3  1.7 12.2
+            O  2.0  2.0 13.0
+            H  1.3  1.3 13.3
+            ");
+            assert!(system.add_bond(0, 1).is_empty());
+            assert!(system.add_bond(1, 2).is_empty());
+            assert!(system.add_bond(3, 4).is_empty());
+            assert!(system.add_bond(4, 5).is_empty());
+            system.cell = UnitCell::ortho(20.0, 20.0, 60.0);
+
+            for particle in system.particles_mut() {
+                if particle.name == "O" {
+                    *particle.charge = -0.8476;
+                } else if particle.name == "H" {
+                    *particle.charge = 0.4238;
+                }
+            }
+            return system;
+        }
+
+        #[test]
+        fn energy() {
+            let system = slab_system();
+            let mut ewald = Ewald::new(8.0, 10, None);
+            ewald.set_slab_correction(Some(1.0));
+            ewald.prepare(&system.cell);
+
+            let mut dipole_z = 0.0;
+            for (&charge, position) in zip!(system.particles().charge, system.particles().position) {
+                dipole_z += charge * position[2];
+            }
+            let expected = 2.0 * PI * dipole_z * dipole_z / (20.0 * 20.0 * 60.0) / FOUR_PI_EPSILON_0;
+            assert_relative_eq!(ewald.dipolar_energy(&system), expected, max_relative = 1e-12);
+
+            // Extending the cell reduces the correction
+            ewald.set_slab_correction(Some(3.0));
+            ewald.prepare(&system.cell);
+            assert_relative_eq!(ewald.dipolar_energy(&system), expected / 3.0, max_relative = 1e-12);
+
+            // No correction without slab geometry
+            ewald.set_slab_correction(None);
+            ewald.prepare(&system.cell);
+            assert_eq!(ewald.dipolar_energy(&system), 0.0);
+        }
+
+        #[test]
+        fn extended_kspace() {
+            let system = slab_system();
+            let mut ewald = Ewald::new(8.0, 10, None);
+            ewald.set_slab_correction(Some(3.0));
+            ewald.prepare(&system.cell);
+
+            let mut extended = system.clone();
+            extended.cell = UnitCell::ortho(20.0, 20.0, 180.0);
+            let mut check = Ewald::new(8.0, 10, None);
+            check.prepare(&extended.cell);
+
+            assert_relative_eq!(
+                ewald.k_space_energy(&system), check.k_space_energy(&extended), max_relative = 1e-12
+            );
+        }
+
+        #[test]
+        fn small_gap() {
+            // The vacuum gap along z in the unit cell is smaller than the
+            // cutoff, the real space part should not see the periodic images
+            // along z.
+            let mut system = slab_system();
+            for position in system.particles_mut().position {
+                position[2] -= 8.5;
+            }
+            system.cell = UnitCell::ortho(20.0, 20.0, 5.0);
+            let mut ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+            ewald.write().set_slab_correction(Some(12.0));
+            ewald.set_restriction(PairRestriction::InterMolecular);
+
+            let mut large = system.clone();
+            large.cell = UnitCell::ortho(20.0, 20.0, 60.0);
+            let mut check = SharedEwald::new(Ewald::new(8.0, 10, None));
+            check.write().set_slab_correction(Some(1.0));
+            check.set_restriction(PairRestriction::InterMolecular);
+
+            assert_relative_eq!(ewald.energy(&system), check.energy(&large), max_relative = 1e-9);
+
+            let mut forces = vec![Vector3D::zero(); 6];
+            ewald.forces(&system, &mut forces);
+            let mut expected = vec![Vector3D::zero(); 6];
+            check.forces(&large, &mut expected);
+            for (force, expected) in zip!(&forces, &expected) {
+                assert_relative_eq!(force, expected, epsilon = 1e-9);
+            }
+
+            assert_relative_eq!(
+                ewald.atomic_virial(&system), check.atomic_virial(&large), epsilon = 1e-9
+            );
+        }
+
+        #[test]
+        #[should_panic(expected = "the a and b unit cell vectors must be in the xy plane to use Ewald slab correction")]
+        fn tilted_cell() {
+            let mut system = slab_system();
+            system.cell = UnitCell::triclinic(20.0, 20.0, 60.0, 80.0, 90.0, 90.0);
+            let mut ewald = Ewald::new(8.0, 10, None);
+            ewald.set_slab_correction(Some(3.0));
+            ewald.prepare(&system.cell);
+        }
+
+        #[test]
+        fn forces() {
+            let mut system = slab_system();
+            let mut ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+            ewald.write().set_slab_correction(Some(3.0));
+            ewald.set_restriction(PairRestriction::InterMolecular);
+
+            let mut forces = vec![Vector3D::zero(); 6];
+            ewald.forces(&system, &mut forces);
+
+            let eps = 1e-9;
+            let e = ewald.energy(&system);
+            system.particles_mut().position[0][2] += eps;
+            let e1 = ewald.energy(&system);
+            assert_relative_eq!((e - e1) / eps, forces[0][2], epsilon = 1e-6);
+        }
+
+        #[test]
+        fn atomic_virial() {
+            let mut system = slab_system();
+            let mut ewald = Ewald::new(8.0, 10, None);
+            ewald.set_slab_correction(Some(3.0));
+            ewald.prepare(&system.cell);
+
+            let eps = 1e-9;
+            let virial = ewald.dipolar_atomic_virial(&system);
+            for i in 0..3 {
+                ewald.prepare(&system.cell);
+                let e = ewald.dipolar_energy(&system);
+                super::atomic_virial::scale(&mut system, i, i, eps);
+                ewald.prepare(&system.cell);
+                let e1 = ewald.dipolar_energy(&system);
+                assert_relative_eq!(virial[i][i], (e - e1) / eps, epsilon = 1e-6);
+            }
+
+            // Check the molecular virial is consistent with the forces
+            let mut forces = vec![Vector3D::zero(); 6];
+            ewald.dipolar_forces(&system, &mut forces);
+            let molecular = ewald.dipolar_molecular_virial(&system);
+            let atomic = ewald.dipolar_atomic_virial(&system);
+            let positions = system.particles().position;
+            let mut correction = Matrix3::zero();
+            for molecule in system.molecules() {
+                let com = molecule.center_of_mass();
+                for i in molecule.indexes() {
+                    correction += forces[i].tensorial(&(positions[i] - com));
+                }
+            }
+            assert_relative_eq!(molecular, atomic - correction, epsilon = 1e-12);
+        }
+
+        #[test]
+        fn move_molecule() {
+            let mut system = slab_system();
+            let mut ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+            ewald.write().set_slab_correction(Some(3.0));
+            ewald.set_restriction(PairRestriction::InterMolecular);
+
+            let check = ewald.clone();
+            // Initialize cached values
+            let _ = ewald.energy(&system);
+            let old_energy = check.energy(&system);
+
+            let delta = Vector3D::new(0.5, -0.2, 1.3);
+            let new_positions = &[
+                system.particles().position[0] + delta,
+                system.particles().position[1] + delta,
+                system.particles().position[2] + delta,
+            ];
+            let cost = ewald.move_molecule_cost(&system, 0, new_positions);
+
+            system.particles_mut().position[0] = new_positions[0];
+            system.particles_mut().position[1] = new_positions[1];
+            system.particles_mut().position[2] = new_positions[2];
+            let new_energy = check.energy(&system);
+            assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+        }
+    }
+
+    mod corrections {
+        use super::*;
+        use crate::{GlobalPotential, GlobalCache, CoulombicPotential, Matrix3};
+        use crate::PairRestriction;
+
+        use approx::assert_relative_eq;
+
+        fn two_waters() -> System {
+            let mut system = system_from_xyz("6
+            cell: 20.0
+            H  0.3 -0.3 -0.8
+            O  0.0  0.0  0.0
+            H -0.7 -0.7  0.3
+            H  2.3  1.7 -0.8
+            O  2.0  2.0  0.0
+            H  1.3  1.3  0.3
+            ");
+            assert!(system.add_bond(0, 1).is_empty());
+            assert!(system.add_bond(1, 2).is_empty());
+            assert!(system.add_bond(3, 4).is_empty());
+            assert!(system.add_bond(4, 5).is_empty());
+
+            for particle in system.particles_mut() {
+                if particle.name == "O" {
+                    *particle.charge = -0.8476;
+                } else if particle.name == "H" {
+                    *particle.charge = 0.4238;
+                }
+            }
+            return system;
+        }
+
+        #[test]
+        fn surface_energy() {
+            let system = two_waters();
+            let mut ewald = Ewald::new(8.0, 10, None);
+            ewald.prepare(&system.cell);
+            assert_eq!(ewald.dipolar_energy(&system), 0.0);
+
+            ewald.set_surface_dielectric(1.0);
+            let dipole = Ewald::dipole(&system);
+            let expected = 2.0 * PI / 3.0 * dipole.norm2() / 8000.0 / FOUR_PI_EPSILON_0;
+            assert_relative_eq!(ewald.dipolar_energy(&system), expected, max_relative = 1e-12);
+
+            // Infinite dielectric constant is tin-foil boundary conditions
+            ewald.set_surface_dielectric(f64::INFINITY);
+            assert_eq!(ewald.dipolar_energy(&system), 0.0);
+        }
+
+        #[test]
+        fn surface_forces() {
+            let mut system = two_waters();
+            let mut ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+            ewald.write().set_surface_dielectric(1.0);
+            ewald.set_restriction(PairRestriction::InterMolecular);
+
+            let mut forces = vec![Vector3D::zero(); 6];
+            ewald.forces(&system, &mut forces);
+
+            let eps = 1e-9;
+            for i in 0..3 {
+                let e = ewald.energy(&system);
+                system.particles_mut().position[1][i] += eps;
+                let e1 = ewald.energy(&system);
+                system.particles_mut().position[1][i] -= eps;
+                assert_relative_eq!((e - e1) / eps, forces[1][i], epsilon = 1e-6);
+            }
+        }
+
+        #[test]
+        fn surface_virial() {
+            let mut system = two_waters();
+            let mut ewald = Ewald::new(8.0, 10, None);
+            ewald.set_surface_dielectric(2.0);
+            ewald.prepare(&system.cell);
+
+            let eps = 1e-9;
+            let virial = ewald.dipolar_atomic_virial(&system);
+            let mut finite_diff = Matrix3::zero();
+            for i in 0..3 {
+                for j in 0..3 {
+                    ewald.prepare(&system.cell);
+                    let e = ewald.dipolar_energy(&system);
+                    super::atomic_virial::scale(&mut system, i, j, eps);
+                    ewald.prepare(&system.cell);
+                    let e1 = ewald.dipolar_energy(&system);
+                    finite_diff[i][j] = (e - e1) / eps;
+                }
+            }
+
+            finite_diff = (finite_diff + finite_diff.transposed()) / 2.0;
+            assert_relative_eq!(virial, finite_diff, epsilon = 1e-6);
+        }
+
+        #[test]
+        fn surface_move_molecule() {
+            let mut system = two_waters();
+            let mut ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+            ewald.write().set_surface_dielectric(1.0);
+            ewald.set_restriction(PairRestriction::InterMolecular);
+
+            let check = ewald.clone();
+            // Initialize cached values
+            let _ = ewald.energy(&system);
+            let old_energy = check.energy(&system);
+
+            #[allow(clippy::unreadable_literal)]
+            let new_positions = &[
+                Vector3D::new(0.41727, 2.29401, -0.0558),
+                Vector3D::new(0.5097743599026461, 3.194114034722624, -0.020364564697826326),
+                Vector3D::new(-0.2501317777731211, 3.562366060753896, -0.6178033542374419),
+            ];
+            let cost = ewald.move_molecule_cost(&system, 0, new_positions);
+
+            system.particles_mut().position[0] = new_positions[0];
+            system.particles_mut().position[1] = new_positions[1];
+            system.particles_mut().position[2] = new_positions[2];
+            let new_energy = check.energy(&system);
+            assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+        }
+
+        #[test]
+        fn background() {
+            let mut system = system_from_xyz("1
+            cell: 20.0
+            Na 0.0 0.0 0.0
+            ");
+            system.particles_mut().charge[0] = 1.0;
+
+            let mut ewald = Ewald::new(8.0, 10, None);
+            ewald.prepare(&system.cell);
+            let alpha = ewald.alpha;
+            let expected = -PI / (2.0 * 8000.0 * alpha * alpha) / FOUR_PI_EPSILON_0;
+            assert_relative_eq!(ewald.background_energy(&system), expected, max_relative = 1e-12);
+
+            // Virial from finite differences
+            let eps = 1e-9;
+            let virial = ewald.background_virial(&system);
+            for i in 0..3 {
+                ewald.prepare(&system.cell);
+                let e = ewald.background_energy(&system);
+                super::atomic_virial::scale(&mut system, i, i, eps);
+                ewald.prepare(&system.cell);
+                let e1 = ewald.background_energy(&system);
+                assert_relative_eq!(virial[i][i], (e - e1) / eps, epsilon = 1e-6);
+            }
+
+            // No correction for neutral systems
+            let system = nacl_pair();
+            ewald.prepare(&system.cell);
+            assert_eq!(ewald.background_energy(&system), 0.0);
+        }
+    }
+
+    mod polarization {
+        use super::*;
+        use crate::{Matrix3, PairRestriction};
+        use crate::energy::{DipoleSolver, Polarization};
+
+        use approx::assert_relative_eq;
+
+        fn two_waters() -> System {
+            let mut system = system_from_xyz("6
+            cell: 20.0
+            H  0.3 -0.3 -0.8
+            O  0.0  0.0  0.0
+            H -0.7 -0.7  0.3
+            H  2.3  1.7 -0.8
+            O  2.0  2.0  0.0
+            H  1.3  1.3  0.3
+            ");
+            assert!(system.add_bond(0, 1).is_empty());
+            assert!(system.add_bond(1, 2).is_empty());
+            assert!(system.add_bond(3, 4).is_empty());
+            assert!(system.add_bond(4, 5).is_empty());
+
+            for particle in system.particles_mut() {
+                if particle.name == "O" {
+                    *particle.charge = -0.8476;
+                } else if particle.name == "H" {
+                    *particle.charge = 0.4238;
+                }
+            }
+            return system;
+        }
+
+        fn polarizable_ewald(solver: DipoleSolver) -> SharedEwald {
+            let mut polarization = Polarization::new(solver, 1e-12);
+            polarization.set_polarizability("O", 1.45);
+            polarization.set_polarizability("H", 0.5);
+
+            let mut ewald = Ewald::new(8.0, 7, None);
+            ewald.set_restriction(PairRestriction::InterMolecular);
+            ewald.set_polarization(polarization);
+            return SharedEwald::new(ewald);
+        }
+
+        #[test]
+        fn energy() {
+            let system = two_waters();
+            let mut ewald = SharedEwald::new(Ewald::new(8.0, 7, None));
+            ewald.set_restriction(PairRestriction::InterMolecular);
+            let no_polarization = ewald.energy(&system);
+
+            let iterative = polarizable_ewald(DipoleSolver::Iterative).energy(&system);
+            let conjugate_gradient = polarizable_ewald(DipoleSolver::ConjugateGradient).energy(&system);
+            assert_relative_eq!(iterative, conjugate_gradient, max_relative = 1e-9);
+            assert!(iterative < no_polarization);
+        }
+
+        #[test]
+        fn forces() {
+            let mut system = two_waters();
+            let ewald = polarizable_ewald(DipoleSolver::ConjugateGradient);
+
+            let mut forces = vec![Vector3D::zero(); system.size()];
+            ewald.forces(&system, &mut forces);
+            let total = forces.iter().fold(Vector3D::zero(), |sum, &force| sum + force);
+            assert!(total.norm() < 1e-10);
+
+            let eps = 1e-6;
+            for i in 0..system.size() {
+                for k in 0..3 {
+                    system.particles_mut().position[i][k] += eps;
+                    let e1 = ewald.energy(&system);
+                    system.particles_mut().position[i][k] -= 2.0 * eps;
+                    let e2 = ewald.energy(&system);
+                    system.particles_mut().position[i][k] += eps;
+                    assert_relative_eq!(-(e1 - e2) / (2.0 * eps), forces[i][k], epsilon = 1e-6);
+                }
+            }
+        }
+
+        #[test]
+        fn atomic_virial() {
+            let system = two_waters();
+            let ewald = polarizable_ewald(DipoleSolver::ConjugateGradient);
+
+            let virial = ewald.atomic_virial(&system);
+            let eps = 1e-6;
+            let mut finite_diff = Matrix3::zero();
+            for i in 0..3 {
+                for j in 0..3 {
+                    let mut plus = system.clone();
+                    super::atomic_virial::scale(&mut plus, i, j, eps);
+                    let mut minus = system.clone();
+                    super::atomic_virial::scale(&mut minus, i, j, -eps);
+                    finite_diff[i][j] = -(ewald.energy(&plus) - ewald.energy(&minus)) / (2.0 * eps);
+                }
+            }
+            assert_relative_eq!(virial, finite_diff, epsilon = 1e-6);
+        }
+
+        #[test]
+        fn surface_dielectric() {
+            let mut system = two_waters();
+            let ewald = polarizable_ewald(DipoleSolver::ConjugateGradient);
+            ewald.write().set_surface_dielectric(1.0);
+
+            let mut forces = vec![Vector3D::zero(); system.size()];
+            ewald.forces(&system, &mut forces);
+
+            let eps = 1e-6;
+            for k in 0..3 {
+                system.particles_mut().position[1][k] += eps;
+                let e1 = ewald.energy(&system);
+                system.particles_mut().position[1][k] -= 2.0 * eps;
+                let e2 = ewald.energy(&system);
+                system.particles_mut().position[1][k] += eps;
+                assert_relative_eq!(-(e1 - e2) / (2.0 * eps), forces[1][k], epsilon = 1e-6);
+            }
+
+            let virial = ewald.atomic_virial(&system);
+            let mut finite_diff = Matrix3::zero();
+            for i in 0..3 {
+                for j in 0..3 {
+                    let mut plus = system.clone();
+                    super::atomic_virial::scale(&mut plus, i, j, eps);
+                    let mut minus = system.clone();
+                    super::atomic_virial::scale(&mut minus, i, j, -eps);
+                    finite_diff[i][j] = -(ewald.energy(&plus) - ewald.energy(&minus)) / (2.0 * eps);
+                }
+            }
+            assert_relative_eq!(virial, finite_diff, epsilon = 1e-6);
+        }
+
+        #[test]
+        fn move_molecule() {
+            let mut system = two_waters();
+            let ewald = polarizable_ewald(DipoleSolver::ConjugateGradient);
+
+            let check = ewald.clone();
+            // Initialize cached values
+            let _ = ewald.energy(&system);
+            let old_energy = check.energy(&system);
+
+            #[allow(clippy::unreadable_literal)]
+            let new_positions = &[
+                Vector3D::new(0.41727, 2.29401, -0.0558),
+                Vector3D::new(0.5097743599026461, 3.194114034722624, -0.020364564697826326),
+                Vector3D::new(-0.2501317777731211, 3.562366060753896, -0.6178033542374419),
+            ];
+            let cost = ewald.move_molecule_cost(&system, 0, new_positions);
+
+            system.particles_mut().position[0] = new_positions[0];
+            system.particles_mut().position[1] = new_positions[1];
+            system.particles_mut().position[2] = new_positions[2];
+            let new_energy = check.energy(&system);
+            assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-8);
+        }
+    }
+
     // Comparing the value for each component of Ewald energy with the NIST
     // reference. See `tests/nist-spce.rs` for more information. These tests
     // check values that are not accessible from the outside of lumol-core.
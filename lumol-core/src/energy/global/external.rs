@@ -0,0 +1,674 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! External potentials acting independently on each particle: uniform
+//! electric field, flat walls, position restraints and spherical confinement.
+use std::collections::BTreeMap;
+use std::f64::consts::{PI, SQRT_2};
+
+use crate::Configuration;
+use crate::{Matrix3, Vector3D};
+
+use super::{GlobalCache, GlobalPotential};
+
+/// An external potential where the energy is a sum of independent
+/// contributions from each particle.
+trait ExternalPotential {
+    /// Get the energy of the particle at index `i` in the `configuration` if
+    /// it was located at `position`, together with the force acting on it.
+    fn particle(&self, configuration: &Configuration, i: usize, position: &Vector3D) -> (f64, Vector3D);
+
+    /// Get the point to use when computing the virial contribution of the
+    /// particle at index `i`, located at `position`. This should be the
+    /// position of the particle relative to the fixed origin of the external
+    /// potential, accounting for periodic boundary conditions if needed.
+    fn virial_position(&self, _: &Configuration, _: usize, position: &Vector3D) -> Vector3D {
+        *position
+    }
+}
+
+/// Implement `GlobalPotential` and `GlobalCache` for a type implementing
+/// `ExternalPotential`.
+macro_rules! impl_external_potential {
+    ($Type: ty) => {
+        impl GlobalPotential for $Type {
+            fn cutoff(&self) -> Option<f64> {
+                None
+            }
+
+            fn energy(&self, configuration: &Configuration) -> f64 {
+                let positions = configuration.particles().position;
+                return positions.iter().enumerate().map(|(i, position)| {
+                    self.particle(configuration, i, position).0
+                }).sum();
+            }
+
+            fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+                assert_eq!(forces.len(), configuration.size());
+                let positions = configuration.particles().position;
+                for (i, position) in positions.iter().enumerate() {
+                    forces[i] += self.particle(configuration, i, position).1;
+                }
+            }
+
+            fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+                let positions = configuration.particles().position;
+                let mut virial = Matrix3::zero();
+                for (i, position) in positions.iter().enumerate() {
+                    let (_, force) = self.particle(configuration, i, position);
+                    virial += force.tensorial(&self.virial_position(configuration, i, position));
+                }
+                return virial;
+            }
+
+            fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+                let positions = configuration.particles().position;
+                let mut virial = Matrix3::zero();
+                for molecule in configuration.molecules() {
+                    let com = molecule.center_of_mass();
+                    for i in molecule.indexes() {
+                        let position = &positions[i];
+                        let (_, force) = self.particle(configuration, i, position);
+                        let point = self.virial_position(configuration, i, position) - position + com;
+                        virial += force.tensorial(&point);
+                    }
+                }
+                return virial;
+            }
+        }
+
+        impl GlobalCache for $Type {
+            fn move_molecule_cost(
+                &self,
+                configuration: &Configuration,
+                molecule_id: usize,
+                new_positions: &[Vector3D],
+            ) -> f64 {
+                let positions = configuration.particles().position;
+                let indexes = configuration.molecule(molecule_id).indexes();
+                let mut cost = 0.0;
+                for (i, new_position) in indexes.zip(new_positions) {
+                    let old_energy = self.particle(configuration, i, &positions[i]).0;
+                    let new_energy = self.particle(configuration, i, new_position).0;
+                    cost += new_energy - old_energy;
+                }
+                return cost;
+            }
+
+            fn update(&self) {
+                // Nothing to do
+            }
+        }
+    };
+}
+
+/// Uniform external electric field.
+///
+/// The energy of a particle with charge `q` at position `r` in the electric
+/// field `E` is `V = - q E · r`, and the force acting on it is `F = q E`.
+/// The energy depends on the absolute position of the particles, and is only
+/// well defined along non-periodic directions. The forces are well defined
+/// in all cases.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{ElectricField, GlobalPotential};
+/// # use lumol_core::{System, Molecule, Particle, Vector3D};
+/// let field = ElectricField::new(Vector3D::new(0.0, 0.0, 0.5));
+///
+/// let mut system = System::new();
+/// let mut particle = Particle::with_position("Na", Vector3D::new(0.0, 0.0, 2.0));
+/// particle.charge = 1.0;
+/// system.add_molecule(Molecule::new(particle));
+///
+/// assert_eq!(field.energy(&system), -1.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct ElectricField {
+    /// The electric field
+    field: Vector3D,
+}
+
+impl ElectricField {
+    /// Create a new uniform electric field with the given value
+    pub fn new(field: Vector3D) -> ElectricField {
+        ElectricField {
+            field: field,
+        }
+    }
+}
+
+impl ExternalPotential for ElectricField {
+    fn particle(&self, configuration: &Configuration, i: usize, position: &Vector3D) -> (f64, Vector3D) {
+        let charge = configuration.particles().charge[i];
+        return (-charge * (self.field * position), charge * self.field);
+    }
+}
+
+impl_external_potential!(ElectricField);
+
+/// Interaction potential between a flat wall and a particle at distance `z`
+/// of the wall.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WallPotential {
+    /// Lennard-Jones 9-3 wall, from the integration of Lennard-Jones
+    /// interactions over a semi-infinite continuous solid:
+    ///
+    /// ```text
+    /// V(z) = ε [2/15 (σ/z)^9 - (σ/z)^3]
+    /// ```
+    LennardJones93 {
+        /// Distance parameter
+        sigma: f64,
+        /// Energy parameter
+        epsilon: f64,
+    },
+    /// Lennard-Jones 10-4-3 wall, from the integration of Lennard-Jones
+    /// interactions over stacked planes of atoms [Steele1973], with `Δ = 0.61
+    /// / √2 σ` the spacing between the planes:
+    ///
+    /// ```text
+    /// V(z) = 2 π ε [2/5 (σ/z)^10 - (σ/z)^4 - √2 σ^3 / (3 (z + Δ)^3)]
+    /// ```
+    ///
+    /// [Steele1973]: Steele, W. A. Surf. Sci. 36, 317 (1973).
+    LennardJones1043 {
+        /// Distance parameter
+        sigma: f64,
+        /// Energy parameter, including the density of the wall
+        epsilon: f64,
+    },
+}
+
+impl WallPotential {
+    /// Get the energy of a particle at distance `z` of the wall
+    fn energy(&self, z: f64) -> f64 {
+        match *self {
+            WallPotential::LennardJones93 { sigma, epsilon } => {
+                let s3 = f64::powi(sigma / z, 3);
+                epsilon * (2.0 / 15.0 * s3 * s3 * s3 - s3)
+            }
+            WallPotential::LennardJones1043 { sigma, epsilon } => {
+                let s4 = f64::powi(sigma / z, 4);
+                let s10 = f64::powi(sigma / z, 10);
+                let delta = 0.61 / SQRT_2 * sigma;
+                let planes = SQRT_2 * f64::powi(sigma, 3) / (3.0 * f64::powi(z + delta, 3));
+                2.0 * PI * epsilon * (0.4 * s10 - s4 - planes)
+            }
+        }
+    }
+
+    /// Get the norm of the force acting on a particle at distance `z` of the
+    /// wall, i.e. `- dV/dz`.
+    fn force(&self, z: f64) -> f64 {
+        match *self {
+            WallPotential::LennardJones93 { sigma, epsilon } => {
+                let s3 = f64::powi(sigma / z, 3);
+                epsilon * (1.2 * s3 * s3 * s3 - 3.0 * s3) / z
+            }
+            WallPotential::LennardJones1043 { sigma, epsilon } => {
+                let s4 = f64::powi(sigma / z, 4);
+                let s10 = f64::powi(sigma / z, 10);
+                let delta = 0.61 / SQRT_2 * sigma;
+                let planes = SQRT_2 * f64::powi(sigma, 3) / f64::powi(z + delta, 4);
+                2.0 * PI * epsilon * ((4.0 * s10 - 4.0 * s4) / z - planes)
+            }
+        }
+    }
+}
+
+/// Flat wall acting on all the particles in the system.
+///
+/// The wall is the plane containing all the points `r` with `n · r = p`, where
+/// `n` is the normal of the wall and `p` its position. Particles interact with
+/// the wall using a [`WallPotential`] depending on their distance to the wall
+/// `z = n · r - p`, truncated and shifted to zero at the cutoff distance.
+/// Particles are expected to stay on the side of the wall the normal points
+/// to, and particles on the other side have an infinite energy.
+///
+/// The distance to the wall uses the absolute position of the particles, so
+/// walls should be used along non-periodic directions, or with a unit cell
+/// large enough to contain the whole wall interaction range.
+///
+/// [`WallPotential`]: enum.WallPotential.html
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{Wall, WallPotential, GlobalPotential};
+/// # use lumol_core::{System, Molecule, Particle, Vector3D};
+/// let potential = WallPotential::LennardJones93 { sigma: 3.0, epsilon: 0.5 };
+/// // Wall in the z = 0 plane, with particles in the z > 0 region
+/// let wall = Wall::new(potential, Vector3D::new(0.0, 0.0, 1.0), 0.0, 10.0);
+///
+/// let mut system = System::new();
+/// system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 12.0))));
+/// assert_eq!(wall.energy(&system), 0.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct Wall {
+    /// Potential function for the interaction with the wall
+    potential: WallPotential,
+    /// Unit vector normal to the wall
+    normal: Vector3D,
+    /// Position of the wall along the normal
+    position: f64,
+    /// Cutoff distance
+    cutoff: f64,
+    /// Energy shift, ensuring that the energy is zero at the cutoff
+    shift: f64,
+}
+
+impl Wall {
+    /// Create a new wall interacting with the particles using the given
+    /// `potential`. The wall is normal to the `normal` vector, located at
+    /// `position` along this vector, and the interactions are truncated
+    /// and shifted at the `cutoff` distance.
+    pub fn new(potential: WallPotential, normal: Vector3D, position: f64, cutoff: f64) -> Wall {
+        assert!(normal.norm() > 0.0, "the normal of a wall can not be the null vector");
+        assert!(cutoff > 0.0, "the cutoff of a wall must be positive");
+        Wall {
+            potential: potential,
+            normal: normal.normalized(),
+            position: position,
+            cutoff: cutoff,
+            shift: potential.energy(cutoff),
+        }
+    }
+}
+
+impl ExternalPotential for Wall {
+    fn particle(&self, _: &Configuration, _: usize, position: &Vector3D) -> (f64, Vector3D) {
+        let z = self.normal * position - self.position;
+        if z <= 0.0 {
+            return (f64::INFINITY, Vector3D::zero());
+        } else if z >= self.cutoff {
+            return (0.0, Vector3D::zero());
+        }
+        let energy = self.potential.energy(z) - self.shift;
+        return (energy, self.potential.force(z) * self.normal);
+    }
+}
+
+impl_external_potential!(Wall);
+
+/// Harmonic and flat-bottom restraints of particles to reference positions.
+///
+/// The energy of a restrained particle at distance `d` of its reference
+/// position is zero if `d < R`, and
+///
+/// ```text
+/// V(d) = 1/2 k (d - R)^2
+/// ```
+///
+/// otherwise. Using `R = 0` gives harmonic restraints. The distance is
+/// computed using periodic boundary conditions. For the virial computation,
+/// reference positions are considered fixed in space when the unit cell
+/// changes.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{PositionRestraint, GlobalPotential};
+/// # use lumol_core::{System, Molecule, Particle, Vector3D};
+/// let mut system = System::new();
+/// system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 2.0))));
+///
+/// let mut restraint = PositionRestraint::harmonic(3.0);
+/// restraint.add_particle(0, Vector3D::zero());
+/// assert_eq!(restraint.energy(&system), 6.0);
+///
+/// let mut restraint = PositionRestraint::flat_bottom(3.0, 2.5);
+/// restraint.add_particle(0, Vector3D::zero());
+/// assert_eq!(restraint.energy(&system), 0.0);
+/// ```
+#[derive(Clone)]
+pub struct PositionRestraint {
+    /// Force constant of the restraint
+    k: f64,
+    /// Radius of the flat-bottom region
+    radius: f64,
+    /// Reference positions, indexed by particle index
+    references: BTreeMap<usize, Vector3D>,
+}
+
+impl PositionRestraint {
+    /// Create new harmonic position restraints with the force constant `k`,
+    /// without any restrained particle.
+    pub fn harmonic(k: f64) -> PositionRestraint {
+        PositionRestraint::flat_bottom(k, 0.0)
+    }
+
+    /// Create new flat-bottom position restraints with the force constant `k`
+    /// and the flat-bottom `radius`, without any restrained particle.
+    pub fn flat_bottom(k: f64, radius: f64) -> PositionRestraint {
+        assert!(radius >= 0.0, "the radius of flat-bottom restraints can not be negative");
+        PositionRestraint {
+            k: k,
+            radius: radius,
+            references: BTreeMap::new(),
+        }
+    }
+
+    /// Restrain the particle at index `i` to the `reference` position
+    pub fn add_particle(&mut self, i: usize, reference: Vector3D) {
+        let _ = self.references.insert(i, reference);
+    }
+
+    /// Get the vector between the reference position of the particle `i` and
+    /// the `position`, if this particle is restrained.
+    fn displacement(&self, configuration: &Configuration, i: usize, position: &Vector3D) -> Option<Vector3D> {
+        return self.references.get(&i).map(|reference| {
+            let mut displacement = position - reference;
+            configuration.cell.vector_image(&mut displacement);
+            displacement
+        });
+    }
+}
+
+impl ExternalPotential for PositionRestraint {
+    fn particle(&self, configuration: &Configuration, i: usize, position: &Vector3D) -> (f64, Vector3D) {
+        match self.displacement(configuration, i, position) {
+            Some(displacement) => flat_bottom(self.k, self.radius, displacement),
+            None => (0.0, Vector3D::zero()),
+        }
+    }
+
+    fn virial_position(&self, configuration: &Configuration, i: usize, position: &Vector3D) -> Vector3D {
+        match self.displacement(configuration, i, position) {
+            Some(displacement) => self.references[&i] + displacement,
+            None => *position,
+        }
+    }
+}
+
+impl_external_potential!(PositionRestraint);
+
+/// Spherical confinement of all the particles in the system.
+///
+/// Particles inside the sphere of radius `R` centered on `c` do not feel this
+/// potential, and particles outside of the sphere are pushed back using an
+/// harmonic potential:
+///
+/// ```text
+/// V(r) = 1/2 k (|r - c| - R)^2
+/// ```
+///
+/// The distance to the center uses the absolute position of the particles,
+/// without periodic boundary conditions.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{SphericalConfinement, GlobalPotential};
+/// # use lumol_core::{System, Molecule, Particle, Vector3D};
+/// let confinement = SphericalConfinement::new(Vector3D::zero(), 10.0, 4.0);
+///
+/// let mut system = System::new();
+/// system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 11.0, 0.0))));
+/// assert_eq!(confinement.energy(&system), 2.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct SphericalConfinement {
+    /// Center of the sphere
+    center: Vector3D,
+    /// Radius of the sphere
+    radius: f64,
+    /// Force constant
+    k: f64,
+}
+
+impl SphericalConfinement {
+    /// Create a new spherical confinement potential, using a sphere with the
+    /// given `center` and `radius`, and the force constant `k`.
+    pub fn new(center: Vector3D, radius: f64, k: f64) -> SphericalConfinement {
+        assert!(radius > 0.0, "the radius of spherical confinement must be positive");
+        SphericalConfinement {
+            center: center,
+            radius: radius,
+            k: k,
+        }
+    }
+}
+
+impl ExternalPotential for SphericalConfinement {
+    fn particle(&self, _: &Configuration, _: usize, position: &Vector3D) -> (f64, Vector3D) {
+        return flat_bottom(self.k, self.radius, position - self.center);
+    }
+}
+
+impl_external_potential!(SphericalConfinement);
+
+/// Get the energy and the force for an harmonic flat-bottom potential with
+/// force constant `k` and flat-bottom `radius`, at the given `displacement`
+/// from the center.
+fn flat_bottom(k: f64, radius: f64, displacement: Vector3D) -> (f64, Vector3D) {
+    let distance = displacement.norm();
+    if distance <= radius {
+        return (0.0, Vector3D::zero());
+    }
+    let excess = distance - radius;
+    return (0.5 * k * excess * excess, -k * excess / distance * displacement);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::System;
+    use crate::utils::system_from_xyz;
+
+    use approx::assert_relative_eq;
+
+    fn testing_system() -> System {
+        let mut system = system_from_xyz(
+            "4
+            cell: 10.0
+            O 0.3 1.2 2.5
+            H 1.1 1.6 2.9
+            Na 9.6 4.5 3.1
+            Cl 2.5 7.2 1.4
+            ",
+        );
+        let _ = system.add_bond(0, 1);
+        system.particles_mut().charge[0] = -0.8;
+        system.particles_mut().charge[1] = 0.8;
+        system.particles_mut().charge[2] = 1.0;
+        system.particles_mut().charge[3] = -1.0;
+        for (i, mass) in [16.0, 1.0, 23.0, 35.5].iter().enumerate() {
+            system.particles_mut().mass[i] = *mass;
+        }
+        return system;
+    }
+
+    /// Get a copy of `system`, scaled by `1 + eps` along the `i, j` direction.
+    /// If `molecular` is true, only the centers of mass of the molecules are
+    /// scaled.
+    fn scaled(system: &System, i: usize, j: usize, eps: f64, molecular: bool) -> System {
+        let mut scaling = Matrix3::one();
+        scaling[i][j] += eps;
+        let mut scaled = system.clone();
+        let new_cell = system.cell.scale(scaling);
+        if molecular {
+            for mut molecule in scaled.molecules_mut() {
+                let com = molecule.as_ref().center_of_mass();
+                let delta = new_cell.cartesian(&system.cell.fractional(&com)) - com;
+                for position in molecule.particles_mut().position {
+                    *position += delta;
+                }
+            }
+        } else {
+            for position in scaled.particles_mut().position {
+                *position = new_cell.cartesian(&system.cell.fractional(position));
+            }
+        }
+        scaled.cell = new_cell;
+        return scaled;
+    }
+
+    fn check_derivatives<P: GlobalPotential>(potential: &P, system: &System) {
+        let mut system = system.clone();
+        let eps = 1e-6;
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        potential.forces(&system, &mut forces);
+        for i in 0..system.size() {
+            for k in 0..3 {
+                system.particles_mut().position[i][k] += eps;
+                let e1 = potential.energy(&system);
+                system.particles_mut().position[i][k] -= 2.0 * eps;
+                let e2 = potential.energy(&system);
+                system.particles_mut().position[i][k] += eps;
+                assert_relative_eq!(-(e1 - e2) / (2.0 * eps), forces[i][k], epsilon = 1e-6);
+            }
+        }
+
+        for &molecular in &[false, true] {
+            let virial = if molecular {
+                potential.molecular_virial(&system)
+            } else {
+                potential.atomic_virial(&system)
+            };
+            let mut finite_diff = Matrix3::zero();
+            for i in 0..3 {
+                for j in 0..3 {
+                    let e1 = potential.energy(&scaled(&system, i, j, eps, molecular));
+                    let e2 = potential.energy(&scaled(&system, i, j, -eps, molecular));
+                    finite_diff[i][j] = -(e1 - e2) / (2.0 * eps);
+                }
+            }
+            assert_relative_eq!(virial, finite_diff, epsilon = 1e-6);
+        }
+    }
+
+    fn check_move_molecule<P: GlobalPotential>(potential: &P, system: &System) {
+        let mut system = system.clone();
+        let old_energy = potential.energy(&system);
+        let new_positions = &[Vector3D::new(0.8, 0.1, 0.9), Vector3D::new(1.5, 0.4, 1.8)];
+        let cost = potential.move_molecule_cost(&system, 0, new_positions);
+
+        system.particles_mut().position[0] = new_positions[0];
+        system.particles_mut().position[1] = new_positions[1];
+        let new_energy = potential.energy(&system);
+        assert_relative_eq!(cost, new_energy - old_energy, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn electric_field() {
+        let system = testing_system();
+        let field = ElectricField::new(Vector3D::new(0.1, -0.2, 0.3));
+        assert_relative_eq!(field.energy(&system), -1.856, epsilon = 1e-12);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        field.forces(&system, &mut forces);
+        assert_eq!(forces[2], Vector3D::new(0.1, -0.2, 0.3));
+        assert_eq!(forces[3], Vector3D::new(-0.1, 0.2, -0.3));
+
+        check_derivatives(&field, &system);
+        check_move_molecule(&field, &system);
+    }
+
+    #[test]
+    fn lennard_jones_93() {
+        let potential = WallPotential::LennardJones93 { sigma: 1.5, epsilon: 2.0 };
+        assert_relative_eq!(potential.energy(1.5), -26.0 / 15.0, epsilon = 1e-12);
+        assert_relative_eq!(potential.energy(3.0), -0.24947916666666667, epsilon = 1e-12);
+        // Minimum of the potential
+        assert_relative_eq!(potential.force(f64::powf(0.4, 1.0 / 6.0) * 1.5), 0.0, epsilon = 1e-12);
+
+        let wall = Wall::new(potential, Vector3D::new(0.0, 0.0, 2.0), 0.5, 2.5);
+        let system = testing_system();
+        assert_relative_eq!(wall.energy(&system), 17.181625128348454, epsilon = 1e-12);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        wall.forces(&system, &mut forces);
+        assert_eq!(forces[2], Vector3D::zero());
+        assert_eq!(forces[0][0], 0.0);
+        assert_eq!(forces[0][1], 0.0);
+
+        check_derivatives(&wall, &system);
+        check_move_molecule(&wall, &system);
+    }
+
+    #[test]
+    fn lennard_jones_1043() {
+        let potential = WallPotential::LennardJones1043 { sigma: 1.5, epsilon: 2.0 };
+        assert_relative_eq!(potential.energy(1.5), -9.559953724218104, epsilon = 1e-12);
+        assert_relative_eq!(potential.energy(3.0), -1.1926525441119302, epsilon = 1e-12);
+
+        let wall = Wall::new(potential, Vector3D::new(0.0, 0.0, 1.0), 0.5, 2.5);
+        let system = testing_system();
+        assert_relative_eq!(wall.energy(&system), 728.3058873844088, epsilon = 1e-12);
+
+        check_derivatives(&wall, &system);
+        check_move_molecule(&wall, &system);
+    }
+
+    #[test]
+    fn wall_cutoff() {
+        let potential = WallPotential::LennardJones93 { sigma: 1.5, epsilon: 2.0 };
+        let wall = Wall::new(potential, Vector3D::new(1.0, 0.0, 0.0), 0.0, 2.5);
+
+        let mut system = testing_system();
+        system.particles_mut().position[0] = Vector3D::new(2.5, 0.0, 0.0);
+        system.particles_mut().position[1] = Vector3D::new(3.0, 0.0, 0.0);
+        system.particles_mut().position[2] = Vector3D::new(5.0, 0.0, 0.0);
+        system.particles_mut().position[3] = Vector3D::new(8.0, 0.0, 0.0);
+        assert_eq!(wall.energy(&system), 0.0);
+
+        system.particles_mut().position[0] = Vector3D::new(-0.1, 0.0, 0.0);
+        assert_eq!(wall.energy(&system), f64::INFINITY);
+    }
+
+    #[test]
+    fn harmonic_restraint() {
+        let system = testing_system();
+        let mut restraint = PositionRestraint::harmonic(3.0);
+        restraint.add_particle(0, Vector3D::new(0.0, 1.0, 2.0));
+        restraint.add_particle(1, Vector3D::new(1.0, 1.5, 3.0));
+        // Using periodic boundary conditions
+        restraint.add_particle(2, Vector3D::new(0.1, 4.4, 3.0));
+        assert_relative_eq!(restraint.energy(&system), 1.02, epsilon = 1e-12);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        restraint.forces(&system, &mut forces);
+        assert_relative_eq!(forces[2], Vector3D::new(1.5, -0.3, -0.3), epsilon = 1e-12);
+        assert_eq!(forces[3], Vector3D::zero());
+
+        check_derivatives(&restraint, &system);
+        check_move_molecule(&restraint, &system);
+    }
+
+    #[test]
+    fn flat_bottom_restraint() {
+        let system = testing_system();
+        let mut restraint = PositionRestraint::flat_bottom(3.0, 0.5);
+        restraint.add_particle(0, Vector3D::new(0.0, 1.0, 2.0));
+        restraint.add_particle(1, Vector3D::new(1.0, 1.5, 3.0));
+        restraint.add_particle(2, Vector3D::new(0.1, 4.4, 3.0));
+        assert_relative_eq!(restraint.energy(&system), 0.020915036148658746, epsilon = 1e-12);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        restraint.forces(&system, &mut forces);
+        // Particle 1 is inside the flat bottom region
+        assert_eq!(forces[1], Vector3D::zero());
+
+        check_derivatives(&restraint, &system);
+        check_move_molecule(&restraint, &system);
+    }
+
+    #[test]
+    fn spherical_confinement() {
+        let system = testing_system();
+        let confinement = SphericalConfinement::new(Vector3D::new(2.0, 2.0, 2.0), 4.0, 3.0);
+        assert_relative_eq!(confinement.energy(&system), 27.2943987134166, epsilon = 1e-12);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        confinement.forces(&system, &mut forces);
+        assert_eq!(forces[0], Vector3D::zero());
+        assert_eq!(forces[1], Vector3D::zero());
+
+        check_derivatives(&confinement, &system);
+        check_move_molecule(&confinement, &system);
+    }
+}
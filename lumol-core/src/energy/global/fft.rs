@@ -0,0 +1,236 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Fast Fourier transforms of complex data, for use in particle-mesh
+//! summations.
+//!
+//! This is a simple mixed-radix implementation of the Cooley-Tukey algorithm,
+//! working for any size. It is fastest for sizes which only contain small
+//! prime factors, see `fft_size`.
+use std::f64::consts::PI;
+
+use rayon::prelude::*;
+
+use crate::Complex;
+
+/// Direction of a Fourier transform
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    /// Forward transform: `X_k = \sum_n x_n exp(-2 i π k n / N)`
+    Forward,
+    /// Backward transform: `x_n = \sum_k X_k exp(2 i π k n / N)`. This
+    /// transform is not normalized.
+    Backward,
+}
+
+impl Direction {
+    fn sign(self) -> f64 {
+        match self {
+            Direction::Forward => -1.0,
+            Direction::Backward => 1.0,
+        }
+    }
+}
+
+/// Get the smallest integer bigger or equal to `n` with only 2, 3 and 5 as
+/// prime factors.
+pub fn fft_size(n: usize) -> usize {
+    let mut size = usize::max(n, 1);
+    loop {
+        let mut remaining = size;
+        for &factor in &[2, 3, 5] {
+            while remaining % factor == 0 {
+                remaining /= factor;
+            }
+        }
+        if remaining == 1 {
+            return size;
+        }
+        size += 1;
+    }
+}
+
+/// Get the smallest prime factor of `n`
+fn smallest_factor(n: usize) -> usize {
+    let mut factor = 2;
+    while factor * factor <= n {
+        if n % factor == 0 {
+            return factor;
+        }
+        factor += 1;
+    }
+    return n;
+}
+
+/// Compute the discrete Fourier transform of `data` in place, using the
+/// naive `O(N²)` algorithm.
+fn dft(data: &mut [Complex], sign: f64) {
+    let n = data.len();
+    let input = data.to_vec();
+    for (k, output) in data.iter_mut().enumerate() {
+        let mut sum = Complex::zero();
+        for (j, &value) in input.iter().enumerate() {
+            let phase = sign * 2.0 * PI * ((j * k) % n) as f64 / n as f64;
+            sum += Complex::polar(1.0, phase) * value;
+        }
+        *output = sum;
+    }
+}
+
+/// Compute the discrete Fourier transform of `data` in place, using a
+/// recursive mixed-radix decomposition of the size.
+fn fft_recursive(data: &mut [Complex], sign: f64) {
+    let n = data.len();
+    if n <= 1 {
+        return;
+    }
+
+    let radix = smallest_factor(n);
+    if radix == n {
+        dft(data, sign);
+        return;
+    }
+
+    // Split the data in `radix` interleaved sub-sequences, and transform each
+    // one of them
+    let m = n / radix;
+    let mut sub = vec![Complex::zero(); n];
+    for r in 0..radix {
+        for j in 0..m {
+            sub[r * m + j] = data[j * radix + r];
+        }
+    }
+    for chunk in sub.chunks_mut(m) {
+        fft_recursive(chunk, sign);
+    }
+
+    // Combine the sub-sequences transforms
+    for s in 0..radix {
+        for k in 0..m {
+            let index = k + m * s;
+            let mut sum = Complex::zero();
+            for r in 0..radix {
+                let phase = sign * 2.0 * PI * ((r * index) % n) as f64 / n as f64;
+                sum += Complex::polar(1.0, phase) * sub[r * m + k];
+            }
+            data[index] = sum;
+        }
+    }
+}
+
+/// Compute the discrete Fourier transform of three-dimensional `data` in
+/// place, in the given `direction`. The data is stored in row-major order,
+/// with the given `size`: the element `(i, j, k)` is at index
+/// `(i * size.1 + j) * size.2 + k`.
+pub fn fft3d(data: &mut [Complex], size: (usize, usize, usize), direction: Direction) {
+    let (n0, n1, n2) = size;
+    assert_eq!(data.len(), n0 * n1 * n2, "wrong data size in fft3d");
+    let sign = direction.sign();
+
+    // The last two dimensions are contiguous inside each slab of constant
+    // first index, and slabs can be transformed in parallel.
+    data.par_chunks_mut(n1 * n2).for_each(|slab| {
+        for line in slab.chunks_mut(n2) {
+            fft_recursive(line, sign);
+        }
+
+        let mut line = vec![Complex::zero(); n1];
+        for k in 0..n2 {
+            for (j, value) in line.iter_mut().enumerate() {
+                *value = slab[j * n2 + k];
+            }
+            fft_recursive(&mut line, sign);
+            for (j, &value) in line.iter().enumerate() {
+                slab[j * n2 + k] = value;
+            }
+        }
+    });
+
+    let mut line = vec![Complex::zero(); n0];
+    for j in 0..n1 {
+        for k in 0..n2 {
+            for (i, value) in line.iter_mut().enumerate() {
+                *value = data[(i * n1 + j) * n2 + k];
+            }
+            fft_recursive(&mut line, sign);
+            for (i, &value) in line.iter().enumerate() {
+                data[(i * n1 + j) * n2 + k] = value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn check(n: usize) {
+        let input = (0..n).map(|i| {
+            Complex::cartesian(f64::cos(i as f64), 0.5 * i as f64)
+        }).collect::<Vec<_>>();
+
+        let mut expected = input.clone();
+        dft(&mut expected, -1.0);
+
+        let mut data = input.clone();
+        fft_recursive(&mut data, Direction::Forward.sign());
+        for (value, expected) in data.iter().zip(&expected) {
+            assert_relative_eq!(value.real(), expected.real(), epsilon = 1e-9);
+            assert_relative_eq!(value.imag(), expected.imag(), epsilon = 1e-9);
+        }
+
+        fft_recursive(&mut data, Direction::Backward.sign());
+        for (value, expected) in data.iter().zip(&input) {
+            assert_relative_eq!(value.real() / n as f64, expected.real(), epsilon = 1e-9);
+            assert_relative_eq!(value.imag() / n as f64, expected.imag(), epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn one_dimensional() {
+        for &n in &[1, 2, 3, 7, 8, 12, 30, 35, 64] {
+            check(n);
+        }
+    }
+
+    #[test]
+    fn three_dimensional() {
+        let size = (4, 3, 5);
+        let input = (0..60).map(|i| Complex::cartesian(f64::sin(i as f64), 0.0)).collect::<Vec<_>>();
+        let mut data = input.clone();
+        fft3d(&mut data, size, Direction::Forward);
+
+        // Check one value against the direct sum
+        let (m0, m1, m2) = (1, 2, 3);
+        let mut expected = Complex::zero();
+        for i in 0..4 {
+            for j in 0..3 {
+                for k in 0..5 {
+                    let phase = -2.0 * PI * (
+                        (m0 * i) as f64 / 4.0 + (m1 * j) as f64 / 3.0 + (m2 * k) as f64 / 5.0
+                    );
+                    expected += Complex::polar(1.0, phase) * input[(i * 3 + j) * 5 + k];
+                }
+            }
+        }
+        let value = data[(m0 * 3 + m1) * 5 + m2];
+        assert_relative_eq!(value.real(), expected.real(), epsilon = 1e-9);
+        assert_relative_eq!(value.imag(), expected.imag(), epsilon = 1e-9);
+
+        fft3d(&mut data, size, Direction::Backward);
+        for (value, expected) in data.iter().zip(&input) {
+            assert_relative_eq!(value.real() / 60.0, expected.real(), epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(fft_size(0), 1);
+        assert_eq!(fft_size(7), 8);
+        assert_eq!(fft_size(11), 12);
+        assert_eq!(fft_size(31), 32);
+        assert_eq!(fft_size(61), 64);
+        assert_eq!(fft_size(26), 27);
+    }
+}
@@ -0,0 +1,454 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Common code for short-range many-body potentials, where the energy can be
+//! decomposed in contributions from each particle, depending on the positions
+//! of the neighbors of this particle.
+use std::io;
+
+use rayon::prelude::*;
+
+use crate::utils::ThreadLocalVec;
+use crate::sys::NeighborList;
+use crate::Configuration;
+use crate::{Matrix3, Vector3D};
+
+/// A neighbor of a central particle
+#[derive(Clone, Copy)]
+pub(super) struct Neighbor {
+    /// Index of the neighbor in the configuration
+    pub index: usize,
+    /// Index of the neighbor element in the potential
+    pub element: usize,
+    /// Vector going from the central particle to the neighbor
+    pub vector: Vector3D,
+    /// Distance between the central particle and the neighbor
+    pub distance: f64,
+}
+
+/// A many-body potential with a per-particle energy decomposition
+pub(super) trait ManyBody: Sync {
+    /// Get the names of the elements in this potential
+    fn elements(&self) -> &[String];
+
+    /// Get the maximal cutoff distance for neighbors
+    fn max_cutoff(&self) -> f64;
+
+    /// Get the neighbor list used to find the neighbors of the particles
+    fn neighbor_list(&self) -> &NeighborList;
+
+    /// Get the energy associated with a particle of the given `element`,
+    /// surrounded by `neighbors`. The gradient of this energy with respect to
+    /// each neighbor `vector` must be added to `gradients`.
+    fn particle_energy(&self, element: usize, neighbors: &[Neighbor], gradients: &mut [Vector3D]) -> f64;
+}
+
+/// Get the element index for all the particles in the `configuration`, or
+/// `None` for particles not interacting with the potential.
+fn elements_indexes<P: ManyBody>(potential: &P, configuration: &Configuration) -> Vec<Option<usize>> {
+    configuration.particles().name.iter().map(|name| {
+        potential.elements().iter().position(|element| element == name)
+    }).collect()
+}
+
+/// Get all the neighbors of the particle `i`, using the `position` function
+/// to get the particles positions. This checks all the particles in the
+/// configuration, and should only be used for particles at new positions.
+fn find_neighbors<P, F>(
+    potential: &P,
+    configuration: &Configuration,
+    elements: &[Option<usize>],
+    i: usize,
+    position: F,
+) -> Vec<Neighbor> where P: ManyBody, F: Fn(usize) -> Vector3D {
+    let cutoff = potential.max_cutoff();
+    let center = position(i);
+
+    let mut neighbors = Vec::new();
+    for (j, element) in elements.iter().enumerate() {
+        if i == j {
+            continue;
+        }
+
+        if let Some(element) = *element {
+            let mut vector = position(j) - center;
+            configuration.cell.vector_image(&mut vector);
+            let distance = vector.norm();
+            if distance < cutoff {
+                neighbors.push(Neighbor {
+                    index: j,
+                    element: element,
+                    vector: vector,
+                    distance: distance,
+                });
+            }
+        }
+    }
+    return neighbors;
+}
+
+/// Get the neighbors of all the particles in the `configuration`, using the
+/// neighbor list of the `potential`. Particles not interacting with the
+/// potential do not have any neighbor.
+fn all_neighbors<P: ManyBody>(
+    potential: &P,
+    configuration: &Configuration,
+    elements: &[Option<usize>],
+) -> Vec<Vec<Neighbor>> {
+    let cutoff = potential.max_cutoff();
+    let list = potential.neighbor_list().neighbors(configuration, Some(cutoff));
+
+    let mut neighbors = vec![Vec::new(); configuration.size()];
+    for i in 0..configuration.size() {
+        let element_i = match elements[i] {
+            Some(element) => element,
+            None => continue,
+        };
+
+        for j in list.of(i) {
+            if let Some(element_j) = elements[j] {
+                // vector going from i to j
+                let vector = -configuration.nearest_image(i, j);
+                let distance = vector.norm();
+                if distance < cutoff {
+                    neighbors[i].push(Neighbor {
+                        index: j,
+                        element: element_j,
+                        vector: vector,
+                        distance: distance,
+                    });
+                    neighbors[j].push(Neighbor {
+                        index: i,
+                        element: element_i,
+                        vector: -vector,
+                        distance: distance,
+                    });
+                }
+            }
+        }
+    }
+    return neighbors;
+}
+
+pub(super) fn energy<P: ManyBody>(potential: &P, configuration: &Configuration) -> f64 {
+    let elements = elements_indexes(potential, configuration);
+    let neighbors = all_neighbors(potential, configuration, &elements);
+    let energies = (0..configuration.size()).into_par_iter().map(|i| {
+        let element = match elements[i] {
+            Some(element) => element,
+            None => return 0.0,
+        };
+        let mut gradients = vec![Vector3D::zero(); neighbors[i].len()];
+        return potential.particle_energy(element, &neighbors[i], &mut gradients);
+    });
+    return energies.sum();
+}
+
+pub(super) fn forces<P: ManyBody>(potential: &P, configuration: &Configuration, forces: &mut [Vector3D]) {
+    assert_eq!(forces.len(), configuration.size());
+    // To avoid race conditions, each thread needs its own local forces Vec
+    let thread_local_forces = ThreadLocalVec::with_size(configuration.size());
+
+    let elements = elements_indexes(potential, configuration);
+    let neighbors = all_neighbors(potential, configuration, &elements);
+    (0..configuration.size()).into_par_iter().for_each(|i| {
+        let element = match elements[i] {
+            Some(element) => element,
+            None => return,
+        };
+        let mut gradients = vec![Vector3D::zero(); neighbors[i].len()];
+        let _ = potential.particle_energy(element, &neighbors[i], &mut gradients);
+
+        // Get the thread local forces Vec
+        let mut forces = thread_local_forces.borrow_mut();
+        for (neighbor, gradient) in neighbors[i].iter().zip(&gradients) {
+            forces[i] += *gradient;
+            forces[neighbor.index] -= *gradient;
+        }
+    });
+
+    // At this point all the forces are computed, but the results are
+    // scattered across all thread local Vecs, here we gather them.
+    thread_local_forces.sum_into(forces);
+}
+
+pub(super) fn atomic_virial<P: ManyBody>(potential: &P, configuration: &Configuration) -> Matrix3 {
+    let elements = elements_indexes(potential, configuration);
+    let neighbors = all_neighbors(potential, configuration, &elements);
+    let virials = (0..configuration.size()).into_par_iter().map(|i| {
+        let element = match elements[i] {
+            Some(element) => element,
+            None => return Matrix3::zero(),
+        };
+        let mut gradients = vec![Vector3D::zero(); neighbors[i].len()];
+        let _ = potential.particle_energy(element, &neighbors[i], &mut gradients);
+
+        let mut virial = Matrix3::zero();
+        for (neighbor, gradient) in neighbors[i].iter().zip(&gradients) {
+            let force = -*gradient;
+            virial += force.tensorial(&neighbor.vector);
+        }
+        return virial;
+    });
+    return virials.sum();
+}
+
+pub(super) fn molecular_virial<P: ManyBody>(potential: &P, configuration: &Configuration) -> Matrix3 {
+    let atomic = atomic_virial(potential, configuration);
+
+    let mut forces = vec![Vector3D::zero(); configuration.size()];
+    self::forces(potential, configuration, &mut forces);
+
+    // Remove the contribution of the intra-molecular part of the forces
+    let positions = configuration.particles().position;
+    let mut correction = Matrix3::zero();
+    for molecule in configuration.molecules() {
+        let com = molecule.center_of_mass();
+        for i in molecule.indexes() {
+            correction += forces[i].tensorial(&(positions[i] - com));
+        }
+    }
+
+    return atomic - correction;
+}
+
+pub(super) fn move_molecule_cost<P: ManyBody>(
+    potential: &P,
+    configuration: &Configuration,
+    molecule_id: usize,
+    new_positions: &[Vector3D],
+) -> f64 {
+    let elements = elements_indexes(potential, configuration);
+    let positions = configuration.particles().position;
+    let moved = configuration.molecule(molecule_id).indexes();
+    let new_position = |i: usize| {
+        if moved.contains(&i) {
+            new_positions[i - moved.start]
+        } else {
+            positions[i]
+        }
+    };
+
+    let old_neighbors = all_neighbors(potential, configuration, &elements);
+    // Neighbors of the moved particles at their new positions
+    let moved_neighbors = moved.clone().map(|i| {
+        if elements[i].is_some() {
+            find_neighbors(potential, configuration, &elements, i, &new_position)
+        } else {
+            Vec::new()
+        }
+    }).collect::<Vec<_>>();
+
+    // Only the energy of the moved particles and of their neighbors before
+    // and after the move changes
+    let mut affected = vec![false; configuration.size()];
+    for (i, new_neighbors) in moved.clone().zip(&moved_neighbors) {
+        affected[i] = true;
+        for neighbor in old_neighbors[i].iter().chain(new_neighbors) {
+            affected[neighbor.index] = true;
+        }
+    }
+
+    let costs = (0..configuration.size()).into_par_iter().filter(|&i| affected[i]).map(|i| {
+        let element = match elements[i] {
+            Some(element) => element,
+            None => return 0.0,
+        };
+
+        let mut gradients = vec![Vector3D::zero(); old_neighbors[i].len()];
+        let old_energy = potential.particle_energy(element, &old_neighbors[i], &mut gradients);
+
+        let new_neighbors = if moved.contains(&i) {
+            moved_neighbors[i - moved.start].clone()
+        } else {
+            // Replace the moved particles in the neighbors of i
+            let mut new_neighbors = old_neighbors[i].iter()
+                .filter(|neighbor| !moved.contains(&neighbor.index))
+                .cloned()
+                .collect::<Vec<_>>();
+            for (j, neighbors_j) in moved.clone().zip(&moved_neighbors) {
+                if let Some(neighbor) = neighbors_j.iter().find(|neighbor| neighbor.index == i) {
+                    new_neighbors.push(Neighbor {
+                        index: j,
+                        element: elements[j].expect("neighbors should interact with the potential"),
+                        vector: -neighbor.vector,
+                        distance: neighbor.distance,
+                    });
+                }
+            }
+            new_neighbors
+        };
+        let mut gradients = vec![Vector3D::zero(); new_neighbors.len()];
+        let new_energy = potential.particle_energy(element, &new_neighbors, &mut gradients);
+
+        return new_energy - old_energy;
+    });
+    return costs.sum();
+}
+
+/// Read the entries in a parameter file in the format used by LAMMPS for
+/// many-body potentials. Each entry contains three elements names, followed by
+/// `count` numeric parameters. Comments start with `#`, and entries can span
+/// multiple lines.
+///
+/// This function returns the list of elements, in the order in which they
+/// first appear in the file, and the parameters for all the triplets of
+/// elements, in the `(i * n + j) * n + k` layout.
+pub(super) fn read_parameters(content: &str, count: usize) -> io::Result<(Vec<String>, Vec<Vec<f64>>)> {
+    let mut tokens = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("");
+        tokens.extend(line.split_whitespace());
+    }
+
+    let size = count + 3;
+    if tokens.len() % size != 0 {
+        return Err(invalid_data(format!(
+            "incomplete entry in parameter file, entries should contain 3 elements and {} parameters", count
+        )));
+    }
+
+    let mut elements: Vec<String> = Vec::new();
+    let mut entries = Vec::new();
+    for entry in tokens.chunks(size) {
+        let mut triplet = [0; 3];
+        for (index, &name) in triplet.iter_mut().zip(&entry[..3]) {
+            *index = match elements.iter().position(|element| element == name) {
+                Some(position) => position,
+                None => {
+                    elements.push(name.into());
+                    elements.len() - 1
+                }
+            };
+        }
+
+        let parameters = entry[3..].iter().map(|token| token.parse::<f64>().map_err(|_| invalid_data(
+            format!("could not parse '{}' as a number in {}-{}-{} entry", token, entry[0], entry[1], entry[2])
+        ))).collect::<io::Result<Vec<_>>>()?;
+        entries.push((triplet, parameters));
+    }
+
+    let n = elements.len();
+    let mut parameters = vec![Vec::new(); n * n * n];
+    for ([i, j, k], values) in entries {
+        let index = (i * n + j) * n + k;
+        if !parameters[index].is_empty() {
+            return Err(invalid_data(format!(
+                "duplicated {}-{}-{} entry in parameter file", elements[i], elements[j], elements[k]
+            )));
+        }
+        parameters[index] = values;
+    }
+
+    for (index, values) in parameters.iter().enumerate() {
+        if values.is_empty() {
+            let (i, j, k) = (index / (n * n), (index / n) % n, index % n);
+            return Err(invalid_data(format!(
+                "missing {}-{}-{} entry in parameter file", elements[i], elements[j], elements[k]
+            )));
+        }
+    }
+
+    return Ok((elements, parameters));
+}
+
+pub(super) fn invalid_data<S: Into<String>>(message: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Get the cosine of the angle between `u` and `v`, and the derivatives of
+/// this cosine with respect to `u` and `v`
+pub(super) fn cos_angle(u: Vector3D, v: Vector3D) -> (f64, Vector3D, Vector3D) {
+    let norm_u = u.norm();
+    let norm_v = v.norm();
+    let cos = (u * v) / (norm_u * norm_v);
+    let d_u = v / (norm_u * norm_v) - cos / (norm_u * norm_u) * u;
+    let d_v = u / (norm_u * norm_v) - cos / (norm_v * norm_v) * v;
+    return (cos, d_u, d_v);
+}
+
+/// Create a system containing 2x2x2 unit cells of diamond-like silicon with
+/// the given lattice constant.
+#[cfg(test)]
+pub(super) fn diamond_silicon(lattice: f64) -> crate::System {
+    use crate::{Molecule, Particle, System, UnitCell};
+
+    const BASIS: [[f64; 3]; 8] = [
+        [0.0, 0.0, 0.0], [0.0, 0.5, 0.5], [0.5, 0.0, 0.5], [0.5, 0.5, 0.0],
+        [0.25, 0.25, 0.25], [0.25, 0.75, 0.75], [0.75, 0.25, 0.75], [0.75, 0.75, 0.25],
+    ];
+
+    let mut system = System::with_cell(UnitCell::cubic(2.0 * lattice));
+    for i in 0..2 {
+        for j in 0..2 {
+            for k in 0..2 {
+                for basis in &BASIS {
+                    let position = lattice * Vector3D::new(
+                        i as f64 + basis[0], j as f64 + basis[1], k as f64 + basis[2]
+                    );
+                    system.add_molecule(Molecule::new(Particle::with_position("Si", position)));
+                }
+            }
+        }
+    }
+    return system;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameters() {
+        let content = "
+        # comment
+        A A A 1 2
+        A B B 3 4 # other comment
+        A A B 5 6
+        A B A 7 8
+        B B B 9 10
+        B A A 11 12
+        B B A
+        13 14
+        B A B 15 16
+        ";
+        let (elements, parameters) = read_parameters(content, 2).unwrap();
+        assert_eq!(elements, ["A", "B"]);
+        assert_eq!(parameters[0], [1.0, 2.0]);
+        assert_eq!(parameters[1], [5.0, 6.0]);
+        assert_eq!(parameters[3], [3.0, 4.0]);
+        assert_eq!(parameters[6], [13.0, 14.0]);
+
+        let error = read_parameters("A A A 1 2 3", 2).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "incomplete entry in parameter file, entries should contain 3 elements and 2 parameters"
+        );
+
+        let error = read_parameters("A A A 1 two", 2).unwrap_err();
+        assert_eq!(error.to_string(), "could not parse 'two' as a number in A-A-A entry");
+
+        let error = read_parameters("A A A 1 2\nA A A 1 2", 2).unwrap_err();
+        assert_eq!(error.to_string(), "duplicated A-A-A entry in parameter file");
+
+        let error = read_parameters("A A A 1 2\nB B B 1 2", 2).unwrap_err();
+        assert_eq!(error.to_string(), "missing A-A-B entry in parameter file");
+    }
+
+    #[test]
+    fn cosine() {
+        let u = Vector3D::new(1.0, 0.2, -0.3);
+        let v = Vector3D::new(-0.5, 1.1, 0.4);
+        let (cos, d_u, d_v) = cos_angle(u, v);
+
+        let eps = 1e-8;
+        for i in 0..3 {
+            let mut delta = Vector3D::zero();
+            delta[i] = eps;
+            let (cos_u, _, _) = cos_angle(u + delta, v);
+            let (cos_v, _, _) = cos_angle(u, v + delta);
+            assert!(f64::abs((cos_u - cos) / eps - d_u[i]) < 1e-6);
+            assert!(f64::abs((cos_v - cos) / eps - d_v[i]) < 1e-6);
+        }
+    }
+}
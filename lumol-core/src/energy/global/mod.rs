@@ -199,12 +199,62 @@ pub trait CoulombicPotential: GlobalPotential + BoxCloneCoulombic {
     /// future call to `GlobalPotential::energy`, `GlobalPotential::force` or
     /// `GlobalPotential::virial` should use this restriction.
     fn set_restriction(&mut self, restriction: PairRestriction);
+
+    /// Compute the derivative of the energy with respect to the alchemical
+    /// coupling parameter of this solver. Solvers without alchemical coupling
+    /// return zero.
+    fn lambda_derivative(&self, _: &Configuration) -> f64 {
+        return 0.0;
+    }
 }
 
 impl_box_clone!(CoulombicPotential, BoxCloneCoulombic, box_clone_coulombic);
 
+mod alchemical;
+pub use self::alchemical::AlchemicalCoulomb;
+
+mod polarization;
+pub use self::polarization::{DipoleSolver, Polarization};
+use self::polarization::DampedDipoles;
+
 mod wolf;
 pub use self::wolf::Wolf;
+use self::wolf::DampedCoulomb;
 
 mod ewald;
 pub use self::ewald::{Ewald, SharedEwald};
+
+mod fft;
+
+mod pme;
+pub use self::pme::{Pme, SharedPme};
+
+mod dispersion;
+pub use self::dispersion::DispersionPme;
+
+mod reaction_field;
+pub use self::reaction_field::ReactionField;
+
+mod dsf;
+pub use self::dsf::DampedShiftedForce;
+
+mod yukawa;
+pub use self::yukawa::Yukawa;
+
+mod thole;
+pub use self::thole::Thole;
+
+mod external;
+pub use self::external::{ElectricField, Wall, WallPotential};
+pub use self::external::{PositionRestraint, SphericalConfinement};
+
+mod eam;
+pub use self::eam::Eam;
+
+mod manybody;
+
+mod stillinger_weber;
+pub use self::stillinger_weber::StillingerWeber;
+
+mod tersoff;
+pub use self::tersoff::Tersoff;
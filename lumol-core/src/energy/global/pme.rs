@@ -0,0 +1,978 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::f64::consts::PI;
+use std::f64;
+
+use rayon::prelude::*;
+
+use log::{warn, info};
+use log_once::warn_once;
+
+use crate::{Configuration, UnitCell, CellShape};
+use crate::{Matrix3, Vector3D, Complex};
+use crate::consts::FOUR_PI_EPSILON_0;
+use crate::PairRestriction;
+
+use super::{GlobalPotential, CoulombicPotential, GlobalCache};
+use super::ewald::{Ewald, AccuracyEstimate};
+use super::fft::{fft3d, fft_size, Direction};
+
+/// Compute the values and derivatives of the cardinal B-spline of the given
+/// `order` at the points `w + j` for `j` in `0..order`, where `0 <= w < 1`.
+///
+/// This uses the recursion relation `M_n(x) = x / (n - 1) M_{n-1}(x) + (n -
+/// x) / (n - 1) M_{n-1}(x - 1)`, and `d M_n(x) / dx = M_{n-1}(x) - M_{n-1}(x
+/// - 1)`.
+fn bspline(w: f64, values: &mut [f64], derivatives: &mut [f64]) {
+    let order = values.len();
+    debug_assert_eq!(derivatives.len(), order);
+    debug_assert!(order >= 2);
+
+    for value in values.iter_mut() {
+        *value = 0.0;
+    }
+    values[0] = 1.0;
+
+    for n in 2..(order + 1) {
+        if n == order {
+            for j in 0..order {
+                let current = if j < order - 1 { values[j] } else { 0.0 };
+                let previous = if j > 0 { values[j - 1] } else { 0.0 };
+                derivatives[j] = current - previous;
+            }
+        }
+
+        let div = 1.0 / (n - 1) as f64;
+        for j in (0..n).rev() {
+            let current = if j < n - 1 { values[j] } else { 0.0 };
+            let previous = if j > 0 { values[j - 1] } else { 0.0 };
+            let x = w + j as f64;
+            values[j] = div * (x * current + (n as f64 - x) * previous);
+        }
+    }
+}
+
+/// Compute the squared modulus of the B-spline Euler exponential factors
+/// `|b(m)|²` for a grid with `size` points, and an interpolation of the given
+/// `order`.
+pub(super) fn bspline_moduli(size: usize, order: usize) -> Vec<f64> {
+    let mut values = vec![0.0; order];
+    let mut derivatives = vec![0.0; order];
+    bspline(0.0, &mut values, &mut derivatives);
+
+    let mut moduli = (0..size).map(|m| {
+        let mut sum = Complex::zero();
+        for k in 0..(order - 1) {
+            let phase = 2.0 * PI * ((m * k) % size) as f64 / size as f64;
+            sum += values[k + 1] * Complex::polar(1.0, phase);
+        }
+        let norm2 = sum.norm2();
+        if norm2 < 1e-7 {
+            // This only happens for odd orders at m = size / 2, the value will
+            // be replaced below.
+            f64::NAN
+        } else {
+            1.0 / norm2
+        }
+    }).collect::<Vec<_>>();
+
+    // Use the average of the neighbors values instead of the singular ones
+    for m in 0..size {
+        if moduli[m].is_nan() {
+            let previous = moduli[(m + size - 1) % size];
+            let next = moduli[(m + 1) % size];
+            moduli[m] = 0.5 * (previous + next);
+        }
+    }
+
+    return moduli;
+}
+
+/// Index of a wave vector along one direction of a grid of the given `size`,
+/// in the `[-size / 2, size / 2]` range.
+#[inline]
+pub(super) fn wave_index(i: usize, size: usize) -> f64 {
+    if i <= size / 2 {
+        i as f64
+    } else {
+        i as f64 - size as f64
+    }
+}
+
+/// B-spline interpolation weights of a single particle on the PME grid
+pub(super) struct Spline {
+    /// Grid point associated with the first weight in each direction. The
+    /// weight `j` is associated with the grid point `base - j`.
+    pub(super) base: [usize; 3],
+    /// Interpolation weights in each direction
+    pub(super) values: [Vec<f64>; 3],
+    /// Derivatives of the interpolation weights with respect to the scaled
+    /// fractional coordinates in each direction
+    pub(super) derivatives: [Vec<f64>; 3],
+}
+
+impl Spline {
+    /// Compute the interpolation of the particle at `position` in the `cell`,
+    /// on a `grid` using B-spline of the given `order`.
+    pub(super) fn new(cell: &UnitCell, position: &Vector3D, grid: [usize; 3], order: usize) -> Spline {
+        let fractional = cell.fractional(position);
+        let mut spline = Spline {
+            base: [0; 3],
+            values: [vec![0.0; order], vec![0.0; order], vec![0.0; order]],
+            derivatives: [vec![0.0; order], vec![0.0; order], vec![0.0; order]],
+        };
+
+        for a in 0..3 {
+            let scaled = grid[a] as f64 * (fractional[a] - f64::floor(fractional[a]));
+            let floor = f64::floor(scaled);
+            spline.base[a] = (floor as usize) % grid[a];
+            bspline(scaled - floor, &mut spline.values[a], &mut spline.derivatives[a]);
+        }
+
+        return spline;
+    }
+
+    /// Get the grid point associated with the weight `j` along the direction
+    /// `a`.
+    #[inline]
+    pub(super) fn point(&self, a: usize, j: usize, grid: [usize; 3]) -> usize {
+        (self.base[a] + grid[a] - j) % grid[a]
+    }
+}
+
+/// Smooth particle-mesh Ewald (SPME) summation for coulombic interactions.
+///
+/// This is a variation of the Ewald summation, where the reciprocal space part
+/// is computed by interpolating the charges on a regular grid using cardinal
+/// B-splines, and then using fast Fourier transforms. This makes the cost of
+/// the reciprocal space part scale as `O(N log(N))` instead of `O(N²)`. The
+/// real space and self-interaction parts are the same as in the
+/// [`Ewald`](struct.Ewald.html) summation [Essmann1995].
+///
+/// In Monte Carlo simulations, this summation should be wrapped in a
+/// [`SharedPme`](struct.SharedPme.html).
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{Pme, SharedPme};
+/// # use lumol_core::sys::{Particle, Molecule, UnitCell, System};
+/// # use lumol_core::types::Vector3D;
+/// // Setup PME summation with a grid of 32 points in each direction, and a
+/// // B-spline interpolation of order 6
+/// let pme = SharedPme::new(Pme::new(8.0, [32, 32, 32], 6, None));
+///
+/// // Setup a system containing a NaCl pair
+/// let mut system = System::with_cell(UnitCell::cubic(20.0));
+///
+/// let mut na = Particle::new("Na");
+/// na.charge = 1.0;
+/// na.position = Vector3D::new(0.0, 0.0, 0.0);
+///
+/// let mut cl = Particle::new("Cl");
+/// cl.charge = -1.0;
+/// cl.position = Vector3D::new(2.0, 0.0, 0.0);
+///
+/// system.add_molecule(Molecule::new(na));
+/// system.add_molecule(Molecule::new(cl));
+///
+/// // Use PME summation for electrostatic interactions
+/// system.set_coulomb_potential(Box::new(pme));
+///
+/// println!("energy is {}", system.potential_energy());
+/// ```
+///
+/// [Essmann1995]: Essmann, U. et al. J. Chem. Phys. 103, 8577 (1995).
+pub struct Pme {
+    /// Real space and self-interaction parts of the summation
+    real_space: Ewald,
+    /// Number of points in the grid along each direction
+    grid: [usize; 3],
+    /// Order of the B-spline interpolation
+    order: usize,
+    /// Squared modulus of the B-spline Euler exponential factors along each
+    /// direction
+    moduli: [Vec<f64>; 3],
+    /// Energetic pre-factor for each point in reciprocal space:
+    /// `|b(m)|² exp(- π² m² / α²) / (2 π V m²)`
+    energy: Vec<f64>,
+    /// Virial pre-factor for each point in reciprocal space: energetic
+    /// pre-factor times `𝟙 - 2 (1 + π² m² / α²) \vec m ⊗ \vec m / m²`
+    virial: Vec<Matrix3>,
+    /// Inverse of the unit cell matrix
+    inverse: Matrix3,
+    /// Fourier transform of the interpolated charges on the grid
+    rho: Vec<Complex>,
+    /// Guard for cache invalidation of `self.energy` and `self.virial`
+    previous_cell: Option<UnitCell>,
+    /// Update the cached quantities
+    updater: Option<Box<dyn Fn(&mut Pme) + Sync + Send>>,
+}
+
+impl Clone for Pme {
+    fn clone(&self) -> Pme {
+        Pme {
+            real_space: self.real_space.clone(),
+            grid: self.grid,
+            order: self.order,
+            moduli: self.moduli.clone(),
+            energy: self.energy.clone(),
+            virial: self.virial.clone(),
+            inverse: self.inverse,
+            rho: self.rho.clone(),
+            previous_cell: self.previous_cell,
+            updater: None,
+        }
+    }
+}
+
+impl Pme {
+    /// Create a PME summation using the given `cutoff` radius in real space,
+    /// a `grid` with the given number of points along each direction of the
+    /// unit cell, and a B-spline interpolation of the given `order`. If
+    /// `alpha` is None, then the default value of `π / cutoff` is used.
+    pub fn new<I: Into<Option<f64>>>(cutoff: f64, grid: [usize; 3], order: usize, alpha: I) -> Pme {
+        let alpha = alpha.into().unwrap_or(PI / cutoff);
+        if cutoff < 0.0 {
+            panic!("the cutoff can not be negative in PME");
+        } else if alpha < 0.0 {
+            panic!("alpha can not be negative in PME");
+        } else if order < 3 {
+            panic!("the B-spline order must be at least 3 in PME");
+        } else if grid.iter().any(|&size| size < order) {
+            panic!("the grid must contain at least as many points as the B-spline order in PME");
+        }
+
+        let moduli = [
+            bspline_moduli(grid[0], order),
+            bspline_moduli(grid[1], order),
+            bspline_moduli(grid[2], order),
+        ];
+
+        Pme {
+            // Only the real space part of this Ewald solver is used
+            real_space: Ewald::new(cutoff, 1, alpha),
+            grid: grid,
+            order: order,
+            moduli: moduli,
+            energy: Vec::new(),
+            virial: Vec::new(),
+            inverse: Matrix3::zero(),
+            rho: Vec::new(),
+            previous_cell: None,
+            updater: None,
+        }
+    }
+
+    /// Create a PME solver with the given real space `cutoff` and B-spline
+    /// interpolation `order`, setting `alpha` and the grid size to ensure that
+    /// the energy is computed with approximately the specified relative
+    /// `accuracy`. The optimal parameter depends on the exact `configuration`
+    /// used: both the total number of charges, and the unit cell.
+    pub fn with_accuracy(cutoff: f64, accuracy: f64, order: usize, configuration: &Configuration) -> Pme {
+        if cutoff < 0.0 {
+            panic!("the cutoff can not be negative in PME");
+        } else if accuracy < 0.0 {
+            panic!("accuracy can not be negative in PME");
+        } else if accuracy > 1.0 {
+            warn!("accuracy is bigger than 1 in Pme::with_accuracy")
+        }
+
+        let estimate = AccuracyEstimate::new(cutoff, accuracy, configuration);
+        let alpha = estimate.alpha;
+
+        // The grid must be able to represent all the k-vectors needed by the
+        // corresponding Ewald summation
+        let lengths = configuration.cell.lengths();
+        let mut grid = [0; 3];
+        for a in 0..3 {
+            let kmax = estimate.kmax(lengths[a], accuracy);
+            grid[a] = fft_size(usize::max(2 * kmax + 1, order));
+        }
+
+        info!(
+            "Setting PME summation parameters: cutoff = {}, alpha = {}, grid = {:?}, order = {}",
+            cutoff, alpha, grid, order
+        );
+
+        Pme::new(cutoff, grid, order, alpha)
+    }
+
+    /// Get the number of points in the grid along each direction
+    pub fn grid(&self) -> [usize; 3] {
+        self.grid
+    }
+
+    /// Get the order of the B-spline interpolation
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Get the splitting parameter between k-space and real space
+    pub fn alpha(&self) -> f64 {
+        self.real_space.alpha
+    }
+
+    /// Get the cutoff radius in real space
+    pub fn cutoff(&self) -> f64 {
+        self.real_space.rc
+    }
+
+    fn prepare(&mut self, cell: &UnitCell) {
+        if let Some(ref prev_cell) = self.previous_cell {
+            if cell == prev_cell {
+                // Do not recompute
+                return;
+            }
+        }
+
+        if cell.shape() == CellShape::Infinite {
+            panic!("PME is not defined with infinite unit cell");
+        }
+        self.previous_cell = Some(*cell);
+
+        let alpha = self.real_space.alpha;
+        let half_min_length = cell.lengths().min() / 2.0;
+        if self.real_space.rc > half_min_length {
+            warn_once!("The PME cutoff is too high for this unit cell, energy and forces might be wrong.");
+        }
+        if f64::exp(-alpha * half_min_length) > 0.05 {
+            warn_once!(
+"PME alpha parameter is too low for this unit cell, energy and forces might be wrong.
+You can manually set alpha to a slightly higher value (current alpha is {})",
+                alpha
+            );
+        }
+
+        self.inverse = cell.matrix().inverse();
+        let reciprocal = self.inverse.transposed();
+        let pi2_alpha2 = PI * PI / (alpha * alpha);
+        let two_pi_v = 2.0 * PI * cell.volume();
+
+        let [n0, n1, n2] = self.grid;
+        self.energy.clear();
+        self.energy.reserve(n0 * n1 * n2);
+        self.virial.clear();
+        self.virial.reserve(n0 * n1 * n2);
+        for i in 0..n0 {
+            for j in 0..n1 {
+                for k in 0..n2 {
+                    if i == 0 && j == 0 && k == 0 {
+                        self.energy.push(0.0);
+                        self.virial.push(Matrix3::zero());
+                        continue;
+                    }
+
+                    let index = Vector3D::new(wave_index(i, n0), wave_index(j, n1), wave_index(k, n2));
+                    let m = reciprocal * index;
+                    let m2 = m.norm2();
+                    let moduli = self.moduli[0][i] * self.moduli[1][j] * self.moduli[2][k];
+
+                    let energy = moduli * f64::exp(-pi2_alpha2 * m2) / (two_pi_v * m2);
+                    let virial_factor = -2.0 * (1.0 + pi2_alpha2 * m2) / m2;
+                    let virial = Matrix3::one() + virial_factor * m.tensorial(&m);
+
+                    self.energy.push(energy);
+                    self.virial.push(energy * virial);
+                }
+            }
+        }
+    }
+
+    /// Get the index in the flattened grid of the point `(i, j, k)`
+    #[inline]
+    fn grid_index(&self, i: usize, j: usize, k: usize) -> usize {
+        (i * self.grid[1] + j) * self.grid[2] + k
+    }
+
+    /// Interpolate the `charges` at `positions` on the `grid`, adding to the
+    /// existing values.
+    fn spread(&self, cell: &UnitCell, positions: &[Vector3D], charges: &[f64], grid: &mut [Complex]) {
+        for (position, &charge) in zip!(positions, charges) {
+            if charge == 0.0 {
+                continue;
+            }
+
+            let spline = Spline::new(cell, position, self.grid, self.order);
+            for (j0, &v0) in spline.values[0].iter().enumerate() {
+                let i = spline.point(0, j0, self.grid);
+                for (j1, &v1) in spline.values[1].iter().enumerate() {
+                    let j = spline.point(1, j1, self.grid);
+                    let q01 = charge * v0 * v1;
+                    for (j2, &v2) in spline.values[2].iter().enumerate() {
+                        let k = spline.point(2, j2, self.grid);
+                        grid[self.grid_index(i, j, k)] += Complex::cartesian(q01 * v2, 0.0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// k-space part of the summation
+impl Pme {
+    /// Compute the Fourier transform of the interpolated charges
+    fn compute_rho(&mut self, configuration: &Configuration) {
+        let [n0, n1, n2] = self.grid;
+        let mut rho = vec![Complex::zero(); n0 * n1 * n2];
+        self.spread(
+            &configuration.cell,
+            configuration.particles().position,
+            configuration.particles().charge,
+            &mut rho,
+        );
+        fft3d(&mut rho, (n0, n1, n2), Direction::Forward);
+        self.rho = rho;
+    }
+
+    /// k-space contribution to the energy
+    fn k_space_energy(&mut self, configuration: &Configuration) -> f64 {
+        self.compute_rho(configuration);
+
+        let energy = self.energy
+            .par_iter()
+            .zip_eq(&self.rho)
+            .map(|(factor, rho)| factor * rho.norm2())
+            .sum::<f64>();
+
+        return energy / FOUR_PI_EPSILON_0;
+    }
+
+    /// k-space contribution to the forces
+    fn k_space_forces(&mut self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+        self.compute_rho(configuration);
+
+        // Convolution of the interpolated charges with the influence function
+        let [n0, n1, n2] = self.grid;
+        let mut potential = self.energy
+            .par_iter()
+            .zip_eq(&self.rho)
+            .map(|(&factor, &rho)| 2.0 * factor * rho)
+            .collect::<Vec<_>>();
+        fft3d(&mut potential, (n0, n1, n2), Direction::Backward);
+
+        let rows = [
+            Vector3D::new(self.inverse[0][0], self.inverse[0][1], self.inverse[0][2]),
+            Vector3D::new(self.inverse[1][0], self.inverse[1][1], self.inverse[1][2]),
+            Vector3D::new(self.inverse[2][0], self.inverse[2][1], self.inverse[2][2]),
+        ];
+
+        let cell = &configuration.cell;
+        let positions = configuration.particles().position;
+        let charges = configuration.particles().charge;
+        forces.par_iter_mut()
+            .zip_eq(positions.par_iter())
+            .zip_eq(charges.par_iter())
+            .for_each(|((force, position), &charge)| {
+                if charge == 0.0 {
+                    return;
+                }
+
+                let spline = Spline::new(cell, position, self.grid, self.order);
+                let mut gradient = [0.0; 3];
+                for j0 in 0..self.order {
+                    let i = spline.point(0, j0, self.grid);
+                    let (v0, d0) = (spline.values[0][j0], spline.derivatives[0][j0]);
+                    for j1 in 0..self.order {
+                        let j = spline.point(1, j1, self.grid);
+                        let (v1, d1) = (spline.values[1][j1], spline.derivatives[1][j1]);
+                        for j2 in 0..self.order {
+                            let k = spline.point(2, j2, self.grid);
+                            let (v2, d2) = (spline.values[2][j2], spline.derivatives[2][j2]);
+
+                            let phi = potential[self.grid_index(i, j, k)].real();
+                            gradient[0] += phi * d0 * v1 * v2;
+                            gradient[1] += phi * v0 * d1 * v2;
+                            gradient[2] += phi * v0 * v1 * d2;
+                        }
+                    }
+                }
+
+                let gradient = (n0 as f64 * gradient[0]) * rows[0]
+                             + (n1 as f64 * gradient[1]) * rows[1]
+                             + (n2 as f64 * gradient[2]) * rows[2];
+                *force -= charge * gradient / FOUR_PI_EPSILON_0;
+            });
+    }
+
+    /// k-space contribution to the atomic virial
+    fn k_space_atomic_virial(&mut self, configuration: &Configuration) -> Matrix3 {
+        self.compute_rho(configuration);
+
+        let virial = self.virial
+            .par_iter()
+            .zip_eq(&self.rho)
+            .map(|(factor, rho)| rho.norm2() * factor)
+            .sum::<Matrix3>();
+
+        return virial / FOUR_PI_EPSILON_0;
+    }
+
+    /// k-space contribution to the molecular virial
+    fn k_space_molecular_virial(&mut self, configuration: &Configuration) -> Matrix3 {
+        let atomic = self.k_space_atomic_virial(configuration);
+
+        let mut forces = vec![Vector3D::zero(); configuration.size()];
+        self.k_space_forces(configuration, &mut forces);
+
+        let positions = configuration.particles().position;
+        let mut correction = Matrix3::zero();
+        for molecule in configuration.molecules() {
+            let com = molecule.center_of_mass();
+            for i in molecule.indexes() {
+                let di = positions[i] - com;
+                correction += forces[i].tensorial(&di);
+            }
+        }
+
+        return atomic - correction;
+    }
+
+    /// Compute the Fourier transform of the interpolated charges changes
+    /// while moving the molecule with the given `molecule_id` to
+    /// `new_positions`
+    fn delta_rho_move_rigid_molecules(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> Vec<Complex> {
+        let molecule = configuration.molecule(molecule_id);
+        let positions = configuration.particles().position;
+        let charges = configuration.particles().charge;
+        let positions = &positions[molecule.indexes()];
+        let charges = &charges[molecule.indexes()];
+        let opposite = charges.iter().map(|q| -q).collect::<Vec<_>>();
+
+        let [n0, n1, n2] = self.grid;
+        let mut delta = vec![Complex::zero(); n0 * n1 * n2];
+        self.spread(&configuration.cell, positions, &opposite, &mut delta);
+        self.spread(&configuration.cell, new_positions, charges, &mut delta);
+        fft3d(&mut delta, (n0, n1, n2), Direction::Forward);
+
+        return delta;
+    }
+
+    fn k_space_move_molecule_cost(
+        &mut self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let mut old_energy = 0.0;
+        for (factor, &rho) in zip!(&self.energy, &self.rho) {
+            old_energy += factor * rho.norm2();
+        }
+        old_energy /= FOUR_PI_EPSILON_0;
+
+        let delta_rho = self.delta_rho_move_rigid_molecules(
+            configuration, molecule_id, new_positions
+        );
+
+        let mut new_energy = 0.0;
+        for (factor, &rho, &delta) in zip!(&self.energy, &self.rho, &delta_rho) {
+            new_energy += factor * (rho + delta).norm2();
+        }
+        new_energy /= FOUR_PI_EPSILON_0;
+
+        self.updater = Some(Box::new(move |pme: &mut Pme| {
+            for (rho, &delta) in zip!(&mut pme.rho, &delta_rho) {
+                *rho += delta;
+            }
+        }));
+
+        return new_energy - old_energy;
+    }
+}
+
+/// Thread-safe wrapper around PME implementing `CoulombicPotential`.
+///
+/// This wrapper allow to share a PME solver between threads (make it `Send +
+/// Sync`) while still using caching in Monte Carlo simulations (with interior
+/// mutability).
+pub struct SharedPme(RwLock<Pme>);
+
+impl SharedPme {
+    /// Wrap `pme` in a thread-safe structure.
+    ///
+    /// # Example
+    /// ```
+    /// # use lumol_core::energy::{Pme, SharedPme, CoulombicPotential};
+    /// let pme = SharedPme::new(Pme::new(12.5, [32, 32, 32], 4, None));
+    /// let boxed: Box<dyn CoulombicPotential> = Box::new(pme);
+    /// ```
+    pub fn new(pme: Pme) -> SharedPme {
+        SharedPme(RwLock::new(pme))
+    }
+
+    /// Get read access to the underlying PME solver
+    fn read(&self) -> RwLockReadGuard<'_, Pme> {
+        // The lock should never be poisoned, because any panic will unwind
+        // and finish the simulation.
+        self.0.read().expect("PME lock is poisoned")
+    }
+
+    /// Get write access to the underlying PME solver
+    fn write(&self) -> RwLockWriteGuard<'_, Pme> {
+        // The lock should never be poisoned, because any panic will unwind
+        // and finish the simulation.
+        self.0.write().expect("PME lock is poisoned")
+    }
+}
+
+impl Clone for SharedPme {
+    fn clone(&self) -> SharedPme {
+        SharedPme::new(self.read().clone())
+    }
+}
+
+impl GlobalPotential for SharedPme {
+    fn cutoff(&self) -> Option<f64> {
+        Some(self.read().real_space.rc)
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        let mut pme = self.write();
+        pme.prepare(&configuration.cell);
+        let real = pme.real_space.real_space_energy(configuration);
+        let self_e = pme.real_space.self_energy(configuration);
+        let k_space = pme.k_space_energy(configuration);
+        return real + self_e + k_space;
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+        let mut pme = self.write();
+        pme.prepare(&configuration.cell);
+
+        pme.real_space.real_space_forces(configuration, forces);
+        // No self force
+        pme.k_space_forces(configuration, forces);
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let mut pme = self.write();
+        pme.prepare(&configuration.cell);
+        let real = pme.real_space.real_space_atomic_virial(configuration);
+        // No self virial
+        let k_space = pme.k_space_atomic_virial(configuration);
+        return real + k_space;
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let mut pme = self.write();
+        pme.prepare(&configuration.cell);
+        let real = pme.real_space.real_space_molecular_virial(configuration);
+        // No self virial
+        let k_space = pme.k_space_molecular_virial(configuration);
+        return real + k_space;
+    }
+}
+
+impl CoulombicPotential for SharedPme {
+    fn set_restriction(&mut self, restriction: PairRestriction) {
+        self.write().real_space.set_restriction(restriction);
+    }
+}
+
+impl GlobalCache for SharedPme {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D]
+    ) -> f64 {
+        let mut pme = self.write();
+        pme.prepare(&configuration.cell);
+        let real = pme.real_space.real_space_move_molecule_cost(configuration, molecule_id, new_positions);
+        /* No self cost */
+        let k_space = pme.k_space_move_molecule_cost(configuration, molecule_id, new_positions);
+        return real + k_space;
+    }
+
+    fn update(&self) {
+        let mut pme = self.write();
+        if pme.updater.is_some() {
+            let mut updater = None;
+            ::std::mem::swap(&mut updater, &mut pme.updater);
+            let updater = updater.unwrap();
+            updater(&mut *pme);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::System;
+    use crate::utils::system_from_xyz;
+
+    use approx::assert_relative_eq;
+
+    pub fn nacl_pair() -> System {
+        let mut system = system_from_xyz("2
+        cell: 20.0
+        Cl 0.0 0.0 0.0
+        Na 1.5 0.0 0.0
+        ");
+        system.particles_mut().charge[0] = -1.0;
+        system.particles_mut().charge[1] = 1.0;
+        return system;
+    }
+
+    pub fn water() -> System {
+        let mut system = system_from_xyz("3
+        cell: 20.0
+        O  0.0  0.0  0.0
+        H -0.7 -0.7  0.3
+        H  0.3 -0.3 -0.8
+        ");
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(0, 2).is_empty());
+        assert!(system.molecules().count() == 1);
+
+        for particle in system.particles_mut() {
+            if particle.name == "O" {
+                *particle.charge = -0.8476;
+            } else if particle.name == "H" {
+                *particle.charge = 0.4238;
+            }
+        }
+        return system;
+    }
+
+    #[test]
+    fn bspline_values() {
+        let mut values = vec![0.0; 4];
+        let mut derivatives = vec![0.0; 4];
+        bspline(0.3, &mut values, &mut derivatives);
+
+        // Partition of unity
+        assert_relative_eq!(values.iter().sum::<f64>(), 1.0, epsilon = 1e-12);
+        assert_relative_eq!(derivatives.iter().sum::<f64>(), 0.0, epsilon = 1e-12);
+
+        // Finite differences for the derivatives
+        let eps = 1e-7;
+        let mut values_eps = vec![0.0; 4];
+        bspline(0.3 + eps, &mut values_eps, &mut derivatives.clone());
+        for j in 0..4 {
+            assert_relative_eq!((values_eps[j] - values[j]) / eps, derivatives[j], epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn with_accuracy() {
+        let pme = Pme::with_accuracy(8.5, 1e-6, 6, &water());
+        assert!(f64::abs(pme.alpha() - 0.2998) < 1e-4);
+        assert_eq!(pme.grid(), [12, 12, 12]);
+        assert_eq!(pme.order(), 6);
+    }
+
+    mod errors {
+        use super::*;
+        use crate::GlobalPotential;
+        use crate::UnitCell;
+
+        #[test]
+        #[should_panic]
+        fn infinite_cell() {
+            let mut system = nacl_pair();
+            system.cell = UnitCell::infinite();
+            let pme = SharedPme::new(Pme::new(8.0, [16, 16, 16], 4, None));
+            let _ = pme.energy(&system);
+        }
+
+        #[test]
+        #[should_panic]
+        fn negative_cutoff() {
+            let _ = Pme::new(-8.0, [16, 16, 16], 4, None);
+        }
+
+        #[test]
+        #[should_panic]
+        fn negative_alpha() {
+            let _ = Pme::new(8.0, [16, 16, 16], 4, -45.2);
+        }
+
+        #[test]
+        #[should_panic]
+        fn small_order() {
+            let _ = Pme::new(8.0, [16, 16, 16], 2, None);
+        }
+
+        #[test]
+        #[should_panic]
+        fn small_grid() {
+            let _ = Pme::new(8.0, [16, 3, 16], 4, None);
+        }
+    }
+
+    mod compare_ewald {
+        use super::*;
+        use crate::{GlobalPotential, CoulombicPotential, PairRestriction};
+        use crate::energy::global::SharedEwald;
+
+        #[test]
+        fn energy() {
+            let system = nacl_pair();
+            let pme = SharedPme::new(Pme::new(8.0, [32, 32, 32], 6, None));
+            let ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+            assert_relative_eq!(pme.energy(&system), ewald.energy(&system), max_relative = 1e-4);
+
+            let system = water();
+            let mut pme = SharedPme::new(Pme::new(8.0, [32, 32, 32], 6, None));
+            pme.set_restriction(PairRestriction::InterMolecular);
+            let mut ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+            ewald.set_restriction(PairRestriction::InterMolecular);
+            assert_relative_eq!(pme.energy(&system), ewald.energy(&system), max_relative = 1e-3);
+        }
+
+        #[test]
+        fn odd_order_and_grid() {
+            let system = nacl_pair();
+            let pme = SharedPme::new(Pme::new(8.0, [30, 27, 25], 5, None));
+            let ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+            assert_relative_eq!(pme.energy(&system), ewald.energy(&system), max_relative = 1e-4);
+        }
+    }
+
+    mod forces {
+        use super::*;
+        use crate::GlobalPotential;
+
+        #[test]
+        fn k_space_finite_differences() {
+            let mut system = water();
+            // Using a small cutoff to increase the weight of k-space
+            let mut pme = Pme::new(2.0, [16, 16, 16], 4, None);
+            pme.real_space.set_restriction(PairRestriction::InterMolecular);
+            pme.prepare(&system.cell);
+
+            let mut forces = vec![Vector3D::zero(); 3];
+            pme.k_space_forces(&system, &mut forces);
+
+            let eps = 1e-9;
+            for i in 0..3 {
+                let e = pme.k_space_energy(&system);
+                system.particles_mut().position[0][i] += eps;
+                let e1 = pme.k_space_energy(&system);
+                system.particles_mut().position[0][i] -= eps;
+                assert_relative_eq!((e - e1) / eps, forces[0][i], epsilon = 1e-6);
+            }
+        }
+
+        #[test]
+        fn total_forces() {
+            let mut system = nacl_pair();
+            let pme = SharedPme::new(Pme::new(8.0, [32, 32, 32], 6, None));
+
+            let mut forces = vec![Vector3D::zero(); 2];
+            pme.forces(&system, &mut forces);
+            // Force is attractive
+            assert!(forces[0][0] > 0.0);
+            assert!(forces[1][0] < 0.0);
+
+            let e = pme.energy(&system);
+            let eps = 1e-9;
+            system.particles_mut().position[0][0] += eps;
+            let e1 = pme.energy(&system);
+            assert_relative_eq!((e - e1) / eps, forces[0][0], epsilon = 1e-6);
+        }
+    }
+
+    mod atomic_virial {
+        use super::*;
+
+        fn scale(system: &mut System, i: usize, j: usize, eps: f64) {
+            let mut scaling = Matrix3::one();
+            scaling[i][j] += eps;
+            let old_cell = system.cell;
+            let new_cell = system.cell.scale(scaling);
+
+            for position in system.particles_mut().position {
+                *position = new_cell.cartesian(&old_cell.fractional(&position));
+            }
+            system.cell = new_cell;
+        }
+
+        #[test]
+        fn k_space_finite_differences() {
+            let mut system = water();
+            let mut pme = Pme::new(2.0, [16, 16, 16], 4, None);
+            pme.prepare(&system.cell);
+
+            let eps = 1e-9;
+            let virial = pme.k_space_atomic_virial(&system);
+            let mut finite_diff = Matrix3::zero();
+
+            for i in 0..3 {
+                for j in 0..3 {
+                    pme.prepare(&system.cell);
+                    let e = pme.k_space_energy(&system);
+                    scale(&mut system, i, j, eps);
+                    pme.prepare(&system.cell);
+                    let e1 = pme.k_space_energy(&system);
+                    finite_diff[i][j] = (e - e1) / eps;
+                }
+            }
+
+            // Make sure the finite_diff matrix is symmetric
+            finite_diff = (finite_diff + finite_diff.transposed()) / 2.0;
+            assert_relative_eq!(virial, finite_diff, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn move_molecule() {
+        let mut system = system_from_xyz("6
+        cell: 20.0
+        H  0.3 -0.3 -0.8
+        O  0.0  0.0  0.0
+        H -0.7 -0.7  0.3
+        H  2.3  1.7 -0.8
+        O  2.0  2.0  0.0
+        H  1.3  1.3  0.3
+        ");
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(1, 2).is_empty());
+        assert!(system.add_bond(3, 4).is_empty());
+        assert!(system.add_bond(4, 5).is_empty());
+
+        for particle in system.particles_mut() {
+            if particle.name == "O" {
+                *particle.charge = -0.8476;
+            } else if particle.name == "H" {
+                *particle.charge = 0.4238;
+            }
+        }
+
+        let mut pme = SharedPme::new(Pme::new(8.0, [16, 16, 16], 4, None));
+        pme.set_restriction(PairRestriction::InterMolecular);
+        let check = pme.clone();
+
+        // Initialize cached values
+        let _ = pme.energy(&system);
+        let old_energy = check.energy(&system);
+
+        #[allow(clippy::unreadable_literal)]
+        let new_positions = &[
+            Vector3D::new(0.41727, 2.29401, -0.0558),
+            Vector3D::new(0.5097743599026461, 3.194114034722624, -0.020364564697826326),
+            Vector3D::new(-0.2501317777731211, 3.562366060753896, -0.6178033542374419),
+        ];
+        let cost = pme.move_molecule_cost(&system, 0, new_positions);
+        pme.update();
+
+        system.particles_mut().position[0] = new_positions[0];
+        system.particles_mut().position[1] = new_positions[1];
+        system.particles_mut().position[2] = new_positions[2];
+        let new_energy = check.energy(&system);
+        assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+
+        // The cache was updated
+        let cost = pme.move_molecule_cost(&system, 0, new_positions);
+        assert_relative_eq!(cost, 0.0, epsilon = 1e-12);
+    }
+}
@@ -0,0 +1,935 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use std::collections::BTreeMap;
+use std::f64::consts::FRAC_2_SQRT_PI;
+use std::ops::Range;
+use std::sync::Mutex;
+
+use rayon::prelude::*;
+
+use log::{debug, warn};
+
+use crate::math::erfc;
+use crate::consts::FOUR_PI_EPSILON_0;
+use crate::PairRestriction;
+use crate::utils::ThreadLocalVec;
+use crate::sys::NeighborList;
+use crate::{Configuration, UnitCell};
+use crate::{Matrix3, Vector3D};
+
+use super::ewald::slab_vector_image;
+
+/// Algorithm used to solve the self-consistent equations giving the induced
+/// dipoles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DipoleSolver {
+    /// Fixed point iterations: the new dipoles are computed from the electric
+    /// field created by the charges and the dipoles of the previous iteration.
+    Iterative,
+    /// Conjugate gradient minimization of the polarization energy, using the
+    /// polarizabilities as preconditioner.
+    ConjugateGradient,
+}
+
+/// Positions of the particles used to compute the dipolar fields. These are
+/// either the positions of the particles in a configuration, or the same
+/// positions with a single molecule moved to new positions. The later allows
+/// to compute the cost of a Monte Carlo move without cloning the
+/// configuration.
+pub(super) struct Positions<'a> {
+    /// The configuration containing the particles
+    pub configuration: &'a Configuration,
+    /// Indexes of the moved particles
+    moved: Range<usize>,
+    /// New positions of the moved particles
+    new_positions: &'a [Vector3D],
+}
+
+impl<'a> Positions<'a> {
+    /// Use the positions of the particles in the `configuration`
+    pub fn new(configuration: &'a Configuration) -> Positions<'a> {
+        Positions {
+            configuration: configuration,
+            moved: 0..0,
+            new_positions: &[],
+        }
+    }
+
+    /// Use the positions of the particles in the `configuration`, after
+    /// moving the molecule with the given `molecule_id` to `new_positions`.
+    pub fn moved(
+        configuration: &'a Configuration,
+        molecule_id: usize,
+        new_positions: &'a [Vector3D],
+    ) -> Positions<'a> {
+        let moved = configuration.molecule(molecule_id).indexes();
+        assert_eq!(moved.len(), new_positions.len());
+        Positions {
+            configuration: configuration,
+            moved: moved,
+            new_positions: new_positions,
+        }
+    }
+
+    /// Get the position of the particle `i`
+    #[inline]
+    pub fn get(&self, i: usize) -> Vector3D {
+        if self.moved.contains(&i) {
+            self.new_positions[i - self.moved.start]
+        } else {
+            self.configuration.particles().position[i]
+        }
+    }
+
+    /// Check if the particle `i` was moved
+    #[inline]
+    pub fn is_moved(&self, i: usize) -> bool {
+        self.moved.contains(&i)
+    }
+}
+
+/// Electric fields and interactions used to compute the induced dipoles.
+///
+/// All the quantities are computed in reduced units, i.e. without the
+/// `1 / (4 π ε_0)` factor.
+pub(super) trait DipolarField {
+    /// Get the electric field created by the charges at all the particles,
+    /// located at the given `positions`.
+    fn charges_field(&self, positions: &Positions<'_>) -> Vec<Vector3D>;
+
+    /// Get the electric field created by the `dipoles` located on the
+    /// particles at all the particles, located at the given `positions`.
+    fn dipoles_field(&self, positions: &Positions<'_>, dipoles: &[Vector3D]) -> Vec<Vector3D>;
+
+    /// Get the forces acting on all the particles coming from the
+    /// charge-dipole and dipole-dipole interactions, at fixed `dipoles`.
+    fn dipoles_forces(&self, positions: &Positions<'_>, dipoles: &[Vector3D]) -> Vec<Vector3D>;
+
+    /// Get the atomic virial coming from the charge-dipole and dipole-dipole
+    /// interactions, at fixed `dipoles`.
+    fn dipoles_virial(&self, positions: &Positions<'_>, dipoles: &[Vector3D]) -> Matrix3;
+}
+
+/// Induced dipoles converged for a given configuration
+#[derive(Clone)]
+struct InducedDipoles {
+    /// Unit cell of the configuration
+    cell: UnitCell,
+    /// Positions of the particles in the configuration
+    positions: Vec<Vector3D>,
+    /// Charges of the particles in the configuration
+    charges: Vec<f64>,
+    /// The induced dipoles
+    dipoles: Vec<Vector3D>,
+    /// Electric field created by the charges
+    charges_field: Vec<Vector3D>,
+}
+
+impl InducedDipoles {
+    /// Create empty induced dipoles, not matching any configuration
+    fn new() -> InducedDipoles {
+        InducedDipoles {
+            cell: UnitCell::infinite(),
+            positions: Vec::new(),
+            charges: Vec::new(),
+            dipoles: Vec::new(),
+            charges_field: Vec::new(),
+        }
+    }
+
+    /// Check if these dipoles were computed for the given `configuration`
+    fn matches(&self, configuration: &Configuration) -> bool {
+        return !self.dipoles.is_empty() &&
+               self.cell == configuration.cell &&
+               self.positions[..] == configuration.particles().position[..] &&
+               self.charges[..] == configuration.particles().charge[..];
+    }
+}
+
+/// Convergence statistics of the induced dipoles solver
+struct Convergence {
+    /// Number of iterations used
+    iterations: usize,
+    /// Largest change in the dipoles at the last iteration
+    residual: f64,
+}
+
+/// Induced point dipoles polarization, to use with a coulombic solver.
+///
+/// Each polarizable particle `i` carries an induced dipole `μ_i = α_i E_i`,
+/// where `α_i` is the polarizability of the particle (as a volume), and `E_i`
+/// the electric field created at this particle by the charges and all the
+/// other induced dipoles. These equations are solved self-consistently, and
+/// the converged dipoles are reused when computing the energy, forces or
+/// virial of the same configuration. For a new configuration, the solver
+/// starts from the dipoles found for the previous one. The polarization
+/// energy is then
+///
+/// ```text
+/// U_pol = - 1/2 \sum_i μ_i · E^0_i
+/// ```
+///
+/// where `E^0_i` is the electric field created by the charges only. The
+/// convergence statistics of the solver are reported through the logging
+/// system, at the debug level.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{Polarization, DipoleSolver, Wolf};
+/// let mut polarization = Polarization::new(DipoleSolver::ConjugateGradient, 1e-6);
+/// polarization.set_polarizability("O", 1.45);
+/// polarization.set_max_iterations(50);
+///
+/// let mut wolf = Wolf::new(12.0);
+/// wolf.set_polarization(polarization);
+/// ```
+pub struct Polarization {
+    /// Polarizabilities of the particles, indexed by name
+    polarizabilities: BTreeMap<String, f64>,
+    /// Algorithm used to solve for the induced dipoles
+    solver: DipoleSolver,
+    /// Convergence threshold on the dipoles
+    tolerance: f64,
+    /// Maximal number of iterations of the solver
+    max_iterations: usize,
+    /// Neighbor list for the real space dipolar interactions
+    neighbors: NeighborList,
+    /// Induced dipoles for the last configuration, reused for the same
+    /// configuration and used as initial guess for the solver otherwise
+    cache: Mutex<InducedDipoles>,
+}
+
+impl Clone for Polarization {
+    fn clone(&self) -> Polarization {
+        let cache = self.cache.lock().expect("polarization lock is poisoned");
+        Polarization {
+            polarizabilities: self.polarizabilities.clone(),
+            solver: self.solver,
+            tolerance: self.tolerance,
+            max_iterations: self.max_iterations,
+            neighbors: self.neighbors.clone(),
+            cache: Mutex::new(cache.clone()),
+        }
+    }
+}
+
+impl Polarization {
+    /// Create a new polarization model without any polarizable particle,
+    /// using the given `solver` for the induced dipoles. The solver is
+    /// converged when the largest change in the dipoles is smaller than
+    /// `tolerance`.
+    pub fn new(solver: DipoleSolver, tolerance: f64) -> Polarization {
+        assert!(tolerance > 0.0, "the tolerance must be positive in polarization");
+        Polarization {
+            polarizabilities: BTreeMap::new(),
+            solver: solver,
+            tolerance: tolerance,
+            max_iterations: 100,
+            neighbors: NeighborList::default(),
+            cache: Mutex::new(InducedDipoles::new()),
+        }
+    }
+
+    /// Set the `polarizability` (as a volume) of all the particles with the
+    /// given `name`.
+    pub fn set_polarizability(&mut self, name: &str, polarizability: f64) {
+        assert!(polarizability > 0.0, "the polarizability must be positive in polarization");
+        let _ = self.polarizabilities.insert(String::from(name), polarizability);
+        self.clear_cache();
+    }
+
+    /// Set the maximal number of iterations of the solver. If the dipoles are
+    /// not converged after this number of iterations, a warning is emitted
+    /// and the last dipoles are used. The default is 100 iterations.
+    pub fn set_max_iterations(&mut self, max_iterations: usize) {
+        assert!(max_iterations > 0, "the maximal number of iterations can not be 0 in polarization");
+        self.max_iterations = max_iterations;
+    }
+
+    /// Forget about the induced dipoles of the last configuration. This must
+    /// be called when the parameters of the dipolar interactions change.
+    pub(super) fn clear_cache(&self) {
+        let mut cache = self.cache.lock().expect("polarization lock is poisoned");
+        *cache = InducedDipoles::new();
+    }
+
+    /// Get the neighbor list to use for the real space dipolar interactions
+    pub(super) fn neighbor_list(&self) -> &NeighborList {
+        &self.neighbors
+    }
+
+    /// Get the polarizabilities of all the particles in the `configuration`
+    fn polarizabilities(&self, configuration: &Configuration) -> Vec<f64> {
+        configuration.particles().name.iter().map(|name| {
+            self.polarizabilities.get(name).cloned().unwrap_or(0.0)
+        }).collect()
+    }
+
+    /// Get the induced dipoles in the `configuration`, together with the
+    /// electric field created by the charges. The dipoles are only computed
+    /// if they are not already known for this configuration.
+    pub(super) fn induced_dipoles<F: DipolarField>(
+        &self,
+        field: &F,
+        configuration: &Configuration,
+    ) -> (Vec<Vector3D>, Vec<Vector3D>) {
+        let mut cache = self.cache.lock().expect("polarization lock is poisoned");
+        if cache.matches(configuration) {
+            return (cache.dipoles.clone(), cache.charges_field.clone());
+        }
+
+        let guess = if cache.dipoles.len() == configuration.size() {
+            Some(cache.dipoles.clone())
+        } else {
+            None
+        };
+        let (dipoles, charges_field) = self.solve(field, &Positions::new(configuration), guess);
+
+        *cache = InducedDipoles {
+            cell: configuration.cell,
+            positions: configuration.particles().position.to_vec(),
+            charges: configuration.particles().charge.to_vec(),
+            dipoles: dipoles.clone(),
+            charges_field: charges_field.clone(),
+        };
+        return (dipoles, charges_field);
+    }
+
+    /// Solve the self-consistent equations for the induced dipoles at the
+    /// given `positions`, starting from the `guess` dipoles if any. This
+    /// returns the dipoles together with the electric field created by the
+    /// charges.
+    fn solve<F: DipolarField>(
+        &self,
+        field: &F,
+        positions: &Positions<'_>,
+        guess: Option<Vec<Vector3D>>,
+    ) -> (Vec<Vector3D>, Vec<Vector3D>) {
+        let polarizabilities = self.polarizabilities(positions.configuration);
+        let charges_field = field.charges_field(positions);
+
+        let guess = guess.unwrap_or_else(|| {
+            polarizabilities.iter().zip(&charges_field).map(|(&alpha, &field)| alpha * field).collect()
+        });
+
+        let (dipoles, convergence) = match self.solver {
+            DipoleSolver::Iterative => {
+                self.iterative(field, positions, &polarizabilities, &charges_field, guess)
+            }
+            DipoleSolver::ConjugateGradient => {
+                self.conjugate_gradient(field, positions, &polarizabilities, &charges_field, guess)
+            }
+        };
+
+        if convergence.residual < self.tolerance {
+            debug!(
+                "induced dipoles converged in {} iterations (residual: {:e})",
+                convergence.iterations, convergence.residual
+            );
+        } else {
+            warn!(
+                "induced dipoles did not converge after {} iterations (residual: {:e})",
+                convergence.iterations, convergence.residual
+            );
+        }
+
+        return (dipoles, charges_field);
+    }
+
+    /// Solve for the induced dipoles using fixed point iterations
+    fn iterative<F: DipolarField>(
+        &self,
+        field: &F,
+        positions: &Positions<'_>,
+        polarizabilities: &[f64],
+        charges_field: &[Vector3D],
+        mut dipoles: Vec<Vector3D>,
+    ) -> (Vec<Vector3D>, Convergence) {
+        let mut convergence = Convergence {
+            iterations: 0,
+            residual: f64::INFINITY,
+        };
+
+        while convergence.iterations < self.max_iterations {
+            convergence.iterations += 1;
+            let dipoles_field = field.dipoles_field(positions, &dipoles);
+
+            convergence.residual = 0.0;
+            for (i, dipole) in dipoles.iter_mut().enumerate() {
+                let new = polarizabilities[i] * (charges_field[i] + dipoles_field[i]);
+                convergence.residual = f64::max(convergence.residual, (new - *dipole).norm());
+                *dipole = new;
+            }
+
+            if convergence.residual < self.tolerance {
+                break;
+            }
+        }
+
+        return (dipoles, convergence);
+    }
+
+    /// Solve for the induced dipoles using a preconditioned conjugate
+    /// gradient. The linear system `(1 / α - T) μ = E^0` is solved for the
+    /// polarizable particles, where `T` is the dipole-dipole interaction
+    /// tensor.
+    fn conjugate_gradient<F: DipolarField>(
+        &self,
+        field: &F,
+        positions: &Positions<'_>,
+        polarizabilities: &[f64],
+        charges_field: &[Vector3D],
+        mut dipoles: Vec<Vector3D>,
+    ) -> (Vec<Vector3D>, Convergence) {
+        let apply = |vector: &[Vector3D]| -> Vec<Vector3D> {
+            let dipoles_field = field.dipoles_field(positions, vector);
+            polarizabilities.iter().enumerate().map(|(i, &alpha)| {
+                if alpha == 0.0 {
+                    Vector3D::zero()
+                } else {
+                    vector[i] / alpha - dipoles_field[i]
+                }
+            }).collect::<Vec<_>>()
+        };
+        let dot = |a: &[Vector3D], b: &[Vector3D]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+        let max_norm = |a: &[Vector3D]| a.iter().map(|a| a.norm()).fold(0.0, f64::max);
+
+        let mut residual = apply(&dipoles);
+        for (i, residual) in residual.iter_mut().enumerate() {
+            if polarizabilities[i] != 0.0 {
+                *residual = charges_field[i] - *residual;
+            }
+        }
+        // The preconditioned residual is the change in the dipoles that a
+        // fixed point iteration would give.
+        let mut preconditioned = residual.iter().zip(polarizabilities).map(|(&r, &alpha)| alpha * r).collect::<Vec<_>>();
+        let mut direction = preconditioned.clone();
+        let mut residual_dot = dot(&residual, &preconditioned);
+
+        let mut convergence = Convergence {
+            iterations: 0,
+            residual: max_norm(&preconditioned),
+        };
+
+        while convergence.residual >= self.tolerance && convergence.iterations < self.max_iterations {
+            convergence.iterations += 1;
+
+            let product = apply(&direction);
+            let step = residual_dot / dot(&direction, &product);
+            for i in 0..dipoles.len() {
+                dipoles[i] += step * direction[i];
+                residual[i] -= step * product[i];
+                preconditioned[i] = polarizabilities[i] * residual[i];
+            }
+
+            let new_residual_dot = dot(&residual, &preconditioned);
+            let beta = new_residual_dot / residual_dot;
+            residual_dot = new_residual_dot;
+            for (direction, &preconditioned) in direction.iter_mut().zip(&preconditioned) {
+                *direction = preconditioned + beta * *direction;
+            }
+
+            convergence.residual = max_norm(&preconditioned);
+        }
+
+        return (dipoles, convergence);
+    }
+
+    /// Get the polarization energy of the `configuration`
+    pub(super) fn energy<F: DipolarField>(&self, field: &F, configuration: &Configuration) -> f64 {
+        let (dipoles, charges_field) = self.induced_dipoles(field, configuration);
+        return polarization_energy(&dipoles, &charges_field);
+    }
+
+    /// Get the polarization energy of the `configuration` after moving the
+    /// molecule with the given `molecule_id` to `new_positions`. The solver
+    /// starts from the dipoles of the last configuration, which should be the
+    /// current `configuration`, and only needs a few iterations. The new
+    /// dipoles are not cached, since the move can be rejected.
+    pub(super) fn moved_energy<F: DipolarField>(
+        &self,
+        field: &F,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let guess = {
+            let cache = self.cache.lock().expect("polarization lock is poisoned");
+            if cache.dipoles.len() == configuration.size() {
+                Some(cache.dipoles.clone())
+            } else {
+                None
+            }
+        };
+
+        let positions = Positions::moved(configuration, molecule_id, new_positions);
+        let (dipoles, charges_field) = self.solve(field, &positions, guess);
+        return polarization_energy(&dipoles, &charges_field);
+    }
+
+    /// Add the polarization forces to `forces`
+    pub(super) fn forces<F: DipolarField>(&self, field: &F, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+        let (dipoles, _) = self.induced_dipoles(field, configuration);
+        let dipoles_forces = field.dipoles_forces(&Positions::new(configuration), &dipoles);
+        for (force, dipole_force) in forces.iter_mut().zip(dipoles_forces) {
+            *force += dipole_force / FOUR_PI_EPSILON_0;
+        }
+    }
+
+    /// Get the polarization contribution to the atomic virial
+    pub(super) fn atomic_virial<F: DipolarField>(&self, field: &F, configuration: &Configuration) -> Matrix3 {
+        let (dipoles, _) = self.induced_dipoles(field, configuration);
+        return field.dipoles_virial(&Positions::new(configuration), &dipoles) / FOUR_PI_EPSILON_0;
+    }
+
+    /// Get the polarization contribution to the molecular virial
+    pub(super) fn molecular_virial<F: DipolarField>(&self, field: &F, configuration: &Configuration) -> Matrix3 {
+        let (dipoles, _) = self.induced_dipoles(field, configuration);
+        let atomic = field.dipoles_virial(&Positions::new(configuration), &dipoles);
+        let forces = field.dipoles_forces(&Positions::new(configuration), &dipoles);
+
+        let positions = configuration.particles().position;
+        let mut correction = Matrix3::zero();
+        for molecule in configuration.molecules() {
+            let com = molecule.center_of_mass();
+            for i in molecule.indexes() {
+                let di = positions[i] - com;
+                correction += forces[i].tensorial(&di);
+            }
+        }
+
+        return (atomic - correction) / FOUR_PI_EPSILON_0;
+    }
+}
+
+/// Get the polarization energy corresponding to the induced `dipoles` in the
+/// electric field created by the charges `charges_field`.
+fn polarization_energy(dipoles: &[Vector3D], charges_field: &[Vector3D]) -> f64 {
+    let energy = dipoles.iter().zip(charges_field).map(|(dipole, field)| dipole * field).sum::<f64>();
+    return -0.5 * energy / FOUR_PI_EPSILON_0;
+}
+
+/// Get the damped dipolar interaction functions `B`, `C` and `D` at distance
+/// `r`, from the coulombic kernel `erfc(α r) / r` [Smith1998]. The interaction
+/// energy between a charge and a dipole is `q (μ · r) B`, and between two
+/// dipoles `(μ_i · μ_j) B - (μ_i · r) (μ_j · r) C`. Using `alpha = 0` gives the
+/// undamped functions: `1 / r^3`, `3 / r^5` and `15 / r^7`.
+///
+/// [Smith1998]: Smith, W. CCP5 Newsletter 46, 18 (1998).
+fn dipolar_functions(alpha: f64, r: f64) -> [f64; 3] {
+    let r2 = r * r;
+    let alpha2 = alpha * alpha;
+    let gaussian = alpha * FRAC_2_SQRT_PI * f64::exp(-alpha2 * r2);
+    let b = (erfc(alpha * r) / r + gaussian) / r2;
+    let c = (3.0 * b + 2.0 * alpha2 * gaussian) / r2;
+    let d = (5.0 * c + 4.0 * alpha2 * alpha2 * gaussian) / r2;
+    return [b, c, d];
+}
+
+/// Get the force acting on the first particle of a pair with charges `qi`
+/// and `qj` and dipoles `dipole_i` and `dipole_j`, separated by `rij`, using
+/// the dipolar `functions` at this distance. The interaction between the
+/// charges is not included.
+#[inline]
+fn dipolar_force_pair(
+    qi: f64,
+    dipole_i: Vector3D,
+    qj: f64,
+    dipole_j: Vector3D,
+    rij: Vector3D,
+    functions: [f64; 3],
+) -> Vector3D {
+    let [b, c, d] = functions;
+    let projection_i = dipole_i * rij;
+    let projection_j = dipole_j * rij;
+    let gradient = qi * (b * dipole_j - c * projection_j * rij)
+                 - qj * (b * dipole_i - c * projection_i * rij)
+                 - c * ((dipole_i * dipole_j) * rij + projection_j * dipole_i + projection_i * dipole_j)
+                 + d * projection_i * projection_j * rij;
+    return -gradient;
+}
+
+/// Real space interactions involving induced dipoles, derived from the damped
+/// coulombic kernel `erfc(α r) / r` and truncated at the cutoff.
+#[derive(Clone, Copy)]
+pub(super) struct DampedDipoles<'a> {
+    /// Damping parameter
+    pub alpha: f64,
+    /// Cutoff radius in real space
+    pub cutoff: f64,
+    /// Restriction scheme
+    pub restriction: PairRestriction,
+    /// Should the full interaction be removed for excluded pairs (when it is
+    /// included in k-space), instead of ignoring these pairs?
+    pub remove_excluded: bool,
+    /// Should the periodic boundary conditions only be used along the x and
+    /// y axes, as with the Ewald slab correction?
+    pub slab: bool,
+    /// Neighbor list used to find the interacting pairs
+    pub neighbors: &'a NeighborList,
+}
+
+/// A pair of particles interacting through `DampedDipoles`
+struct DipolarPair {
+    /// Index of the first particle
+    i: usize,
+    /// Index of the second particle
+    j: usize,
+    /// Vector between the second and the first particle
+    rij: Vector3D,
+    /// Dipolar functions for this pair
+    functions: [f64; 3],
+}
+
+impl<'a> DampedDipoles<'a> {
+    /// Get the dipolar interaction between the particles `i` and `j`, or
+    /// `None` if the pair does not interact.
+    fn pair(&self, positions: &Positions<'_>, i: usize, j: usize) -> Option<DipolarPair> {
+        let configuration = positions.configuration;
+        let mut rij = positions.get(i) - positions.get(j);
+        if self.slab {
+            slab_vector_image(&configuration.cell, &mut rij);
+        } else {
+            configuration.cell.vector_image(&mut rij);
+        }
+
+        let r = rij.norm();
+        if r > self.cutoff {
+            return None;
+        }
+
+        let info = self.restriction.information(configuration.bond_path(i, j));
+        let damped = dipolar_functions(self.alpha, r);
+        let functions = if info.excluded {
+            if self.remove_excluded {
+                let full = dipolar_functions(0.0, r);
+                [damped[0] - full[0], damped[1] - full[1], damped[2] - full[2]]
+            } else {
+                return None;
+            }
+        } else {
+            let scaling = info.scaling;
+            [scaling * damped[0], scaling * damped[1], scaling * damped[2]]
+        };
+
+        return Some(DipolarPair {
+            i: i,
+            j: j,
+            rij: rij,
+            functions: functions,
+        });
+    }
+
+    /// Get all the interacting pairs of particles `i` and `j` for which
+    /// `filter(i, j)` is `true`. The pairs between particles which were not
+    /// moved come from the neighbor list, and the moved particles are checked
+    /// against all the other particles.
+    fn pairs<P>(&self, positions: &Positions<'_>, filter: P) -> Vec<DipolarPair>
+    where
+        P: Fn(usize, usize) -> bool + Sync,
+    {
+        let natoms = positions.configuration.size();
+        let neighbors = self.neighbors.neighbors(positions.configuration, Some(self.cutoff));
+        return (0..natoms).into_par_iter().flat_map(|i| {
+            let mut pairs = Vec::new();
+            if positions.is_moved(i) {
+                let others = (0..natoms).filter(|&j| j != i && (j > i || !positions.is_moved(j)));
+                for j in others.filter(|&j| filter(i, j)) {
+                    pairs.extend(self.pair(positions, i, j));
+                }
+            } else {
+                let others = neighbors.of(i).filter(|&j| !positions.is_moved(j));
+                for j in others.filter(|&j| filter(i, j)) {
+                    pairs.extend(self.pair(positions, i, j));
+                }
+            }
+            pairs
+        }).collect();
+    }
+}
+
+impl<'a> DipolarField for DampedDipoles<'a> {
+    fn charges_field(&self, positions: &Positions<'_>) -> Vec<Vector3D> {
+        let natoms = positions.configuration.size();
+        let charges = positions.configuration.particles().charge;
+        let pairs = self.pairs(positions, |i, j| charges[i] != 0.0 || charges[j] != 0.0);
+
+        let thread_local_field = ThreadLocalVec::with_size(natoms);
+        pairs.par_iter().for_each(|pair| {
+            let mut field = thread_local_field.borrow_mut();
+            let b = pair.functions[0];
+            field[pair.i] += charges[pair.j] * b * pair.rij;
+            field[pair.j] -= charges[pair.i] * b * pair.rij;
+        });
+
+        let mut field = vec![Vector3D::zero(); natoms];
+        thread_local_field.sum_into(&mut field);
+        return field;
+    }
+
+    fn dipoles_field(&self, positions: &Positions<'_>, dipoles: &[Vector3D]) -> Vec<Vector3D> {
+        let natoms = positions.configuration.size();
+        let pairs = self.pairs(positions, |i, j| dipoles[i].norm2() != 0.0 || dipoles[j].norm2() != 0.0);
+
+        let thread_local_field = ThreadLocalVec::with_size(natoms);
+        pairs.par_iter().for_each(|pair| {
+            let mut field = thread_local_field.borrow_mut();
+            let [b, c, _] = pair.functions;
+            let (i, j, rij) = (pair.i, pair.j, pair.rij);
+            field[i] += c * (dipoles[j] * rij) * rij - b * dipoles[j];
+            field[j] += c * (dipoles[i] * rij) * rij - b * dipoles[i];
+        });
+
+        let mut field = vec![Vector3D::zero(); natoms];
+        thread_local_field.sum_into(&mut field);
+        return field;
+    }
+
+    fn dipoles_forces(&self, positions: &Positions<'_>, dipoles: &[Vector3D]) -> Vec<Vector3D> {
+        let natoms = positions.configuration.size();
+        let charges = positions.configuration.particles().charge;
+        let interacting = |i: usize| charges[i] != 0.0 || dipoles[i].norm2() != 0.0;
+        let pairs = self.pairs(positions, |i, j| interacting(i) && interacting(j));
+
+        let thread_local_forces = ThreadLocalVec::with_size(natoms);
+        pairs.par_iter().for_each(|pair| {
+            let mut forces = thread_local_forces.borrow_mut();
+            let (i, j) = (pair.i, pair.j);
+            let force = dipolar_force_pair(charges[i], dipoles[i], charges[j], dipoles[j], pair.rij, pair.functions);
+            forces[i] += force;
+            forces[j] -= force;
+        });
+
+        let mut forces = vec![Vector3D::zero(); natoms];
+        thread_local_forces.sum_into(&mut forces);
+        return forces;
+    }
+
+    fn dipoles_virial(&self, positions: &Positions<'_>, dipoles: &[Vector3D]) -> Matrix3 {
+        let charges = positions.configuration.particles().charge;
+        let interacting = |i: usize| charges[i] != 0.0 || dipoles[i].norm2() != 0.0;
+        let pairs = self.pairs(positions, |i, j| interacting(i) && interacting(j));
+
+        return pairs.par_iter().map(|pair| {
+            let (i, j) = (pair.i, pair.j);
+            let force = dipolar_force_pair(charges[i], dipoles[i], charges[j], dipoles[j], pair.rij, pair.functions);
+            force.tensorial(&pair.rij)
+        }).sum();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CoulombicPotential, GlobalCache, GlobalPotential, Wolf};
+    use crate::System;
+    use crate::utils::system_from_xyz;
+
+    use std::f64::consts::PI;
+    use approx::assert_relative_eq;
+
+    pub fn two_waters() -> System {
+        let mut system = system_from_xyz("6
+        cell: 20.0
+        H  0.3 -0.3 -0.8
+        O  0.0  0.0  0.0
+        H -0.7 -0.7  0.3
+        H  2.3  1.7 -0.8
+        O  2.0  2.0  0.0
+        H  1.3  1.3  0.3
+        ");
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(1, 2).is_empty());
+        assert!(system.add_bond(3, 4).is_empty());
+        assert!(system.add_bond(4, 5).is_empty());
+
+        for particle in system.particles_mut() {
+            if particle.name == "O" {
+                *particle.charge = -0.8476;
+            } else if particle.name == "H" {
+                *particle.charge = 0.4238;
+            }
+        }
+        return system;
+    }
+
+    pub fn polarization(solver: DipoleSolver) -> Polarization {
+        let mut polarization = Polarization::new(solver, 1e-12);
+        polarization.set_polarizability("O", 1.45);
+        polarization.set_polarizability("H", 0.5);
+        return polarization;
+    }
+
+    fn wolf() -> Wolf {
+        let mut wolf = Wolf::new(8.0);
+        wolf.set_restriction(PairRestriction::InterMolecular);
+        wolf.set_polarization(polarization(DipoleSolver::ConjugateGradient));
+        return wolf;
+    }
+
+    fn damped_dipoles(neighbors: &NeighborList) -> DampedDipoles<'_> {
+        DampedDipoles {
+            alpha: PI / 8.0,
+            cutoff: 8.0,
+            restriction: PairRestriction::InterMolecular,
+            remove_excluded: false,
+            slab: false,
+            neighbors: neighbors,
+        }
+    }
+
+    #[test]
+    fn solvers() {
+        let system = two_waters();
+        let neighbors = NeighborList::default();
+        let field = damped_dipoles(&neighbors);
+
+        let iterative = polarization(DipoleSolver::Iterative);
+        let (dipoles, charges_field) = iterative.induced_dipoles(&field, &system);
+        let conjugate_gradient = polarization(DipoleSolver::ConjugateGradient);
+        let (expected, _) = conjugate_gradient.induced_dipoles(&field, &system);
+
+        for (dipole, expected) in dipoles.iter().zip(&expected) {
+            assert_relative_eq!(*dipole, *expected, epsilon = 1e-10);
+        }
+
+        // The dipoles are self-consistent
+        let dipoles_field = field.dipoles_field(&Positions::new(&system), &dipoles);
+        let polarizabilities = [0.5, 1.45, 0.5, 0.5, 1.45, 0.5];
+        for i in 0..6 {
+            let induced = polarizabilities[i] * (charges_field[i] + dipoles_field[i]);
+            assert_relative_eq!(dipoles[i], induced, epsilon = 1e-10);
+        }
+
+        // Particles without polarizability do not get a dipole
+        let mut polarization = Polarization::new(DipoleSolver::Iterative, 1e-12);
+        polarization.set_polarizability("O", 1.45);
+        let (dipoles, _) = polarization.induced_dipoles(&field, &system);
+        assert_eq!(dipoles[0], Vector3D::zero());
+        assert!(dipoles[1].norm() > 0.0);
+    }
+
+    #[test]
+    fn energy() {
+        let system = two_waters();
+        let neighbors = NeighborList::default();
+        let field = damped_dipoles(&neighbors);
+        let polarization = polarization(DipoleSolver::ConjugateGradient);
+        // Polarization always decreases the energy
+        assert!(polarization.energy(&field, &system) < 0.0);
+
+        let empty = Polarization::new(DipoleSolver::ConjugateGradient, 1e-12);
+        assert_eq!(empty.energy(&field, &system), 0.0);
+    }
+
+    #[test]
+    fn cache() {
+        let mut system = two_waters();
+        let neighbors = NeighborList::default();
+        let field = damped_dipoles(&neighbors);
+        let polarization = polarization(DipoleSolver::Iterative);
+        let matches = |system: &System| {
+            polarization.cache.lock().expect("polarization lock is poisoned").matches(system)
+        };
+
+        assert!(!matches(&system));
+        let energy = polarization.energy(&field, &system);
+        assert!(matches(&system));
+        assert_eq!(polarization.energy(&field, &system), energy);
+
+        // Computing the energy of a move does not change the cached dipoles
+        let new_positions = &[
+            Vector3D::new(0.8, 0.1, -0.9),
+            Vector3D::new(0.5, 0.4, -0.1),
+            Vector3D::new(-0.2, -0.3, 0.2),
+        ];
+        let moved = polarization.moved_energy(&field, &system, 0, new_positions);
+        assert!(matches(&system));
+        assert!(moved != energy);
+
+        system.particles_mut().position[0][0] += 0.1;
+        assert!(!matches(&system));
+        assert!(polarization.energy(&field, &system) != energy);
+        assert!(matches(&system));
+
+        polarization.clear_cache();
+        assert!(!matches(&system));
+    }
+
+    #[test]
+    fn forces() {
+        let mut system = two_waters();
+        let wolf = wolf();
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        wolf.forces(&system, &mut forces);
+        let total = forces.iter().fold(Vector3D::zero(), |sum, &force| sum + force);
+        assert!(total.norm() < 1e-12);
+
+        let eps = 1e-6;
+        for i in 0..system.size() {
+            for k in 0..3 {
+                system.particles_mut().position[i][k] += eps;
+                let e1 = wolf.energy(&system);
+                system.particles_mut().position[i][k] -= 2.0 * eps;
+                let e2 = wolf.energy(&system);
+                system.particles_mut().position[i][k] += eps;
+                assert_relative_eq!(-(e1 - e2) / (2.0 * eps), forces[i][k], epsilon = 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn atomic_virial() {
+        fn scaled(system: &System, i: usize, j: usize, eps: f64) -> System {
+            let mut scaling = Matrix3::one();
+            scaling[i][j] += eps;
+            let mut system = system.clone();
+            let old_cell = system.cell;
+            let new_cell = system.cell.scale(scaling);
+
+            for position in system.particles_mut().position {
+                *position = new_cell.cartesian(&old_cell.fractional(&position));
+            }
+            system.cell = new_cell;
+            return system;
+        }
+
+        let system = two_waters();
+        let wolf = wolf();
+
+        let virial = wolf.atomic_virial(&system);
+        let eps = 1e-6;
+        let mut finite_diff = Matrix3::zero();
+        for i in 0..3 {
+            for j in 0..3 {
+                let e1 = wolf.energy(&scaled(&system, i, j, eps));
+                let e2 = wolf.energy(&scaled(&system, i, j, -eps));
+                finite_diff[i][j] = -(e1 - e2) / (2.0 * eps);
+            }
+        }
+        assert_relative_eq!(virial, finite_diff, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn move_molecule() {
+        let mut system = two_waters();
+        let wolf = wolf();
+        let old_energy = wolf.energy(&system);
+
+        let new_positions = &[
+            Vector3D::new(0.8, 0.1, -0.9),
+            Vector3D::new(0.5, 0.4, -0.1),
+            Vector3D::new(-0.2, -0.3, 0.2),
+        ];
+        let cost = wolf.move_molecule_cost(&system, 0, new_positions);
+
+        system.particles_mut().position[0] = new_positions[0];
+        system.particles_mut().position[1] = new_positions[1];
+        system.particles_mut().position[2] = new_positions[2];
+        let new_energy = wolf.energy(&system);
+        assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+    }
+}
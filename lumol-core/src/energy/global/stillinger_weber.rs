@@ -0,0 +1,382 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::units;
+use crate::sys::NeighborList;
+use crate::Configuration;
+use crate::{Matrix3, Vector3D};
+
+use super::{GlobalCache, GlobalPotential};
+use super::manybody::{self, ManyBody, Neighbor, cos_angle, invalid_data};
+
+/// Parameters of the Stillinger-Weber potential for a triplet of elements
+#[derive(Clone, Copy, Debug)]
+struct Parameters {
+    epsilon: f64,
+    sigma: f64,
+    /// Cutoff of the potential, in units of `sigma`
+    a: f64,
+    lambda: f64,
+    gamma: f64,
+    cos_theta0: f64,
+    big_a: f64,
+    big_b: f64,
+    p: f64,
+    q: f64,
+}
+
+impl Parameters {
+    fn cutoff(&self) -> f64 {
+        self.a * self.sigma
+    }
+
+    /// Get the two-body energy at distance `r` and its derivative
+    fn two_body(&self, r: f64) -> (f64, f64) {
+        if r >= self.cutoff() {
+            return (0.0, 0.0);
+        }
+
+        let delta = r - self.cutoff();
+        let exp = f64::exp(self.sigma / delta);
+        let exp_derivative = -self.sigma / (delta * delta) * exp;
+
+        let sigma_r_p = f64::powf(self.sigma / r, self.p);
+        let sigma_r_q = f64::powf(self.sigma / r, self.q);
+        let factor = self.big_b * sigma_r_p - sigma_r_q;
+        let factor_derivative = (self.q * sigma_r_q - self.p * self.big_b * sigma_r_p) / r;
+
+        let prefactor = self.big_a * self.epsilon;
+        let energy = prefactor * factor * exp;
+        let derivative = prefactor * (factor_derivative * exp + factor * exp_derivative);
+        return (energy, derivative);
+    }
+
+    /// Get the radial part of the three-body energy at distance `r`, and its
+    /// derivative
+    fn three_body_radial(&self, r: f64) -> (f64, f64) {
+        if r >= self.cutoff() {
+            return (0.0, 0.0);
+        }
+
+        let delta = r - self.cutoff();
+        let exp = f64::exp(self.gamma * self.sigma / delta);
+        return (exp, -self.gamma * self.sigma / (delta * delta) * exp);
+    }
+}
+
+/// Stillinger-Weber three-body potential.
+///
+/// This potential [SW1985] was designed for silicon, and is also used for
+/// other covalent materials and for coarse-grained water models such as mW. The
+/// energy is a sum of two-body and three-body terms:
+///
+/// $$ E = \sum_i \sum_{j > i} \phi_2(r_{ij}) + \sum_i \sum_{j \neq i}
+///        \sum_{k > j} \phi_3(r_{ij}, r_{ik}, \theta_{jik}) $$
+///
+/// $$ \phi_2(r) = A \epsilon \left[B \left(\frac \sigma r\right)^p -
+///        \left(\frac \sigma r\right)^q \right]
+///        \exp\left(\frac{\sigma}{r - a \sigma}\right) $$
+///
+/// $$ \phi_3(r_{ij}, r_{ik}, \theta) = \lambda \epsilon
+///        (\cos \theta - \cos \theta_0)^2
+///        \exp\left(\frac{\gamma \sigma}{r_{ij} - a \sigma}\right)
+///        \exp\left(\frac{\gamma \sigma}{r_{ik} - a \sigma}\right) $$
+///
+/// The parameters are read from a file in the same format as [LAMMPS], with
+/// energies in eV and distances in Angstroms. As in LAMMPS, the parameters for
+/// the two-body term and the radial part of the three-body term come from the
+/// `i-j-j` entry, and the angular parameters come from the `i-j-k` entry.
+///
+/// The elements in the potential are matched with the particles names, and
+/// particles with another name do not interact with this potential. All the
+/// particles interact, including particles in the same molecule.
+///
+/// [SW1985]: Stillinger, F. H. & Weber, T. A. Phys. Rev. B 31, 5262 (1985).
+/// [LAMMPS]: https://lammps.sandia.gov/doc/pair_sw.html
+#[derive(Clone, Debug)]
+pub struct StillingerWeber {
+    /// Names of the elements in this potential
+    elements: Vec<String>,
+    /// Parameters for all the triplets of elements
+    parameters: Vec<Parameters>,
+    /// Maximal cutoff for all the elements
+    cutoff: f64,
+    /// Neighbor list for the pairs closer than the cutoff
+    neighbors: NeighborList,
+}
+
+impl StillingerWeber {
+    /// Read the Stillinger-Weber parameters from the file at `path`, in the
+    /// format used by LAMMPS.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<StillingerWeber> {
+        let content = fs::read_to_string(path)?;
+        return StillingerWeber::parse(&content);
+    }
+
+    /// Get the names of the elements in this potential
+    pub fn elements(&self) -> &[String] {
+        &self.elements
+    }
+
+    fn parse(content: &str) -> io::Result<StillingerWeber> {
+        let ev = units::from(1.0, "eV").expect("eV is a valid unit");
+
+        let (elements, values) = manybody::read_parameters(content, 11)?;
+        let parameters = values.iter().map(|values| {
+            // The last parameter is a tolerance only used by LAMMPS
+            let parameters = Parameters {
+                epsilon: values[0] * ev,
+                sigma: values[1],
+                a: values[2],
+                lambda: values[3],
+                gamma: values[4],
+                cos_theta0: values[5],
+                big_a: values[6],
+                big_b: values[7],
+                p: values[8],
+                q: values[9],
+            };
+
+            if parameters.epsilon < 0.0 || parameters.sigma <= 0.0 || parameters.a <= 0.0 {
+                return Err(invalid_data("epsilon, sigma and a must be positive in Stillinger-Weber parameters"));
+            }
+            Ok(parameters)
+        }).collect::<io::Result<Vec<_>>>()?;
+
+        let cutoff = parameters.iter().map(Parameters::cutoff).fold(0.0, f64::max);
+        Ok(StillingerWeber {
+            elements: elements,
+            parameters: parameters,
+            cutoff: cutoff,
+            neighbors: NeighborList::default(),
+        })
+    }
+
+    #[inline]
+    fn parameters(&self, i: usize, j: usize, k: usize) -> &Parameters {
+        let n = self.elements.len();
+        return &self.parameters[(i * n + j) * n + k];
+    }
+}
+
+impl ManyBody for StillingerWeber {
+    fn elements(&self) -> &[String] {
+        &self.elements
+    }
+
+    fn max_cutoff(&self) -> f64 {
+        self.cutoff
+    }
+
+    fn neighbor_list(&self) -> &NeighborList {
+        &self.neighbors
+    }
+
+    fn particle_energy(&self, element: usize, neighbors: &[Neighbor], gradients: &mut [Vector3D]) -> f64 {
+        let mut energy = 0.0;
+        for (j, neighbor_j) in neighbors.iter().enumerate() {
+            let parameters_ij = self.parameters(element, neighbor_j.element, neighbor_j.element);
+            let (radial_j, radial_j_derivative) = parameters_ij.three_body_radial(neighbor_j.distance);
+
+            // Two-body energy is shared between the two particles
+            let (phi, phi_derivative) = parameters_ij.two_body(neighbor_j.distance);
+            energy += 0.5 * phi;
+            gradients[j] += 0.5 * phi_derivative / neighbor_j.distance * neighbor_j.vector;
+
+            if radial_j == 0.0 {
+                continue;
+            }
+
+            for (k, neighbor_k) in neighbors.iter().enumerate().skip(j + 1) {
+                let parameters_ik = self.parameters(element, neighbor_k.element, neighbor_k.element);
+                let (radial_k, radial_k_derivative) = parameters_ik.three_body_radial(neighbor_k.distance);
+                if radial_k == 0.0 {
+                    continue;
+                }
+
+                let parameters_ijk = self.parameters(element, neighbor_j.element, neighbor_k.element);
+                let (cos, cos_derivative_j, cos_derivative_k) = cos_angle(neighbor_j.vector, neighbor_k.vector);
+                let delta = cos - parameters_ijk.cos_theta0;
+                let prefactor = parameters_ijk.lambda * parameters_ijk.epsilon;
+
+                energy += prefactor * delta * delta * radial_j * radial_k;
+
+                let angular = 2.0 * prefactor * delta * radial_j * radial_k;
+                let radial = prefactor * delta * delta;
+                gradients[j] += angular * cos_derivative_j;
+                gradients[j] += radial * radial_k * radial_j_derivative / neighbor_j.distance * neighbor_j.vector;
+                gradients[k] += angular * cos_derivative_k;
+                gradients[k] += radial * radial_j * radial_k_derivative / neighbor_k.distance * neighbor_k.vector;
+            }
+        }
+        return energy;
+    }
+}
+
+impl GlobalCache for StillingerWeber {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        manybody::move_molecule_cost(self, configuration, molecule_id, new_positions)
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+impl GlobalPotential for StillingerWeber {
+    fn cutoff(&self) -> Option<f64> {
+        Some(self.cutoff)
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        manybody::energy(self, configuration)
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        manybody::forces(self, configuration, forces);
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        manybody::atomic_virial(self, configuration)
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        manybody::molecular_virial(self, configuration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::System;
+    use crate::energy::global::manybody::diamond_silicon;
+
+    use approx::assert_relative_eq;
+
+    const SILICON: &str = "
+    # Stillinger & Weber, Phys. Rev. B 31, 5262 (1985)
+    # element1 element2 element3 epsilon sigma a lambda gamma costheta0 A B p q tol
+    Si Si Si 2.1683 2.0951 1.80 21.0 1.20 -0.333333333333
+             7.049556277 0.6022245584 4.0 0.0 0.0
+    ";
+
+    /// Diamond silicon with some random-looking displacements
+    fn distorted_silicon() -> System {
+        let mut system = diamond_silicon(5.431);
+        for (i, position) in system.particles_mut().position.iter_mut().enumerate() {
+            let i = i as f64;
+            *position += 0.2 * Vector3D::new(f64::sin(i), f64::cos(3.0 * i), f64::sin(7.0 * i));
+        }
+        return system;
+    }
+
+    #[test]
+    fn parse() {
+        let sw = StillingerWeber::parse(SILICON).unwrap();
+        assert_eq!(sw.elements(), ["Si"]);
+        assert_relative_eq!(sw.cutoff().unwrap(), 1.80 * 2.0951);
+
+        let error = StillingerWeber::parse("Si Si Si 2.1683 -2.0951 1.80 21.0 1.20 -0.33 7.04 0.60 4.0 0.0 0.0").unwrap_err();
+        assert_eq!(error.to_string(), "epsilon, sigma and a must be positive in Stillinger-Weber parameters");
+    }
+
+    #[test]
+    fn energy() {
+        let sw = StillingerWeber::parse(SILICON).unwrap();
+        let system = diamond_silicon(5.431);
+        let ev = units::from(1.0, "eV").unwrap();
+
+        // The three-body term vanishes in the perfect crystal, and the
+        // cohesive energy is 2 epsilon
+        let energy = sw.energy(&system) / ev / system.size() as f64;
+        assert_relative_eq!(energy, -2.0 * 2.1683, max_relative = 1e-6);
+
+        let system = distorted_silicon();
+        let energy = sw.energy(&system) / ev / system.size() as f64;
+        assert!(energy > -2.0 * 2.1683);
+    }
+
+    #[test]
+    fn forces() {
+        let sw = StillingerWeber::parse(SILICON).unwrap();
+        let mut system = distorted_silicon();
+        let ev = units::from(1.0, "eV").unwrap();
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        sw.forces(&system, &mut forces);
+
+        let total = forces.iter().fold(Vector3D::zero(), |total, &force| total + force);
+        assert_relative_eq!(total.norm() / ev, 0.0, epsilon = 1e-9);
+
+        let eps = 1e-6;
+        for &i in &[0, 7, 33] {
+            for k in 0..3 {
+                system.particles_mut().position[i][k] += eps;
+                let e_plus = sw.energy(&system) / ev;
+                system.particles_mut().position[i][k] -= 2.0 * eps;
+                let e_minus = sw.energy(&system) / ev;
+                system.particles_mut().position[i][k] += eps;
+
+                let finite_diff = -(e_plus - e_minus) / (2.0 * eps);
+                assert_relative_eq!(finite_diff, forces[i][k] / ev, epsilon = 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn atomic_virial() {
+        let sw = StillingerWeber::parse(SILICON).unwrap();
+        let mut system = distorted_silicon();
+        let ev = units::from(1.0, "eV").unwrap();
+        let virial = sw.atomic_virial(&system) / ev;
+
+        let eps = 1e-6;
+        let mut finite_diff = Matrix3::zero();
+        for i in 0..3 {
+            for j in 0..3 {
+                let old_cell = system.cell;
+                let old_positions = system.particles().position.to_vec();
+
+                let mut energies = [0.0; 2];
+                for (energy, &delta) in energies.iter_mut().zip(&[eps, -eps]) {
+                    let mut scaling = Matrix3::one();
+                    scaling[i][j] += delta;
+                    let new_cell = old_cell.scale(scaling);
+                    for (position, old) in system.particles_mut().position.iter_mut().zip(&old_positions) {
+                        *position = new_cell.cartesian(&old_cell.fractional(old));
+                    }
+                    system.cell = new_cell;
+                    *energy = sw.energy(&system) / ev;
+                }
+
+                system.cell = old_cell;
+                system.particles_mut().position.copy_from_slice(&old_positions);
+                finite_diff[i][j] = -(energies[0] - energies[1]) / (2.0 * eps);
+            }
+        }
+
+        assert_relative_eq!(virial, finite_diff, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn move_molecule() {
+        let sw = StillingerWeber::parse(SILICON).unwrap();
+        let mut system = distorted_silicon();
+        let old_energy = sw.energy(&system);
+
+        let new_position = system.particles().position[12] + Vector3D::new(0.3, -0.2, 0.1);
+        let cost = sw.move_molecule_cost(&system, 12, &[new_position]);
+
+        system.particles_mut().position[12] = new_position;
+        let new_energy = sw.energy(&system);
+        assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+    }
+}
@@ -0,0 +1,488 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::units;
+use crate::sys::NeighborList;
+use crate::Configuration;
+use crate::{Matrix3, Vector3D};
+
+use super::{GlobalCache, GlobalPotential};
+use super::manybody::{self, ManyBody, Neighbor, cos_angle, invalid_data};
+
+/// Parameters of the Tersoff potential for a triplet of elements
+#[derive(Clone, Copy, Debug)]
+struct Parameters {
+    /// Is the exponent `m` 3 (`true`) or 1 (`false`)
+    cubic: bool,
+    gamma: f64,
+    lambda3: f64,
+    c: f64,
+    d: f64,
+    cos_theta0: f64,
+    n: f64,
+    beta: f64,
+    lambda2: f64,
+    big_b: f64,
+    big_r: f64,
+    big_d: f64,
+    lambda1: f64,
+    big_a: f64,
+}
+
+impl Parameters {
+    fn cutoff(&self) -> f64 {
+        self.big_r + self.big_d
+    }
+
+    /// Get the value of the cutoff function at distance `r`, and its derivative
+    fn cutoff_function(&self, r: f64) -> (f64, f64) {
+        if r < self.big_r - self.big_d {
+            return (1.0, 0.0);
+        } else if r > self.big_r + self.big_d {
+            return (0.0, 0.0);
+        }
+
+        let x = 0.5 * PI * (r - self.big_r) / self.big_d;
+        return (0.5 - 0.5 * f64::sin(x), -0.25 * PI / self.big_d * f64::cos(x));
+    }
+
+    /// Get the value of the repulsive function at distance `r`, and its
+    /// derivative
+    fn repulsive(&self, r: f64) -> (f64, f64) {
+        let value = self.big_a * f64::exp(-self.lambda1 * r);
+        return (value, -self.lambda1 * value);
+    }
+
+    /// Get the value of the attractive function at distance `r`, and its
+    /// derivative
+    fn attractive(&self, r: f64) -> (f64, f64) {
+        let value = -self.big_b * f64::exp(-self.lambda2 * r);
+        return (value, -self.lambda2 * value);
+    }
+
+    /// Get the value of the angular function for the given `cos` of the
+    /// angle, and its derivative with respect to `cos`
+    fn angular(&self, cos: f64) -> (f64, f64) {
+        let c2 = self.c * self.c;
+        let d2 = self.d * self.d;
+        let delta = self.cos_theta0 - cos;
+        let denominator = d2 + delta * delta;
+
+        let value = self.gamma * (1.0 + c2 / d2 - c2 / denominator);
+        let derivative = -2.0 * self.gamma * c2 * delta / (denominator * denominator);
+        return (value, derivative);
+    }
+
+    /// Get the value of the exponential factor in the bond order for the
+    /// difference of distance `delta`, and its derivative
+    fn exponential(&self, delta: f64) -> (f64, f64) {
+        if self.lambda3 == 0.0 {
+            return (1.0, 0.0);
+        }
+
+        let (argument, derivative) = if self.cubic {
+            let lambda3_3 = self.lambda3 * self.lambda3 * self.lambda3;
+            (lambda3_3 * delta * delta * delta, 3.0 * lambda3_3 * delta * delta)
+        } else {
+            (self.lambda3 * delta, self.lambda3)
+        };
+
+        let value = f64::exp(argument);
+        return (value, derivative * value);
+    }
+
+    /// Get the value of the bond order for the given `zeta`, and its
+    /// derivative with respect to `zeta`
+    fn bond_order(&self, zeta: f64) -> (f64, f64) {
+        if zeta <= 0.0 {
+            return (1.0, 0.0);
+        }
+
+        let beta_zeta_n = f64::powf(self.beta * zeta, self.n);
+        let value = f64::powf(1.0 + beta_zeta_n, -0.5 / self.n);
+        let derivative = -0.5 * value * beta_zeta_n / (zeta * (1.0 + beta_zeta_n));
+        return (value, derivative);
+    }
+}
+
+/// Tersoff bond-order potential.
+///
+/// This potential [Tersoff1988] describes covalent materials such as silicon,
+/// carbon or germanium, where the strength of the bonds depends on the local
+/// environment of the atoms:
+///
+/// $$ E = \frac 12 \sum_i \sum_{j \neq i} f_C(r_{ij})
+///        \left[f_R(r_{ij}) + b_{ij} f_A(r_{ij})\right] $$
+///
+/// with $f_R(r) = A e^{-\lambda_1 r}$ and $f_A(r) = -B e^{-\lambda_2 r}$. The
+/// bond order is
+///
+/// $$ b_{ij} = \left(1 + \beta^n \zeta_{ij}^n\right)^{-1/2n} $$
+///
+/// $$ \zeta_{ij} = \sum_{k \neq i, j} f_C(r_{ik}) g(\theta_{ijk})
+///        \exp\left[\lambda_3^m (r_{ij} - r_{ik})^m\right] $$
+///
+/// $$ g(\theta) = \gamma \left(1 + \frac{c^2}{d^2} -
+///        \frac{c^2}{d^2 + (\cos \theta_0 - \cos \theta)^2}\right) $$
+///
+/// and the cutoff function `f_C` goes smoothly from 1 to 0 between `R - D`
+/// and `R + D`.
+///
+/// The parameters are read from a file in the same format as [LAMMPS], with
+/// energies in eV and distances in Angstroms. As in LAMMPS, the parameters
+/// for the pair terms come from the `i-j-j` entry, and the parameters for the
+/// contribution of `k` to `ζ_ij` come from the `i-j-k` entry.
+///
+/// The elements in the potential are matched with the particles names, and
+/// particles with another name do not interact with this potential. All the
+/// particles interact, including particles in the same molecule.
+///
+/// [Tersoff1988]: Tersoff, J. Phys. Rev. B 37, 6991 (1988).
+/// [LAMMPS]: https://lammps.sandia.gov/doc/pair_tersoff.html
+#[derive(Clone, Debug)]
+pub struct Tersoff {
+    /// Names of the elements in this potential
+    elements: Vec<String>,
+    /// Parameters for all the triplets of elements
+    parameters: Vec<Parameters>,
+    /// Maximal cutoff for all the elements
+    cutoff: f64,
+    /// Neighbor list for the pairs closer than the cutoff
+    neighbors: NeighborList,
+}
+
+impl Tersoff {
+    /// Read the Tersoff parameters from the file at `path`, in the format used
+    /// by LAMMPS.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Tersoff> {
+        let content = fs::read_to_string(path)?;
+        return Tersoff::parse(&content);
+    }
+
+    /// Get the names of the elements in this potential
+    pub fn elements(&self) -> &[String] {
+        &self.elements
+    }
+
+    fn parse(content: &str) -> io::Result<Tersoff> {
+        let ev = units::from(1.0, "eV").expect("eV is a valid unit");
+
+        let (elements, values) = manybody::read_parameters(content, 14)?;
+        let parameters = values.iter().map(|values| {
+            let m = values[0];
+            if f64::abs(m - 1.0) > 1e-12 && f64::abs(m - 3.0) > 1e-12 {
+                return Err(invalid_data("m must be 1 or 3 in Tersoff parameters"));
+            }
+
+            let parameters = Parameters {
+                cubic: m > 2.0,
+                gamma: values[1],
+                lambda3: values[2],
+                c: values[3],
+                d: values[4],
+                cos_theta0: values[5],
+                n: values[6],
+                beta: values[7],
+                lambda2: values[8],
+                big_b: values[9] * ev,
+                big_r: values[10],
+                big_d: values[11],
+                lambda1: values[12],
+                big_a: values[13] * ev,
+            };
+            if parameters.n <= 0.0 || parameters.d == 0.0 || parameters.big_d <= 0.0 {
+                return Err(invalid_data("n, d and D must be positive in Tersoff parameters"));
+            }
+            Ok(parameters)
+        }).collect::<io::Result<Vec<_>>>()?;
+
+        let cutoff = parameters.iter().map(Parameters::cutoff).fold(0.0, f64::max);
+        Ok(Tersoff {
+            elements: elements,
+            parameters: parameters,
+            cutoff: cutoff,
+            neighbors: NeighborList::default(),
+        })
+    }
+
+    #[inline]
+    fn parameters(&self, i: usize, j: usize, k: usize) -> &Parameters {
+        let n = self.elements.len();
+        return &self.parameters[(i * n + j) * n + k];
+    }
+}
+
+impl ManyBody for Tersoff {
+    fn elements(&self) -> &[String] {
+        &self.elements
+    }
+
+    fn max_cutoff(&self) -> f64 {
+        self.cutoff
+    }
+
+    fn neighbor_list(&self) -> &NeighborList {
+        &self.neighbors
+    }
+
+    fn particle_energy(&self, element: usize, neighbors: &[Neighbor], gradients: &mut [Vector3D]) -> f64 {
+        let mut energy = 0.0;
+        // Gradient of zeta_ij with respect to all the neighbors
+        let mut zeta_gradients = vec![Vector3D::zero(); neighbors.len()];
+        for (j, neighbor_j) in neighbors.iter().enumerate() {
+            let parameters_ij = self.parameters(element, neighbor_j.element, neighbor_j.element);
+            let r_ij = neighbor_j.distance;
+            let (cutoff_ij, cutoff_ij_derivative) = parameters_ij.cutoff_function(r_ij);
+            if cutoff_ij == 0.0 {
+                continue;
+            }
+
+            let mut zeta = 0.0;
+            for gradient in &mut zeta_gradients {
+                *gradient = Vector3D::zero();
+            }
+
+            for (k, neighbor_k) in neighbors.iter().enumerate() {
+                if k == j {
+                    continue;
+                }
+
+                let parameters_ijk = self.parameters(element, neighbor_j.element, neighbor_k.element);
+                let r_ik = neighbor_k.distance;
+                let (cutoff_ik, cutoff_ik_derivative) = parameters_ijk.cutoff_function(r_ik);
+                if cutoff_ik == 0.0 {
+                    continue;
+                }
+
+                let (cos, cos_derivative_j, cos_derivative_k) = cos_angle(neighbor_j.vector, neighbor_k.vector);
+                let (angular, angular_derivative) = parameters_ijk.angular(cos);
+                let (exponential, exponential_derivative) = parameters_ijk.exponential(r_ij - r_ik);
+
+                zeta += cutoff_ik * angular * exponential;
+
+                zeta_gradients[j] += cutoff_ik * angular_derivative * exponential * cos_derivative_j;
+                zeta_gradients[j] += cutoff_ik * angular * exponential_derivative / r_ij * neighbor_j.vector;
+
+                zeta_gradients[k] += cutoff_ik * angular_derivative * exponential * cos_derivative_k;
+                let radial_k = cutoff_ik_derivative * angular * exponential - cutoff_ik * angular * exponential_derivative;
+                zeta_gradients[k] += radial_k / r_ik * neighbor_k.vector;
+            }
+
+            let (bond_order, bond_order_derivative) = parameters_ij.bond_order(zeta);
+            let (repulsive, repulsive_derivative) = parameters_ij.repulsive(r_ij);
+            let (attractive, attractive_derivative) = parameters_ij.attractive(r_ij);
+
+            // The bond energy is shared between the two particles
+            energy += 0.5 * cutoff_ij * (repulsive + bond_order * attractive);
+
+            let derivative = cutoff_ij_derivative * (repulsive + bond_order * attractive)
+                + cutoff_ij * (repulsive_derivative + bond_order * attractive_derivative);
+            gradients[j] += 0.5 * derivative / r_ij * neighbor_j.vector;
+
+            let factor = 0.5 * cutoff_ij * attractive * bond_order_derivative;
+            for (gradient, zeta_gradient) in gradients.iter_mut().zip(&zeta_gradients) {
+                *gradient += factor * zeta_gradient;
+            }
+        }
+        return energy;
+    }
+}
+
+impl GlobalCache for Tersoff {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        manybody::move_molecule_cost(self, configuration, molecule_id, new_positions)
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+impl GlobalPotential for Tersoff {
+    fn cutoff(&self) -> Option<f64> {
+        Some(self.cutoff)
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        manybody::energy(self, configuration)
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        manybody::forces(self, configuration, forces);
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        manybody::atomic_virial(self, configuration)
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        manybody::molecular_virial(self, configuration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::System;
+    use crate::energy::global::manybody::diamond_silicon;
+
+    use approx::assert_relative_eq;
+
+    const SILICON: &str = "
+    # Tersoff, Phys. Rev. B 38, 9902 (1988)
+    # element1 element2 element3 m gamma lambda3 c d costheta0 n beta lambda2 B R D lambda1 A
+    Si Si Si 3.0 1.0 0.0 1.0039e5 16.217 -0.59825 0.78734 1.1e-6 1.7322 471.18 2.85 0.15 2.4799 1830.8
+    ";
+
+    /// Silicon parameters with a non-zero lambda3, to check the corresponding
+    /// derivatives
+    const SILICON_LAMBDA3: &str = "
+    Si Si Si 3.0 1.0 1.3 1.0039e5 16.217 -0.59825 0.78734 1.1e-6 1.7322 471.18 2.85 0.15 2.4799 1830.8
+    ";
+
+    /// Diamond silicon with some random-looking displacements
+    fn distorted_silicon() -> System {
+        let mut system = diamond_silicon(5.432);
+        for (i, position) in system.particles_mut().position.iter_mut().enumerate() {
+            let i = i as f64;
+            *position += 0.2 * Vector3D::new(f64::sin(i), f64::cos(3.0 * i), f64::sin(7.0 * i));
+        }
+        return system;
+    }
+
+    #[test]
+    fn parse() {
+        let tersoff = Tersoff::parse(SILICON).unwrap();
+        assert_eq!(tersoff.elements(), ["Si"]);
+        assert_relative_eq!(tersoff.cutoff().unwrap(), 3.0);
+
+        let error = Tersoff::parse(
+            "Si Si Si 2.0 1.0 0.0 1.0039e5 16.217 -0.59825 0.78734 1.1e-6 1.7322 471.18 2.85 0.15 2.4799 1830.8"
+        ).unwrap_err();
+        assert_eq!(error.to_string(), "m must be 1 or 3 in Tersoff parameters");
+    }
+
+    #[test]
+    fn energy() {
+        let tersoff = Tersoff::parse(SILICON).unwrap();
+        let system = diamond_silicon(5.432);
+        let ev = units::from(1.0, "eV").unwrap();
+
+        // Cohesive energy of diamond silicon
+        let energy = tersoff.energy(&system) / ev / system.size() as f64;
+        assert_relative_eq!(energy, -4.6296, max_relative = 1e-4);
+    }
+
+    #[test]
+    fn forces() {
+        let ev = units::from(1.0, "eV").unwrap();
+        for parameters in &[SILICON, SILICON_LAMBDA3] {
+            let tersoff = Tersoff::parse(parameters).unwrap();
+            let mut system = distorted_silicon();
+
+            let mut forces = vec![Vector3D::zero(); system.size()];
+            tersoff.forces(&system, &mut forces);
+
+            let total = forces.iter().fold(Vector3D::zero(), |total, &force| total + force);
+            assert_relative_eq!(total.norm() / ev, 0.0, epsilon = 1e-9);
+
+            let eps = 1e-6;
+            for &i in &[0, 7, 33] {
+                for k in 0..3 {
+                    system.particles_mut().position[i][k] += eps;
+                    let e_plus = tersoff.energy(&system) / ev;
+                    system.particles_mut().position[i][k] -= 2.0 * eps;
+                    let e_minus = tersoff.energy(&system) / ev;
+                    system.particles_mut().position[i][k] += eps;
+
+                    let finite_diff = -(e_plus - e_minus) / (2.0 * eps);
+                    assert_relative_eq!(finite_diff, forces[i][k] / ev, epsilon = 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn atomic_virial() {
+        let tersoff = Tersoff::parse(SILICON_LAMBDA3).unwrap();
+        let mut system = distorted_silicon();
+        let ev = units::from(1.0, "eV").unwrap();
+        let virial = tersoff.atomic_virial(&system) / ev;
+
+        let eps = 1e-6;
+        let mut finite_diff = Matrix3::zero();
+        for i in 0..3 {
+            for j in 0..3 {
+                let old_cell = system.cell;
+                let old_positions = system.particles().position.to_vec();
+
+                let mut energies = [0.0; 2];
+                for (energy, &delta) in energies.iter_mut().zip(&[eps, -eps]) {
+                    let mut scaling = Matrix3::one();
+                    scaling[i][j] += delta;
+                    let new_cell = old_cell.scale(scaling);
+                    for (position, old) in system.particles_mut().position.iter_mut().zip(&old_positions) {
+                        *position = new_cell.cartesian(&old_cell.fractional(old));
+                    }
+                    system.cell = new_cell;
+                    *energy = tersoff.energy(&system) / ev;
+                }
+
+                system.cell = old_cell;
+                system.particles_mut().position.copy_from_slice(&old_positions);
+                finite_diff[i][j] = -(energies[0] - energies[1]) / (2.0 * eps);
+            }
+        }
+
+        assert_relative_eq!(virial, finite_diff, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn neighbor_list() {
+        let tersoff = Tersoff::parse(SILICON).unwrap();
+        // Without skin, the neighbor list uses linked cells for this system
+        let mut linked_cells = Tersoff::parse(SILICON).unwrap();
+        linked_cells.neighbors = NeighborList::new(0.0);
+
+        let mut system = distorted_silicon();
+        assert_relative_eq!(linked_cells.energy(&system), tersoff.energy(&system), max_relative = 1e-12);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        tersoff.forces(&system, &mut forces);
+        let mut expected = vec![Vector3D::zero(); system.size()];
+        linked_cells.forces(&system, &mut expected);
+        for (force, expected) in forces.iter().zip(&expected) {
+            assert_relative_eq!(force, expected, epsilon = 1e-12);
+        }
+
+        let old_energy = linked_cells.energy(&system);
+        let new_position = system.particles().position[3] + Vector3D::new(-0.2, 0.4, 0.1);
+        let cost = linked_cells.move_molecule_cost(&system, 3, &[new_position]);
+        system.particles_mut().position[3] = new_position;
+        let new_energy = linked_cells.energy(&system);
+        assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+    }
+
+    #[test]
+    fn move_molecule() {
+        let tersoff = Tersoff::parse(SILICON).unwrap();
+        let mut system = distorted_silicon();
+        let old_energy = tersoff.energy(&system);
+
+        let new_position = system.particles().position[12] + Vector3D::new(0.3, -0.2, 0.1);
+        let cost = tersoff.move_molecule_cost(&system, 12, &[new_position]);
+
+        system.particles_mut().position[12] = new_position;
+        let new_energy = tersoff.energy(&system);
+        assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+    }
+}
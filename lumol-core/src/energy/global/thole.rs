@@ -0,0 +1,319 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use std::collections::BTreeMap;
+
+use crate::consts::FOUR_PI_EPSILON_0;
+use crate::PairRestriction;
+use crate::Configuration;
+use crate::{Matrix3, Vector3D};
+
+use super::{GlobalCache, GlobalPotential};
+
+/// Parameters of a Drude particle kind
+#[derive(Clone, Copy)]
+struct DrudeParameters {
+    /// Polarizability of the core-Drude pair, as a volume
+    polarizability: f64,
+    /// Dimensionless Thole screening factor
+    thole: f64,
+}
+
+/// A single core-Drude pair in a configuration
+struct Dipole {
+    core: usize,
+    drude: usize,
+    /// Charge of the Drude particle
+    charge: f64,
+    parameters: DrudeParameters,
+}
+
+/// Thole screening of the electrostatic interactions between Drude induced
+/// dipoles in the same molecule.
+///
+/// Each core-Drude pair is an induced dipole, made of the charge of the Drude
+/// particle `q_D`, and of the opposite charge `-q_D` located on the core
+/// particle. The interactions between the charges of two different dipoles
+/// in the same molecule are screened with the Thole function [Thole1981]:
+///
+/// ```text
+/// V(r) = q_a q_b / (4 π ε_0 r) [1 - (1 + s r / 2) exp(-s r)]
+/// s = (a_i + a_j) / (α_i α_j)^(1/6)
+/// ```
+///
+/// where `α_i` is the polarizability of the dipole `i` (as a volume), and
+/// `a_i` its dimensionless Thole factor. The coulombic interactions already
+/// computed by the coulombic solver for the same charges are removed, using
+/// the same restriction as the coulombic solver. This makes this potential a
+/// correction to use together with a coulombic solver.
+///
+/// Drude particles are identified by their name, and their core is the only
+/// particle they are bonded to.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{Thole, PairRestriction};
+/// let mut thole = Thole::new();
+/// // Use the same restriction as the coulombic solver
+/// thole.set_restriction(PairRestriction::Exclude13);
+/// // Drude particles named 'DO' with a polarizability of 1 A^3, and a Thole
+/// // factor of 1.3
+/// thole.add_drude("DO", 1.0, 1.3);
+/// ```
+///
+/// [Thole1981]: Thole, B. T. Chem. Phys. 59, 341 (1981).
+#[derive(Clone)]
+pub struct Thole {
+    /// Parameters for the Drude particles, indexed by name
+    drudes: BTreeMap<String, DrudeParameters>,
+    /// Restriction scheme of the coulombic solver
+    restriction: PairRestriction,
+}
+
+impl Thole {
+    /// Create a new Thole screening potential, without any Drude particle
+    pub fn new() -> Thole {
+        Thole {
+            drudes: BTreeMap::new(),
+            restriction: PairRestriction::None,
+        }
+    }
+
+    /// Set the pair restriction scheme to use to the given `restriction`.
+    /// This should be the same restriction as the coulombic solver.
+    pub fn set_restriction(&mut self, restriction: PairRestriction) {
+        self.restriction = restriction;
+    }
+
+    /// Add the Drude particles with the given `name`, with the given
+    /// `polarizability` (as a volume) and dimensionless `thole` factor.
+    pub fn add_drude(&mut self, name: &str, polarizability: f64, thole: f64) {
+        assert!(polarizability > 0.0, "the polarizability must be positive in Thole screening");
+        let parameters = DrudeParameters {
+            polarizability: polarizability,
+            thole: thole,
+        };
+        let _ = self.drudes.insert(String::from(name), parameters);
+    }
+
+    /// Get all the dipoles in the `configuration`, grouped by molecule.
+    /// Molecules without any dipole are not included.
+    fn dipoles(&self, configuration: &Configuration) -> Vec<Vec<Dipole>> {
+        let names = configuration.particles().name;
+        let charges = configuration.particles().charge;
+        let pairs = configuration.drude_pairs(|i| self.drudes.contains_key(&names[i]));
+
+        // The pairs are sorted by molecule
+        let mut molecules: Vec<Vec<Dipole>> = Vec::new();
+        let mut current_molecule = None;
+        for (core, drude) in pairs {
+            let molecule_id = configuration.molecule_id(core);
+            if current_molecule != Some(molecule_id) {
+                current_molecule = Some(molecule_id);
+                molecules.push(Vec::new());
+            }
+
+            let dipoles = molecules.last_mut().expect("there should be at least one molecule");
+            dipoles.push(Dipole {
+                core: core,
+                drude: drude,
+                charge: charges[drude],
+                parameters: self.drudes[&names[drude]],
+            });
+        }
+        return molecules;
+    }
+
+    /// Call `function` for all the pairs of charges in different dipoles of
+    /// the same molecule, with the particles indexes `a` and `b`, the
+    /// screening parameter `s`, the product of the charges, the scaling of
+    /// the coulombic interaction already computed by the solver, and the
+    /// vector between `b` and `a`.
+    fn for_all_pairs<F>(&self, configuration: &Configuration, mut function: F)
+    where
+        F: FnMut(usize, usize, f64, f64, f64, Vector3D),
+    {
+        for dipoles in self.dipoles(configuration) {
+            for (m, dipole_m) in dipoles.iter().enumerate() {
+                for dipole_n in dipoles.iter().skip(m + 1) {
+                    let alphas = dipole_m.parameters.polarizability * dipole_n.parameters.polarizability;
+                    let s = (dipole_m.parameters.thole + dipole_n.parameters.thole) / f64::powf(alphas, 1.0 / 6.0);
+
+                    let charges_m = [(dipole_m.core, -dipole_m.charge), (dipole_m.drude, dipole_m.charge)];
+                    let charges_n = [(dipole_n.core, -dipole_n.charge), (dipole_n.drude, dipole_n.charge)];
+                    for &(a, q_a) in &charges_m {
+                        for &(b, q_b) in &charges_n {
+                            let info = self.restriction.information(configuration.bond_path(a, b));
+                            let scaling = if info.excluded { 0.0 } else { info.scaling };
+                            let r_ab = configuration.nearest_image(a, b);
+                            function(a, b, s, q_a * q_b, scaling, r_ab);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Compute the energy of a pair of screened charges with product `qaqb`, at
+/// distance `r`, using the screening parameter `s`, and removing the `scaling`
+/// part of the unscreened interaction.
+#[inline]
+fn energy_pair(s: f64, qaqb: f64, scaling: f64, r: f64) -> f64 {
+    let screening = 1.0 - (1.0 + 0.5 * s * r) * f64::exp(-s * r);
+    return qaqb * (screening - scaling) / (FOUR_PI_EPSILON_0 * r);
+}
+
+/// Compute the force on the first particle of a pair of screened charges,
+/// separated by `r_ab`. The parameters are the same as for `energy_pair`.
+#[inline]
+fn force_pair(s: f64, qaqb: f64, scaling: f64, r_ab: Vector3D) -> Vector3D {
+    let r = r_ab.norm();
+    let exp = f64::exp(-s * r);
+    let screening = 1.0 - (1.0 + 0.5 * s * r) * exp;
+    let screening_derivative = 0.5 * s * (1.0 + s * r) * exp;
+    let derivative = qaqb * (screening_derivative / r - (screening - scaling) / (r * r)) / FOUR_PI_EPSILON_0;
+    return -derivative / r * r_ab;
+}
+
+impl GlobalCache for Thole {
+    fn move_molecule_cost(&self, _: &Configuration, _: usize, _: &[Vector3D]) -> f64 {
+        // Only intramolecular interactions are screened, and they do not
+        // change when moving a rigid molecule.
+        return 0.0;
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+impl GlobalPotential for Thole {
+    fn cutoff(&self) -> Option<f64> {
+        None
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        let mut energy = 0.0;
+        self.for_all_pairs(configuration, |_, _, s, qaqb, scaling, r_ab| {
+            energy += energy_pair(s, qaqb, scaling, r_ab.norm());
+        });
+        return energy;
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+        self.for_all_pairs(configuration, |a, b, s, qaqb, scaling, r_ab| {
+            let force = force_pair(s, qaqb, scaling, r_ab);
+            forces[a] += force;
+            forces[b] -= force;
+        });
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let mut virial = Matrix3::zero();
+        self.for_all_pairs(configuration, |_, _, s, qaqb, scaling, r_ab| {
+            let force = force_pair(s, qaqb, scaling, r_ab);
+            virial += force.tensorial(&r_ab);
+        });
+        return virial;
+    }
+
+    fn molecular_virial(&self, _: &Configuration) -> Matrix3 {
+        // All the interactions are intramolecular
+        return Matrix3::zero();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::System;
+    use crate::utils::system_from_xyz;
+
+    use approx::assert_relative_eq;
+
+    fn testing_system() -> System {
+        let mut system = system_from_xyz(
+            "4
+            cell: 20.0
+            C 0.0 0.0 0.0
+            D 0.2 0.1 0.0
+            C 1.5 0.0 0.0
+            D 1.6 0.2 0.1
+            ",
+        );
+        let _ = system.add_bond(0, 1);
+        let _ = system.add_bond(0, 2);
+        let _ = system.add_bond(2, 3);
+        system.particles_mut().charge[0] = 1.0;
+        system.particles_mut().charge[1] = -1.0;
+        system.particles_mut().charge[2] = 0.5;
+        system.particles_mut().charge[3] = -0.8;
+        return system;
+    }
+
+    fn thole() -> Thole {
+        let mut thole = Thole::new();
+        thole.set_restriction(PairRestriction::Exclude13);
+        thole.add_drude("D", 1.0, 1.3);
+        return thole;
+    }
+
+    #[test]
+    fn energy() {
+        let system = testing_system();
+        assert_relative_eq!(thole().energy(&system), -0.07918964383680985, max_relative = 1e-12);
+
+        // No screening between separated molecules
+        let mut system = system_from_xyz(
+            "4
+            cell: 20.0
+            C 0.0 0.0 0.0
+            D 0.2 0.1 0.0
+            C 1.5 0.0 0.0
+            D 1.6 0.2 0.1
+            ",
+        );
+        let _ = system.add_bond(0, 1);
+        let _ = system.add_bond(2, 3);
+        system.particles_mut().charge[1] = -1.0;
+        system.particles_mut().charge[3] = -0.8;
+        assert_eq!(thole().energy(&system), 0.0);
+    }
+
+    #[test]
+    fn forces() {
+        let mut system = testing_system();
+        let thole = thole();
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        thole.forces(&system, &mut forces);
+        let total = forces.iter().fold(Vector3D::zero(), |sum, &force| sum + force);
+        assert!(total.norm() < 1e-12);
+
+        let eps = 1e-9;
+        for i in 0..system.size() {
+            for k in 0..3 {
+                let e = thole.energy(&system);
+                system.particles_mut().position[i][k] += eps;
+                let e1 = thole.energy(&system);
+                system.particles_mut().position[i][k] -= eps;
+                assert_relative_eq!(-(e1 - e) / eps, forces[i][k], epsilon = 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn virial() {
+        let system = testing_system();
+        let thole = thole();
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        thole.forces(&system, &mut forces);
+        let expected = (0..system.size()).map(|i| system.particles().position[i] * forces[i]).sum::<f64>();
+        assert_relative_eq!(thole.atomic_virial(&system).trace(), expected, max_relative = 1e-9);
+
+        assert_eq!(thole.molecular_virial(&system), Matrix3::zero());
+    }
+}
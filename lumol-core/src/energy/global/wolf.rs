@@ -8,10 +8,12 @@ use crate::math::erfc;
 use crate::consts::FOUR_PI_EPSILON_0;
 use crate::PairRestriction;
 use crate::utils::ThreadLocalVec;
+use crate::sys::NeighborList;
 use crate::Configuration;
 use crate::{Matrix3, Vector3D};
 
 use super::{CoulombicPotential, GlobalCache, GlobalPotential};
+use super::{Polarization, DampedDipoles};
 
 /// Wolf summation for coulombic interactions.
 ///
@@ -48,51 +50,176 @@ use super::{CoulombicPotential, GlobalCache, GlobalPotential};
 /// assert_eq!(system.potential_energy(), -0.0729290269539354);
 /// ```
 ///
+/// Induced point dipoles can be added with `Wolf::set_polarization`. The
+/// interactions involving the dipoles use the damped `erfc(α r) / r` kernel,
+/// truncated at the cutoff.
+///
 /// [Wolf1999]: Wolf, D. et al. J. Chem. Phys. 110, 8254 (1999).
 #[derive(Clone)]
 pub struct Wolf {
-    /// Splitting parameter
-    alpha: f64,
-    /// Cutoff radius in real space
-    cutoff: f64,
-    /// Energy constant for wolf computation
-    energy_constant: f64,
-    /// Force constant for wolf computation
-    force_constant: f64,
-    /// Restriction scheme
-    restriction: PairRestriction,
+    /// Damped coulombic interactions between the charges
+    coulomb: DampedCoulomb,
+    /// Induced dipoles polarization, if any
+    polarization: Option<Polarization>,
 }
 
 impl Wolf {
     /// Create a new Wolf summation, using a real-space cutoff of `cutoff`.
     pub fn new(cutoff: f64) -> Wolf {
         assert!(cutoff > 0.0, "Got a negative cutoff in Wolf summation");
-        let alpha = PI / cutoff;
+        Wolf {
+            coulomb: DampedCoulomb::new(cutoff, PI / cutoff, false),
+            polarization: None,
+        }
+    }
+
+    /// Add induced point dipoles `polarization` to this Wolf summation.
+    pub fn set_polarization(&mut self, polarization: Polarization) {
+        self.polarization = Some(polarization);
+    }
+
+    /// Get the real space dipolar interactions corresponding to this Wolf
+    /// summation, using the given `neighbors` list
+    fn damped_dipoles<'a>(&self, neighbors: &'a NeighborList) -> DampedDipoles<'a> {
+        DampedDipoles {
+            alpha: self.coulomb.alpha,
+            cutoff: self.coulomb.cutoff,
+            restriction: self.coulomb.restriction,
+            remove_excluded: false,
+            slab: false,
+            neighbors: neighbors,
+        }
+    }
+}
+
+impl GlobalCache for Wolf {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let mut cost = self.coulomb.move_molecule_cost(configuration, molecule_id, new_positions);
+        if let Some(ref polarization) = self.polarization {
+            let dipoles = self.damped_dipoles(polarization.neighbor_list());
+            let old = polarization.energy(&dipoles, configuration);
+            let new = polarization.moved_energy(&dipoles, configuration, molecule_id, new_positions);
+            cost += new - old;
+        }
+        return cost;
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+impl GlobalPotential for Wolf {
+    fn cutoff(&self) -> Option<f64> {
+        Some(self.coulomb.cutoff)
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        let mut energy = self.coulomb.energy(configuration);
+        if let Some(ref polarization) = self.polarization {
+            let dipoles = self.damped_dipoles(polarization.neighbor_list());
+            energy += polarization.energy(&dipoles, configuration);
+        }
+        return energy;
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        self.coulomb.forces(configuration, forces);
+        if let Some(ref polarization) = self.polarization {
+            let dipoles = self.damped_dipoles(polarization.neighbor_list());
+            polarization.forces(&dipoles, configuration, forces);
+        }
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let mut virial = self.coulomb.atomic_virial(configuration);
+        if let Some(ref polarization) = self.polarization {
+            let dipoles = self.damped_dipoles(polarization.neighbor_list());
+            virial += polarization.atomic_virial(&dipoles, configuration);
+        }
+        return virial;
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let mut virial = self.coulomb.molecular_virial(configuration);
+        if let Some(ref polarization) = self.polarization {
+            let dipoles = self.damped_dipoles(polarization.neighbor_list());
+            virial += polarization.molecular_virial(&dipoles, configuration);
+        }
+        return virial;
+    }
+}
+
+impl CoulombicPotential for Wolf {
+    fn set_restriction(&mut self, restriction: PairRestriction) {
+        self.coulomb.restriction = restriction;
+        if let Some(ref polarization) = self.polarization {
+            polarization.clear_cache();
+        }
+    }
+}
+
+/// Damped coulombic interactions between charges, truncated at a cutoff.
+///
+/// This is the pairwise kernel shared by the Wolf summation and the damped
+/// shifted force method. The pair energy is `q_i q_j / (4 π ε_0) [erfc(α r) /
+/// r - erfc(α r_c) / r_c]`, with an additional `(r - r_c) f_c` term when using
+/// shifted forces, where `f_c` is the force at the cutoff.
+#[derive(Clone)]
+pub(super) struct DampedCoulomb {
+    /// Damping parameter
+    pub alpha: f64,
+    /// Cutoff radius
+    pub cutoff: f64,
+    /// Restriction scheme
+    pub restriction: PairRestriction,
+    /// Should the energy be shifted to make the force go to zero at the
+    /// cutoff?
+    shifted_force: bool,
+    /// Energy shift at the cutoff: `erfc(α r_c) / r_c`
+    energy_constant: f64,
+    /// Force shift at the cutoff: `erfc(α r_c) / r_c² + 2 α / √π exp(-α² r_c²) / r_c`
+    force_constant: f64,
+}
 
+impl DampedCoulomb {
+    /// Create new damped coulombic interactions with the given `cutoff` and
+    /// damping parameter `alpha`, using shifted forces if `shifted_force` is
+    /// `true`.
+    pub fn new(cutoff: f64, alpha: f64, shifted_force: bool) -> DampedCoulomb {
         let alpha_cutoff = alpha * cutoff;
         let alpha_cutoff_2 = alpha_cutoff * alpha_cutoff;
 
         let energy_constant = erfc(alpha_cutoff) / cutoff;
         let force_constant = erfc(alpha_cutoff) / (cutoff * cutoff) + FRAC_2_SQRT_PI * alpha * f64::exp(-alpha_cutoff_2) / cutoff;
-        Wolf {
+        DampedCoulomb {
             alpha: alpha,
             cutoff: cutoff,
+            restriction: PairRestriction::None,
+            shifted_force: shifted_force,
             energy_constant: energy_constant,
             force_constant: force_constant,
-            restriction: PairRestriction::None,
         }
     }
 
     /// Compute the energy for the pair of particles with charge `qi` and `qj`,
-    /// at the distance of `rij`. The `scaling` parameter comes from the
-    /// restriction associated with this potential.
+    /// at the distance of `rij`.
     #[inline]
     fn energy_pair(&self, qiqj: f64, rij: f64) -> f64 {
         if rij > self.cutoff {
-            0.0
-        } else {
-            qiqj * (erfc(self.alpha * rij) / rij - self.energy_constant) / FOUR_PI_EPSILON_0
+            return 0.0;
+        }
+
+        let mut energy = erfc(self.alpha * rij) / rij - self.energy_constant;
+        if self.shifted_force {
+            energy += (rij - self.cutoff) * self.force_constant;
         }
+        return qiqj * energy / FOUR_PI_EPSILON_0;
     }
 
     /// Compute the energy for self interaction of a particle with charge `qi`
@@ -115,10 +242,10 @@ impl Wolf {
             return qiqj * (factor - self.force_constant) / (rij * FOUR_PI_EPSILON_0);
         }
     }
-}
 
-impl GlobalCache for Wolf {
-    fn move_molecule_cost(
+    /// Get the energy change when moving the molecule at `molecule_id` in
+    /// the `configuration` to `new_positions`.
+    pub fn move_molecule_cost(
         &self,
         configuration: &Configuration,
         molecule_id: usize,
@@ -164,17 +291,8 @@ impl GlobalCache for Wolf {
         return new_energy - old_energy;
     }
 
-    fn update(&self) {
-        // Nothing to do
-    }
-}
-
-impl GlobalPotential for Wolf {
-    fn cutoff(&self) -> Option<f64> {
-        Some(self.cutoff)
-    }
-
-    fn energy(&self, configuration: &Configuration) -> f64 {
+    /// Compute the energy of the charges in the `configuration`
+    pub fn energy(&self, configuration: &Configuration) -> f64 {
         let natoms = configuration.size();
         let charges = configuration.particles().charge;
 
@@ -206,7 +324,9 @@ impl GlobalPotential for Wolf {
         return energies.sum();
     }
 
-    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+    /// Compute the forces acting on the charges in the `configuration`, and
+    /// add them to `forces`
+    pub fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
         assert_eq!(forces.len(), configuration.size());
 
         let natoms = configuration.size();
@@ -245,10 +365,11 @@ impl GlobalPotential for Wolf {
 
         // At this point all the forces are computed, but the results are
         // scattered across all thread local Vecs, here we gather them.
-        thread_local_forces.sum_into(forces)
+        thread_local_forces.sum_into(forces);
     }
 
-    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+    /// Compute the atomic virial of the charges in the `configuration`
+    pub fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
         let natoms = configuration.size();
         let charges = configuration.particles().charge;
 
@@ -282,7 +403,8 @@ impl GlobalPotential for Wolf {
         return virials.sum();
     }
 
-    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+    /// Compute the molecular virial of the charges in the `configuration`
+    pub fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
         let charges = configuration.particles().charge;
         let virials = configuration.molecules().enumerate().par_bridge().map(|(i, molecule_i)| {
             let mut local_virial = Matrix3::zero();
@@ -315,18 +437,12 @@ impl GlobalPotential for Wolf {
                         let force = info.scaling * self.force_pair(q_a * q_b, r_ab.norm()) * r_ab;
                         let w_ab = force.tensorial(&r_ab);
                         local_virial += w_ab * (r_ab * r_ij) / r_ab.norm2();
-                     }
-                 }
-             }
-             return local_virial;
-         });
-         return virials.sum();
-     }
-}
-
-impl CoulombicPotential for Wolf {
-    fn set_restriction(&mut self, restriction: PairRestriction) {
-        self.restriction = restriction;
+                    }
+                }
+            }
+            return local_virial;
+        });
+        return virials.sum();
     }
 }
 
@@ -0,0 +1,500 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use std::f64::consts::PI;
+
+use rayon::prelude::*;
+
+use crate::consts::{FOUR_PI_EPSILON_0, K_BOLTZMANN};
+use crate::PairRestriction;
+use crate::utils::ThreadLocalVec;
+use crate::Configuration;
+use crate::{Matrix3, Vector3D};
+
+use super::{CoulombicPotential, GlobalCache, GlobalPotential};
+
+/// Yukawa (screened coulomb) potential for coulombic interactions.
+///
+/// This potential describes the electrostatic interactions between charges in
+/// an implicit electrolyte, as given by the Debye-Hückel theory. The pair
+/// energy is
+///
+/// ```text
+/// V(r) = q_i q_j / (4 π ε_0 ε_r) [exp(-κ r) / r - exp(-κ r_c) / r_c]
+/// ```
+///
+/// where `κ` is the inverse of the Debye screening length, and `ε_r` the
+/// relative permittivity of the solvent. The energy is shifted to go to zero
+/// at the cutoff `r_c`.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::sys::{Particle, Molecule, UnitCell, System};
+/// # use lumol_core::energy::Yukawa;
+/// # use lumol_core::types::Vector3D;
+/// // Yukawa potential with a cutoff of 30 A, a screening length of 10 A, in
+/// // water
+/// let yukawa = Yukawa::new(30.0, 0.1, 78.5);
+///
+/// // Setup a system containing a NaCl pair
+/// let mut system = System::with_cell(UnitCell::cubic(80.0));
+///
+/// let mut na = Particle::new("Na");
+/// na.charge = 1.0;
+/// na.position = Vector3D::new(0.0, 0.0, 0.0);
+///
+/// let mut cl = Particle::new("Cl");
+/// cl.charge = -1.0;
+/// cl.position = Vector3D::new(2.0, 0.0, 0.0);
+///
+/// system.add_molecule(Molecule::new(na));
+/// system.add_molecule(Molecule::new(cl));
+///
+/// // Use the Yukawa potential for electrostatic interactions
+/// system.set_coulomb_potential(Box::new(yukawa));
+///
+/// println!("energy is {}", system.potential_energy());
+/// ```
+#[derive(Clone)]
+pub struct Yukawa {
+    /// Inverse screening length
+    kappa: f64,
+    /// Relative permittivity of the solvent
+    permittivity: f64,
+    /// Cutoff radius
+    cutoff: f64,
+    /// Energy shift at the cutoff: `exp(-κ r_c) / r_c`
+    energy_shift: f64,
+    /// Restriction scheme
+    restriction: PairRestriction,
+}
+
+impl Yukawa {
+    /// Create a new Yukawa potential, using a cutoff of `cutoff`, the inverse
+    /// screening length `kappa` and the relative `permittivity` of the
+    /// solvent.
+    pub fn new(cutoff: f64, kappa: f64, permittivity: f64) -> Yukawa {
+        assert!(cutoff > 0.0, "Got a negative cutoff in Yukawa potential");
+        assert!(kappa >= 0.0, "Got a negative kappa in Yukawa potential");
+        assert!(permittivity > 0.0, "Got a negative permittivity in Yukawa potential");
+
+        Yukawa {
+            kappa: kappa,
+            permittivity: permittivity,
+            cutoff: cutoff,
+            energy_shift: f64::exp(-kappa * cutoff) / cutoff,
+            restriction: PairRestriction::None,
+        }
+    }
+
+    /// Get the inverse Debye screening length of an electrolyte with the
+    /// given `ionic_strength` (as a number density of ions), at the given
+    /// `temperature` and in a solvent with the relative `permittivity`:
+    ///
+    /// ```text
+    /// κ² = 2 I e² / (ε_0 ε_r k_B T)
+    /// ```
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lumol_core::energy::Yukawa;
+    /// # use lumol_core::units;
+    /// // Sodium chloride at 0.1 mol/L in water at room temperature
+    /// let ionic_strength = units::from(0.1, "mol/L").unwrap();
+    /// let kappa = Yukawa::debye_kappa(ionic_strength, 298.15, 78.5);
+    ///
+    /// // The Debye length is close to 10 A
+    /// assert!(f64::abs(1.0 / kappa - 9.6) < 0.1);
+    /// ```
+    pub fn debye_kappa(ionic_strength: f64, temperature: f64, permittivity: f64) -> f64 {
+        assert!(ionic_strength >= 0.0, "Got a negative ionic strength in Debye screening length");
+        assert!(temperature > 0.0, "Got a negative temperature in Debye screening length");
+        assert!(permittivity > 0.0, "Got a negative permittivity in Debye screening length");
+        // e² / ε_0 = 4 π / (4 π ε_0) in internal units
+        let kappa2 = 8.0 * PI * ionic_strength / (FOUR_PI_EPSILON_0 * permittivity * K_BOLTZMANN * temperature);
+        return f64::sqrt(kappa2);
+    }
+
+    /// Get the inverse screening length
+    pub fn kappa(&self) -> f64 {
+        self.kappa
+    }
+
+    /// Get the relative permittivity of the solvent
+    pub fn permittivity(&self) -> f64 {
+        self.permittivity
+    }
+
+    /// Compute the energy for the pair of particles with charge `qi` and `qj`,
+    /// at the distance of `rij`.
+    #[inline]
+    fn energy_pair(&self, qiqj: f64, rij: f64) -> f64 {
+        if rij > self.cutoff {
+            0.0
+        } else {
+            let screened = f64::exp(-self.kappa * rij) / rij - self.energy_shift;
+            qiqj * screened / (FOUR_PI_EPSILON_0 * self.permittivity)
+        }
+    }
+
+    /// Compute the force over the distance for the pair of particles with
+    /// charge `qi` and `qj`, at the distance `rij`.
+    #[inline]
+    fn force_pair(&self, qiqj: f64, rij: f64) -> f64 {
+        if rij > self.cutoff {
+            0.0
+        } else {
+            let factor = f64::exp(-self.kappa * rij) * (1.0 + self.kappa * rij) / (rij * rij * rij);
+            return qiqj * factor / (FOUR_PI_EPSILON_0 * self.permittivity);
+        }
+    }
+}
+
+impl GlobalCache for Yukawa {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let mut old_energy = 0.0;
+        let mut new_energy = 0.0;
+
+        let charges = configuration.particles().charge;
+        let positions = configuration.particles().position;
+
+        // Iterate over all interactions between a particle in the moved
+        // molecule and a particle in another molecule
+        let molecule = configuration.molecule(molecule_id);
+        for (i, part_i) in molecule.indexes().enumerate() {
+            let qi = charges[part_i];
+            if qi == 0.0 {
+                continue;
+            }
+
+            for (_, other_molecule) in configuration.molecules().enumerate().filter(|(id, _)| molecule_id != *id) {
+                for part_j in other_molecule.indexes() {
+                    let qj = charges[part_j];
+                    if qj == 0.0 {
+                        continue;
+                    }
+
+                    let path = configuration.bond_path(part_i, part_j);
+                    let info = self.restriction.information(path);
+                    if info.excluded {
+                        continue;
+                    }
+
+                    let old_r = configuration.distance(part_i, part_j);
+                    let new_r = configuration.cell.distance(&new_positions[i], &positions[part_j]);
+
+                    old_energy += info.scaling * self.energy_pair(qi * qj, old_r);
+                    new_energy += info.scaling * self.energy_pair(qi * qj, new_r);
+                }
+            }
+        }
+
+        return new_energy - old_energy;
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+impl GlobalPotential for Yukawa {
+    fn cutoff(&self) -> Option<f64> {
+        Some(self.cutoff)
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        let natoms = configuration.size();
+        let charges = configuration.particles().charge;
+
+        let energies = (0..natoms).into_par_iter().map(|i| {
+            let mut energy = 0.0;
+            let qi = charges[i];
+            if qi == 0.0 {
+                return 0.0;
+            }
+
+            for j in i + 1..natoms {
+                let qj = charges[j];
+                if qj == 0.0 {
+                    continue;
+                }
+
+                let path = configuration.bond_path(i, j);
+                let info = self.restriction.information(path);
+                if info.excluded {
+                    continue;
+                }
+
+                let rij = configuration.distance(i, j);
+                energy += info.scaling * self.energy_pair(qi * qj, rij);
+            }
+
+            return energy;
+        });
+        return energies.sum();
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+
+        let natoms = configuration.size();
+        let charges = configuration.particles().charge;
+        // To avoid race conditions, each thread needs its own local forces Vec
+        let thread_local_forces = ThreadLocalVec::with_size(natoms);
+
+        (0..natoms).into_par_iter().for_each(|i| {
+            // Get the thread local forces Vec
+            let mut forces = thread_local_forces.borrow_mut();
+
+            let mut force_i = Vector3D::zero();
+            let qi = charges[i];
+            if qi == 0.0 {
+                return;
+            }
+            for j in i + 1..natoms {
+                let qj = charges[j];
+                if qj == 0.0 {
+                    continue;
+                }
+
+                let path = configuration.bond_path(i, j);
+                let info = self.restriction.information(path);
+                if info.excluded {
+                    continue;
+                }
+
+                let rij = configuration.nearest_image(i, j);
+                let force = info.scaling * self.force_pair(qi * qj, rij.norm()) * rij;
+                force_i += force;
+                forces[j] -= force;
+            }
+            forces[i] += force_i;
+        });
+
+        // At this point all the forces are computed, but the results are
+        // scattered across all thread local Vecs, here we gather them.
+        thread_local_forces.sum_into(forces)
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let natoms = configuration.size();
+        let charges = configuration.particles().charge;
+
+        let virials = (0..natoms).into_par_iter().map(|i| {
+            let qi = charges[i];
+            if qi == 0.0 {
+                return Matrix3::zero();
+            }
+            let mut local_virial = Matrix3::zero();
+
+            for j in i + 1..natoms {
+                let qj = charges[j];
+                if qj == 0.0 {
+                    continue;
+                }
+
+                let path = configuration.bond_path(i, j);
+                let info = self.restriction.information(path);
+                if info.excluded {
+                    continue;
+                }
+
+                let rij = configuration.nearest_image(i, j);
+                let force = info.scaling * self.force_pair(qi * qj, rij.norm()) * rij;
+                local_virial += force.tensorial(&rij);
+            }
+
+            local_virial
+        });
+
+        return virials.sum();
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let charges = configuration.particles().charge;
+        let virials = configuration.molecules().enumerate().par_bridge().map(|(i, molecule_i)| {
+            let mut local_virial = Matrix3::zero();
+            let ri = molecule_i.center_of_mass();
+
+            for molecule_j in configuration.molecules().skip(i + 1) {
+                let rj = molecule_j.center_of_mass();
+                let mut r_ij = ri - rj;
+                configuration.cell.vector_image(&mut r_ij);
+
+                for part_a in molecule_i.indexes() {
+                    let q_a = charges[part_a];
+                    if q_a == 0.0 {
+                        continue;
+                    }
+
+                    for part_b in molecule_j.indexes() {
+                        let q_b = charges[part_b];
+                        if q_b == 0.0 {
+                            continue;
+                        }
+
+                        let path = configuration.bond_path(part_a, part_b);
+                        let info = self.restriction.information(path);
+                        if info.excluded {
+                            continue;
+                        }
+
+                        let r_ab = configuration.nearest_image(part_a, part_b);
+                        let force = info.scaling * self.force_pair(q_a * q_b, r_ab.norm()) * r_ab;
+                        let w_ab = force.tensorial(&r_ab);
+                        local_virial += w_ab * (r_ab * r_ij) / r_ab.norm2();
+                    }
+                }
+            }
+            return local_virial;
+        });
+        return virials.sum();
+    }
+}
+
+impl CoulombicPotential for Yukawa {
+    fn set_restriction(&mut self, restriction: PairRestriction) {
+        self.restriction = restriction;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CoulombicPotential, PairRestriction};
+    use crate::System;
+    use crate::utils::system_from_xyz;
+    use crate::units;
+
+    use approx::{assert_ulps_eq, assert_relative_eq};
+
+    fn testing_system() -> System {
+        let mut system = system_from_xyz(
+            "2
+            cell: 30.0
+            Cl 0.0 0.0 0.0
+            Na 1.5 0.0 0.0
+            ",
+        );
+        system.particles_mut().charge[0] = -1.0;
+        system.particles_mut().charge[1] = 1.0;
+        return system;
+    }
+
+    #[test]
+    fn energy() {
+        let mut system = testing_system();
+        let yukawa = Yukawa::new(12.0, 0.1, 78.5);
+
+        let expected = -(f64::exp(-0.15) / 1.5 - f64::exp(-1.2) / 12.0) / (FOUR_PI_EPSILON_0 * 78.5);
+        assert_relative_eq!(yukawa.energy(&system), expected, max_relative = 1e-12);
+
+        // Without screening, this is a shifted coulomb potential
+        let coulomb = Yukawa::new(12.0, 0.0, 1.0);
+        let expected = -(1.0 / 1.5 - 1.0 / 12.0) / FOUR_PI_EPSILON_0;
+        assert_relative_eq!(coulomb.energy(&system), expected, max_relative = 1e-12);
+
+        // The energy goes continuously to zero at the cutoff
+        system.particles_mut().position[1][0] = 12.0 - 1e-9;
+        assert!(yukawa.energy(&system).abs() < 1e-9);
+        system.particles_mut().position[1][0] = 12.5;
+        assert_eq!(yukawa.energy(&system), 0.0);
+    }
+
+    #[test]
+    fn debye_kappa() {
+        let ionic_strength = units::from(0.1, "mol/L").unwrap();
+        let kappa = Yukawa::debye_kappa(ionic_strength, 298.15, 78.5);
+        assert_relative_eq!(1.0 / kappa, 9.6, epsilon = 0.05);
+
+        // κ scales with the square root of the ionic strength
+        let kappa_4 = Yukawa::debye_kappa(4.0 * ionic_strength, 298.15, 78.5);
+        assert_relative_eq!(kappa_4, 2.0 * kappa, max_relative = 1e-12);
+
+        assert_eq!(Yukawa::debye_kappa(0.0, 298.15, 78.5), 0.0);
+    }
+
+    #[test]
+    fn forces() {
+        let mut system = testing_system();
+        system.particles_mut().position[1] = Vector3D::new(1.5, 0.4, -0.3);
+        let yukawa = Yukawa::new(12.0, 0.1, 78.5);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        yukawa.forces(&system, &mut forces);
+        assert_ulps_eq!((forces[0] + forces[1]).norm(), 0.0);
+
+        let eps = 1e-6;
+        for k in 0..3 {
+            system.particles_mut().position[0][k] += eps;
+            let e1 = yukawa.energy(&system);
+            system.particles_mut().position[0][k] -= 2.0 * eps;
+            let e2 = yukawa.energy(&system);
+            system.particles_mut().position[0][k] += eps;
+            assert_relative_eq!(-(e1 - e2) / (2.0 * eps), forces[0][k], epsilon = 1e-8);
+        }
+    }
+
+    #[test]
+    fn atomic_virial() {
+        let system = testing_system();
+        let yukawa = Yukawa::new(12.0, 0.1, 78.5);
+
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        yukawa.forces(&system, &mut forces);
+        let force = forces[0][0];
+        let expected = Matrix3::new([[-force * 1.5, 0.0, 0.0], [0.0; 3], [0.0; 3]]);
+
+        assert_ulps_eq!(yukawa.atomic_virial(&system), expected);
+    }
+
+    #[test]
+    #[allow(clippy::unreadable_literal)]
+    fn move_molecule() {
+        let mut system = system_from_xyz(
+            "6
+            cell: 20.0
+            O  0.0  0.0  0.0
+            H -0.7 -0.7  0.3
+            H  0.3 -0.3 -0.8
+            O  2.0  2.0  0.0
+            H  1.3  1.3  0.3
+            H  2.3  1.7 -0.8
+            ",
+        );
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(0, 2).is_empty());
+        assert!(system.add_bond(3, 4).is_empty());
+        assert!(system.add_bond(3, 5).is_empty());
+        for particle in system.particles_mut() {
+            if particle.name == "O" {
+                *particle.charge = -0.8476;
+            } else if particle.name == "H" {
+                *particle.charge = 0.4238;
+            }
+        }
+
+        let mut yukawa = Yukawa::new(8.0, 0.1, 78.5);
+        yukawa.set_restriction(PairRestriction::InterMolecular);
+        let old_energy = yukawa.energy(&system);
+
+        let new_positions = &[
+            Vector3D::new(4.0, 0.0, -2.0),
+            Vector3D::new(3.010010191494968, 0.19045656166589708, -2.1166435218719863),
+            Vector3D::new(4.0761078062722484, -0.8995901989882638, -2.0703212322750546),
+        ];
+        let cost = yukawa.move_molecule_cost(&system, 0, new_positions);
+
+        system.particles_mut().position[0] = new_positions[0];
+        system.particles_mut().position[1] = new_positions[1];
+        system.particles_mut().position[2] = new_positions[2];
+        let new_energy = yukawa.energy(&system);
+        assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-12);
+    }
+}
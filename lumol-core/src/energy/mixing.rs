@@ -0,0 +1,122 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use crate::LennardJones;
+
+/// Mixing rules to compute Lennard-Jones parameters for a pair of different
+/// particles from the parameters of each particle.
+///
+/// # Examples
+///
+/// ```
+/// use lumol_core::energy::{LennardJones, MixingRule};
+///
+/// let a = LennardJones{sigma: 3.0, epsilon: 1.0};
+/// let b = LennardJones{sigma: 4.0, epsilon: 4.0};
+///
+/// let mixed = MixingRule::LorentzBerthelot.mix(&a, &b);
+/// assert_eq!(mixed.sigma, 3.5);
+/// assert_eq!(mixed.epsilon, 2.0);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MixingRule {
+    /// Lorentz-Berthelot mixing rule, using the arithmetic mean of `sigma` and
+    /// the geometric mean of `epsilon`:
+    ///
+    /// $$ \sigma_{ij} = \frac{\sigma_i + \sigma_j}{2} \qquad
+    ///    \epsilon_{ij} = \sqrt{\epsilon_i \epsilon_j} $$
+    LorentzBerthelot,
+    /// Geometric mixing rule, using the geometric mean of both `sigma` and
+    /// `epsilon`:
+    ///
+    /// $$ \sigma_{ij} = \sqrt{\sigma_i \sigma_j} \qquad
+    ///    \epsilon_{ij} = \sqrt{\epsilon_i \epsilon_j} $$
+    Geometric,
+    /// Waldman-Hagler mixing rule, also known as the sixth-power mixing
+    /// rule. It was designed to better reproduce the interactions between
+    /// rare gases:
+    ///
+    /// $$ \sigma_{ij} = \left(\frac{\sigma_i^6 + \sigma_j^6}{2}\right)^{1/6}
+    ///    \qquad \epsilon_{ij} = 2 \sqrt{\epsilon_i \epsilon_j}
+    ///    \frac{\sigma_i^3 \sigma_j^3}{\sigma_i^6 + \sigma_j^6} $$
+    WaldmanHagler,
+}
+
+impl MixingRule {
+    /// Get the Lennard-Jones parameters for the interaction between a
+    /// particle with parameters `i` and a particle with parameters `j`.
+    pub fn mix(self, i: &LennardJones, j: &LennardJones) -> LennardJones {
+        let epsilon = f64::sqrt(i.epsilon * j.epsilon);
+        match self {
+            MixingRule::LorentzBerthelot => LennardJones {
+                sigma: 0.5 * (i.sigma + j.sigma),
+                epsilon: epsilon,
+            },
+            MixingRule::Geometric => LennardJones {
+                sigma: f64::sqrt(i.sigma * j.sigma),
+                epsilon: epsilon,
+            },
+            MixingRule::WaldmanHagler => {
+                let sigma_i_3 = f64::powi(i.sigma, 3);
+                let sigma_j_3 = f64::powi(j.sigma, 3);
+                let sum = sigma_i_3 * sigma_i_3 + sigma_j_3 * sigma_j_3;
+                LennardJones {
+                    sigma: f64::powf(0.5 * sum, 1.0 / 6.0),
+                    epsilon: 2.0 * epsilon * sigma_i_3 * sigma_j_3 / sum,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_ulps_eq;
+
+    #[test]
+    fn same_particles() {
+        let lj = LennardJones {
+            sigma: 3.4,
+            epsilon: 0.8,
+        };
+
+        for rule in &[MixingRule::LorentzBerthelot, MixingRule::Geometric, MixingRule::WaldmanHagler] {
+            let mixed = rule.mix(&lj, &lj);
+            assert_ulps_eq!(mixed.sigma, lj.sigma);
+            assert_ulps_eq!(mixed.epsilon, lj.epsilon);
+        }
+    }
+
+    #[test]
+    fn mixing() {
+        let a = LennardJones {
+            sigma: 2.0,
+            epsilon: 1.0,
+        };
+        let b = LennardJones {
+            sigma: 4.0,
+            epsilon: 9.0,
+        };
+
+        let mixed = MixingRule::LorentzBerthelot.mix(&a, &b);
+        assert_ulps_eq!(mixed.sigma, 3.0);
+        assert_ulps_eq!(mixed.epsilon, 3.0);
+
+        let mixed = MixingRule::Geometric.mix(&a, &b);
+        assert_ulps_eq!(mixed.sigma, f64::sqrt(8.0));
+        assert_ulps_eq!(mixed.epsilon, 3.0);
+
+        let mixed = MixingRule::WaldmanHagler.mix(&a, &b);
+        assert_ulps_eq!(mixed.sigma, f64::powf(2080.0, 1.0 / 6.0));
+        assert_ulps_eq!(mixed.epsilon, 6.0 * 512.0 / 4160.0);
+
+        // Mixing rules are symmetric
+        for rule in &[MixingRule::LorentzBerthelot, MixingRule::Geometric, MixingRule::WaldmanHagler] {
+            let ab = rule.mix(&a, &b);
+            let ba = rule.mix(&b, &a);
+            assert_ulps_eq!(ab.sigma, ba.sigma);
+            assert_ulps_eq!(ab.epsilon, ba.epsilon);
+        }
+    }
+}
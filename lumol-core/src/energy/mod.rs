@@ -18,8 +18,11 @@
 //!
 //! - [`PairPotential`][PairPotential] for non-bonded two body interactions;
 //! - [`BondPotential`][BondPotential] for covalent bonds interactions;
-//! - [`AnglePotential`][AnglePotential] for covalent angles interactions;
+//! - [`AnglePotential`][AnglePotential] for covalent angles interactions,
+//!   possibly depending on the bonds lengths and the 1-3 distance;
 //! - [`DihedralPotential`][DihedralPotential] for covalent dihedral angles
+//!   interactions;
+//! - [`ImproperPotential`][ImproperPotential] for improper dihedral angles
 //!   interactions.
 //!
 //! ```
@@ -57,6 +60,7 @@
 //! [BondPotential]: trait.BondPotential.html
 //! [AnglePotential]: trait.AnglePotential.html
 //! [DihedralPotential]: trait.DihedralPotential.html
+//! [ImproperPotential]: trait.ImproperPotential.html
 //! [GlobalPotential]: trait.GlobalPotential.html
 //! [CoulombicPotential]: trait.CoulombicPotential.html
 use crate::{Matrix3, Vector3D};
@@ -194,26 +198,96 @@ pub trait BondPotential: Potential + BoxCloneBond {
 }
 impl_box_clone!(BondPotential, BoxCloneBond, box_clone_bond);
 
-/// Marker trait for potentials that can be used for molecular angles.
+/// Geometry of an angle formed by the particles `i`, `j` and `k`, where `j` is
+/// the central particle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AngleGeometry {
+    /// Value of the angle
+    pub theta: f64,
+    /// Distance between the particles `i` and `j`
+    pub r_ij: f64,
+    /// Distance between the particles `j` and `k`
+    pub r_jk: f64,
+    /// Distance between the particles `i` and `k`, *i.e.* the 1-3 distance
+    pub r_ik: f64,
+}
+
+impl AngleGeometry {
+    /// Get the geometry of the same angle, with the particles `i` and `k`
+    /// exchanged.
+    pub fn reversed(&self) -> AngleGeometry {
+        AngleGeometry {
+            theta: self.theta,
+            r_ij: self.r_jk,
+            r_jk: self.r_ij,
+            r_ik: self.r_ik,
+        }
+    }
+}
+
+/// Generalized forces acting on the coordinates of an angle, *i.e.* the
+/// opposite of the derivatives of the energy with respect to each of the
+/// coordinates in [`AngleGeometry`](struct.AngleGeometry.html).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AngleForces {
+    /// Opposite of the derivative of the energy with respect to the angle
+    pub theta: f64,
+    /// Opposite of the derivative of the energy with respect to the distance
+    /// between `i` and `j`
+    pub r_ij: f64,
+    /// Opposite of the derivative of the energy with respect to the distance
+    /// between `j` and `k`
+    pub r_jk: f64,
+    /// Opposite of the derivative of the energy with respect to the distance
+    /// between `i` and `k`
+    pub r_ik: f64,
+}
+
+impl AngleForces {
+    /// Get the forces acting on the same angle, with the particles `i` and
+    /// `k` exchanged.
+    pub fn reversed(&self) -> AngleForces {
+        AngleForces {
+            theta: self.theta,
+            r_ij: self.r_jk,
+            r_jk: self.r_ij,
+            r_ik: self.r_ik,
+        }
+    }
+}
+
+/// Trait for potentials that can be used for molecular angles.
+///
+/// Angles potentials can depend on the value of the angle, on the lengths of
+/// the two bonds, and on the 1-3 distance (Urey-Bradley, class II cross
+/// terms). Potentials depending only on the value of the angle usually
+/// implement `Potential` too, and evaluate it at `geometry.theta`.
 ///
 /// # Example
 ///
 /// ```
-/// use lumol_core::energy::{Potential, AnglePotential};
+/// use lumol_core::energy::{AnglePotential, AngleGeometry, AngleForces};
 ///
 /// // A no-op potential
 /// #[derive(Clone)]
 /// struct Null;
 ///
-/// impl Potential for Null {
-///     fn energy(&self, x: f64) -> f64 {0.0}
-///     fn force(&self, x: f64) -> f64 {0.0}
-/// }
-///
 /// // Now we can use the Null potential for angles
-/// impl AnglePotential for Null {}
+/// impl AnglePotential for Null {
+///     fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {0.0}
+///     fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+///         AngleForces { theta: 0.0, r_ij: 0.0, r_jk: 0.0, r_ik: 0.0 }
+///     }
+/// }
 /// ```
-pub trait AnglePotential: Potential + BoxCloneAngle {}
+pub trait AnglePotential: BoxCloneAngle + Sync + Send {
+    /// Get the energy of an angle with the given `geometry`.
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64;
+
+    /// Get the generalized forces acting on an angle with the given
+    /// `geometry`.
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces;
+}
 impl_box_clone!(AnglePotential, BoxCloneAngle, box_clone_angle);
 
 /// Marker trait for potentials that can be used for molecular dihedral angles.
@@ -238,10 +312,42 @@ impl_box_clone!(AnglePotential, BoxCloneAngle, box_clone_angle);
 pub trait DihedralPotential: Potential + BoxCloneDihedral {}
 impl_box_clone!(DihedralPotential, BoxCloneDihedral, box_clone_dihedral);
 
+/// Marker trait for potentials that can be used for improper dihedral angles,
+/// keeping a particle in the plane formed by three of its neighbors.
+///
+/// # Example
+///
+/// ```
+/// use lumol_core::energy::{Potential, ImproperPotential};
+///
+/// // A no-op potential
+/// #[derive(Clone)]
+/// struct Null;
+///
+/// impl Potential for Null {
+///     fn energy(&self, x: f64) -> f64 {0.0}
+///     fn force(&self, x: f64) -> f64 {0.0}
+/// }
+///
+/// // Now we can use the Null potential for improper dihedral angles
+/// impl ImproperPotential for Null {}
+/// ```
+pub trait ImproperPotential: Potential + BoxCloneImproper {}
+impl_box_clone!(ImproperPotential, BoxCloneImproper, box_clone_improper);
+
 mod functions;
 pub use self::functions::{BornMayerHuggins, Buckingham, Gaussian, Morse, Torsion};
 pub use self::functions::{CosineHarmonic, Harmonic, LennardJones, NullPotential};
 pub use self::functions::Mie;
+pub use self::functions::{OplsTorsion, RyckaertBellemans};
+pub use self::functions::{BondAngle, BondBond, UreyBradley};
+pub use self::functions::{CosineSquared, Fene, Wca};
+
+mod custom;
+pub use self::custom::CustomPotential;
+
+mod tabulated;
+pub use self::tabulated::{TabulatedDomain, TabulatedPotential};
 
 mod computations;
 pub use self::computations::{Computation, TableComputation};
@@ -252,6 +358,16 @@ pub use self::restrictions::{PairRestriction, RestrictionInfo, BondPath};
 mod global;
 pub use self::global::{CoulombicPotential, GlobalCache, GlobalPotential};
 pub use self::global::{Ewald, SharedEwald, Wolf};
+pub use self::global::AlchemicalCoulomb;
+pub use self::global::{DipoleSolver, Polarization};
+pub use self::global::{Pme, SharedPme, DispersionPme};
+pub use self::global::{DampedShiftedForce, ReactionField, Thole, Yukawa};
+pub use self::global::{Eam, StillingerWeber, Tersoff};
+pub use self::global::{ElectricField, Wall, WallPotential};
+pub use self::global::{PositionRestraint, SphericalConfinement};
 
 mod pairs;
-pub use self::pairs::PairInteraction;
+pub use self::pairs::{PairInteraction, SoftCore};
+
+mod mixing;
+pub use self::mixing::MixingRule;
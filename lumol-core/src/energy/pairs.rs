@@ -3,6 +3,11 @@
 
 use crate::{PairPotential, PairRestriction};
 use crate::{Matrix3, Vector3D};
+use crate::math::integrate;
+
+/// Number of intervals used to integrate the tail corrections over the
+/// switching region
+const SWITCH_INTERVALS: usize = 200;
 
 /// The different way to compute non-bonded pair interactions
 #[derive(Clone, Copy, Debug)]
@@ -11,6 +16,118 @@ enum PairComputation {
     Cutoff,
     /// Using a cutoff distance and a shift
     Shifted(f64),
+    /// Using a CHARMM-style switching of the energy, starting at the given
+    /// distance
+    EnergySwitch(f64),
+    /// Using a polynomial switching of the force, starting at `switch`. The
+    /// force is modified by `a x^2 + b x^3` with `x = r - switch`, and the
+    /// energy is shifted by `shift`.
+    ForceSwitch {
+        switch: f64,
+        a: f64,
+        b: f64,
+        shift: f64,
+    },
+    /// Using a cubic spline taper between `switch` and the cutoff, going
+    /// from the `energy` and `force` at `switch` to zero at the cutoff.
+    Taper {
+        switch: f64,
+        energy: f64,
+        force: f64,
+    },
+}
+
+impl PairComputation {
+    /// Get the parameters of the force switching for the given `potential`
+    fn force_switch(potential: &dyn PairPotential, switch: f64, cutoff: f64) -> PairComputation {
+        let d = cutoff - switch;
+        let force = potential.force(cutoff);
+        // Numerical derivative of the force at the cutoff
+        let h = 1e-5 * cutoff;
+        let derivative = (potential.force(cutoff + h) - potential.force(cutoff - h)) / (2.0 * h);
+
+        let a = (-3.0 * force + derivative * d) / (d * d);
+        let b = (2.0 * force - derivative * d) / (d * d * d);
+        let shift = potential.energy(cutoff) - a * d * d * d / 3.0 - b * d * d * d * d / 4.0;
+        return PairComputation::ForceSwitch {
+            switch: switch,
+            a: a,
+            b: b,
+            shift: shift,
+        };
+    }
+
+    /// Get the parameters of the cubic spline taper for the given `potential`
+    fn taper(potential: &dyn PairPotential, switch: f64) -> PairComputation {
+        PairComputation::Taper {
+            switch: switch,
+            energy: potential.energy(switch),
+            force: potential.force(switch),
+        }
+    }
+
+    /// Get the same computation mode, with parameters updated for a
+    /// different `potential`
+    fn with_potential(&self, potential: &dyn PairPotential, cutoff: f64) -> PairComputation {
+        match *self {
+            PairComputation::Cutoff => PairComputation::Cutoff,
+            PairComputation::Shifted(_) => PairComputation::Shifted(potential.energy(cutoff)),
+            PairComputation::EnergySwitch(switch) => PairComputation::EnergySwitch(switch),
+            PairComputation::ForceSwitch { switch, .. } => {
+                PairComputation::force_switch(potential, switch, cutoff)
+            }
+            PairComputation::Taper { switch, .. } => PairComputation::taper(potential, switch),
+        }
+    }
+
+    /// Check if this computation mode uses a switching function
+    fn is_switched(&self) -> bool {
+        !matches!(*self, PairComputation::Cutoff | PairComputation::Shifted(_))
+    }
+}
+
+/// Check that the `switch` distance is compatible with the `cutoff`
+fn check_switch(switch: f64, cutoff: f64) {
+    assert!(
+        switch >= 0.0 && switch < cutoff,
+        "the switching distance must be positive and smaller than the cutoff in pair interaction"
+    );
+}
+
+/// Soft-core coupling of a pair interaction to an alchemical parameter `λ`.
+///
+/// The coupled energy uses the soft-core scheme from [Beutler1994], removing
+/// the singularity at short distances when the interaction is decoupled:
+///
+/// ```text
+/// V_λ(r) = λ V(r_λ)  with  r_λ = (α σ^6 (1 - λ) + r^6)^(1/6)
+/// ```
+///
+/// where `V` is the uncoupled potential, `α` the soft-core strength and `σ`
+/// the soft-core distance.
+///
+/// [Beutler1994]: Beutler, T. C. et al. Chem. Phys. Lett. 222, 529 (1994).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoftCore {
+    /// Coupling parameter, between 0 (decoupled) and 1 (fully coupled)
+    pub lambda: f64,
+    /// Dimensionless soft-core strength
+    pub alpha: f64,
+    /// Soft-core distance
+    pub sigma: f64,
+}
+
+impl SoftCore {
+    /// Get the soft-core distance `r_λ` corresponding to the distance `r`
+    #[inline]
+    fn distance(&self, r: f64) -> f64 {
+        let shift = self.alpha * f64::powi(self.sigma, 6) * (1.0 - self.lambda);
+        if shift == 0.0 {
+            // Fully coupled interaction, or no soft-core
+            return r;
+        }
+        return f64::powf(shift + f64::powi(r, 6), 1.0 / 6.0);
+    }
 }
 
 /// A non-bonded interaction between two particle.
@@ -21,6 +138,14 @@ enum PairComputation {
 /// potential can be used in molecular dynamics, to ensure that the energy is
 /// continuous at the cutoff distance.
 ///
+/// Shifting the energy still leaves a discontinuity in the force at the
+/// cutoff. Switching functions can be used to bring both the energy and the
+/// force smoothly to zero between a switching distance and the cutoff: see
+/// [`energy_switch`], [`force_switch`] and [`taper`].
+///
+/// [`energy_switch`]: struct.PairInteraction.html#method.energy_switch
+/// [`force_switch`]: struct.PairInteraction.html#method.force_switch
+/// [`taper`]: struct.PairInteraction.html#method.taper
 /// [PairPotential]: trait.PairPotential.html
 /// [PairRestriction]: enum.PairRestriction.html
 #[derive(Clone)]
@@ -35,6 +160,8 @@ pub struct PairInteraction {
     tail: bool,
     /// The computation mode
     computation: PairComputation,
+    /// Soft-core coupling to an alchemical parameter, if any
+    soft_core: Option<SoftCore>,
 }
 
 impl PairInteraction {
@@ -61,6 +188,7 @@ impl PairInteraction {
             restriction: PairRestriction::None,
             computation: PairComputation::Cutoff,
             tail: false,
+            soft_core: None,
         }
     }
 
@@ -91,6 +219,130 @@ impl PairInteraction {
             restriction: PairRestriction::None,
             computation: PairComputation::Shifted(shift),
             tail: false,
+            soft_core: None,
+        }
+    }
+
+    /// Create a new `PairInteraction` with the given `cutoff`, using the
+    /// CHARMM energy switching function from [Brooks1983] between the
+    /// `switch` distance and the `cutoff`. The energy is multiplied by
+    ///
+    /// ```text
+    /// S(r) = (rc^2 - r^2)^2 (rc^2 + 2 r^2 - 3 rs^2) / (rc^2 - rs^2)^3
+    /// ```
+    ///
+    /// which goes smoothly from 1 at `switch` to 0 at `cutoff`.
+    ///
+    /// [Brooks1983]: Brooks, B. R. et al. J. Comput. Chem. 4, 187 (1983).
+    ///
+    /// # Panics
+    ///
+    /// If `switch` is negative or not smaller than `cutoff`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lumol_core::energy::PairInteraction;
+    /// use lumol_core::energy::Harmonic;
+    ///
+    /// let potential = Box::new(Harmonic{x0: 0.5, k: 4.2});
+    /// let interaction = PairInteraction::energy_switch(potential, 1.5, 2.0);
+    ///
+    /// // the potential is not modified before the switching distance
+    /// assert_eq!(interaction.energy(1.0), 0.525);
+    ///
+    /// // energy and force go smoothly to zero at the cutoff
+    /// assert!(interaction.energy(1.999).abs() < 1e-3);
+    /// assert!(interaction.force(1.9999).abs() < 1e-1);
+    /// assert_eq!(interaction.energy(2.0), 0.0);
+    /// ```
+    pub fn energy_switch(potential: Box<dyn PairPotential>, switch: f64, cutoff: f64) -> PairInteraction {
+        check_switch(switch, cutoff);
+        PairInteraction {
+            potential: potential,
+            cutoff: cutoff,
+            restriction: PairRestriction::None,
+            computation: PairComputation::EnergySwitch(switch),
+            tail: false,
+            soft_core: None,
+        }
+    }
+
+    /// Create a new `PairInteraction` with the given `cutoff`, switching the
+    /// force to zero between the `switch` distance and the `cutoff`. A
+    /// polynomial `A (r - rs)^2 + B (r - rs)^3` is added to the force after
+    /// `switch` so that the force and its derivative are zero at the cutoff,
+    /// and the energy is shifted accordingly over the whole range.
+    ///
+    /// # Panics
+    ///
+    /// If `switch` is negative or not smaller than `cutoff`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lumol_core::energy::PairInteraction;
+    /// use lumol_core::energy::LennardJones;
+    ///
+    /// let potential = Box::new(LennardJones{sigma: 1.0, epsilon: 2.0});
+    /// let interaction = PairInteraction::force_switch(potential, 2.0, 3.0);
+    ///
+    /// // the force is not modified before the switching distance
+    /// assert_eq!(interaction.force(1.5), LennardJones{sigma: 1.0, epsilon: 2.0}.force(1.5));
+    ///
+    /// // energy and force go smoothly to zero at the cutoff
+    /// assert!(interaction.energy(2.999).abs() < 1e-6);
+    /// assert!(interaction.force(2.999).abs() < 1e-6);
+    /// ```
+    pub fn force_switch(potential: Box<dyn PairPotential>, switch: f64, cutoff: f64) -> PairInteraction {
+        check_switch(switch, cutoff);
+        let computation = PairComputation::force_switch(&*potential, switch, cutoff);
+        PairInteraction {
+            potential: potential,
+            cutoff: cutoff,
+            restriction: PairRestriction::None,
+            computation: computation,
+            tail: false,
+            soft_core: None,
+        }
+    }
+
+    /// Create a new `PairInteraction` with the given `cutoff`, replacing the
+    /// potential between the `switch` distance and the `cutoff` by a cubic
+    /// spline. The spline matches the energy and the force of the potential
+    /// at `switch`, and goes to zero with a zero force at the cutoff.
+    ///
+    /// # Panics
+    ///
+    /// If `switch` is negative or not smaller than `cutoff`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lumol_core::energy::PairInteraction;
+    /// use lumol_core::energy::Harmonic;
+    ///
+    /// let potential = Box::new(Harmonic{x0: 0.5, k: 4.2});
+    /// let interaction = PairInteraction::taper(potential, 1.5, 2.0);
+    ///
+    /// // the potential is not modified before the switching distance
+    /// assert_eq!(interaction.energy(1.0), 0.525);
+    ///
+    /// // energy and force go smoothly to zero at the cutoff
+    /// assert!(interaction.energy(1.999).abs() < 1e-3);
+    /// assert!(interaction.force(1.999).abs() < 1e-1);
+    /// assert_eq!(interaction.energy(2.0), 0.0);
+    /// ```
+    pub fn taper(potential: Box<dyn PairPotential>, switch: f64, cutoff: f64) -> PairInteraction {
+        check_switch(switch, cutoff);
+        let computation = PairComputation::taper(&*potential, switch);
+        PairInteraction {
+            potential: potential,
+            cutoff: cutoff,
+            restriction: PairRestriction::None,
+            computation: computation,
+            tail: false,
+            soft_core: None,
         }
     }
 
@@ -164,6 +416,61 @@ impl PairInteraction {
     pub fn cutoff(&self) -> f64 {
         self.cutoff
     }
+
+    /// Create a new `PairInteraction` for a different `potential`, using the
+    /// same cutoff, computation mode, tail corrections and restriction as
+    /// this interaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lumol_core::energy::PairInteraction;
+    /// use lumol_core::energy::{Harmonic, NullPotential};
+    ///
+    /// let template = PairInteraction::shifted(Box::new(NullPotential), 2.0);
+    ///
+    /// let potential = Box::new(Harmonic{x0: 0.5, k: 4.2});
+    /// let interaction = template.with_potential(potential);
+    ///
+    /// assert_eq!(interaction.cutoff(), 2.0);
+    /// assert_eq!(interaction.energy(1.0), -4.2);
+    /// ```
+    pub fn with_potential(&self, potential: Box<dyn PairPotential>) -> PairInteraction {
+        let computation = self.computation.with_potential(&*potential, self.cutoff);
+        PairInteraction {
+            potential: potential,
+            cutoff: self.cutoff,
+            restriction: self.restriction,
+            computation: computation,
+            tail: self.tail,
+            soft_core: self.soft_core,
+        }
+    }
+
+    /// Create a copy of this `PairInteraction`, coupled to an alchemical
+    /// parameter using the given `soft_core` scheme. Tail corrections are
+    /// not affected by the coupling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lumol_core::energy::{PairInteraction, SoftCore};
+    /// use lumol_core::energy::LennardJones;
+    ///
+    /// let potential = Box::new(LennardJones{sigma: 3.0, epsilon: 0.5});
+    /// let interaction = PairInteraction::new(potential, 10.0);
+    ///
+    /// let decoupled = interaction.with_soft_core(SoftCore{lambda: 0.0, alpha: 0.5, sigma: 3.0});
+    /// assert_eq!(decoupled.energy(0.0), 0.0);
+    ///
+    /// let coupled = interaction.with_soft_core(SoftCore{lambda: 1.0, alpha: 0.5, sigma: 3.0});
+    /// assert_eq!(coupled.energy(3.5), interaction.energy(3.5));
+    /// ```
+    pub fn with_soft_core(&self, soft_core: SoftCore) -> PairInteraction {
+        let mut interaction = self.clone();
+        interaction.soft_core = Some(soft_core);
+        return interaction;
+    }
 }
 
 impl PairInteraction {
@@ -184,13 +491,11 @@ impl PairInteraction {
     /// ```
     pub fn energy(&self, r: f64) -> f64 {
         if r >= self.cutoff {
-            0.0
-        } else {
-            let energy = self.potential.energy(r);
-            match self.computation {
-                PairComputation::Cutoff => energy,
-                PairComputation::Shifted(shift) => energy - shift,
-            }
+            return 0.0;
+        }
+        match self.soft_core {
+            Some(soft_core) => soft_core.lambda * self.uncoupled_energy(soft_core.distance(r)),
+            None => self.uncoupled_energy(r),
         }
     }
 
@@ -211,9 +516,16 @@ impl PairInteraction {
     /// ```
     pub fn force(&self, r: f64) -> f64 {
         if r >= self.cutoff {
-            0.0
-        } else {
-            self.potential.force(r)
+            return 0.0;
+        }
+        match self.soft_core {
+            Some(soft_core) => {
+                // dr_λ/dr = (r / r_λ)^5
+                let distance = soft_core.distance(r);
+                let derivative = f64::powi(r / distance, 5);
+                soft_core.lambda * self.uncoupled_force(distance) * derivative
+            }
+            None => self.uncoupled_force(r),
         }
     }
 
@@ -237,11 +549,150 @@ impl PairInteraction {
     pub fn virial(&self, r: &Vector3D) -> Matrix3 {
         if r.norm() >= self.cutoff {
             Matrix3::zero()
+        } else if self.soft_core.is_some() || self.computation.is_switched() {
+            let force = self.force(r.norm()) * r.normalized();
+            force.tensorial(r)
         } else {
             self.potential.virial(r)
         }
     }
 
+    /// Get the derivative of the energy with respect to the alchemical
+    /// coupling parameter `λ` at the distance `r`. This is zero for
+    /// interactions without soft-core coupling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lumol_core::energy::{PairInteraction, SoftCore};
+    /// use lumol_core::energy::LennardJones;
+    ///
+    /// let potential = Box::new(LennardJones{sigma: 3.0, epsilon: 0.5});
+    /// let interaction = PairInteraction::new(potential, 10.0);
+    /// assert_eq!(interaction.lambda_derivative(3.5), 0.0);
+    ///
+    /// // Without soft-core (alpha = 0), the energy is linear in lambda
+    /// let coupled = interaction.with_soft_core(SoftCore{lambda: 0.5, alpha: 0.0, sigma: 3.0});
+    /// assert_eq!(coupled.lambda_derivative(3.5), interaction.energy(3.5));
+    /// ```
+    pub fn lambda_derivative(&self, r: f64) -> f64 {
+        let soft_core = match self.soft_core {
+            Some(soft_core) if r < self.cutoff => soft_core,
+            _ => return 0.0,
+        };
+        // dV_λ/dλ = V(r_λ) - λ V'(r_λ) α σ^6 / (6 r_λ^5)
+        let distance = soft_core.distance(r);
+        let distance_derivative = -soft_core.alpha * f64::powi(soft_core.sigma, 6) / (6.0 * f64::powi(distance, 5));
+        let energy = self.uncoupled_energy(distance);
+        let force = self.uncoupled_force(distance);
+        return energy - soft_core.lambda * force * distance_derivative;
+    }
+
+    /// Get the energy of this interaction at distance `r` without the
+    /// alchemical coupling, accounting for the cutoff, the shift and the
+    /// switching function.
+    fn uncoupled_energy(&self, r: f64) -> f64 {
+        if r >= self.cutoff {
+            return 0.0;
+        }
+        match self.computation {
+            PairComputation::Shifted(shift) => self.potential.energy(r) - shift,
+            PairComputation::ForceSwitch { shift, .. } => self.switched_energy(r) - shift,
+            _ => self.switched_energy(r),
+        }
+    }
+
+    /// Get the norm of the force of this interaction at distance `r` without
+    /// the alchemical coupling, accounting for the cutoff and the switching
+    /// function.
+    fn uncoupled_force(&self, r: f64) -> f64 {
+        if r >= self.cutoff {
+            return 0.0;
+        }
+        match self.computation {
+            PairComputation::Cutoff | PairComputation::Shifted(_) => self.potential.force(r),
+            PairComputation::EnergySwitch(switch) => {
+                if r <= switch {
+                    return self.potential.force(r);
+                }
+                let rc2 = self.cutoff * self.cutoff;
+                let rs2 = switch * switch;
+                let r2 = r * r;
+                let denominator = f64::powi(rc2 - rs2, 3);
+                let value = (rc2 - r2) * (rc2 - r2) * (rc2 + 2.0 * r2 - 3.0 * rs2) / denominator;
+                let derivative = 12.0 * r * (rc2 - r2) * (rs2 - r2) / denominator;
+                self.potential.force(r) * value - self.potential.energy(r) * derivative
+            }
+            PairComputation::ForceSwitch { switch, a, b, .. } => {
+                let force = self.potential.force(r);
+                if r <= switch {
+                    return force;
+                }
+                let x = r - switch;
+                force + a * x * x + b * x * x * x
+            }
+            PairComputation::Taper { switch, energy, force } => {
+                if r <= switch {
+                    return self.potential.force(r);
+                }
+                let h = self.cutoff - switch;
+                let t = (r - switch) / h;
+                let derivative = -force * h;
+                let slope = (6.0 * t * t - 6.0 * t) * energy + (3.0 * t * t - 4.0 * t + 1.0) * derivative;
+                -slope / h
+            }
+        }
+    }
+
+    /// Get the energy of this interaction at distance `r`, accounting for the
+    /// switching function but not for any constant shift of the energy.
+    fn switched_energy(&self, r: f64) -> f64 {
+        let energy = self.potential.energy(r);
+        match self.computation {
+            PairComputation::Cutoff | PairComputation::Shifted(_) => energy,
+            PairComputation::EnergySwitch(switch) => {
+                if r <= switch {
+                    return energy;
+                }
+                let rc2 = self.cutoff * self.cutoff;
+                let rs2 = switch * switch;
+                let r2 = r * r;
+                let value = (rc2 - r2) * (rc2 - r2) * (rc2 + 2.0 * r2 - 3.0 * rs2) / f64::powi(rc2 - rs2, 3);
+                energy * value
+            }
+            PairComputation::ForceSwitch { switch, a, b, .. } => {
+                if r <= switch {
+                    return energy;
+                }
+                let x = r - switch;
+                energy - a * x * x * x / 3.0 - b * x * x * x * x / 4.0
+            }
+            PairComputation::Taper { switch, energy: start, force } => {
+                if r <= switch {
+                    return energy;
+                }
+                let h = self.cutoff - switch;
+                let t = (r - switch) / h;
+                let derivative = -force * h;
+                (2.0 * t * t * t - 3.0 * t * t + 1.0) * start + (t * t * t - 2.0 * t * t + t) * derivative
+            }
+        }
+    }
+
+    /// Integrate `function` between the switching distance and the cutoff,
+    /// using a Gauss-Legendre quadrature. This is zero for interactions
+    /// without switching function.
+    fn switch_integral<F: Fn(f64) -> f64>(&self, function: F) -> f64 {
+        let switch = match self.computation {
+            PairComputation::Cutoff | PairComputation::Shifted(_) => return 0.0,
+            PairComputation::EnergySwitch(switch) |
+            PairComputation::ForceSwitch { switch, .. } |
+            PairComputation::Taper { switch, .. } => switch,
+        };
+
+        return integrate(function, switch, self.cutoff, SWITCH_INTERVALS);
+    }
+
     /// Get the tail correction to the energy for this pair interaction
     ///
     /// # Examples
@@ -258,7 +709,11 @@ impl PairInteraction {
     /// ```
     pub fn tail_energy(&self) -> f64 {
         if self.tail {
-            self.potential.tail_energy(self.cutoff)
+            // Energy removed by the switching function before the cutoff
+            let switched = self.switch_integral(|r| {
+                r * r * (self.potential.energy(r) - self.switched_energy(r))
+            });
+            self.potential.tail_energy(self.cutoff) + switched
         } else {
             0.0
         }
@@ -289,7 +744,11 @@ impl PairInteraction {
     pub fn tail_virial(&self) -> Matrix3 {
         if self.tail {
             let tensor = Matrix3::one() / 3.0;
-            return self.potential.tail_virial(self.cutoff) * tensor;
+            // Virial removed by the switching function before the cutoff
+            let switched = self.switch_integral(|r| {
+                r * r * r * (self.potential.force(r) - self.uncoupled_force(r))
+            });
+            return (self.potential.tail_virial(self.cutoff) + switched) * tensor;
         } else {
             return Matrix3::zero();
         }
@@ -303,7 +762,7 @@ mod tests {
     use crate::{LennardJones, NullPotential, PairRestriction};
     use crate::Potential;
 
-    use approx::assert_ulps_eq;
+    use approx::{assert_relative_eq, assert_ulps_eq};
 
     #[test]
     fn restriction() {
@@ -358,4 +817,131 @@ mod tests {
         assert_eq!(pairs.tail_energy(), -0.041663275824652776);
         assert_ulps_eq!(pairs.tail_virial().trace(), -0.24995930989583334);
     }
+
+    #[test]
+    fn switching() {
+        let lj = LennardJones {
+            sigma: 1.0,
+            epsilon: 2.0,
+        };
+        let all = [
+            PairInteraction::energy_switch(Box::new(lj), 3.0, 4.0),
+            PairInteraction::force_switch(Box::new(lj), 3.0, 4.0),
+            PairInteraction::taper(Box::new(lj), 3.0, 4.0),
+        ];
+
+        let eps = 1e-6;
+        for pairs in &all {
+            // Energy and force are continuous at the cutoff
+            assert_relative_eq!(pairs.energy(4.0 - eps), 0.0, epsilon = 1e-9);
+            assert_relative_eq!(pairs.force(4.0 - eps), 0.0, epsilon = 1e-6);
+            assert_eq!(pairs.force(4.1), 0.0);
+            assert_eq!(pairs.energy(4.1), 0.0);
+
+            // Energy and force are continuous at the switching distance
+            assert_relative_eq!(pairs.energy(3.0 - eps), pairs.energy(3.0 + eps), epsilon = 1e-9);
+            assert_relative_eq!(pairs.force(3.0 - eps), pairs.force(3.0 + eps), epsilon = 1e-6);
+
+            for &r in &[1.1, 2.5, 3.2, 3.5, 3.9] {
+                let finite_diff = -(pairs.energy(r + eps) - pairs.energy(r - eps)) / (2.0 * eps);
+                assert_relative_eq!(pairs.force(r), finite_diff, epsilon = 1e-7);
+
+                let vector = Vector3D::new(0.0, 0.6 * r, 0.8 * r);
+                let force = pairs.force(r) * vector.normalized();
+                assert_relative_eq!(pairs.virial(&vector), force.tensorial(&vector), epsilon = 1e-12);
+            }
+
+            // with_potential recompute the switching parameters
+            let other = LennardJones {
+                sigma: 1.2,
+                epsilon: 0.5,
+            };
+            let other_pairs = pairs.with_potential(Box::new(other));
+            assert_relative_eq!(other_pairs.energy(4.0 - eps), 0.0, epsilon = 1e-9);
+            assert_relative_eq!(other_pairs.force(4.0 - eps), 0.0, epsilon = 1e-6);
+        }
+
+        // The potential is unchanged before the switching distance
+        assert_eq!(all[0].energy(2.5), lj.energy(2.5));
+        assert_eq!(all[0].force(2.5), lj.force(2.5));
+        assert_eq!(all[1].force(2.5), lj.force(2.5));
+        assert_eq!(all[2].energy(2.5), lj.energy(2.5));
+        assert_eq!(all[2].force(2.5), lj.force(2.5));
+    }
+
+    #[test]
+    fn switching_tail_corrections() {
+        let lj = LennardJones {
+            sigma: 1.0,
+            epsilon: 2.0,
+        };
+        let mut pairs = PairInteraction::energy_switch(Box::new(lj), 3.0, 4.0);
+        pairs.enable_tail_corrections();
+
+        assert_relative_eq!(pairs.tail_energy(), -0.06218370232110649, max_relative = 1e-8);
+        assert_relative_eq!(pairs.tail_virial().trace(), -0.18655110696331947, max_relative = 1e-8);
+
+        let mut pairs = PairInteraction::force_switch(Box::new(lj), 3.0, 4.0);
+        pairs.enable_tail_corrections();
+        assert_relative_eq!(pairs.tail_energy(), -0.03297479758186946, max_relative = 1e-8);
+
+        let mut pairs = PairInteraction::taper(Box::new(lj), 3.0, 4.0);
+        pairs.enable_tail_corrections();
+        assert_relative_eq!(pairs.tail_energy(), -0.05996978027664407, max_relative = 1e-8);
+    }
+
+    #[test]
+    #[should_panic(expected = "the switching distance must be positive and smaller than the cutoff in pair interaction")]
+    fn invalid_switch() {
+        let _ = PairInteraction::taper(Box::new(NullPotential), 4.0, 4.0);
+    }
+
+    #[test]
+    fn soft_core() {
+        let lj = LennardJones {
+            sigma: 1.0,
+            epsilon: 2.0,
+        };
+        let pairs = PairInteraction::shifted(Box::new(lj), 4.0);
+        let soft_core = SoftCore {
+            lambda: 0.5,
+            alpha: 0.5,
+            sigma: 1.0,
+        };
+        let coupled = pairs.with_soft_core(soft_core);
+
+        let shift = lj.energy(4.0);
+        let distance = f64::powf(0.25 + f64::powi(0.8, 6), 1.0 / 6.0);
+        assert_ulps_eq!(coupled.energy(0.8), 0.5 * (lj.energy(distance) - shift));
+        assert_relative_eq!(coupled.energy(0.8), 7.439906261094915 - 0.5 * shift, epsilon = 1e-12);
+        // No singularity at r = 0
+        assert!(coupled.energy(0.0).is_finite());
+        assert_eq!(coupled.energy(4.1), 0.0);
+
+        // Fully coupled and decoupled interactions
+        let full = pairs.with_soft_core(SoftCore { lambda: 1.0, ..soft_core });
+        assert_eq!(full.energy(1.2), pairs.energy(1.2));
+        assert_eq!(full.force(1.2), pairs.force(1.2));
+        let decoupled = pairs.with_soft_core(SoftCore { lambda: 0.0, ..soft_core });
+        assert_eq!(decoupled.energy(0.5), 0.0);
+        assert_eq!(decoupled.force(0.5), 0.0);
+
+        let eps = 1e-6;
+        for &r in &[0.3, 0.8, 1.2, 2.5] {
+            let finite_diff = -(coupled.energy(r + eps) - coupled.energy(r - eps)) / (2.0 * eps);
+            assert_relative_eq!(coupled.force(r), finite_diff, epsilon = 1e-6);
+
+            let vector = Vector3D::new(0.0, 0.6 * r, 0.8 * r);
+            let force = coupled.force(r) * vector.normalized();
+            assert_relative_eq!(coupled.virial(&vector), force.tensorial(&vector), epsilon = 1e-12);
+
+            let plus = pairs.with_soft_core(SoftCore { lambda: 0.5 + eps, ..soft_core });
+            let minus = pairs.with_soft_core(SoftCore { lambda: 0.5 - eps, ..soft_core });
+            let finite_diff = (plus.energy(r) - minus.energy(r)) / (2.0 * eps);
+            assert_relative_eq!(coupled.lambda_derivative(r), finite_diff, epsilon = 1e-6);
+        }
+
+        assert_eq!(pairs.lambda_derivative(1.2), 0.0);
+        assert_eq!(coupled.lambda_derivative(4.1), 0.0);
+    }
 }
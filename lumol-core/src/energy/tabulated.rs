@@ -0,0 +1,476 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Potentials interpolated from tabulated energies and forces
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::f64::consts::PI;
+
+use log_once::warn_once;
+
+use crate::math::integrate;
+use crate::{AnglePotential, BondPotential, DihedralPotential, PairPotential, Potential};
+use crate::{AngleForces, AngleGeometry};
+
+/// Tolerance used when checking that a table covers the domain of angles and
+/// dihedral angles, to account for rounding errors in units conversion.
+const DOMAIN_TOLERANCE: f64 = 1e-9;
+
+/// Domain of the variable of a [`TabulatedPotential`], defining what happens
+/// outside of the tabulated values.
+///
+/// [`TabulatedPotential`]: struct.TabulatedPotential.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TabulatedDomain {
+    /// Distance between two particles in pair interactions. After the last
+    /// tabulated point, the energy and the force are zero.
+    Pair,
+    /// Length of a bond. After the last tabulated point, the energy is
+    /// infinite.
+    Bond,
+    /// Value of an angle. The table must cover the `[0, π]` range.
+    Angle,
+    /// Value of a dihedral angle. The table must cover a full period of `2π`,
+    /// and the angle is wrapped inside the tabulated range.
+    Dihedral,
+}
+
+impl TabulatedDomain {
+    /// Check if a table going from `first` to `last` covers this domain
+    pub fn covered_by(&self, first: f64, last: f64) -> bool {
+        match *self {
+            TabulatedDomain::Pair | TabulatedDomain::Bond => true,
+            TabulatedDomain::Angle => first <= DOMAIN_TOLERANCE && last >= PI - DOMAIN_TOLERANCE,
+            TabulatedDomain::Dihedral => last - first >= 2.0 * PI - DOMAIN_TOLERANCE,
+        }
+    }
+}
+
+/// Potential interpolated from tabulated values of the energy and the force.
+///
+/// The energy is interpolated between the tabulated points with a cubic
+/// Hermite spline, using the tabulated forces as the derivatives of the
+/// energy. The force is the exact derivative of the interpolated energy,
+/// which makes the energy and the forces consistent with one another.
+///
+/// Before the first tabulated point, the energy is extrapolated linearly
+/// using the first force. What happens after the last tabulated point depends
+/// on the [`TabulatedDomain`] of the potential, which is `Pair` by default and
+/// can be changed with [`set_domain`]:
+///
+/// - for pair potentials, the energy and the force are zero. The table should
+///   thus extend up to the cutoff, and tail corrections are computed by
+///   integrating the interpolated potential between the cutoff and the last
+///   tabulated point. This means that there are no tail corrections if the
+///   table ends at the cutoff;
+/// - for bonds, the energy is infinite;
+/// - angles tables must cover all the possible values from 0 to π;
+/// - dihedral angles tables must cover a full period, and the angle is wrapped
+///   modulo 2π inside the tabulated range.
+///
+/// This can be used for potentials coming from force-matching or iterative
+/// Boltzmann inversion of coarse-grained models.
+///
+/// [`TabulatedDomain`]: enum.TabulatedDomain.html
+/// [`set_domain`]: struct.TabulatedPotential.html#method.set_domain
+///
+/// # Examples
+///
+/// ```
+/// use lumol_core::energy::{TabulatedPotential, Potential};
+///
+/// // Tabulated values for an harmonic potential with k = 2 and x0 = 1
+/// let x = vec![0.0, 0.5, 1.0, 1.5, 2.0];
+/// let energy = x.iter().map(|x| (x - 1.0) * (x - 1.0)).collect();
+/// let force = x.iter().map(|x| -2.0 * (x - 1.0)).collect();
+///
+/// let potential = TabulatedPotential::new(x, energy, force);
+/// assert!(f64::abs(potential.energy(0.7) - 0.09) < 1e-12);
+/// assert!(f64::abs(potential.force(0.7) - 0.6) < 1e-12);
+/// ```
+#[derive(Clone)]
+pub struct TabulatedPotential {
+    /// Tabulated values of the variable, in increasing order
+    x: Vec<f64>,
+    /// Energy at the tabulated points
+    energy: Vec<f64>,
+    /// Force at the tabulated points
+    force: Vec<f64>,
+    /// Domain of the variable
+    domain: TabulatedDomain,
+}
+
+impl TabulatedPotential {
+    /// Create a new tabulated potential from the `energy` and `force` values
+    /// at the points `x`.
+    ///
+    /// # Panics
+    ///
+    /// If the three vectors do not have the same size, if there are less than
+    /// two points, or if the values in `x` are not strictly increasing.
+    pub fn new(x: Vec<f64>, energy: Vec<f64>, force: Vec<f64>) -> TabulatedPotential {
+        assert_eq!(x.len(), energy.len(), "x and energy must have the same size in tabulated potential");
+        assert_eq!(x.len(), force.len(), "x and force must have the same size in tabulated potential");
+        assert!(x.len() >= 2, "tabulated potential needs at least two points");
+        assert!(
+            x.windows(2).all(|w| w[0] < w[1]),
+            "x values must be strictly increasing in tabulated potential"
+        );
+
+        TabulatedPotential {
+            x: x,
+            energy: energy,
+            force: force,
+            domain: TabulatedDomain::Pair,
+        }
+    }
+
+    /// Set the domain of the variable of this potential to `domain`.
+    ///
+    /// # Panics
+    ///
+    /// If the tabulated values do not cover the domain.
+    pub fn set_domain(&mut self, domain: TabulatedDomain) {
+        let (first, last) = self.range();
+        assert!(
+            domain.covered_by(first, last),
+            "tabulated potential from {} to {} does not cover the {:?} domain", first, last, domain
+        );
+        self.domain = domain;
+    }
+
+    /// Get the domain of the variable of this potential
+    pub fn domain(&self) -> TabulatedDomain {
+        self.domain
+    }
+
+    /// Read a tabulated potential from the file at `path`. The file should
+    /// contain three columns with the values of the variable, the energy and
+    /// the force. Empty lines and lines starting with `#` are ignored.
+    /// `x_unit` and `energy_unit` are the conversion factors from the units
+    /// used in the file to the internal units, and the forces are given in
+    /// energy unit over x unit.
+    pub fn read<P: AsRef<Path>>(path: P, x_unit: f64, energy_unit: f64) -> io::Result<TabulatedPotential> {
+        let content = fs::read_to_string(path)?;
+
+        let mut x = Vec::new();
+        let mut energy = Vec::new();
+        let mut force = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let values = line.split_whitespace().map(|value| value.parse::<f64>()).collect::<Result<Vec<_>, _>>();
+            let values = match values {
+                Ok(values) if values.len() == 3 => values,
+                _ => return Err(invalid_data(format!("expected three numbers at line {}", i + 1))),
+            };
+
+            if let Some(&previous) = x.last() {
+                if values[0] * x_unit <= previous {
+                    return Err(invalid_data(format!("values in the first column must be increasing at line {}", i + 1)));
+                }
+            }
+
+            x.push(values[0] * x_unit);
+            energy.push(values[1] * energy_unit);
+            force.push(values[2] * energy_unit / x_unit);
+        }
+
+        if x.len() < 2 {
+            return Err(invalid_data("expected at least two points"));
+        }
+
+        return Ok(TabulatedPotential::new(x, energy, force));
+    }
+
+    /// Get the first and last tabulated values of the variable
+    pub fn range(&self) -> (f64, f64) {
+        (self.x[0], self.x[self.x.len() - 1])
+    }
+
+    /// Get the energy and force from the cubic Hermite spline in the `i`-th
+    /// interval, at `x`
+    #[inline]
+    fn spline(&self, i: usize, x: f64) -> (f64, f64) {
+        let h = self.x[i + 1] - self.x[i];
+        let t = (x - self.x[i]) / h;
+        let t2 = t * t;
+        let t3 = t2 * t;
+
+        let (e0, e1) = (self.energy[i], self.energy[i + 1]);
+        // derivatives of the energy
+        let (d0, d1) = (-self.force[i] * h, -self.force[i + 1] * h);
+
+        let energy = (2.0 * t3 - 3.0 * t2 + 1.0) * e0 + (t3 - 2.0 * t2 + t) * d0
+                   + (-2.0 * t3 + 3.0 * t2) * e1 + (t3 - t2) * d1;
+        let derivative = (6.0 * t2 - 6.0 * t) * e0 + (3.0 * t2 - 4.0 * t + 1.0) * d0
+                       + (-6.0 * t2 + 6.0 * t) * e1 + (3.0 * t2 - 2.0 * t) * d1;
+        return (energy, -derivative / h);
+    }
+
+    /// Get the energy and force at `x`
+    fn evaluate(&self, x: f64) -> (f64, f64) {
+        let (first, last) = self.range();
+        let x = match self.domain {
+            TabulatedDomain::Pair | TabulatedDomain::Bond => x,
+            // The table covers the full domain, up to rounding errors
+            TabulatedDomain::Angle => f64::max(f64::min(x, last), first),
+            TabulatedDomain::Dihedral => {
+                let x = first + (x - first).rem_euclid(2.0 * PI);
+                f64::min(x, last)
+            }
+        };
+
+        if x < first {
+            warn_once!("value before the first point of tabulated potential, using linear extrapolation");
+            return (self.energy[0] - self.force[0] * (x - first), self.force[0]);
+        } else if x >= last {
+            if x > last {
+                if self.domain == TabulatedDomain::Bond {
+                    warn_once!("bond length after the last point of tabulated potential, energy is infinite");
+                    return (f64::INFINITY, f64::NEG_INFINITY);
+                }
+                warn_once!("value after the last point of tabulated potential, energy and force are set to zero");
+                return (0.0, 0.0);
+            }
+            let n = self.x.len();
+            return (self.energy[n - 1], self.force[n - 1]);
+        }
+
+        // index of the interval containing x
+        let i = self.x.partition_point(|&value| value <= x) - 1;
+        return self.spline(i, x);
+    }
+
+    /// Integrate `r^n f(r)` between `cutoff` and the last tabulated point,
+    /// where `f` is the energy if `energy` is true and the force otherwise.
+    /// This uses a Gauss-Legendre quadrature in each interval, which is exact
+    /// for the tail energy and virial since the interpolated energy is a cubic
+    /// polynomial.
+    fn tail_integral(&self, cutoff: f64, n: i32, energy: bool) -> f64 {
+        let mut integral = 0.0;
+        for i in 0..(self.x.len() - 1) {
+            if self.x[i + 1] <= cutoff {
+                continue;
+            }
+            let start = f64::max(self.x[i], cutoff);
+            integral += integrate(|r| {
+                let (e, f) = self.spline(i, r);
+                let value = if energy { e } else { f };
+                f64::powi(r, n) * value
+            }, start, self.x[i + 1], 1);
+        }
+        return integral;
+    }
+}
+
+/// Create an `InvalidData` IO error with the given `message`
+fn invalid_data<S: Into<String>>(message: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+impl Potential for TabulatedPotential {
+    fn energy(&self, x: f64) -> f64 {
+        self.evaluate(x).0
+    }
+
+    fn force(&self, x: f64) -> f64 {
+        self.evaluate(x).1
+    }
+}
+
+impl PairPotential for TabulatedPotential {
+    fn tail_energy(&self, cutoff: f64) -> f64 {
+        self.tail_integral(cutoff, 2, true)
+    }
+
+    fn tail_virial(&self, cutoff: f64) -> f64 {
+        self.tail_integral(cutoff, 3, false)
+    }
+}
+
+impl BondPotential for TabulatedPotential {}
+impl AnglePotential for TabulatedPotential {
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {
+        self.energy(geometry.theta)
+    }
+
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+        AngleForces {
+            theta: self.force(geometry.theta),
+            r_ij: 0.0,
+            r_jk: 0.0,
+            r_ik: 0.0,
+        }
+    }
+}
+impl DihedralPotential for TabulatedPotential {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LennardJones;
+    use std::io::Write;
+
+    use approx::assert_relative_eq;
+
+    fn lennard_jones() -> TabulatedPotential {
+        let lj = LennardJones { sigma: 2.0, epsilon: 0.8 };
+        let x = (0..=400).map(|i| 1.5 + 0.02 * i as f64).collect::<Vec<_>>();
+        let energy = x.iter().map(|&x| lj.energy(x)).collect();
+        let force = x.iter().map(|&x| lj.force(x)).collect();
+        return TabulatedPotential::new(x, energy, force);
+    }
+
+    #[test]
+    fn interpolation() {
+        let lj = LennardJones { sigma: 2.0, epsilon: 0.8 };
+        let table = lennard_jones();
+
+        // exact values at the tabulated points
+        assert_relative_eq!(table.energy(2.5), lj.energy(2.5), max_relative = 1e-12);
+        assert_relative_eq!(table.force(2.5), lj.force(2.5), max_relative = 1e-12);
+
+        for &r in &[1.91, 2.71, 3.1415, 6.001] {
+            assert_relative_eq!(table.energy(r), lj.energy(r), max_relative = 1e-5);
+            assert_relative_eq!(table.force(r), lj.force(r), max_relative = 1e-4);
+        }
+
+        // forces are the derivatives of the interpolated energy
+        let eps = 1e-6;
+        for &r in &[1.91, 2.71, 3.1415, 6.001] {
+            let finite_diff = -(table.energy(r + eps) - table.energy(r - eps)) / (2.0 * eps);
+            assert_relative_eq!(table.force(r), finite_diff, max_relative = 1e-6);
+        }
+    }
+
+    #[test]
+    fn out_of_range() {
+        let table = lennard_jones();
+        let (first, last) = table.range();
+        assert_eq!(first, 1.5);
+        assert_relative_eq!(last, 9.5);
+
+        let e0 = table.energy(1.5);
+        let f0 = table.force(1.5);
+        assert_relative_eq!(table.energy(1.4), e0 + 0.1 * f0, max_relative = 1e-12);
+        assert_eq!(table.force(1.4), f0);
+
+        assert_eq!(table.energy(10.0), 0.0);
+        assert_eq!(table.force(10.0), 0.0);
+    }
+
+    #[test]
+    fn tail_corrections() {
+        let table = lennard_jones();
+        let lj = LennardJones { sigma: 2.0, epsilon: 0.8 };
+
+        // The table stops at 9.5, so remove the missing part
+        let expected_energy = lj.tail_energy(5.0) - lj.tail_energy(9.5);
+        let expected_virial = lj.tail_virial(5.0) - lj.tail_virial(9.5);
+        assert_relative_eq!(table.tail_energy(5.0), expected_energy, max_relative = 1e-6);
+        assert_relative_eq!(table.tail_virial(5.0), expected_virial, max_relative = 1e-6);
+
+        // No tail corrections if the table ends at the cutoff
+        assert_eq!(table.tail_energy(9.5), 0.0);
+        assert_eq!(table.tail_energy(12.0), 0.0);
+    }
+
+    #[test]
+    fn bonds() {
+        let mut table = lennard_jones();
+        table.set_domain(TabulatedDomain::Bond);
+        assert_eq!(table.domain(), TabulatedDomain::Bond);
+
+        let lj = LennardJones { sigma: 2.0, epsilon: 0.8 };
+        assert_relative_eq!(table.energy(2.5), lj.energy(2.5), max_relative = 1e-12);
+        assert_eq!(table.energy(10.0), f64::INFINITY);
+        assert_eq!(table.force(10.0), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn angles() {
+        let x = (0..=18).map(|i| f64::to_radians(10.0 * i as f64)).collect::<Vec<_>>();
+        let energy = x.iter().map(|x| f64::cos(*x)).collect();
+        let force = x.iter().map(|x| f64::sin(*x)).collect();
+        let mut table = TabulatedPotential::new(x, energy, force);
+        table.set_domain(TabulatedDomain::Angle);
+
+        assert_relative_eq!(table.energy(PI), -1.0, max_relative = 1e-12);
+        assert_relative_eq!(table.energy(PI + 1e-12), -1.0, max_relative = 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not cover the Angle domain")]
+    fn angles_not_covered() {
+        let x = vec![0.0, 1.0, 2.0];
+        let mut table = TabulatedPotential::new(x, vec![0.0; 3], vec![0.0; 3]);
+        table.set_domain(TabulatedDomain::Angle);
+    }
+
+    #[test]
+    fn dihedrals() {
+        let x = (0..=36).map(|i| f64::to_radians(-180.0 + 10.0 * i as f64)).collect::<Vec<_>>();
+        let energy = x.iter().map(|x| 1.0 + f64::cos(3.0 * x)).collect();
+        let force = x.iter().map(|x| 3.0 * f64::sin(3.0 * x)).collect();
+        let mut table = TabulatedPotential::new(x, energy, force);
+        table.set_domain(TabulatedDomain::Dihedral);
+
+        for &phi in &[-2.5, -0.3, 0.7, 3.0] {
+            assert_relative_eq!(table.energy(phi + 2.0 * PI), table.energy(phi), max_relative = 1e-9);
+            assert_relative_eq!(table.energy(phi - 2.0 * PI), table.energy(phi), max_relative = 1e-9);
+            assert_relative_eq!(table.force(phi + 4.0 * PI), table.force(phi), max_relative = 1e-9);
+        }
+        assert_relative_eq!(table.energy(PI), 0.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not cover the Dihedral domain")]
+    fn dihedrals_not_covered() {
+        let x = (0..=18).map(|i| f64::to_radians(10.0 * i as f64)).collect::<Vec<_>>();
+        let mut table = TabulatedPotential::new(x, vec![0.0; 19], vec![0.0; 19]);
+        table.set_domain(TabulatedDomain::Dihedral);
+    }
+
+    #[test]
+    fn read() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "# x  energy  force").unwrap();
+        writeln!(file, "1.0  4.0  -2.0").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "2.0  1.0  -1.0").unwrap();
+        writeln!(file, "3.0  0.0  0.0").unwrap();
+
+        let table = TabulatedPotential::read(file.path(), 10.0, 2.0).unwrap();
+        assert_eq!(table.range(), (10.0, 30.0));
+        assert_eq!(table.energy(20.0), 2.0);
+        assert_eq!(table.force(20.0), -0.2);
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "1.0  4.0").unwrap();
+        writeln!(file, "2.0  1.0").unwrap();
+        let error = TabulatedPotential::read(file.path(), 1.0, 1.0).err().unwrap();
+        assert_eq!(error.to_string(), "expected three numbers at line 1");
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "2.0  4.0  1.0").unwrap();
+        writeln!(file, "1.0  1.0  1.0").unwrap();
+        let error = TabulatedPotential::read(file.path(), 1.0, 1.0).err().unwrap();
+        assert_eq!(error.to_string(), "values in the first column must be increasing at line 2");
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "2.0  4.0  1.0").unwrap();
+        let error = TabulatedPotential::read(file.path(), 1.0, 1.0).err().unwrap();
+        assert_eq!(error.to_string(), "expected at least two points");
+    }
+
+    #[test]
+    #[should_panic(expected = "x values must be strictly increasing in tabulated potential")]
+    fn not_increasing() {
+        let _ = TabulatedPotential::new(vec![0.0, 2.0, 1.0], vec![0.0; 3], vec![0.0; 3]);
+    }
+}
@@ -3,6 +3,8 @@
 
 //! Access usual math function directly, without having to use a `f64::` prefix,
 //! or to resort to method style call.
+//!
+//! This module also contains the numerical integration used by the potentials.
 #![allow(clippy::inline_always)]
 
 use special::Error;
@@ -16,3 +18,27 @@ pub fn erf(value: f64) -> f64 {
 pub fn erfc(value: f64) -> f64 {
     f64::compl_error(value)
 }
+
+/// Abscissas and weights of the 3 points Gauss-Legendre quadrature on [0, 1]
+const GAUSS_LEGENDRE: [(f64, f64); 3] = [
+    (0.112_701_665_379_258_3, 5.0 / 18.0),
+    (0.5, 8.0 / 18.0),
+    (0.887_298_334_620_741_7, 5.0 / 18.0),
+];
+
+/// Integrate `function` between `start` and `stop`, splitting the domain in
+/// `intervals` sub-intervals of the same length and using a 3 points
+/// Gauss-Legendre quadrature in each one. This is exact for polynomials up to
+/// the fifth degree in each sub-interval, and never evaluates `function` at
+/// `start` or `stop`.
+pub fn integrate<F: Fn(f64) -> f64>(function: F, start: f64, stop: f64, intervals: usize) -> f64 {
+    let h = (stop - start) / intervals as f64;
+    let mut integral = 0.0;
+    for i in 0..intervals {
+        let lower = start + i as f64 * h;
+        for &(point, weight) in &GAUSS_LEGENDRE {
+            integral += weight * function(lower + point * h);
+        }
+    }
+    return integral * h;
+}
@@ -34,6 +34,8 @@ pub struct EnergyCache {
     angles: f64,
     /// Energy of all the dihedrals angles in the system
     dihedrals: f64,
+    /// Energy of all the improper dihedrals angles in the system
+    impropers: f64,
     /// Energy of coulombic interactions
     coulomb: f64,
     /// Energy of global interactions
@@ -52,6 +54,7 @@ impl EnergyCache {
             bonds: 0.0,
             angles: 0.0,
             dihedrals: 0.0,
+            impropers: 0.0,
             coulomb: 0.0,
             global: 0.0,
             updater: None,
@@ -66,6 +69,7 @@ impl EnergyCache {
         self.bonds = 0.0;
         self.angles = 0.0;
         self.dihedrals = 0.0;
+        self.impropers = 0.0;
         self.coulomb = 0.0;
         self.global = 0.0;
     }
@@ -79,8 +83,9 @@ impl EnergyCache {
 
         let evaluator = system.energy_evaluator();
 
+        let neighbors = system.neighbors();
         for i in 0..system.size() {
-            for j in (i + 1)..system.size() {
+            for j in neighbors.of(i) {
                 let r = system.nearest_image(i, j).norm();
                 let path = system.bond_path(i, j);
                 let energy = evaluator.pair(path, r, i, j);
@@ -94,6 +99,7 @@ impl EnergyCache {
         self.bonds = evaluator.bonds();
         self.angles = evaluator.angles();
         self.dihedrals = evaluator.dihedrals();
+        self.impropers = evaluator.impropers();
         self.coulomb = evaluator.coulomb();
         self.global = evaluator.global();
     }
@@ -107,6 +113,7 @@ impl EnergyCache {
         energy += self.bonds;
         energy += self.angles;
         energy += self.dihedrals;
+        energy += self.impropers;
 
         energy += self.coulomb;
         energy += self.global;
@@ -10,8 +10,9 @@ use soa_derive::soa_zip;
 use log_once::warn_once;
 
 use crate::consts::K_BOLTZMANN;
-use crate::{Matrix3, Vector3D};
+use crate::{Array2, Matrix3, Vector3D};
 use crate::{System, DegreesOfFreedom};
+use crate::{AngleForces, AngleGeometry, AnglePotential};
 
 use crate::utils::ThreadLocalVec;
 
@@ -25,6 +26,22 @@ pub trait Compute {
     fn compute(&self, system: &System) -> Self::Output;
 }
 
+/// Get the generalized forces acting on the angle `i, j, k` with the given
+/// `geometry`. Angle potentials use the particle with the smallest kind first,
+/// so the geometry and the forces are reversed if needed.
+fn angle_forces(
+    system: &System,
+    potential: &dyn AnglePotential,
+    (i, k): (usize, usize),
+    geometry: &AngleGeometry,
+) -> AngleForces {
+    if system.particles().kind[i] <= system.particles().kind[k] {
+        potential.angle_forces(geometry)
+    } else {
+        potential.angle_forces(&geometry.reversed()).reversed()
+    }
+}
+
 /// Compute all the forces acting on the system, and return a vector of
 /// force acting on each particles
 pub struct Forces;
@@ -34,10 +51,11 @@ impl Compute for Forces {
         let natoms = system.size();
         let thread_local_forces = ThreadLocalVec::with_size(natoms);
 
+        let neighbors = system.neighbors();
         (0..natoms).into_par_iter().for_each(|i| {
             let mut forces = thread_local_forces.borrow_mut();
             let mut force_i = Vector3D::zero();
-            for j in (i + 1)..system.size() {
+            for j in neighbors.of(i) {
                 let path = system.bond_path(i, j);
                 let d = system.nearest_image(i, j);
                 let dn = d.normalized();
@@ -66,7 +84,16 @@ impl Compute for Forces {
                 let dn = d.normalized();
                 let r = d.norm();
                 if let Some(potential) = system.bond_potential(i, j) {
-                    let force = potential.force(r) * dn;
+                    let force = potential.force(r);
+                    if !force.is_finite() {
+                        let names = system.particles().name;
+                        panic!(
+                            "got an infinite force for the bond between particles {} ({}) and {} ({}) \
+                            at a distance of {} A, this bond is overstretched",
+                            i, names[i], j, names[j], r
+                        );
+                    }
+                    let force = force * dn;
                     forces[i] += force;
                     forces[j] -= force;
                 }
@@ -74,12 +101,35 @@ impl Compute for Forces {
 
             for angle in molecule.angles() {
                 let (i, j, k) = (angle.i(), angle.j(), angle.k());
-                let (theta, d1, d2, d3) = system.angle_and_derivatives(i, j, k);
                 if let Some(potential) = system.angle_potential(i, j, k) {
-                    let force = potential.force(theta);
-                    forces[i] += force * d1;
-                    forces[j] += force * d2;
-                    forces[k] += force * d3;
+                    let (theta, d1, d2, d3) = system.angle_and_derivatives(i, j, k);
+                    let r_ij = system.nearest_image(i, j);
+                    let r_jk = system.nearest_image(j, k);
+                    let r_ik = system.nearest_image(i, k);
+                    let geometry = AngleGeometry {
+                        theta: theta,
+                        r_ij: r_ij.norm(),
+                        r_jk: r_jk.norm(),
+                        r_ik: r_ik.norm(),
+                    };
+
+                    let force = angle_forces(system, potential, (i, k), &geometry);
+                    forces[i] += force.theta * d1;
+                    forces[j] += force.theta * d2;
+                    forces[k] += force.theta * d3;
+
+                    // Contributions from the bonds lengths and 1-3 distance
+                    let force_ij = force.r_ij * r_ij.normalized();
+                    forces[i] += force_ij;
+                    forces[j] -= force_ij;
+
+                    let force_jk = force.r_jk * r_jk.normalized();
+                    forces[j] += force_jk;
+                    forces[k] -= force_jk;
+
+                    let force_ik = force.r_ik * r_ik.normalized();
+                    forces[i] += force_ik;
+                    forces[k] -= force_ik;
                 }
             }
 
@@ -94,6 +144,18 @@ impl Compute for Forces {
                     forces[m] += force * d4;
                 }
             }
+
+            for improper in molecule.impropers() {
+                if let Some((improper, potential)) = system.improper_potential(improper) {
+                    let (center, i, j, k) = (improper.center(), improper.i(), improper.j(), improper.k());
+                    let (phi, d1, d2, d3, d4) = system.dihedral_and_derivatives(center, i, j, k);
+                    let force = potential.force(phi);
+                    forces[center] += force * d1;
+                    forces[i] += force * d2;
+                    forces[j] += force * d3;
+                    forces[k] += force * d4;
+                }
+            }
         }
 
         if let Some(coulomb) = system.coulomb_potential() {
@@ -119,6 +181,7 @@ impl Compute for PotentialEnergy {
         energy += evaluator.bonds();
         energy += evaluator.angles();
         energy += evaluator.dihedrals();
+        energy += evaluator.impropers();
         energy += evaluator.coulomb();
         energy += evaluator.global();
 
@@ -161,12 +224,24 @@ impl Compute for TotalEnergy {
 /// where $N_f$ is the number of degrees of freedom in the system, $k_B$ is the
 /// Boltzman constant, $m_i$ the mass of particle $i$ and $\vec v_i$ the
 /// velocity of particle $i$.
+///
+/// If the system contains Drude particles, the kinetic energy of the relative
+/// motion of each core-Drude pair $\frac 12 \mu \|\vec v_D - \vec v_C\|^2$ is
+/// removed from the sum, where $\mu$ is the reduced mass of the pair.
 pub struct Temperature;
 impl Compute for Temperature {
     type Output = f64;
     fn compute(&self, system: &System) -> f64 {
-        let kinetic = KineticEnergy.compute(system);
-        let dof = system.degrees_of_freedom() as f64;
+        let mut kinetic = KineticEnergy.compute(system);
+        let masses = system.particles().mass;
+        let velocities = system.particles().velocity;
+        // This does not allocate if there are no Drude particles
+        let drude_pairs = system.drude_pairs();
+        for &(core, drude) in &drude_pairs {
+            let reduced_mass = masses[core] * masses[drude] / (masses[core] + masses[drude]);
+            kinetic -= 0.5 * reduced_mass * (velocities[drude] - velocities[core]).norm2();
+        }
+        let dof = system.degrees_of_freedom_with_drudes(drude_pairs.len()) as f64;
         return 2.0 * kinetic / (dof * K_BOLTZMANN);
     }
 }
@@ -199,9 +274,10 @@ impl Compute for AtomicVirial {
         assert!(!system.cell.is_infinite(), "Can not compute virial for infinite cell");
 
         // Pair potentials contributions
+        let neighbors = system.neighbors();
         let pair_virials = (0..system.size()).into_par_iter().map(|i| {
             let mut local_virial = Matrix3::zero();
-            for j in (i + 1)..system.size() {
+            for j in neighbors.of(i) {
                 let path = system.bond_path(i, j);
                 if let Some(potential) = system.pair_potential(i, j) {
                     let info = potential.restriction().information(path);
@@ -238,9 +314,31 @@ impl Compute for AtomicVirial {
             }
         }
 
-        // Angles and dihedrals potentials do not contribute as they only have
-        // an angular part (see DL_POLY 4 manual page 18, or Smith, W., 1993,
-        // CCP5 Information Quarterly, 39, 14. 18, 21, 24).
+        // Angles potentials contributions. The angular part of angles,
+        // dihedrals and impropers potentials does not contribute (see DL_POLY 4
+        // manual page 18, or Smith, W., 1993, CCP5 Information Quarterly, 39,
+        // 14. 18, 21, 24), only the parts depending on distances do.
+        for molecule in system.molecules() {
+            for angle in molecule.angles() {
+                let (i, j, k) = (angle.i(), angle.j(), angle.k());
+                if let Some(potential) = system.angle_potential(i, j, k) {
+                    let r_ij = system.nearest_image(i, j);
+                    let r_jk = system.nearest_image(j, k);
+                    let r_ik = system.nearest_image(i, k);
+                    let geometry = AngleGeometry {
+                        theta: system.angle(i, j, k),
+                        r_ij: r_ij.norm(),
+                        r_jk: r_jk.norm(),
+                        r_ik: r_ik.norm(),
+                    };
+
+                    let force = angle_forces(system, potential, (i, k), &geometry);
+                    virial += (force.r_ij * r_ij.normalized()).tensorial(&r_ij);
+                    virial += (force.r_jk * r_jk.normalized()).tensorial(&r_jk);
+                    virial += (force.r_ik * r_ik.normalized()).tensorial(&r_ik);
+                }
+            }
+        }
 
         if let Some(coulomb) = system.coulomb_potential() {
             virial += coulomb.atomic_virial(system);
@@ -347,9 +445,10 @@ impl Compute for MolecularVirial {
             }
         }
 
-        // Angles and dihedrals potentials do not contribute as they only have
-        // an angular part (see DL_POLY 4 manual page 18, or Smith, W., 1993,
-        // CCP5 Information Quarterly, 39, 14. 18, 21, 24).
+        // Angles, dihedrals and impropers potentials do not contribute as their
+        // angular part vanishes (see DL_POLY 4 manual page 18, or Smith, W., 1993,
+        // CCP5 Information Quarterly, 39, 14. 18, 21, 24). The parts of angles
+        // potentials depending on distances are ignored, as for bonds.
 
         if let Some(coulomb) = system.coulomb_potential() {
             virial += coulomb.molecular_virial(system);
@@ -365,7 +464,8 @@ impl Compute for MolecularVirial {
 
 /// Compute the virial tensor of the system, picking between [`AtomicVirial`]
 /// and [`MolecularVirial`] depending on the number of degrees of freedom
-/// simulated on the system.
+/// simulated on the system. When some degrees of freedom are frozen by
+/// constraints, the virial of the constraint forces is also included.
 ///
 /// [`AtomicVirial`]: struct.AtomicVirial.html
 /// [`MolecularVirial`]: struct.MolecularVirial.html
@@ -375,7 +475,8 @@ impl Compute for Virial {
     fn compute(&self, system: &System) -> Matrix3 {
         match system.simulated_degrees_of_freedom {
             DegreesOfFreedom::Molecules => MolecularVirial.compute(system),
-            DegreesOfFreedom::Particles | DegreesOfFreedom::Frozen(_) => AtomicVirial.compute(system),
+            DegreesOfFreedom::Particles => AtomicVirial.compute(system),
+            DegreesOfFreedom::Frozen(_) => AtomicVirial.compute(system) + system.constraints_virial,
         }
     }
 }
@@ -479,16 +580,232 @@ impl Compute for Stress {
     }
 }
 
+/// Groups of particles used to decompose the non-bonded energy in
+/// [`EnergyDecomposition`].
+///
+/// [`EnergyDecomposition`]: struct.EnergyDecomposition.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnergyGroups {
+    /// Group particles by name
+    Particles,
+    /// Group particles by molecule type, as given by the molecule hash. The
+    /// name of a group is the concatenation of the names of the particles in
+    /// the molecule. If multiple molecule types have the same name (e.g.
+    /// isomers), a numeric suffix is added to the name to get unique names:
+    /// `CCCC`, `CCCC-2`, `CCCC-3`, ...
+    Molecules,
+}
+
+/// Components of the potential energy of a system, as computed by
+/// [`EnergyDecomposition`].
+///
+/// [`EnergyDecomposition`]: struct.EnergyDecomposition.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnergyComponents {
+    /// Energy of the pair interactions
+    pub pairs: f64,
+    /// Long range corrections to the pair interactions
+    pub pairs_tail: f64,
+    /// Energy of the bonds
+    pub bonds: f64,
+    /// Energy of the angles
+    pub angles: f64,
+    /// Energy of the dihedral angles
+    pub dihedrals: f64,
+    /// Energy of the improper dihedral angles
+    pub impropers: f64,
+    /// Energy of the electrostatic interactions
+    pub coulomb: f64,
+    /// Energy of the global potentials
+    pub global: f64,
+    /// Names of the groups used to decompose the pair energy
+    pub groups: Vec<String>,
+    /// Decomposition of the pair energy between groups: `pairs_between[(a,
+    /// b)]` is the energy of the pairs with one particle in group `a` and the
+    /// other in group `b`. This matrix is symmetric, and the diagonal contains
+    /// the energy of pairs inside a single group. Only the pair interactions
+    /// are included, the electrostatic and global energies are not decomposed.
+    pub pairs_between: Array2<f64>,
+}
+
+impl EnergyComponents {
+    /// Get the total potential energy, i.e. the sum of all components
+    pub fn total(&self) -> f64 {
+        self.pairs + self.pairs_tail + self.bonds + self.angles + self.dihedrals +
+        self.impropers + self.coulomb + self.global
+    }
+
+    /// Get the pair energy between the groups named `a` and `b`, or `None` if
+    /// any of these groups does not exist. This does not include the
+    /// electrostatic energy between the groups.
+    pub fn pairs_between_groups(&self, a: &str, b: &str) -> Option<f64> {
+        let a = self.groups.iter().position(|name| name == a)?;
+        let b = self.groups.iter().position(|name| name == b)?;
+        return Some(self.pairs_between[(a, b)]);
+    }
+}
+
+/// Compute all the components of the potential energy separately, and
+/// decompose the pair energy between groups of particles.
+///
+/// Only the pair interactions are decomposed, as the electrostatic and global
+/// interactions can not generally be split between pairs of particles. The
+/// energy between groups is then only the pair energy, and does not include
+/// the electrostatic energy, even for pairwise electrostatic solvers.
+pub struct EnergyDecomposition {
+    /// Groups used for the decomposition of the pair energy
+    pub groups: EnergyGroups,
+}
+
+impl EnergyDecomposition {
+    /// Get the names of all groups in the `system`, and the group index of
+    /// each particle.
+    fn groups(&self, system: &System) -> (Vec<String>, Vec<usize>) {
+        let mut names = Vec::new();
+        let mut indexes = Vec::with_capacity(system.size());
+        match self.groups {
+            EnergyGroups::Particles => {
+                for name in system.particles().name {
+                    let index = match names.iter().position(|group| group == name) {
+                        Some(index) => index,
+                        None => {
+                            names.push(name.clone());
+                            names.len() - 1
+                        }
+                    };
+                    indexes.push(index);
+                }
+            }
+            EnergyGroups::Molecules => {
+                let mut hashes = Vec::new();
+                for molecule in system.molecules() {
+                    let hash = molecule.hash();
+                    let index = match hashes.iter().position(|&other| other == hash) {
+                        Some(index) => index,
+                        None => {
+                            hashes.push(hash);
+                            let base = molecule.particles().name.concat();
+                            let mut name = base.clone();
+                            let mut suffix = 1;
+                            while names.contains(&name) {
+                                suffix += 1;
+                                name = format!("{}-{}", base, suffix);
+                            }
+                            names.push(name);
+                            names.len() - 1
+                        }
+                    };
+                    indexes.extend(std::iter::repeat(index).take(molecule.size()));
+                }
+            }
+        }
+        return (names, indexes);
+    }
+}
+
+impl Compute for EnergyDecomposition {
+    type Output = EnergyComponents;
+    fn compute(&self, system: &System) -> EnergyComponents {
+        let evaluator = system.energy_evaluator();
+        let (groups, indexes) = self.groups(system);
+
+        let mut pairs = 0.0;
+        let mut pairs_between = Array2::zeros((groups.len(), groups.len()));
+        let neighbors = system.neighbors();
+        for i in 0..system.size() {
+            for j in neighbors.of(i) {
+                let r = system.nearest_image(i, j).norm();
+                let energy = evaluator.pair(system.bond_path(i, j), r, i, j);
+                pairs += energy;
+
+                let (a, b) = (indexes[i], indexes[j]);
+                pairs_between[(a, b)] += energy;
+                if a != b {
+                    pairs_between[(b, a)] += energy;
+                }
+            }
+        }
+
+        return EnergyComponents {
+            pairs: pairs,
+            pairs_tail: evaluator.pairs_tail(),
+            bonds: evaluator.bonds(),
+            angles: evaluator.angles(),
+            dihedrals: evaluator.dihedrals(),
+            impropers: evaluator.impropers(),
+            coulomb: evaluator.coulomb(),
+            global: evaluator.global(),
+            groups: groups,
+            pairs_between: pairs_between,
+        };
+    }
+}
+
+/// Compute the derivative of the potential energy with respect to one of the
+/// alchemical coupling parameters of the system, for thermodynamic
+/// integration.
+///
+/// The derivative is zero if the system does not contain an alchemical
+/// molecule (see [`System::set_alchemical_molecule`]).
+///
+/// [`System::set_alchemical_molecule`]: struct.System.html#method.set_alchemical_molecule
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LambdaDerivative {
+    /// Derivative with respect to the coupling parameter of the van der Waals
+    /// (pair) interactions
+    Vdw,
+    /// Derivative with respect to the coupling parameter of the coulombic
+    /// interactions
+    Coulomb,
+}
+
+impl Compute for LambdaDerivative {
+    type Output = f64;
+    fn compute(&self, system: &System) -> f64 {
+        match *self {
+            LambdaDerivative::Vdw => {
+                let molecule = match system.alchemical_molecule() {
+                    Some(molecule) => molecule,
+                    None => return 0.0,
+                };
+
+                let mut derivative = 0.0;
+                for i in system.molecule(molecule).indexes() {
+                    for j in 0..system.size() {
+                        if system.molecule_id(j) == molecule {
+                            continue;
+                        }
+                        if let Some(potential) = system.pair_potential(i, j) {
+                            let info = potential.restriction().information(system.bond_path(i, j));
+                            if !info.excluded {
+                                let r = system.nearest_image(i, j).norm();
+                                derivative += info.scaling * potential.lambda_derivative(r);
+                            }
+                        }
+                    }
+                }
+                return derivative;
+            }
+            LambdaDerivative::Coulomb => {
+                return system.coulomb_potential().map_or(0.0, |coulomb| coulomb.lambda_derivative(system));
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::System;
     use crate::consts::K_BOLTZMANN;
-    use crate::{Harmonic, NullPotential, PairInteraction};
+    use crate::{Fene, Harmonic, NullPotential, PairInteraction};
+    use crate::{AlchemicalCoulomb, LennardJones, PairRestriction, SoftCore, Wolf};
+    use crate::CoulombicPotential;
+    use crate::{BondAngle, BondBond, UreyBradley};
     use crate::utils::system_from_xyz;
     use crate::units;
 
-    use approx::assert_ulps_eq;
+    use approx::{assert_ulps_eq, assert_relative_eq};
 
     fn test_pairs_system() -> System {
         let mut system = system_from_xyz(
@@ -590,6 +907,117 @@ mod test {
         assert_ulps_eq!(forces_tot.norm2(), 0.0);
     }
 
+    #[test]
+    #[should_panic(expected = "got an infinite force for the bond between particles 1 (F) and 2 (F)")]
+    fn forces_overstretched_bond() {
+        let mut system = test_molecular_system();
+        system.set_bond_potential(("F", "F"), Box::new(Fene { k: 30.0, r0: 1.2 }));
+        system.particles_mut().position[2] = Vector3D::new(1.0, 1.5, 0.0);
+        let _ = Forces.compute(&system);
+    }
+
+    #[test]
+    fn forces_impropers() {
+        let mut system = system_from_xyz(
+            "4
+            cell: 10.0
+            C 0.0 0.0 0.3
+            O 1.0 0.0 0.0
+            H -0.5 0.866 0.0
+            H -0.5 -0.866 0.2
+            ",
+        );
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(0, 2).is_empty());
+        assert!(system.add_bond(0, 3).is_empty());
+        system.set_improper_potential(
+            ("C", "O", "H", "H"),
+            Box::new(Harmonic {
+                k: units::from(100.0, "kJ/mol/deg^2").unwrap(),
+                x0: units::from(10.0, "deg").unwrap(),
+            }),
+        );
+
+        let forces = Forces.compute(&system);
+        let total = forces[0] + forces[1] + forces[2] + forces[3];
+        assert_ulps_eq!(total.norm2(), 0.0);
+
+        let eps = 1e-6;
+        for i in 0..4 {
+            for k in 0..3 {
+                system.particles_mut().position[i][k] += eps;
+                let e_plus = PotentialEnergy.compute(&system);
+                system.particles_mut().position[i][k] -= 2.0 * eps;
+                let e_minus = PotentialEnergy.compute(&system);
+                system.particles_mut().position[i][k] += eps;
+
+                let finite_diff = -(e_plus - e_minus) / (2.0 * eps);
+                assert_relative_eq!(finite_diff, forces[i][k], max_relative = 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn angles_cross_terms() {
+        let mut system = system_from_xyz(
+            "3
+            cell: 10.0
+            O 1.2 0.1 0.0
+            C 0.0 0.0 0.0
+            H -0.4 1.0 0.1
+            ",
+        );
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(1, 2).is_empty());
+
+        let urey_bradley = UreyBradley { k: 2.0, x0: 1.9, k_ub: 3.0, r_ub: 2.0 };
+        let bond_bond = BondBond { k: 4.0, r1: 1.0, r2: 1.1 };
+        let bond_angle = BondAngle { k1: 1.5, k2: 2.5, r1: 1.0, r2: 1.1, theta0: 1.9 };
+        // Use the reverse order of the particles in the angle, to check that
+        // the parameters are associated with the right bonds
+        system.add_angle_potential(("H", "C", "O"), Box::new(urey_bradley));
+        system.add_angle_potential(("H", "C", "O"), Box::new(bond_bond));
+        system.add_angle_potential(("H", "C", "O"), Box::new(bond_angle));
+
+        let geometry = AngleGeometry {
+            theta: system.angle(0, 1, 2),
+            r_ij: system.distance(2, 1),
+            r_jk: system.distance(0, 1),
+            r_ik: system.distance(0, 2),
+        };
+        let expected = urey_bradley.angle_energy(&geometry)
+                     + bond_bond.angle_energy(&geometry)
+                     + bond_angle.angle_energy(&geometry);
+        assert_ulps_eq!(PotentialEnergy.compute(&system), expected, epsilon = 1e-12);
+
+        let forces = Forces.compute(&system);
+        let total = forces[0] + forces[1] + forces[2];
+        assert_ulps_eq!(total.norm2(), 0.0, epsilon = 1e-20);
+
+        let eps = 1e-6;
+        for i in 0..3 {
+            for k in 0..3 {
+                system.particles_mut().position[i][k] += eps;
+                let e_plus = PotentialEnergy.compute(&system);
+                system.particles_mut().position[i][k] -= 2.0 * eps;
+                let e_minus = PotentialEnergy.compute(&system);
+                system.particles_mut().position[i][k] += eps;
+
+                let finite_diff = -(e_plus - e_minus) / (2.0 * eps);
+                assert_relative_eq!(finite_diff, forces[i][k], epsilon = 1e-6);
+            }
+        }
+
+        // No particle interacts with a periodic image, so the trace of the
+        // virial is the sum of the r_i * f_i products
+        let mut expected = 0.0;
+        for i in 0..3 {
+            expected += system.particles().position[i] * forces[i];
+        }
+        let virial = AtomicVirial.compute(&system);
+        assert_relative_eq!(virial.trace(), expected, epsilon = 1e-9);
+    }
+
     #[test]
     #[allow(clippy::unreadable_literal)]
     fn energy_pairs() {
@@ -620,6 +1048,34 @@ mod test {
         assert_eq!(temperature, system.temperature());
     }
 
+    #[test]
+    fn temperature_drude() {
+        let mut system = system_from_xyz(
+            "2
+            cell: 10.0
+            O 0.0 0.0 0.0
+            DO 0.1 0.0 0.0
+            ",
+        );
+        let _ = system.add_bond(0, 1);
+        system.particles_mut().mass[0] = 15.0;
+        system.particles_mut().mass[1] = 1.0;
+        system.add_drude_particles("DO");
+
+        let com_velocity = Vector3D::new(0.01, -0.02, 0.005);
+        system.particles_mut().velocity[0] = com_velocity;
+        system.particles_mut().velocity[1] = com_velocity;
+        let expected = 0.5 * 16.0 * com_velocity.norm2() * 2.0 / (3.0 * K_BOLTZMANN);
+        assert_ulps_eq!(Temperature.compute(&system), expected);
+
+        // The relative motion of the Drude particle does not contribute to
+        // the temperature
+        let relative = Vector3D::new(0.3, 0.1, -0.2);
+        system.particles_mut().velocity[0] = com_velocity - 1.0 / 16.0 * relative;
+        system.particles_mut().velocity[1] = com_velocity + 15.0 / 16.0 * relative;
+        assert_relative_eq!(Temperature.compute(&system), expected, max_relative = 1e-12);
+    }
+
     #[test]
     fn volume() {
         let system = &test_pairs_system();
@@ -647,6 +1103,23 @@ mod test {
         assert_eq!(virial, system.virial());
     }
 
+    #[test]
+    fn virial_constraints() {
+        let mut system = test_pairs_system();
+        let atomic = AtomicVirial.compute(&system);
+
+        let mut constraints = Matrix3::zero();
+        constraints[1][1] = 3.0;
+        constraints[2][2] = -2.0;
+        system.constraints_virial = constraints;
+        // The constraints virial is only used when some degrees of freedom
+        // are frozen
+        assert_eq!(Virial.compute(&system), atomic);
+
+        system.simulated_degrees_of_freedom = DegreesOfFreedom::Frozen(1);
+        assert_ulps_eq!(Virial.compute(&system), atomic + constraints);
+    }
+
     #[test]
     fn virial_molecular() {
         let system = &test_molecular_system();
@@ -784,4 +1257,147 @@ mod test {
         assert_ulps_eq!(pressure, expected);
         assert_eq!(pressure, system.pressure());
     }
+
+    #[test]
+    fn energy_decomposition() {
+        let mut system = system_from_xyz(
+            "4
+            cell: 10.0
+            A 0.0 0.0 0.0
+            B 1.5 0.0 0.0
+            A 0.0 1.6 0.0
+            B 3.0 0.2 0.0
+            ",
+        );
+        assert!(system.add_bond(0, 1).is_empty());
+        for &(a, b, sigma) in &[("A", "A", 1.2), ("A", "B", 1.3), ("B", "B", 1.4)] {
+            system.set_pair_potential((a, b), PairInteraction::new(Box::new(LennardJones {
+                sigma: sigma,
+                epsilon: units::from(1.0, "kJ/mol").unwrap(),
+            }), 5.0));
+        }
+        system.set_bond_potential(("A", "B"), Box::new(Harmonic {
+            k: units::from(100.0, "kJ/mol/A^2").unwrap(),
+            x0: units::from(1.2, "A").unwrap(),
+        }));
+
+        let evaluator = system.energy_evaluator();
+        let components = EnergyDecomposition { groups: EnergyGroups::Particles }.compute(&system);
+        assert_relative_eq!(components.total(), PotentialEnergy.compute(&system), max_relative = 1e-12);
+        assert_relative_eq!(components.pairs, evaluator.pairs(), max_relative = 1e-12);
+        assert_eq!(components.bonds, evaluator.bonds());
+        assert_eq!(components.coulomb, 0.0);
+
+        let pair = |i, j| evaluator.pair(system.bond_path(i, j), system.distance(i, j), i, j);
+        assert_eq!(components.groups, ["A", "B"]);
+        assert_ulps_eq!(components.pairs_between_groups("A", "A").unwrap(), pair(0, 2));
+        assert_ulps_eq!(components.pairs_between_groups("B", "B").unwrap(), pair(1, 3));
+        let a_b = pair(0, 1) + pair(0, 3) + pair(1, 2) + pair(2, 3);
+        assert_ulps_eq!(components.pairs_between_groups("A", "B").unwrap(), a_b);
+        assert_ulps_eq!(components.pairs_between_groups("B", "A").unwrap(), a_b);
+        assert_eq!(components.pairs_between_groups("A", "C"), None);
+
+        let components = EnergyDecomposition { groups: EnergyGroups::Molecules }.compute(&system);
+        assert_eq!(components.groups, ["AB", "A", "B"]);
+        assert_ulps_eq!(components.pairs_between[(0, 0)], pair(0, 1));
+        assert_ulps_eq!(components.pairs_between[(0, 1)], pair(0, 2) + pair(1, 2));
+        assert_ulps_eq!(components.pairs_between[(1, 2)], pair(2, 3));
+        assert_eq!(components.pairs_between[(1, 1)], 0.0);
+
+        let mut sum = 0.0;
+        for a in 0..3 {
+            for b in a..3 {
+                sum += components.pairs_between[(a, b)];
+            }
+        }
+        assert_relative_eq!(sum, components.pairs, max_relative = 1e-12);
+    }
+
+    #[test]
+    fn energy_decomposition_same_names() {
+        let mut system = system_from_xyz(
+            "9
+            cell: 20.0
+            A 0.0 0.0 0.0
+            B 1.0 0.0 0.0
+            A 1.5 0.9 0.0
+            A 5.0 0.0 0.0
+            B 6.0 0.0 0.0
+            A 5.5 0.9 0.0
+            A 0.0 5.0 0.0
+            B 1.0 5.0 0.0
+            A 1.5 5.9 0.0
+            ",
+        );
+        // Two linear ABA molecules, and a cyclic one
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(1, 2).is_empty());
+        assert!(system.add_bond(3, 4).is_empty());
+        assert!(system.add_bond(4, 5).is_empty());
+        assert!(system.add_bond(3, 5).is_empty());
+        assert!(system.add_bond(6, 7).is_empty());
+        assert!(system.add_bond(7, 8).is_empty());
+        for &(a, b) in &[("A", "A"), ("A", "B"), ("B", "B")] {
+            system.set_pair_potential((a, b), PairInteraction::new(Box::new(LennardJones {
+                sigma: 1.0,
+                epsilon: units::from(1.0, "kJ/mol").unwrap(),
+            }), 3.0));
+        }
+
+        let components = EnergyDecomposition { groups: EnergyGroups::Molecules }.compute(&system);
+        assert_eq!(components.groups, ["ABA", "ABA-2"]);
+
+        let evaluator = system.energy_evaluator();
+        let pair = |i, j| evaluator.pair(system.bond_path(i, j), system.distance(i, j), i, j);
+        let linear = pair(0, 1) + pair(0, 2) + pair(1, 2) + pair(6, 7) + pair(6, 8) + pair(7, 8);
+        assert_ulps_eq!(components.pairs_between_groups("ABA", "ABA").unwrap(), linear);
+        let cyclic = pair(3, 4) + pair(3, 5) + pair(4, 5);
+        assert_ulps_eq!(components.pairs_between_groups("ABA-2", "ABA-2").unwrap(), cyclic);
+        assert_eq!(components.pairs_between_groups("ABA", "ABA-2").unwrap(), 0.0);
+    }
+
+    #[test]
+    fn lambda_derivative() {
+        fn alchemical_system(lambda: f64) -> System {
+            let mut system = system_from_xyz(
+                "3
+                cell: 20.0
+                Ar 0.0 0.0 0.0
+                Ar 3.4 0.0 0.0
+                Ar 0.0 3.1 1.2
+                ",
+            );
+            system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(Box::new(LennardJones {
+                sigma: 3.4,
+                epsilon: units::from(1.0, "kJ/mol").unwrap(),
+            }), 8.0));
+            system.set_alchemical_molecule(0, SoftCore {
+                lambda: lambda,
+                alpha: 0.5,
+                sigma: 3.4,
+            });
+            let mut coulomb = AlchemicalCoulomb::new(Box::new(Wolf::new(8.0)), 0, lambda);
+            coulomb.set_restriction(PairRestriction::InterMolecular);
+            system.set_coulomb_potential(Box::new(coulomb));
+            system.particles_mut().charge[0] = 0.5;
+            system.particles_mut().charge[1] = -0.8;
+            system.particles_mut().charge[2] = 0.3;
+            return system;
+        }
+
+        let eps = 1e-6;
+        let system = alchemical_system(0.4);
+        let plus = alchemical_system(0.4 + eps);
+        let minus = alchemical_system(0.4 - eps);
+
+        let finite_diff = (plus.energy_evaluator().pairs() - minus.energy_evaluator().pairs()) / (2.0 * eps);
+        assert_relative_eq!(LambdaDerivative::Vdw.compute(&system), finite_diff, epsilon = 1e-6);
+
+        let finite_diff = (plus.energy_evaluator().coulomb() - minus.energy_evaluator().coulomb()) / (2.0 * eps);
+        assert_relative_eq!(LambdaDerivative::Coulomb.compute(&system), finite_diff, epsilon = 1e-6);
+
+        let system = test_pairs_system();
+        assert_eq!(LambdaDerivative::Vdw.compute(&system), 0.0);
+        assert_eq!(LambdaDerivative::Coulomb.compute(&system), 0.0);
+    }
 }
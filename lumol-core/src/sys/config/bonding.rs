@@ -5,13 +5,13 @@ use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
 use std::ops::Range;
 
-use crate::{Angle, Bond, BondDistances, Dihedral};
+use crate::{Angle, Bond, BondDistances, Dihedral, Improper};
 use crate::Array2;
 
 
 /// The basic building block for a topology. A `Bonding` contains data about
-/// the connectivity (bonds, angles, dihedrals) between particles in a single
-/// molecule.
+/// the connectivity (bonds, angles, dihedrals, impropers) between particles in
+/// a single molecule.
 #[derive(Debug, Clone)]
 pub struct Bonding {
     /// All the bonds in the molecule.
@@ -21,6 +21,10 @@ pub struct Bonding {
     /// All the dihedral angles in the molecule. Rebuilt as needed from the
     /// bond list.
     dihedrals: HashSet<Dihedral>,
+    /// All the improper dihedral angles in the molecule, around particles
+    /// bonded to exactly three other particles. Rebuilt as needed from the
+    /// bond list.
+    impropers: HashSet<Improper>,
     /// Matrix of bond distances in the molecule. The item at index `i, j`
     /// encode the bond distance between the particles `i + self.first` and
     /// `j + self.first`
@@ -36,6 +40,7 @@ impl Bonding {
             bonds: HashSet::new(),
             angles: HashSet::new(),
             dihedrals: HashSet::new(),
+            impropers: HashSet::new(),
             distances: Array2::default((1, 1)),
             range: i..i + 1,
         }
@@ -74,10 +79,12 @@ impl Bonding {
         }
     }
 
-    /// Rebuild the full list of angles and dihedral angles from the list of bonds
+    /// Rebuild the full list of angles, dihedral angles and improper dihedral
+    /// angles from the list of bonds
     fn rebuild(&mut self) {
         self.angles.clear();
         self.dihedrals.clear();
+        self.impropers.clear();
         for bond1 in &self.bonds {
             // Find angles
             for bond2 in &self.bonds {
@@ -122,6 +129,27 @@ impl Bonding {
                 }
             }
         }
+
+        // Find improper dihedral angles. The neighbors are sorted by index
+        // here, and re-ordered to match the potential when computing the
+        // energy.
+        for center in self.indexes() {
+            let mut neighbors = self.bonds.iter().filter_map(|bond| {
+                if bond.i() == center {
+                    Some(bond.j())
+                } else if bond.j() == center {
+                    Some(bond.i())
+                } else {
+                    None
+                }
+            }).collect::<Vec<_>>();
+
+            if neighbors.len() == 3 {
+                neighbors.sort_unstable();
+                let improper = Improper::new(center, neighbors[0], neighbors[1], neighbors[2]);
+                let _ = self.impropers.insert(improper);
+            }
+        }
         self.rebuild_connections();
     }
 
@@ -163,8 +191,8 @@ impl Bonding {
             let _ = self.bonds.insert(bond);
         }
 
-        // Get angles and dihedrals from the other molecule, there is no need to
-        // rebuild these.
+        // Get angles, dihedrals and impropers from the other molecule, there
+        // is no need to rebuild these.
         for angle in other.angles {
             let _ = self.angles.insert(angle);
         }
@@ -173,6 +201,10 @@ impl Bonding {
             let _ = self.dihedrals.insert(dihedral);
         }
 
+        for improper in other.impropers {
+            let _ = self.impropers.insert(improper);
+        }
+
         self.rebuild_connections();
     }
 
@@ -217,6 +249,17 @@ impl Bonding {
             ));
         }
         self.dihedrals = new_dihedrals;
+
+        let mut new_impropers = HashSet::new();
+        for improper in &self.impropers {
+            let _ = new_impropers.insert(Improper::new(
+                improper.center().wrapping_add(delta),
+                improper.i().wrapping_add(delta),
+                improper.j().wrapping_add(delta),
+                improper.k().wrapping_add(delta),
+            ));
+        }
+        self.impropers = new_impropers;
     }
 
     /// Add a bond between the particles at indexes `i` and `j`. These particles
@@ -272,6 +315,11 @@ impl Bonding {
         &self.dihedrals
     }
 
+    /// Get the internal list of improper dihedrals
+    pub fn impropers(&self) -> &HashSet<Improper> {
+        &self.impropers
+    }
+
     /// Get the all the possible bond paths the particles `i` and `j` in this molecule
     pub fn bond_distances(&self, i: usize, j: usize) -> BondDistances {
         assert!(self.contains(i) && self.contains(j));
@@ -288,7 +336,7 @@ impl Bonding {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{Angle, Bond, BondDistances, Dihedral};
+    use crate::{Angle, Bond, BondDistances, Dihedral, Improper};
 
     #[test]
     fn translate_by() {
@@ -400,6 +448,9 @@ mod test {
             assert!(bonding.dihedrals().contains(dihedral));
         }
 
+        // Carbon atoms have four neighbors, there are no impropers
+        assert!(bonding.impropers().is_empty());
+
         assert!(bonding.bond_distances(0, 1).contains(BondDistances::ONE));
         assert!(bonding.bond_distances(1, 0).contains(BondDistances::ONE));
 
@@ -437,6 +488,33 @@ mod test {
         assert!(bonding.angles.contains(&Angle::new(0, 1, 2)));
     }
 
+    #[test]
+    fn impropers() {
+        // Create formaldehyde like this
+        //       O            1
+        //       ||           |
+        //       C            0
+        //     /   \        /   \
+        //    H     H      2     3
+        let mut bonding = Bonding::new(0);
+        for i in 1..4 {
+            bonding.merge_with(Bonding::new(i));
+        }
+        bonding.add_bond(0, 1);
+        bonding.add_bond(0, 2);
+        assert!(bonding.impropers().is_empty());
+
+        bonding.add_bond(0, 3);
+        assert_eq!(bonding.impropers().len(), 1);
+        assert!(bonding.impropers().contains(&Improper::new(0, 3, 1, 2)));
+
+        bonding.translate_by(4);
+        assert!(bonding.impropers().contains(&Improper::new(4, 5, 6, 7)));
+
+        bonding.remove_particle(7);
+        assert!(bonding.impropers().is_empty());
+    }
+
     #[test]
     fn remove_particle() {
         let mut bonding = Bonding::new(0);
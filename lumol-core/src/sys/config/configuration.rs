@@ -143,6 +143,42 @@ impl Configuration {
         }
     }
 
+    /// Get the list of `(core, drude)` particles indexes for all the Drude
+    /// particles in this configuration. The `is_drude` function is called
+    /// with a particle index, and should return `true` if this particle is a
+    /// Drude particle. The core is the only particle bonded to the Drude
+    /// particle, and Drude particles bonded to zero or multiple other
+    /// particles are ignored.
+    pub fn drude_pairs<F: Fn(usize) -> bool>(&self, is_drude: F) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for molecule in self.molecules() {
+            for drude in molecule.indexes() {
+                if !is_drude(drude) {
+                    continue;
+                }
+
+                let mut cores = molecule.bonds().iter().filter_map(|bond| {
+                    if bond.i() == drude {
+                        Some(bond.j())
+                    } else if bond.j() == drude {
+                        Some(bond.i())
+                    } else {
+                        None
+                    }
+                });
+
+                match (cores.next(), cores.next()) {
+                    (Some(core), None) => pairs.push((core, drude)),
+                    _ => warn_once!(
+                        "the Drude particle {} is not bonded to exactly one core particle, it will be ignored",
+                        self.particles().name[drude]
+                    ),
+                }
+            }
+        }
+        return pairs;
+    }
+
     /// Remove the molecule at index `i`
     pub fn remove_molecule(&mut self, molid: usize) {
         let molecule = self.bondings.remove(molid);
@@ -867,4 +903,22 @@ mod tests {
             configuration.molecule(4).hash()
         );
     }
+
+    #[test]
+    fn drude_pairs() {
+        let mut configuration = Configuration::new();
+        let mut molecule = Molecule::new(particle("O"));
+        molecule.add_particle_bonded_to(0, particle("D"));
+        molecule.add_particle_bonded_to(0, particle("H"));
+        configuration.add_molecule(molecule);
+        // Not bonded to any core
+        configuration.add_molecule(Molecule::new(particle("D")));
+
+        let names = configuration.particles().name;
+        let pairs = configuration.drude_pairs(|i| names[i] == "D");
+        assert_eq!(pairs, vec![(0, 1)]);
+
+        let pairs = configuration.drude_pairs(|_| false);
+        assert!(pairs.is_empty());
+    }
 }
@@ -3,6 +3,7 @@
 
 //! Data about bonds and angles in the system.
 use std::cmp::{max, min};
+use std::hash::{Hash, Hasher};
 
 use bitflags::bitflags;
 
@@ -138,6 +139,88 @@ impl Dihedral {
     }
 }
 
+/// An `Improper` dihedral angle formed by the particle at index `center`,
+/// bonded to the three particles at indexes `i`, `j` and `k`.
+///
+/// The value of the improper angle is the dihedral angle between the planes
+/// formed by `center, i, j` and `i, j, k`, and depends on the order of the
+/// particles. This order is kept as given on creation, but two `Improper`
+/// with the same central particle and the same three other particles in a
+/// different order compare equal, since they describe the same improper
+/// dihedral angle.
+#[derive(Clone, Copy, Debug)]
+pub struct Improper {
+    center: usize,
+    i: usize,
+    j: usize,
+    k: usize,
+}
+
+impl Improper {
+    /// Create a new `Improper` dihedral angle around the particle at index
+    /// `center`, using the particles at indexes `first`, `second` and `third`
+    pub fn new(center: usize, first: usize, second: usize, third: usize) -> Improper {
+        assert_ne!(center, first);
+        assert_ne!(center, second);
+        assert_ne!(center, third);
+        assert_ne!(first, second);
+        assert_ne!(first, third);
+        assert_ne!(second, third);
+        Improper {
+            center: center,
+            i: first,
+            j: second,
+            k: third,
+        }
+    }
+
+    /// Get the canonical representation of this improper dihedral angle,
+    /// with the non-central particles sorted by index.
+    fn canonical(&self) -> (usize, [usize; 3]) {
+        let mut others = [self.i, self.j, self.k];
+        others.sort_unstable();
+        (self.center, others)
+    }
+
+    /// Get the central particle in the improper dihedral angle
+    #[inline]
+    pub fn center(&self) -> usize {
+        self.center
+    }
+
+    /// Get the first non-central particle in the improper dihedral angle
+    #[inline]
+    pub fn i(&self) -> usize {
+        self.i
+    }
+
+    /// Get the second non-central particle in the improper dihedral angle
+    #[inline]
+    pub fn j(&self) -> usize {
+        self.j
+    }
+
+    /// Get the third non-central particle in the improper dihedral angle
+    #[inline]
+    pub fn k(&self) -> usize {
+        self.k
+    }
+}
+
+impl PartialEq for Improper {
+    fn eq(&self, other: &Improper) -> bool {
+        self.canonical() == other.canonical()
+    }
+}
+
+impl Eq for Improper {}
+
+impl Hash for Improper {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical().hash(state);
+    }
+}
+
 
 bitflags! {
     /// The `BondDistances` bitflag encode the topological distance between
@@ -166,6 +249,7 @@ impl Default for BondDistances {
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::collections::HashSet;
 
     #[test]
     fn bond() {
@@ -196,4 +280,21 @@ mod test {
         assert_eq!(dihedral.k, 6);
         assert_eq!(dihedral.m, 8);
     }
+
+    #[test]
+    fn improper() {
+        let improper = Improper::new(3, 8, 0, 6);
+        assert_eq!(improper.center, 3);
+        assert_eq!(improper.i, 8);
+        assert_eq!(improper.j, 0);
+        assert_eq!(improper.k, 6);
+
+        assert_eq!(improper, Improper::new(3, 6, 8, 0));
+        assert_ne!(improper, Improper::new(8, 3, 0, 6));
+
+        let mut impropers = HashSet::new();
+        assert!(impropers.insert(improper));
+        assert!(!impropers.insert(Improper::new(3, 0, 6, 8)));
+        assert!(impropers.insert(Improper::new(0, 3, 6, 8)));
+    }
 }
@@ -10,8 +10,8 @@ use std::f64::consts::PI;
 
 use rayon::prelude::*;
 
-use crate::BondPath;
-use crate::System;
+use crate::{AngleGeometry, BondPath};
+use crate::{Improper, System};
 
 /// An helper struct to evaluate energy components of a system.
 pub struct EnergyEvaluator<'a> {
@@ -45,10 +45,11 @@ impl<'a> EnergyEvaluator<'a> {
 
     /// Compute the energy of all the pairs in the system
     pub fn pairs(&self) -> f64 {
+        let neighbors = self.system.neighbors();
         let energies = (0..self.system.size()).into_par_iter().map(|i| {
             let mut local_energy = 0.0;
 
-            for j in (i + 1)..self.system.size() {
+            for j in neighbors.of(i) {
                 let r = self.system.nearest_image(i, j).norm();
                 let path = self.system.bond_path(i, j);
                 local_energy += self.pair(path, r, i, j);
@@ -99,11 +100,18 @@ impl<'a> EnergyEvaluator<'a> {
         return energy;
     }
 
-    /// Compute the energy associated with the angle `i, j, k` at angle `theta`
+    /// Compute the energy associated with the angle `i, j, k` with the given
+    /// `geometry`
     #[inline]
-    pub fn angle(&self, theta: f64, i: usize, j: usize, k: usize) -> f64 {
-        self.system.angle_potential(i, j, k)
-                   .map_or(0.0, |potential| potential.energy(theta))
+    pub fn angle(&self, geometry: &AngleGeometry, i: usize, j: usize, k: usize) -> f64 {
+        self.system.angle_potential(i, j, k).map_or(0.0, |potential| {
+            // angle potentials use the particle with the smallest kind first
+            if self.system.particles().kind[i] <= self.system.particles().kind[k] {
+                potential.angle_energy(geometry)
+            } else {
+                potential.angle_energy(&geometry.reversed())
+            }
+        })
     }
 
     /// Compute the energy of all the angles in the system
@@ -112,8 +120,13 @@ impl<'a> EnergyEvaluator<'a> {
         for molecule in self.system.molecules() {
             for angle in molecule.angles() {
                 let (i, j, k) = (angle.i(), angle.j(), angle.k());
-                let theta = self.system.angle(i, j, k);
-                energy += self.angle(theta, i, j, k);
+                let geometry = AngleGeometry {
+                    theta: self.system.angle(i, j, k),
+                    r_ij: self.system.distance(i, j),
+                    r_jk: self.system.distance(j, k),
+                    r_ik: self.system.distance(i, k),
+                };
+                energy += self.angle(&geometry, i, j, k);
             }
         }
         return energy;
@@ -140,6 +153,29 @@ impl<'a> EnergyEvaluator<'a> {
         return energy;
     }
 
+    /// Compute the energy associated with the `improper` dihedral angle
+    #[inline]
+    pub fn improper(&self, improper: &Improper) -> f64 {
+        match self.system.improper_potential(improper) {
+            Some((improper, potential)) => {
+                let (center, i, j, k) = (improper.center(), improper.i(), improper.j(), improper.k());
+                potential.energy(self.system.dihedral(center, i, j, k))
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Compute the energy of all the improper dihedral angles in the system
+    pub fn impropers(&self) -> f64 {
+        let mut energy = 0.0;
+        for molecule in self.system.molecules() {
+            for improper in molecule.impropers() {
+                energy += self.improper(improper);
+            }
+        }
+        return energy;
+    }
+
     /// Compute the energy of the electrostatic interactions
     #[inline]
     pub fn coulomb(&self) -> f64 {
@@ -166,7 +202,7 @@ mod tests {
     use crate::utils::system_from_xyz;
     use crate::units;
 
-    use approx::assert_ulps_eq;
+    use approx::{assert_ulps_eq, assert_relative_eq};
 
     fn testing_system() -> System {
         let mut system = system_from_xyz(
@@ -261,4 +297,87 @@ mod tests {
         let evaluator = EnergyEvaluator::new(&system);
         assert_ulps_eq!(evaluator.dihedrals(), units::from(1250.0, "kJ/mol").unwrap(), max_ulps = 15);
     }
+
+    #[test]
+    #[allow(clippy::unreadable_literal)]
+    fn impropers() {
+        let mut system = system_from_xyz(
+            "4
+            cell: 10.0
+            C 0.0 0.0 0.3
+            O 1.0 0.0 0.0
+            H -0.5 0.866 0.0
+            H -0.5 -0.866 0.0
+            ",
+        );
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(0, 2).is_empty());
+        assert!(system.add_bond(0, 3).is_empty());
+
+        let evaluator = EnergyEvaluator::new(&system);
+        assert_eq!(evaluator.impropers(), 0.0);
+
+        system.set_improper_potential(
+            ("C", "H", "O", "H"),
+            Box::new(Harmonic {
+                k: units::from(100.0, "kJ/mol/deg^2").unwrap(),
+                x0: 0.0,
+            }),
+        );
+
+        let evaluator = EnergyEvaluator::new(&system);
+        assert_relative_eq!(
+            evaluator.impropers(),
+            units::from(47939.43292976772, "kJ/mol").unwrap(),
+            max_relative = 1e-9
+        );
+    }
+
+    #[test]
+    #[allow(clippy::unreadable_literal)]
+    fn impropers_order() {
+        let potential = Harmonic {
+            k: units::from(100.0, "kJ/mol/deg^2").unwrap(),
+            x0: units::from(10.0, "deg").unwrap(),
+        };
+
+        let mut system = system_from_xyz(
+            "4
+            cell: 10.0
+            C 0.0 0.0 0.3
+            O 1.0 0.0 0.0
+            N -0.5 0.866 0.0
+            H -0.5 -0.866 0.2
+            ",
+        );
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(0, 2).is_empty());
+        assert!(system.add_bond(0, 3).is_empty());
+        system.set_improper_potential(("C", "O", "N", "H"), Box::new(potential));
+
+        // The same molecule, with the particles in a different order
+        let mut shuffled = system_from_xyz(
+            "4
+            cell: 10.0
+            H -0.5 -0.866 0.2
+            N -0.5 0.866 0.0
+            C 0.0 0.0 0.3
+            O 1.0 0.0 0.0
+            ",
+        );
+        assert!(shuffled.add_bond(2, 0).is_empty());
+        assert!(shuffled.add_bond(2, 1).is_empty());
+        assert!(shuffled.add_bond(2, 3).is_empty());
+        shuffled.set_improper_potential(("C", "O", "N", "H"), Box::new(potential));
+
+        let expected = units::from(55676.19037118263, "kJ/mol").unwrap();
+        assert_relative_eq!(EnergyEvaluator::new(&system).impropers(), expected, max_relative = 1e-9);
+        assert_relative_eq!(EnergyEvaluator::new(&shuffled).impropers(), expected, max_relative = 1e-9);
+
+        // The order of the particles in the potential definition changes the
+        // sign of the improper angle
+        system.set_improper_potential(("C", "N", "O", "H"), Box::new(potential));
+        let expected = units::from(8937.182064985798, "kJ/mol").unwrap();
+        assert_relative_eq!(EnergyEvaluator::new(&system).impropers(), expected, max_relative = 1e-9);
+    }
 }
@@ -5,19 +5,21 @@
 //! associations.
 
 use std::cmp::{max, min};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::f64;
 
 use log::warn;
 
-use crate::{AnglePotential, BondPotential, DihedralPotential, PairInteraction};
-use crate::{CoulombicPotential, GlobalPotential};
+use crate::{AnglePotential, BondPotential, DihedralPotential, ImproperPotential, PairInteraction};
+use crate::{AngleForces, AngleGeometry, CoulombicPotential, GlobalPotential, Potential};
+use crate::{LennardJones, MixingRule, SoftCore};
 use crate::ParticleKind;
 
 pub type PairKind = (ParticleKind, ParticleKind);
 pub type BondKind = (ParticleKind, ParticleKind);
 pub type AngleKind = (ParticleKind, ParticleKind, ParticleKind);
 pub type DihedralKind = (ParticleKind, ParticleKind, ParticleKind, ParticleKind);
+pub type ImproperKind = (ParticleKind, ParticleKind, ParticleKind, ParticleKind);
 
 /// Normalize pair indexes to get a canonical representation
 #[inline]
@@ -55,6 +57,75 @@ fn normalize_dihedral((i, j, k, m): DihedralKind) -> DihedralKind {
     }
 }
 
+/// Normalize improper indexes to get a canonical representation. The first
+/// kind is the central particle, and is left unchanged.
+#[inline]
+fn normalize_improper((center, i, j, k): ImproperKind) -> ImproperKind {
+    let mut others = [i, j, k];
+    others.sort_unstable();
+    (center, others[0], others[1], others[2])
+}
+
+/// Angle potential with the first and last particles exchanged, used when
+/// normalizing the angle kinds changes the order of the particles.
+#[derive(Clone)]
+struct ReversedAngle(Box<dyn AnglePotential>);
+
+impl AnglePotential for ReversedAngle {
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {
+        self.0.angle_energy(&geometry.reversed())
+    }
+
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+        self.0.angle_forces(&geometry.reversed()).reversed()
+    }
+}
+
+/// Sum of multiple angle potentials acting on the same angle, used to
+/// represent angles with cross terms.
+#[derive(Clone)]
+struct AngleSum(Vec<Box<dyn AnglePotential>>);
+
+impl AnglePotential for AngleSum {
+    fn angle_energy(&self, geometry: &AngleGeometry) -> f64 {
+        self.0.iter().map(|potential| potential.angle_energy(geometry)).sum()
+    }
+
+    fn angle_forces(&self, geometry: &AngleGeometry) -> AngleForces {
+        let mut forces = AngleForces {
+            theta: 0.0,
+            r_ij: 0.0,
+            r_jk: 0.0,
+            r_ik: 0.0,
+        };
+        for potential in &self.0 {
+            let partial = potential.angle_forces(geometry);
+            forces.theta += partial.theta;
+            forces.r_ij += partial.r_ij;
+            forces.r_jk += partial.r_jk;
+            forces.r_ik += partial.r_ik;
+        }
+        return forces;
+    }
+}
+
+/// Sum of multiple dihedral potentials acting on the same dihedral angle,
+/// used to represent multi-term dihedral interactions.
+#[derive(Clone)]
+struct DihedralSum(Vec<Box<dyn DihedralPotential>>);
+
+impl Potential for DihedralSum {
+    fn energy(&self, phi: f64) -> f64 {
+        self.0.iter().map(|potential| potential.energy(phi)).sum()
+    }
+
+    fn force(&self, phi: f64) -> f64 {
+        self.0.iter().map(|potential| potential.force(phi)).sum()
+    }
+}
+
+impl DihedralPotential for DihedralSum {}
+
 /// The `Interaction` type hold all data about the potentials in the system.
 ///
 /// Its main role is to store and provide access
@@ -66,12 +137,30 @@ pub struct Interactions {
     pub globals: Vec<Box<dyn GlobalPotential>>,
     /// Pair potentials
     pairs: BTreeMap<PairKind, PairInteraction>,
+    /// Lennard-Jones parameters for single particles kinds, used with the
+    /// mixing rule
+    lennard_jones: BTreeMap<ParticleKind, LennardJones>,
+    /// Mixing rule, and template for the pair interactions created with it
+    mixing: Option<(MixingRule, PairInteraction)>,
+    /// Pair potentials created with the mixing rule, for pairs without an
+    /// explicit pair potential
+    mixed_pairs: BTreeMap<PairKind, PairInteraction>,
+    /// Soft-core coupling for the pair interactions of the alchemical molecule
+    soft_core: Option<SoftCore>,
+    /// Pair potentials with soft-core coupling, used between the alchemical
+    /// molecule and the other molecules
+    alchemical_pairs: BTreeMap<PairKind, PairInteraction>,
     /// Bond potentials
     bonds: BTreeMap<BondKind, Box<dyn BondPotential>>,
     /// Angle potentials
     angles: BTreeMap<AngleKind, Box<dyn AnglePotential>>,
     /// Dihedral angles potentials
     dihedrals: BTreeMap<DihedralKind, Box<dyn DihedralPotential>>,
+    /// Improper dihedral angles potentials, together with the particles
+    /// kinds in the order used to define the potential
+    impropers: BTreeMap<ImproperKind, (ImproperKind, Box<dyn ImproperPotential>)>,
+    /// Particles kinds used for Drude particles
+    drudes: BTreeSet<ParticleKind>,
     /// Association particles names to particle kinds
     kinds: BTreeMap<String, ParticleKind>,
 }
@@ -83,9 +172,16 @@ impl Interactions {
             coulomb: None,
             globals: Vec::new(),
             pairs: BTreeMap::new(),
+            lennard_jones: BTreeMap::new(),
+            mixing: None,
+            mixed_pairs: BTreeMap::new(),
+            soft_core: None,
+            alchemical_pairs: BTreeMap::new(),
             bonds: BTreeMap::new(),
             angles: BTreeMap::new(),
             dihedrals: BTreeMap::new(),
+            impropers: BTreeMap::new(),
+            drudes: BTreeSet::new(),
             kinds: BTreeMap::new(),
         }
     }
@@ -107,6 +203,63 @@ impl Interactions {
         if self.pairs.insert(kind, potential).is_some() {
             warn!("replaced pair potential for ({}, {})", i, j);
         }
+        self.update_mixed_pairs();
+    }
+
+    /// Set the Lennard-Jones `parameters` for atoms with type `name`. These
+    /// parameters are combined with the mixing rule to create the pair
+    /// interactions without an explicit pair potential.
+    pub fn set_lennard_jones(&mut self, name: &str, parameters: LennardJones) {
+        let kind = self.get_kind(name);
+        if self.lennard_jones.insert(kind, parameters).is_some() {
+            warn!("replaced Lennard-Jones parameters for {}", name);
+        }
+        self.update_mixed_pairs();
+    }
+
+    /// Set the mixing `rule` used to create pair interactions from the
+    /// Lennard-Jones parameters of single atoms types. The created
+    /// interactions use the same cutoff, computation mode, tail corrections
+    /// and restriction as `template`.
+    pub fn set_mixing_rule(&mut self, rule: MixingRule, template: PairInteraction) {
+        self.mixing = Some((rule, template));
+        self.update_mixed_pairs();
+    }
+
+    /// Create the pair interactions for all the pairs of atoms types with
+    /// Lennard-Jones parameters and without an explicit pair potential.
+    fn update_mixed_pairs(&mut self) {
+        self.mixed_pairs.clear();
+        if let Some((rule, ref template)) = self.mixing {
+            for (&kind_i, lj_i) in &self.lennard_jones {
+                for (&kind_j, lj_j) in self.lennard_jones.range(kind_i..) {
+                    let kind = normalize_pair((kind_i, kind_j));
+                    if self.pairs.contains_key(&kind) {
+                        continue;
+                    }
+                    let potential = Box::new(rule.mix(lj_i, lj_j));
+                    let _ = self.mixed_pairs.insert(kind, template.with_potential(potential));
+                }
+            }
+        }
+        self.update_alchemical_pairs();
+    }
+
+    /// Set the soft-core coupling used for the pair interactions between the
+    /// alchemical molecule and the other molecules.
+    pub fn set_soft_core(&mut self, soft_core: SoftCore) {
+        self.soft_core = Some(soft_core);
+        self.update_alchemical_pairs();
+    }
+
+    /// Create the soft-core coupled version of all the pair interactions
+    fn update_alchemical_pairs(&mut self) {
+        self.alchemical_pairs.clear();
+        if let Some(soft_core) = self.soft_core {
+            for (&kind, pair) in self.pairs.iter().chain(&self.mixed_pairs) {
+                let _ = self.alchemical_pairs.insert(kind, pair.with_soft_core(soft_core));
+            }
+        }
     }
 
     /// Set the bond interaction `potential` for atoms with types `i` and `j`
@@ -119,12 +272,41 @@ impl Interactions {
 
     /// Set the angle interaction `potential` for atoms with types `i`, `j`, and `k`
     pub fn set_angle(&mut self, (i, j, k): (&str, &str, &str), potential: Box<dyn AnglePotential>) {
-        let kind = normalize_angle((self.get_kind(i), self.get_kind(j), self.get_kind(k)));
+        let (kind, potential) = self.normalize_angle_potential((i, j, k), potential);
         if self.angles.insert(kind, potential).is_some() {
             warn!("replaced angle potential for ({}, {}, {})", i, j, k);
         }
     }
 
+    /// Add the angle interaction `potential` for atoms with types `i`, `j`,
+    /// and `k`. Contrary to `set_angle`, any potential already associated
+    /// with these atoms types is kept, and the energy and forces for this
+    /// angle will be the sum of all potentials.
+    pub fn add_angle(&mut self, (i, j, k): (&str, &str, &str), potential: Box<dyn AnglePotential>) {
+        let (kind, potential) = self.normalize_angle_potential((i, j, k), potential);
+        let potential: Box<dyn AnglePotential> = match self.angles.remove(&kind) {
+            Some(existing) => Box::new(AngleSum(vec![existing, potential])),
+            None => potential,
+        };
+        let _ = self.angles.insert(kind, potential);
+    }
+
+    /// Get the normalized kind for the angle between atoms with types `i`,
+    /// `j`, and `k`; and the angle `potential` with the same particles order
+    /// as the normalized kind.
+    fn normalize_angle_potential(
+        &mut self,
+        (i, j, k): (&str, &str, &str),
+        potential: Box<dyn AnglePotential>,
+    ) -> (AngleKind, Box<dyn AnglePotential>) {
+        let (kind_i, kind_j, kind_k) = (self.get_kind(i), self.get_kind(j), self.get_kind(k));
+        if kind_i <= kind_k {
+            ((kind_i, kind_j, kind_k), potential)
+        } else {
+            ((kind_k, kind_j, kind_i), Box::new(ReversedAngle(potential)))
+        }
+    }
+
     /// Set the dihedral angle interaction `potential` for atoms with types
     /// `i`, `j`, `k`, and `m`.
     pub fn set_dihedral(&mut self, (i, j, k, m): (&str, &str, &str, &str), potential: Box<dyn DihedralPotential>) {
@@ -134,6 +316,41 @@ impl Interactions {
             warn!("replaced dihedral angle potential for ({}, {}, {}, {})", i, j, k, m);
         }
     }
+
+    /// Add the dihedral angle interaction `potential` for atoms with types
+    /// `i`, `j`, `k`, and `m`. Contrary to `set_dihedral`, any potential
+    /// already associated with these atoms types is kept, and the energy and
+    /// forces for this dihedral angle will be the sum of all potentials.
+    pub fn add_dihedral(&mut self, (i, j, k, m): (&str, &str, &str, &str), potential: Box<dyn DihedralPotential>) {
+        let kind = (self.get_kind(i), self.get_kind(j), self.get_kind(k), self.get_kind(m));
+        let kind = normalize_dihedral(kind);
+        let potential: Box<dyn DihedralPotential> = match self.dihedrals.remove(&kind) {
+            Some(existing) => Box::new(DihedralSum(vec![existing, potential])),
+            None => potential,
+        };
+        let _ = self.dihedrals.insert(kind, potential);
+    }
+
+    /// Set the improper dihedral angle interaction `potential` for a central
+    /// atom with type `center`, bonded to atoms with types `i`, `j`, and `k`.
+    /// The improper angle used with this potential is computed with the atoms
+    /// in this order.
+    pub fn set_improper(&mut self, (center, i, j, k): (&str, &str, &str, &str), potential: Box<dyn ImproperPotential>) {
+        let kind = (self.get_kind(center), self.get_kind(i), self.get_kind(j), self.get_kind(k));
+        if self.impropers.insert(normalize_improper(kind), (kind, potential)).is_some() {
+            warn!("replaced improper dihedral angle potential for ({}, {}, {}, {})", center, i, j, k);
+        }
+    }
+
+    /// Mark the atoms with type `name` as Drude particles. Each Drude
+    /// particle should be bonded to a single core particle, usually with an
+    /// harmonic bond potential.
+    pub fn add_drude(&mut self, name: &str) {
+        let kind = self.get_kind(name);
+        if !self.drudes.insert(kind) {
+            warn!("{} was already registered as a Drude particle", name);
+        }
+    }
 }
 
 
@@ -141,7 +358,15 @@ impl Interactions {
     /// Get the pair interactions corresponding to the `pair`, if any exists.
     pub fn pair(&self, pair: PairKind) -> Option<&PairInteraction> {
         let kind = normalize_pair(pair);
-        self.pairs.get(&kind)
+        self.pairs.get(&kind).or_else(|| self.mixed_pairs.get(&kind))
+    }
+
+    /// Get the soft-core coupled pair interactions corresponding to the
+    /// `pair`, if any exists. These interactions are used between the
+    /// alchemical molecule and the other molecules.
+    pub fn alchemical_pair(&self, pair: PairKind) -> Option<&PairInteraction> {
+        let kind = normalize_pair(pair);
+        self.alchemical_pairs.get(&kind)
     }
 
     /// Get the bond interactions corresponding to the `bond`, if any exists.
@@ -151,6 +376,9 @@ impl Interactions {
     }
 
     /// Get the angle interactions corresponding to the `angle`, if any exists.
+    ///
+    /// The potential expects the `AngleGeometry` to be computed with the
+    /// particle with the smallest kind first.
     pub fn angle(&self, angle: AngleKind) -> Option<&dyn AnglePotential> {
         let kind = normalize_angle(angle);
         self.angles.get(&kind).map(|potential| &**potential)
@@ -162,6 +390,41 @@ impl Interactions {
         self.dihedrals.get(&kind).map(|potential| &**potential)
     }
 
+    /// Get the improper dihedral interactions corresponding to the
+    /// `improper`, if any exists. The first kind is the central particle.
+    ///
+    /// The potential expects the improper angle to be computed with the
+    /// particles in the same order as the kinds returned with it.
+    pub fn improper(&self, improper: ImproperKind) -> Option<(ImproperKind, &dyn ImproperPotential)> {
+        let kind = normalize_improper(improper);
+        self.impropers.get(&kind).map(|(order, potential)| (*order, &**potential))
+    }
+
+    /// Check if the particles with the given `kind` are Drude particles
+    pub fn is_drude(&self, kind: ParticleKind) -> bool {
+        self.drudes.contains(&kind)
+    }
+
+    /// Check if any particle kind was registered as Drude particle
+    pub(crate) fn has_drudes(&self) -> bool {
+        !self.drudes.is_empty()
+    }
+
+    /// Get the maximal cutoff of the pair interactions. This returns `None`
+    /// if there is no pair interactions, or if any of the pair interactions
+    /// does not have a finite cutoff.
+    pub(crate) fn pairs_cutoff(&self) -> Option<f64> {
+        let mut maximum_cutoff = None;
+        for pair in self.pairs.values().chain(self.mixed_pairs.values()) {
+            let cutoff = pair.cutoff();
+            if !cutoff.is_finite() {
+                return None;
+            }
+            maximum_cutoff = Some(maximum_cutoff.map_or(cutoff, |max| f64::max(max, cutoff)));
+        }
+        return maximum_cutoff;
+    }
+
     /// Get maximum cutoff from `coulomb`, `pairs` and `global` interactons.
     pub fn maximum_cutoff(&self) -> Option<f64> {
         // Coulomb potential, return cutoff
@@ -185,6 +448,7 @@ impl Interactions {
 
         // Pair interactions, return maximum cutoff
         let pairs_cutoff = self.pairs.values()
+                               .chain(self.mixed_pairs.values())
                                .map(|pair| pair.cutoff())
                                .fold(f64::NAN, f64::max);
 
@@ -201,7 +465,9 @@ impl Interactions {
 mod test {
     use super::*;
 
-    use crate::{NullPotential, PairInteraction, Wolf};
+    use crate::{LennardJones, MixingRule, NullPotential, PairInteraction, Torsion, Wolf};
+    use crate::{BondBond, Harmonic};
+    use approx::assert_ulps_eq;
     use crate::ParticleKind as Kind;
 
     #[test]
@@ -343,6 +609,59 @@ mod test {
         assert!(interactions.pair((Kind(55), Kind(55))).is_none());
     }
 
+    #[test]
+    fn mixing() {
+        let mut interactions = Interactions::new();
+        let lj_a = LennardJones {sigma: 2.0, epsilon: 1.0};
+        let lj_b = LennardJones {sigma: 4.0, epsilon: 9.0};
+        interactions.set_lennard_jones("A", lj_a);
+        interactions.set_lennard_jones("B", lj_b);
+        // No mixing rule yet
+        assert!(interactions.pair((Kind(0), Kind(1))).is_none());
+        assert_eq!(interactions.maximum_cutoff(), None);
+
+        let template = PairInteraction::new(Box::new(NullPotential), 10.0);
+        interactions.set_mixing_rule(MixingRule::LorentzBerthelot, template);
+        assert_eq!(interactions.maximum_cutoff(), Some(10.0));
+
+        let expected = PairInteraction::new(Box::new(lj_a), 10.0);
+        assert_eq!(interactions.pair((Kind(0), Kind(0))).unwrap().energy(2.5), expected.energy(2.5));
+
+        let mixed = MixingRule::LorentzBerthelot.mix(&lj_a, &lj_b);
+        let expected = PairInteraction::new(Box::new(mixed), 10.0);
+        assert_eq!(interactions.pair((Kind(0), Kind(1))).unwrap().energy(2.5), expected.energy(2.5));
+        assert_eq!(interactions.pair((Kind(1), Kind(0))).unwrap().energy(2.5), expected.energy(2.5));
+
+        // Explicit pairs take precedence over the mixing rule
+        interactions.set_pair(("A", "B"), PairInteraction::new(Box::new(NullPotential), 5.0));
+        assert_eq!(interactions.pair((Kind(0), Kind(1))).unwrap().energy(2.5), 0.0);
+        assert_eq!(interactions.pair((Kind(0), Kind(1))).unwrap().cutoff(), 5.0);
+
+        // Atoms without Lennard-Jones parameters do not get mixed pairs
+        interactions.set_bond(("A", "C"), Box::new(NullPotential));
+        assert!(interactions.pair((Kind(0), Kind(2))).is_none());
+    }
+
+    #[test]
+    fn alchemical_pairs() {
+        let mut interactions = Interactions::new();
+        let lj = LennardJones {sigma: 2.0, epsilon: 1.0};
+        interactions.set_pair(("A", "B"), PairInteraction::new(Box::new(lj), 10.0));
+        assert!(interactions.alchemical_pair((Kind(0), Kind(1))).is_none());
+
+        let soft_core = SoftCore {lambda: 0.0, alpha: 0.5, sigma: 2.0};
+        interactions.set_soft_core(soft_core);
+        let pair = interactions.alchemical_pair((Kind(1), Kind(0))).unwrap();
+        assert_eq!(pair.energy(2.5), 0.0);
+
+        // Pairs added after the soft-core coupling are also coupled
+        interactions.set_lennard_jones("A", lj);
+        interactions.set_lennard_jones("C", lj);
+        interactions.set_mixing_rule(MixingRule::LorentzBerthelot, PairInteraction::new(Box::new(NullPotential), 10.0));
+        assert!(interactions.pair((Kind(0), Kind(2))).unwrap().energy(2.5) < 0.0);
+        assert_eq!(interactions.alchemical_pair((Kind(0), Kind(2))).unwrap().energy(2.5), 0.0);
+    }
+
     #[test]
     fn bonds() {
         let mut interactions = Interactions::new();
@@ -381,6 +700,37 @@ mod test {
         assert!(interactions.angle((Kind(55), Kind(55), Kind(55))).is_none());
     }
 
+    #[test]
+    fn angles_cross_terms() {
+        let mut interactions = Interactions::new();
+        interactions.set_angle(("A", "B", "C"), Box::new(NullPotential));
+
+        // The particles order is reversed by the normalization
+        let bond_bond = BondBond { k: 1.0, r1: 1.0, r2: 2.2 };
+        interactions.set_angle(("C", "B", "A"), Box::new(bond_bond));
+        let potential = interactions.angle((Kind(0), Kind(1), Kind(2))).unwrap();
+
+        let geometry = AngleGeometry { theta: 2.0, r_ij: 2.5, r_jk: 1.5, r_ik: 3.0 };
+        assert_ulps_eq!(potential.angle_energy(&geometry), bond_bond.angle_energy(&geometry.reversed()));
+        let forces = potential.angle_forces(&geometry);
+        assert_ulps_eq!(forces.r_ij, -0.5, epsilon = 1e-12);
+        assert_ulps_eq!(forces.r_jk, -0.3, epsilon = 1e-12);
+
+        // Adding more potentials
+        let harmonic = Harmonic { k: 3.0, x0: 1.8 };
+        interactions.add_angle(("A", "B", "C"), Box::new(harmonic));
+        let potential = interactions.angle((Kind(2), Kind(1), Kind(0))).unwrap();
+        assert_ulps_eq!(
+            potential.angle_energy(&geometry),
+            bond_bond.angle_energy(&geometry.reversed()) + harmonic.energy(2.0)
+        );
+        let forces = potential.angle_forces(&geometry);
+        assert_ulps_eq!(forces.theta, harmonic.force(2.0));
+        assert_ulps_eq!(forces.r_ij, -0.5, epsilon = 1e-12);
+        assert_ulps_eq!(forces.r_jk, -0.3, epsilon = 1e-12);
+        assert_ulps_eq!(forces.r_ik, 0.0);
+    }
+
     #[test]
     fn dihedrals() {
         let mut interactions = Interactions::new();
@@ -406,6 +756,66 @@ mod test {
         assert!(interactions.dihedral((Kind(55), Kind(55), Kind(55), Kind(55))).is_none());
     }
 
+    #[test]
+    fn multiple_dihedrals() {
+        let mut interactions = Interactions::new();
+
+        let first = Torsion { k: 2.0, delta: 0.0, n: 1 };
+        let second = Torsion { k: 3.0, delta: 0.0, n: 3 };
+        interactions.add_dihedral(("A", "B", "B", "A"), Box::new(first));
+        interactions.add_dihedral(("A", "B", "B", "A"), Box::new(second));
+
+        let potential = interactions.dihedral((Kind(0), Kind(1), Kind(1), Kind(0))).unwrap();
+        for &phi in &[0.0, 0.3, 1.2, 2.5] {
+            assert_ulps_eq!(potential.energy(phi), first.energy(phi) + second.energy(phi));
+            assert_ulps_eq!(potential.force(phi), first.force(phi) + second.force(phi));
+        }
+
+        // set_dihedral replaces all the potentials
+        interactions.set_dihedral(("A", "B", "B", "A"), Box::new(first));
+        let potential = interactions.dihedral((Kind(0), Kind(1), Kind(1), Kind(0))).unwrap();
+        assert_ulps_eq!(potential.energy(0.3), first.energy(0.3));
+    }
+
+    #[test]
+    fn impropers() {
+        let mut interactions = Interactions::new();
+
+        interactions.set_improper(("A", "B", "C", "D"), Box::new(NullPotential));
+        assert!(interactions.improper((Kind(0), Kind(1), Kind(2), Kind(3))).is_some());
+        assert!(interactions.improper((Kind(0), Kind(3), Kind(1), Kind(2))).is_some());
+        assert!(interactions.improper((Kind(0), Kind(2), Kind(3), Kind(1))).is_some());
+
+        // The central particle is different
+        assert!(interactions.improper((Kind(1), Kind(0), Kind(2), Kind(3))).is_none());
+
+        interactions.set_improper(("B", "A", "A", "C"), Box::new(NullPotential));
+        assert!(interactions.improper((Kind(1), Kind(0), Kind(0), Kind(2))).is_some());
+        assert!(interactions.improper((Kind(1), Kind(2), Kind(0), Kind(0))).is_some());
+
+        // The order used to define the potential is kept
+        let (order, _) = interactions.improper((Kind(0), Kind(3), Kind(1), Kind(2))).unwrap();
+        assert_eq!(order, (Kind(0), Kind(1), Kind(2), Kind(3)));
+        let (order, _) = interactions.improper((Kind(1), Kind(2), Kind(0), Kind(0))).unwrap();
+        assert_eq!(order, (Kind(1), Kind(0), Kind(0), Kind(2)));
+
+        // 'out of bounds' kinds
+        assert!(interactions.improper((Kind(55), Kind(55), Kind(55), Kind(55))).is_none());
+    }
+
+    #[test]
+    fn drudes() {
+        let mut interactions = Interactions::new();
+        assert!(!interactions.has_drudes());
+
+        interactions.add_drude("DO");
+        assert!(interactions.has_drudes());
+        let drude = interactions.get_kind("DO");
+        let core = interactions.get_kind("O");
+        assert!(interactions.is_drude(drude));
+        assert!(!interactions.is_drude(core));
+    }
+
     #[test]
     fn test_maximum_cutoff() {
         let mut interactions = Interactions::new();
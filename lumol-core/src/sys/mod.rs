@@ -19,6 +19,10 @@ pub use self::energy::EnergyEvaluator;
 mod cache;
 pub use self::cache::EnergyCache;
 
+mod neighbors;
+pub use self::neighbors::NeighborList;
+pub(crate) use self::neighbors::Neighbors;
+
 mod chfl;
 pub use chemfiles::Error as TrajectoryError;
 pub use self::chfl::{OpenMode, Trajectory, TrajectoryBuilder};
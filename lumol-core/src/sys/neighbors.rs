@@ -0,0 +1,568 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Neighbor lists for the non-bonded pair interactions.
+//!
+//! The neighbor list is built using linked cells, and contains all the pairs
+//! of particles closer than the maximal pair cutoff plus a skin distance. The
+//! list is only rebuilt when a particle moved by more than half the skin since
+//! the last rebuild, or when the unit cell changed. This makes the evaluation
+//! of pair interactions scale linearly with the number of particles.
+use std::ops::Range;
+use std::slice;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{Configuration, UnitCell, CellShape, Vector3D};
+
+/// Default skin distance used by the neighbor lists, in Angstrom
+const DEFAULT_SKIN: f64 = 1.0;
+
+/// Verlet neighbor list, built with linked cells.
+///
+/// This type uses inner mutability to allow the rebuilding of the list when
+/// computing properties of a `System`.
+#[derive(Debug)]
+pub struct NeighborList {
+    /// Skin distance added to the cutoff when building the list
+    skin: f64,
+    /// The actual list
+    list: RwLock<VerletList>,
+}
+
+impl NeighborList {
+    /// Create a new empty `NeighborList` with the given `skin` distance.
+    pub fn new(skin: f64) -> NeighborList {
+        assert!(skin >= 0.0, "the skin distance of neighbor lists must be positive");
+        NeighborList {
+            skin: skin,
+            list: RwLock::new(VerletList::new()),
+        }
+    }
+
+    /// Get the skin distance used when building this neighbor list
+    pub fn skin(&self) -> f64 {
+        self.skin
+    }
+
+    /// Get read access to the list
+    fn read(&self) -> RwLockReadGuard<'_, VerletList> {
+        self.list.read().expect("neighbor list lock is poisoned")
+    }
+
+    /// Get write access to the list
+    fn write(&self) -> RwLockWriteGuard<'_, VerletList> {
+        self.list.write().expect("neighbor list lock is poisoned")
+    }
+
+    /// Get the neighbors of all the particles in the `configuration`, for
+    /// pair interactions with the given maximal `cutoff`. If `cutoff` is
+    /// `None`, the returned neighbors contains all the pairs of particles.
+    ///
+    /// The list is rebuilt as needed before being returned.
+    pub(crate) fn neighbors(&self, configuration: &Configuration, cutoff: Option<f64>) -> Neighbors<'_> {
+        let cutoff = match cutoff {
+            Some(cutoff) => cutoff,
+            None => {
+                return Neighbors {
+                    list: None,
+                    size: configuration.size(),
+                };
+            }
+        };
+
+        if self.read().need_rebuild(configuration, cutoff + self.skin, self.skin) {
+            self.write().rebuild(configuration, cutoff + self.skin);
+        }
+
+        return Neighbors {
+            list: Some(self.read()),
+            size: configuration.size(),
+        };
+    }
+}
+
+impl Default for NeighborList {
+    fn default() -> NeighborList {
+        NeighborList::new(DEFAULT_SKIN)
+    }
+}
+
+impl Clone for NeighborList {
+    fn clone(&self) -> NeighborList {
+        // The list itself will be rebuilt when needed
+        NeighborList::new(self.skin)
+    }
+}
+
+/// Neighbors of all the particles in a configuration
+pub(crate) struct Neighbors<'a> {
+    /// The Verlet list, or `None` if all the pairs should be used
+    list: Option<RwLockReadGuard<'a, VerletList>>,
+    /// Number of particles in the configuration
+    size: usize,
+}
+
+impl<'a> Neighbors<'a> {
+    /// Get an iterator over all the neighbors `j` of the particle `i` such as
+    /// `j > i`.
+    pub fn of(&self, i: usize) -> NeighborsIter<'_> {
+        match self.list {
+            Some(ref list) => NeighborsIter::List(list.neighbors[i].iter()),
+            None => NeighborsIter::All((i + 1)..self.size),
+        }
+    }
+}
+
+/// Iterator over the neighbors of a given particle
+pub(crate) enum NeighborsIter<'a> {
+    /// Using all the particles after the current one
+    All(Range<usize>),
+    /// Using the neighbors in a Verlet list
+    List(slice::Iter<'a, usize>),
+}
+
+impl<'a> Iterator for NeighborsIter<'a> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        match *self {
+            NeighborsIter::All(ref mut range) => range.next(),
+            NeighborsIter::List(ref mut iter) => iter.next().cloned(),
+        }
+    }
+}
+
+/// Verlet list data
+#[derive(Debug)]
+struct VerletList {
+    /// For each particle `i`, the particles `j > i` in the list
+    neighbors: Vec<Vec<usize>>,
+    /// Positions of the particles when the list was built
+    positions: Vec<Vector3D>,
+    /// Unit cell when the list was built
+    cell: UnitCell,
+    /// Cutoff (including the skin) used to build the list
+    cutoff: f64,
+    /// Is this list initialized?
+    initialized: bool,
+}
+
+impl VerletList {
+    fn new() -> VerletList {
+        VerletList {
+            neighbors: Vec::new(),
+            positions: Vec::new(),
+            cell: UnitCell::infinite(),
+            cutoff: 0.0,
+            initialized: false,
+        }
+    }
+
+    /// Check if this list needs to be rebuilt for the given `configuration`
+    /// and `cutoff`.
+    fn need_rebuild(&self, configuration: &Configuration, cutoff: f64, skin: f64) -> bool {
+        if !self.initialized {
+            return true;
+        }
+
+        if f64::abs(self.cutoff - cutoff) > 1e-12 || self.cell != configuration.cell {
+            return true;
+        }
+
+        let positions = configuration.particles().position;
+        if positions.len() != self.positions.len() {
+            return true;
+        }
+
+        // Two particles moving in opposite directions by half the skin can
+        // get inside the cutoff sphere.
+        let max_displacement2 = 0.25 * skin * skin;
+        for (position, reference) in zip!(positions, &self.positions) {
+            if (position - reference).norm2() > max_displacement2 {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    /// Rebuild this list for the given `configuration` and `cutoff`
+    fn rebuild(&mut self, configuration: &Configuration, cutoff: f64) {
+        let positions = configuration.particles().position;
+        let cell = configuration.cell;
+
+        self.positions.clear();
+        self.positions.extend_from_slice(positions);
+        self.cell = cell;
+        self.cutoff = cutoff;
+        self.initialized = true;
+
+        self.neighbors.resize_with(positions.len(), Vec::new);
+        for neighbors in &mut self.neighbors {
+            neighbors.clear();
+        }
+
+        match LinkedCells::new(&cell, positions, cutoff) {
+            Some(cells) => self.build_with_cells(&cells),
+            None => self.build_all_pairs(),
+        }
+    }
+
+    /// Build the list by checking all the pairs of particles
+    fn build_all_pairs(&mut self) {
+        let natoms = self.positions.len();
+        for i in 0..natoms {
+            for j in (i + 1)..natoms {
+                if is_within(&self.cell, &self.positions[i], &self.positions[j], self.cutoff) {
+                    self.neighbors[i].push(j);
+                }
+            }
+        }
+    }
+
+    /// Build the list using the given linked `cells`
+    fn build_with_cells(&mut self, cells: &LinkedCells) {
+        for i in 0..self.positions.len() {
+            for cell in cells.neighbors_cells(cells.particle_cell[i]) {
+                for &j in &cells.particles[cell] {
+                    if j > i && is_within(&self.cell, &self.positions[i], &self.positions[j], self.cutoff) {
+                        self.neighbors[i].push(j);
+                    }
+                }
+            }
+            self.neighbors[i].sort_unstable();
+        }
+    }
+}
+
+/// Check if the particles at `ri` and `rj` are closer than `cutoff`, taking
+/// all the periodic images into account.
+fn is_within(cell: &UnitCell, ri: &Vector3D, rj: &Vector3D, cutoff: f64) -> bool {
+    let cutoff2 = cutoff * cutoff;
+    let mut rij = rj - ri;
+    cell.vector_image(&mut rij);
+    if rij.norm2() < cutoff2 {
+        return true;
+    }
+
+    if cell.shape() == CellShape::Triclinic {
+        // The nearest image convention is not exact for very skewed cells,
+        // check the neighboring images too.
+        for &a in &[-1.0, 0.0, 1.0] {
+            for &b in &[-1.0, 0.0, 1.0] {
+                for &c in &[-1.0, 0.0, 1.0] {
+                    let image = rij + cell.cartesian(&Vector3D::new(a, b, c));
+                    if image.norm2() < cutoff2 {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+    return false;
+}
+
+/// Get the widths of the unit `cell`, i.e. the distances between opposite
+/// faces of the cell. For triclinic cells, these are smaller than the lengths
+/// of the cell vectors.
+fn widths(cell: &UnitCell) -> Vector3D {
+    let matrix = cell.matrix();
+    let a = Vector3D::new(matrix[0][0], matrix[1][0], matrix[2][0]);
+    let b = Vector3D::new(matrix[0][1], matrix[1][1], matrix[2][1]);
+    let c = Vector3D::new(matrix[0][2], matrix[1][2], matrix[2][2]);
+    let volume = cell.volume();
+    return Vector3D::new(
+        volume / (b ^ c).norm(),
+        volume / (c ^ a).norm(),
+        volume / (a ^ b).norm(),
+    );
+}
+
+/// Linked cells decomposition of a configuration. The cells are defined in
+/// fractional coordinates, and can be used with any unit cell.
+struct LinkedCells {
+    /// Number of cells in each direction
+    n_cells: [usize; 3],
+    /// Are the cells periodic?
+    periodic: bool,
+    /// Particles in each cell
+    particles: Vec<Vec<usize>>,
+    /// The cell containing each particle
+    particle_cell: Vec<usize>,
+}
+
+impl LinkedCells {
+    /// Create the linked cells for the given unit `cell` and `positions`,
+    /// with cells wider than `cutoff` in all directions. This returns `None`
+    /// if the cell is too small to use linked cells.
+    fn new(cell: &UnitCell, positions: &[Vector3D], cutoff: f64) -> Option<LinkedCells> {
+        if positions.is_empty() {
+            return None;
+        }
+
+        let (fractional, widths, periodic) = if cell.is_infinite() {
+            // Use the bounding box of the particles as a non-periodic cell
+            let mut min = positions[0];
+            let mut max = positions[0];
+            for position in positions {
+                for k in 0..3 {
+                    min[k] = f64::min(min[k], position[k]);
+                    max[k] = f64::max(max[k], position[k]);
+                }
+            }
+            // Ensure that the fractional coordinates stays in [0, 1)
+            let lengths = (max - min) + Vector3D::new(1e-6, 1e-6, 1e-6);
+            let fractional = positions.iter().map(|position| {
+                let delta = position - min;
+                Vector3D::new(delta[0] / lengths[0], delta[1] / lengths[1], delta[2] / lengths[2])
+            }).collect::<Vec<_>>();
+            (fractional, lengths, false)
+        } else {
+            let fractional = positions.iter().map(|position| {
+                let mut fractional = cell.fractional(position);
+                fractional[0] -= f64::floor(fractional[0]);
+                fractional[1] -= f64::floor(fractional[1]);
+                fractional[2] -= f64::floor(fractional[2]);
+                fractional
+            }).collect::<Vec<_>>();
+            (fractional, widths(cell), true)
+        };
+
+        let mut n_cells = [0; 3];
+        for i in 0..3 {
+            n_cells[i] = usize::max(f64::floor(widths[i] / cutoff) as usize, 1);
+            if periodic && n_cells[i] < 3 {
+                // With less than 3 cells, the same neighboring cell would
+                // be visited multiple times.
+                return None;
+            }
+        }
+
+        let total = n_cells[0] * n_cells[1] * n_cells[2];
+        if total == 1 || total > 8 * positions.len() {
+            // No speedup to expect from the linked cells here
+            return None;
+        }
+
+        let mut particles = vec![Vec::new(); total];
+        let mut particle_cell = Vec::with_capacity(positions.len());
+        for (i, fractional) in fractional.iter().enumerate() {
+            let mut index = [0; 3];
+            for k in 0..3 {
+                let value = f64::floor(fractional[k] * n_cells[k] as f64) as usize;
+                index[k] = usize::min(value, n_cells[k] - 1);
+            }
+            let cell = index[0] + n_cells[0] * (index[1] + n_cells[1] * index[2]);
+            particles[cell].push(i);
+            particle_cell.push(cell);
+        }
+
+        Some(LinkedCells {
+            n_cells: n_cells,
+            periodic: periodic,
+            particles: particles,
+            particle_cell: particle_cell,
+        })
+    }
+
+    /// Get the list of cells neighboring the cell at index `cell`, including
+    /// the cell itself.
+    fn neighbors_cells(&self, cell: usize) -> Vec<usize> {
+        let [na, nb, nc] = self.n_cells;
+        let index = [cell % na, (cell / na) % nb, cell / (na * nb)];
+
+        let mut cells = Vec::with_capacity(27);
+        for da in -1..=1 {
+            for db in -1..=1 {
+                for dc in -1..=1 {
+                    let a = self.shift(index[0], da, na);
+                    let b = self.shift(index[1], db, nb);
+                    let c = self.shift(index[2], dc, nc);
+                    if let (Some(a), Some(b), Some(c)) = (a, b, c) {
+                        let neighbor = a + na * (b + nb * c);
+                        if !cells.contains(&neighbor) {
+                            cells.push(neighbor);
+                        }
+                    }
+                }
+            }
+        }
+        return cells;
+    }
+
+    /// Shift the cell index `index` by `delta` in a direction containing `n`
+    /// cells, taking periodicity into account.
+    fn shift(&self, index: usize, delta: isize, n: usize) -> Option<usize> {
+        let shifted = index as isize + delta;
+        if self.periodic {
+            Some(shifted.rem_euclid(n as isize) as usize)
+        } else if shifted < 0 || shifted >= n as isize {
+            None
+        } else {
+            Some(shifted as usize)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Molecule, Particle, UnitCell};
+
+    fn testing_configuration(cell: UnitCell) -> Configuration {
+        let mut configuration = Configuration::new();
+        configuration.cell = cell;
+        // Pseudo-random positions inside the cell
+        let mut seed = 42_u64;
+        let mut random = || {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (seed >> 11) as f64 / (1_u64 << 53) as f64
+        };
+
+        for _ in 0..500 {
+            let fractional = Vector3D::new(random(), random(), random());
+            let position = if cell.is_infinite() {
+                30.0 * fractional
+            } else {
+                cell.cartesian(&fractional)
+            };
+            configuration.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+        }
+        return configuration;
+    }
+
+    fn brute_force(configuration: &Configuration, cutoff: f64) -> Vec<Vec<usize>> {
+        let mut pairs = vec![Vec::new(); configuration.size()];
+        for i in 0..configuration.size() {
+            for j in (i + 1)..configuration.size() {
+                if configuration.nearest_image(i, j).norm() < cutoff {
+                    pairs[i].push(j);
+                }
+            }
+        }
+        return pairs;
+    }
+
+    fn check_neighbors(configuration: &Configuration, list: &NeighborList, cutoff: f64) {
+        let expected = brute_force(configuration, cutoff);
+        let neighbors = list.neighbors(configuration, Some(cutoff));
+        for i in 0..configuration.size() {
+            let actual = neighbors.of(i).collect::<Vec<_>>();
+            // All the pairs inside the cutoff must be in the list
+            for j in &expected[i] {
+                assert!(actual.contains(j), "missing pair ({}, {})", i, j);
+            }
+            // And no pair should be much further than cutoff + skin
+            for &j in &actual {
+                assert!(j > i);
+                assert!(configuration.nearest_image(i, j).norm() < cutoff + 2.0 * list.skin());
+            }
+        }
+    }
+
+    #[test]
+    fn orthorhombic() {
+        let configuration = testing_configuration(UnitCell::ortho(25.0, 30.0, 35.0));
+        let list = NeighborList::new(1.0);
+        check_neighbors(&configuration, &list, 5.0);
+    }
+
+    #[test]
+    fn triclinic() {
+        let configuration = testing_configuration(UnitCell::triclinic(25.0, 30.0, 35.0, 80.0, 100.0, 65.0));
+        let list = NeighborList::new(1.0);
+        check_neighbors(&configuration, &list, 5.0);
+    }
+
+    #[test]
+    fn skewed_triclinic() {
+        // The distance between the faces of this cell is 15 A along the
+        // first two vectors, while the vectors are 30 A long.
+        let cell = UnitCell::triclinic(30.0, 30.0, 30.0, 90.0, 90.0, 30.0);
+        let mut configuration = testing_configuration(cell);
+        let widths = widths(&cell);
+        assert_ulps_eq!(widths[0], 15.0, epsilon = 1e-12);
+        assert_ulps_eq!(widths[1], 15.0, epsilon = 1e-12);
+        assert_ulps_eq!(widths[2], 30.0, epsilon = 1e-12);
+
+        let cutoff = 4.9;
+        let cells = LinkedCells::new(&cell, configuration.particles().position, cutoff).unwrap();
+        assert_eq!(cells.n_cells, [3, 3, 6]);
+
+        // Add pairs of particles just inside the cutoff, along the directions
+        // normal to the faces of the cell
+        let matrix = cell.matrix();
+        let a = Vector3D::new(matrix[0][0], matrix[1][0], matrix[2][0]);
+        let b = Vector3D::new(matrix[0][1], matrix[1][1], matrix[2][1]);
+        let c = Vector3D::new(matrix[0][2], matrix[1][2], matrix[2][2]);
+        for normal in &[(b ^ c).normalized(), (c ^ a).normalized()] {
+            for k in 0..10 {
+                let start = cell.cartesian(&Vector3D::new(0.1 * k as f64, 0.05 * k as f64, 0.07 * k as f64));
+                let end = start + (cutoff - 1e-3) * normal;
+                configuration.add_molecule(Molecule::new(Particle::with_position("Ar", start)));
+                configuration.add_molecule(Molecule::new(Particle::with_position("Ar", end)));
+            }
+        }
+
+        let mut all_pairs = VerletList::new();
+        all_pairs.positions = configuration.particles().position.to_vec();
+        all_pairs.cell = cell;
+        all_pairs.cutoff = cutoff;
+        all_pairs.neighbors = vec![Vec::new(); configuration.size()];
+        all_pairs.build_all_pairs();
+
+        let list = NeighborList::new(0.0);
+        let neighbors = list.neighbors(&configuration, Some(cutoff));
+        for i in 0..configuration.size() {
+            assert_eq!(neighbors.of(i).collect::<Vec<_>>(), all_pairs.neighbors[i]);
+        }
+    }
+
+    #[test]
+    fn infinite() {
+        let configuration = testing_configuration(UnitCell::infinite());
+        let list = NeighborList::new(1.0);
+        check_neighbors(&configuration, &list, 5.0);
+    }
+
+    #[test]
+    fn small_cell() {
+        // Less than three cells in each direction, using all pairs
+        let configuration = testing_configuration(UnitCell::cubic(14.0));
+        let list = NeighborList::new(1.0);
+        check_neighbors(&configuration, &list, 6.0);
+    }
+
+    #[test]
+    fn all_pairs() {
+        let configuration = testing_configuration(UnitCell::cubic(20.0));
+        let list = NeighborList::new(1.0);
+        let neighbors = list.neighbors(&configuration, None);
+        for i in 0..configuration.size() {
+            assert_eq!(neighbors.of(i).collect::<Vec<_>>(), ((i + 1)..configuration.size()).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn rebuild() {
+        let mut configuration = testing_configuration(UnitCell::cubic(25.0));
+        let list = NeighborList::new(1.0);
+        check_neighbors(&configuration, &list, 5.0);
+        assert!(!list.read().need_rebuild(&configuration, 6.0, 1.0));
+
+        // Small displacements do not need a rebuild
+        configuration.particles_mut().position[3] += Vector3D::new(0.2, 0.2, 0.0);
+        assert!(!list.read().need_rebuild(&configuration, 6.0, 1.0));
+        check_neighbors(&configuration, &list, 5.0);
+
+        // Large ones do
+        configuration.particles_mut().position[3] += Vector3D::new(0.5, 0.0, 0.0);
+        assert!(list.read().need_rebuild(&configuration, 6.0, 1.0));
+        check_neighbors(&configuration, &list, 5.0);
+
+        // Changing the cell also requires a rebuild
+        configuration.cell = UnitCell::cubic(26.0);
+        assert!(list.read().need_rebuild(&configuration, 6.0, 1.0));
+        check_neighbors(&configuration, &list, 5.0);
+    }
+}
@@ -8,10 +8,12 @@ use soa_derive::soa_zip;
 use log_once::warn_once;
 
 use crate::{Matrix3, Vector3D};
-use crate::{AnglePotential, BondPotential, DihedralPotential, PairInteraction};
+use crate::{AnglePotential, BondPotential, DihedralPotential, ImproperPotential, PairInteraction};
 use crate::{CoulombicPotential, GlobalPotential};
+use crate::{LennardJones, MixingRule, SoftCore};
 use crate::{Composition, EnergyEvaluator, Interactions};
-use crate::{Configuration, Molecule, UnitCell};
+use crate::{Configuration, Improper, Molecule, UnitCell};
+use crate::sys::{NeighborList, Neighbors};
 
 /// The number of degrees of freedom simulated in a given system
 #[derive(Clone, PartialEq, Debug)]
@@ -43,11 +45,19 @@ pub struct System {
     configuration: Configuration,
     /// All the interactions in this system
     interactions: Interactions,
+    /// Neighbor list for the pair interactions
+    neighbors: NeighborList,
     /// Externally managed temperature for the system
     external_temperature: Option<f64>,
+    /// Index of the molecule coupled to the rest of the system with an
+    /// alchemical parameter, if any
+    alchemical_molecule: Option<usize>,
     /// Number of degrees of freedom simulated in the system. This default to
     /// `DegreesOfFreedom::Particles`, and is set in the simulation setup.
     pub simulated_degrees_of_freedom: DegreesOfFreedom,
+    /// Virial contribution of the constraint forces. This default to zero,
+    /// and is set by the molecular dynamics integrators using constraints.
+    pub constraints_virial: Matrix3,
     /// The current simulation step
     pub step: u64,
 }
@@ -70,9 +80,12 @@ impl System {
         System {
             configuration: configuration,
             interactions: Interactions::new(),
+            neighbors: NeighborList::default(),
             step: 0,
             external_temperature: None,
+            alchemical_molecule: None,
             simulated_degrees_of_freedom: DegreesOfFreedom::Particles,
+            constraints_virial: Matrix3::zero(),
         }
     }
 
@@ -129,6 +142,27 @@ impl System {
         self.interactions.set_pair((i, j), potential)
     }
 
+    /// Set the Lennard-Jones `parameters` for atoms with type `name`, to be
+    /// used with the mixing rule for pairs without an explicit potential.
+    pub fn set_lennard_jones_parameters(&mut self, name: &str, parameters: LennardJones) {
+        self.interactions.set_lennard_jones(name, parameters)
+    }
+
+    /// Set the mixing `rule` used to create the pair interactions between
+    /// atoms types with Lennard-Jones parameters and without an explicit pair
+    /// potential. The created interactions use the cutoff, computation mode,
+    /// tail corrections and restriction of `template`.
+    pub fn set_mixing_rule(&mut self, rule: MixingRule, template: PairInteraction) {
+        if self.cell.lengths().iter().any(|&d| 0.5 * d < template.cutoff()) {
+            panic!(
+                "Can not add a potential with a cutoff bigger than half of the \
+                smallest cell length. Try increasing the cell size or decreasing \
+                the cutoff."
+            );
+        }
+        self.interactions.set_mixing_rule(rule, template)
+    }
+
     /// Set the bond interaction `potential` for atoms with types `i` and `j`
     pub fn set_bond_potential(&mut self, (i, j): (&str, &str), potential: Box<dyn BondPotential>) {
         self.interactions.set_bond((i, j), potential)
@@ -143,6 +177,17 @@ impl System {
         self.interactions.set_angle((i, j, k), potential)
     }
 
+    /// Add the angle interaction `potential` for atoms with types `i`, `j`,
+    /// and `k`. The potential is added to any potential already set for these
+    /// atoms types, and the energy of the angle is the sum of all potentials.
+    pub fn add_angle_potential(
+        &mut self,
+        (i, j, k): (&str, &str, &str),
+        potential: Box<dyn AnglePotential>,
+    ) {
+        self.interactions.add_angle((i, j, k), potential)
+    }
+
     /// Set the dihedral angle interaction `potential` for atoms with types
     /// `i`, `j`, `k`, and `m`.
     pub fn set_dihedral_potential(
@@ -153,6 +198,28 @@ impl System {
         self.interactions.set_dihedral((i, j, k, m), potential)
     }
 
+    /// Add the dihedral angle interaction `potential` for atoms with types
+    /// `i`, `j`, `k`, and `m`. The potential is added to any potential
+    /// already set for these atoms types, and the energy of the dihedral
+    /// angle is the sum of all potentials.
+    pub fn add_dihedral_potential(
+        &mut self,
+        (i, j, k, m): (&str, &str, &str, &str),
+        potential: Box<dyn DihedralPotential>,
+    ) {
+        self.interactions.add_dihedral((i, j, k, m), potential)
+    }
+
+    /// Set the improper dihedral angle interaction `potential` for a central
+    /// atom with type `center`, bonded to atoms with types `i`, `j`, and `k`.
+    pub fn set_improper_potential(
+        &mut self,
+        (center, i, j, k): (&str, &str, &str, &str),
+        potential: Box<dyn ImproperPotential>,
+    ) {
+        self.interactions.set_improper((center, i, j, k), potential)
+    }
+
     /// Set the coulombic interaction for all pairs to `potential`
     pub fn set_coulomb_potential(&mut self, potential: Box<dyn CoulombicPotential>) {
         if let Some(cutoff) = potential.cutoff() {
@@ -172,10 +239,66 @@ impl System {
         self.interactions.globals.push(potential);
     }
 
-    /// Get the pair potential acting between the particles at indexes `i` and `j`.
+    /// Couple the pair interactions between the molecule at index `molecule`
+    /// and the rest of the system to an alchemical parameter, using the given
+    /// `soft_core` scheme. The pair interactions inside the molecule are not
+    /// affected. Coulombic interactions can be coupled with an
+    /// [`AlchemicalCoulomb`][AlchemicalCoulomb] solver.
+    ///
+    /// [AlchemicalCoulomb]: ../energy/struct.AlchemicalCoulomb.html
+    pub fn set_alchemical_molecule(&mut self, molecule: usize, soft_core: SoftCore) {
+        assert!(molecule < self.molecules().count(), "invalid molecule index for alchemical coupling");
+        self.alchemical_molecule = Some(molecule);
+        self.interactions.set_soft_core(soft_core);
+    }
+
+    /// Get the index of the molecule coupled to the rest of the system with
+    /// an alchemical parameter, if any.
+    pub fn alchemical_molecule(&self) -> Option<usize> {
+        self.alchemical_molecule
+    }
+
+    /// Mark all the particles with name `name` as Drude particles. Each Drude
+    /// particle should be bonded to a single core particle, and the relative
+    /// motion of the core-Drude pairs is removed from the degrees of freedom
+    /// and the temperature of the system.
+    pub fn add_drude_particles(&mut self, name: &str) {
+        self.interactions.add_drude(name)
+    }
+
+    /// Get the list of `(core, drude)` particles indexes for all the Drude
+    /// particles in the system. Drude particles not bonded to exactly one
+    /// other particle are ignored.
+    pub fn drude_pairs(&self) -> Vec<(usize, usize)> {
+        if !self.has_drude_particles() {
+            return Vec::new();
+        }
+
+        let kinds = self.particles().kind;
+        return self.configuration.drude_pairs(|i| self.interactions.is_drude(kinds[i]));
+    }
+
+    /// Check if any particle in the system is a Drude particle, without
+    /// looking at the bonds.
+    fn has_drude_particles(&self) -> bool {
+        if !self.interactions.has_drudes() {
+            return false;
+        }
+        let kinds = self.particles().kind;
+        return kinds.iter().any(|&kind| self.interactions.is_drude(kind));
+    }
+
+    /// Get the pair potential acting between the particles at indexes `i` and
+    /// `j`. Pairs between the alchemical molecule and another molecule use the
+    /// soft-core coupled pair potential.
     pub fn pair_potential(&self, i: usize, j: usize) -> Option<&PairInteraction> {
         let kind_i = self.particles().kind[i];
         let kind_j = self.particles().kind[j];
+        if let Some(molecule) = self.alchemical_molecule {
+            if (self.molecule_id(i) == molecule) != (self.molecule_id(j) == molecule) {
+                return self.interactions.alchemical_pair((kind_i, kind_j));
+            }
+        }
         return self.interactions.pair((kind_i, kind_j));
     }
 
@@ -217,6 +340,33 @@ impl System {
         return self.interactions.dihedral((kind_i, kind_j, kind_k, kind_m));
     }
 
+    /// Get the improper dihedral angles potential acting on the `improper`
+    /// dihedral angle.
+    ///
+    /// The potential is returned together with the `improper` re-ordered to
+    /// match the order of the particles types used to define the potential,
+    /// which should be used to compute the improper angle. When multiple
+    /// particles have the same type, they are kept in the initial order.
+    pub fn improper_potential(&self, improper: &Improper) -> Option<(Improper, &dyn ImproperPotential)> {
+        let kinds = self.particles().kind;
+        let center = improper.center();
+        let others = [improper.i(), improper.j(), improper.k()];
+        let kind = (kinds[center], kinds[others[0]], kinds[others[1]], kinds[others[2]]);
+        let ((_, kind_i, kind_j, kind_k), potential) = self.interactions.improper(kind)?;
+
+        let mut used = [false; 3];
+        let mut ordered = [0; 3];
+        for (n, expected) in [kind_i, kind_j, kind_k].iter().enumerate() {
+            let m = (0..3).find(|&m| !used[m] && kinds[others[m]] == *expected)
+                          .expect("the improper potential should match the particles kinds");
+            used[m] = true;
+            ordered[n] = others[m];
+        }
+
+        let improper = Improper::new(center, ordered[0], ordered[1], ordered[2]);
+        return Some((improper, potential));
+    }
+
     /// Get the coulombic interaction for the system
     pub fn coulomb_potential(&self) -> Option<&dyn CoulombicPotential> {
         self.interactions.coulomb.as_deref()
@@ -231,6 +381,22 @@ impl System {
     pub fn maximum_cutoff(&self) -> Option<f64> {
         self.interactions.maximum_cutoff()
     }
+
+    /// Set the skin distance used by the neighbor list for pair interactions.
+    /// The neighbor list contains all the pairs closer than the pairs cutoff
+    /// plus `skin`, and is rebuilt when any particle moved by more than half
+    /// of the skin. The default skin is 1 A.
+    pub fn set_neighbor_skin(&mut self, skin: f64) {
+        self.neighbors = NeighborList::new(skin);
+    }
+
+    /// Get the neighbors of all the particles for the pair interactions. If
+    /// all the pair potentials have a finite cutoff, this uses a Verlet list
+    /// which is rebuilt as needed. Else, all the pairs of particles are used.
+    pub(crate) fn neighbors(&self) -> Neighbors<'_> {
+        let cutoff = self.interactions.pairs_cutoff();
+        self.neighbors.neighbors(&self.configuration, cutoff)
+    }
 }
 
 use crate::compute::{KineticEnergy, PotentialEnergy, TotalEnergy};
@@ -243,11 +409,24 @@ use crate::compute::Volume;
 
 /// Functions to get physical properties of a system.
 impl System {
-    /// Get the number of degrees of freedom in the system
+    /// Get the number of degrees of freedom in the system. When simulating
+    /// particles, the relative motion of the core-Drude pairs is not counted
+    /// in the degrees of freedom.
     pub fn degrees_of_freedom(&self) -> usize {
+        let drude_pairs = match self.simulated_degrees_of_freedom {
+            DegreesOfFreedom::Molecules => 0,
+            _ => self.drude_pairs().len(),
+        };
+        return self.degrees_of_freedom_with_drudes(drude_pairs);
+    }
+
+    /// Get the number of degrees of freedom in the system, when the system
+    /// contains `drude_pairs` core-Drude pairs. This avoids looking for the
+    /// Drude pairs again when they are already known.
+    pub(crate) fn degrees_of_freedom_with_drudes(&self, drude_pairs: usize) -> usize {
         match self.simulated_degrees_of_freedom {
-            DegreesOfFreedom::Particles => 3 * self.size(),
-            DegreesOfFreedom::Frozen(frozen) => 3 * self.size() - frozen,
+            DegreesOfFreedom::Particles => 3 * self.size() - 3 * drude_pairs,
+            DegreesOfFreedom::Frozen(frozen) => 3 * self.size() - frozen - 3 * drude_pairs,
             DegreesOfFreedom::Molecules => 3 * self.molecules().count(),
         }
     }
@@ -376,6 +555,9 @@ impl System {
                     );
                 }
             }
+
+            // Improper dihedral angles are not checked, since most particles
+            // bonded to three neighbors do not use an improper potential
         }
 
         // check the need for a coulombic potential
@@ -445,7 +627,8 @@ impl DerefMut for System {
 
 #[cfg(test)]
 mod tests {
-    use crate::{System, Molecule, Particle, ParticleKind};
+    use crate::{System, Improper, Molecule, Particle, ParticleKind};
+    use super::DegreesOfFreedom;
 
     #[test]
     #[should_panic]
@@ -511,6 +694,34 @@ mod tests {
         assert!(system.bond_potential(0, 0).is_none());
         assert!(system.angle_potential(0, 0, 0).is_none());
         assert!(system.dihedral_potential(0, 0, 0, 0).is_none());
+        assert!(system.improper_potential(&Improper::new(0, 1, 2, 3)).is_none());
+    }
+
+    #[test]
+    fn drude_pairs() {
+        let mut system = System::new();
+        system.add_molecule(Molecule::new(Particle::new("O")));
+        system.add_molecule(Molecule::new(Particle::new("DO")));
+        system.add_molecule(Molecule::new(Particle::new("H")));
+        system.add_molecule(Molecule::new(Particle::new("Ar")));
+        system.add_molecule(Molecule::new(Particle::new("DAr")));
+        let _ = system.add_bond(0, 1);
+        let _ = system.add_bond(0, 2);
+        let _ = system.add_bond(3, 4);
+
+        assert!(system.drude_pairs().is_empty());
+        assert_eq!(system.degrees_of_freedom(), 15);
+
+        system.add_drude_particles("DO");
+        system.add_drude_particles("DAr");
+        assert_eq!(system.drude_pairs(), vec![(0, 1), (3, 4)]);
+        assert_eq!(system.degrees_of_freedom(), 9);
+
+        system.simulated_degrees_of_freedom = DegreesOfFreedom::Frozen(2);
+        assert_eq!(system.degrees_of_freedom(), 7);
+
+        system.simulated_degrees_of_freedom = DegreesOfFreedom::Molecules;
+        assert_eq!(system.degrees_of_freedom(), 6);
     }
 
     #[test]
@@ -41,6 +41,9 @@ lazy_static!{
         assert!(map.insert("m", 1e10).is_none());
         assert!(map.insert("bohr", BOHR_RADIUS).is_none());
 
+        // Volume units.
+        assert!(map.insert("L", 1e27).is_none());
+
         // Time units.
         assert!(map.insert("fs", 1.0).is_none());
         assert!(map.insert("ps", 1e3).is_none());
@@ -70,6 +73,9 @@ lazy_static!{
         assert!(map.insert("H", 4.35974417e-18 * 1e-10 / U_IN_KG).is_none());
         assert!(map.insert("Ry", 4.35974417e-18 / 2.0 * 1e-10 / U_IN_KG).is_none());
 
+        // Electric potential unit, for charges in units of the elementary charge.
+        assert!(map.insert("V", 1.60217653e-19 * 1e-10 / U_IN_KG).is_none());
+
         // Force unit.
         assert!(map.insert("N", 1e-20 / U_IN_KG).is_none());
 
@@ -461,6 +467,8 @@ mod test {
         assert_eq!(UnitExpr::parse("(Ry / rad^-3   )").unwrap().eval(), 0.1312749878912494);
         assert_eq!(UnitExpr::parse("bar/(m * fs^2)").unwrap().eval(), 6.022141794216763e-19);
         assert_eq!(UnitExpr::parse("kJ/mol/deg^2").unwrap().eval(), 0.3282806352310398);
+        assert_eq!(UnitExpr::parse("V").unwrap().eval(), UnitExpr::parse("eV").unwrap().eval());
+        assert_eq!(UnitExpr::parse("L").unwrap().eval(), 1e27);
         assert_eq!(UnitExpr::parse("(kcal/mol/A)^2").unwrap().eval(), 1.7505856024515547e-7);
 
         assert_ulps_eq!(UnitExpr::parse("kcal/mol/A^2").unwrap().eval(), 4.184e-4, epsilon = 1e-9);
@@ -1,12 +1,15 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
-use toml::value::Table;
+use toml::value::{Table, Value};
 
-use lumol_core::energy::{AnglePotential, DihedralPotential};
+use lumol_core::energy::{AnglePotential, DihedralPotential, ImproperPotential};
 use lumol_core::energy::{CosineHarmonic, Harmonic, Morse, NullPotential, Torsion};
+use lumol_core::energy::{OplsTorsion, RyckaertBellemans};
+use lumol_core::energy::{BondAngle, BondBond, UreyBradley};
+use lumol_core::energy::{CustomPotential, TabulatedDomain};
 use lumol_core::System;
 
-use crate::{InteractionsInput, FromToml, Error};
+use crate::{InteractionsInput, FromToml, FromTomlWithData, Error};
 use crate::extract;
 
 impl InteractionsInput {
@@ -21,22 +24,48 @@ impl InteractionsInput {
             Error::from("the 'angles' section must be a table")
         )?;
 
-        for (key, table) in angles {
+        for (key, value) in angles {
             let atoms = key.split('-').collect::<Vec<_>>();
             if atoms.len() != 3 {
                 return Err(Error::from(format!(
                     "expected three atoms for angle potential, got {} ({:?})", atoms.len(), atoms
                 )));
             }
+            let atoms = (atoms[0], atoms[1], atoms[2]);
 
-            let table = table.as_table().ok_or(
-                Error::from(format!(
-                    "angle potential associated with {} must be a table", key
-                ))
-            )?;
+            match *value {
+                Value::Table(ref table) => {
+                    let potential = read_angle_potential(self, table)?;
+                    system.set_angle_potential(atoms, potential);
+                }
+                Value::Array(ref array) => {
+                    if array.is_empty() {
+                        return Err(Error::from(format!(
+                            "empty array of angle potentials associated with {}", key
+                        )));
+                    }
 
-            let potential = read_angle_potential(table)?;
-            system.set_angle_potential((atoms[0], atoms[1], atoms[2]), potential);
+                    for (i, value) in array.iter().enumerate() {
+                        let table = value.as_table().ok_or(
+                            Error::from(format!(
+                                "angle potentials associated with {} must be tables", key
+                            ))
+                        )?;
+
+                        let potential = read_angle_potential(self, table)?;
+                        if i == 0 {
+                            system.set_angle_potential(atoms, potential);
+                        } else {
+                            system.add_angle_potential(atoms, potential);
+                        }
+                    }
+                }
+                _ => {
+                    return Err(Error::from(format!(
+                        "angle potential associated with {} must be a table or an array of tables", key
+                    )));
+                }
+            }
         }
         Ok(())
     }
@@ -52,44 +81,118 @@ impl InteractionsInput {
             Error::from("the 'dihedrals' section must be a table")
         )?;
 
-        for (key, table) in dihedrals {
+        for (key, value) in dihedrals {
             let atoms = key.split('-').collect::<Vec<_>>();
             if atoms.len() != 4 {
                 return Err(Error::from(format!(
                     "expected four atoms for dihedral potential, got {} ({:?})", atoms.len(), atoms
                 )));
             }
+            let atoms = (atoms[0], atoms[1], atoms[2], atoms[3]);
+
+            match *value {
+                Value::Table(ref table) => {
+                    let potential = read_dihedral_potential(self, table)?;
+                    system.set_dihedral_potential(atoms, potential);
+                }
+                Value::Array(ref array) => {
+                    if array.is_empty() {
+                        return Err(Error::from(format!(
+                            "empty array of dihedral potentials associated with {}", key
+                        )));
+                    }
+
+                    for (i, value) in array.iter().enumerate() {
+                        let table = value.as_table().ok_or(
+                            Error::from(format!(
+                                "dihedral potentials associated with {} must be tables", key
+                            ))
+                        )?;
+
+                        let potential = read_dihedral_potential(self, table)?;
+                        if i == 0 {
+                            system.set_dihedral_potential(atoms, potential);
+                        } else {
+                            system.add_dihedral_potential(atoms, potential);
+                        }
+                    }
+                }
+                _ => {
+                    return Err(Error::from(format!(
+                        "dihedral potential associated with {} must be a table or an array of tables", key
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the "impropers" section from the potential configuration.
+    pub(crate) fn read_impropers(&self, system: &mut System) -> Result<(), Error> {
+        let impropers = match self.config.get("impropers") {
+            Some(impropers) => impropers,
+            None => return Ok(()),
+        };
+
+        let impropers = impropers.as_table().ok_or(
+            Error::from("the 'impropers' section must be a table")
+        )?;
+
+        for (key, table) in impropers {
+            let atoms = key.split('-').collect::<Vec<_>>();
+            if atoms.len() != 4 {
+                return Err(Error::from(format!(
+                    "expected four atoms for improper potential, got {} ({:?})", atoms.len(), atoms
+                )));
+            }
 
             let table = table.as_table().ok_or(
                 Error::from(format!(
-                    "dihedral potential associated with {} must be a table", key
+                    "improper potential associated with {} must be a table", key
                 ))
             )?;
 
-            let potential = read_dihedral_potential(table)?;
-            system.set_dihedral_potential((atoms[0], atoms[1], atoms[2], atoms[3]), potential);
+            let potential = read_improper_potential(table)?;
+            system.set_improper_potential((atoms[0], atoms[1], atoms[2], atoms[3]), potential);
         }
         Ok(())
     }
 }
 
-fn read_angle_potential(table: &Table) -> Result<Box<dyn AnglePotential>, Error> {
+fn read_angle_potential(input: &InteractionsInput, table: &Table) -> Result<Box<dyn AnglePotential>, Error> {
     match extract::typ(table, "angle potential")? {
         "null" => Ok(Box::new(NullPotential::from_toml(table)?)),
         "harmonic" => Ok(Box::new(Harmonic::from_toml(table)?)),
         "cosine-harmonic" => Ok(Box::new(CosineHarmonic::from_toml(table)?)),
         "morse" => Ok(Box::new(Morse::from_toml(table)?)),
+        "urey-bradley" => Ok(Box::new(UreyBradley::from_toml(table)?)),
+        "bond-bond" => Ok(Box::new(BondBond::from_toml(table)?)),
+        "bond-angle" => Ok(Box::new(BondAngle::from_toml(table)?)),
+        "custom" => Ok(Box::new(CustomPotential::from_toml(table, "theta")?)),
+        "tabulated" => Ok(Box::new(input.read_tabulated(table, "deg", TabulatedDomain::Angle)?)),
         other => Err(Error::from(format!("unknown potential type '{}'", other))),
     }
 }
 
-fn read_dihedral_potential(table: &Table) -> Result<Box<dyn DihedralPotential>, Error> {
+fn read_dihedral_potential(input: &InteractionsInput, table: &Table) -> Result<Box<dyn DihedralPotential>, Error> {
     match extract::typ(table, "dihedral potential")? {
         "null" => Ok(Box::new(NullPotential::from_toml(table)?)),
         "harmonic" => Ok(Box::new(Harmonic::from_toml(table)?)),
         "cosine-harmonic" => Ok(Box::new(CosineHarmonic::from_toml(table)?)),
         "torsion" => Ok(Box::new(Torsion::from_toml(table)?)),
+        "ryckaert-bellemans" => Ok(Box::new(RyckaertBellemans::from_toml(table)?)),
+        "opls" => Ok(Box::new(OplsTorsion::from_toml(table)?)),
         "morse" => Ok(Box::new(Morse::from_toml(table)?)),
+        "tabulated" => Ok(Box::new(input.read_tabulated(table, "deg", TabulatedDomain::Dihedral)?)),
+        other => Err(Error::from(format!("unknown potential type '{}'", other))),
+    }
+}
+
+fn read_improper_potential(table: &Table) -> Result<Box<dyn ImproperPotential>, Error> {
+    match extract::typ(table, "improper potential")? {
+        "null" => Ok(Box::new(NullPotential::from_toml(table)?)),
+        "harmonic" => Ok(Box::new(Harmonic::from_toml(table)?)),
+        "torsion" => Ok(Box::new(Torsion::from_toml(table)?)),
         other => Err(Error::from(format!("unknown potential type '{}'", other))),
     }
 }
@@ -3,6 +3,8 @@
 use toml::Value;
 
 use lumol_core::energy::{CoulombicPotential, Ewald, SharedEwald, Wolf};
+use lumol_core::energy::{Pme, SharedPme, ReactionField, DampedShiftedForce, Yukawa};
+use lumol_core::energy::Polarization;
 use lumol_core::System;
 
 use log::{info, warn};
@@ -20,7 +22,9 @@ impl InteractionsInput {
 
         let coulomb = coulomb.as_table().ok_or(Error::from("the 'coulomb' section must be a table"))?;
 
-        let solvers = coulomb.keys().cloned().filter(|key| key != "restriction").collect::<Vec<_>>();
+        let solvers = coulomb.keys().cloned().filter(|key| {
+            key != "restriction" && key != "polarization"
+        }).collect::<Vec<_>>();
 
         if solvers.len() != 1 {
             return Err(Error::from(
@@ -29,13 +33,44 @@ impl InteractionsInput {
         }
 
         let key = &*solvers[0];
+        let polarization = match coulomb.get("polarization") {
+            Some(polarization) => {
+                let polarization = polarization.as_table().ok_or(
+                    Error::from("'polarization' must be a table in coulomb section")
+                )?;
+                if key != "wolf" && key != "ewald" {
+                    return Err(Error::from(
+                        "polarization can only be used with 'wolf' and 'ewald' coulombic solvers"
+                    ));
+                }
+                Some(Polarization::from_toml(polarization)?)
+            }
+            None => None,
+        };
+
         if let Value::Table(ref table) = coulomb[key] {
             let mut potential: Box<dyn CoulombicPotential> = match key {
-                "wolf" => Box::new(Wolf::from_toml(table)?),
+                "wolf" => {
+                    let mut wolf = Wolf::from_toml(table)?;
+                    if let Some(polarization) = polarization {
+                        wolf.set_polarization(polarization);
+                    }
+                    Box::new(wolf)
+                }
+                "reaction_field" => Box::new(ReactionField::from_toml(table)?),
+                "dsf" => Box::new(DampedShiftedForce::from_toml(table)?),
+                "yukawa" => Box::new(Yukawa::from_toml(table)?),
                 "ewald" => {
-                    let ewald = Ewald::from_toml(table, &system)?;
+                    let mut ewald = Ewald::from_toml(table, &system)?;
+                    if let Some(polarization) = polarization {
+                        ewald.set_polarization(polarization);
+                    }
                     Box::new(SharedEwald::new(ewald))
                 }
+                "pme" => {
+                    let pme = Pme::from_toml(table, &system)?;
+                    Box::new(SharedPme::new(pme))
+                }
                 other => return Err(Error::from(format!("unknown coulomb solver '{}'", other))),
             };
 
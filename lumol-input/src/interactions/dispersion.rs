@@ -0,0 +1,112 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use toml::value::Table;
+
+use lumol_core::energy::{DispersionPme, PairRestriction};
+use lumol_core::System;
+use lumol_core::units;
+
+use log::{info, warn};
+
+use super::read_restriction;
+use crate::{Error, FromToml, InteractionsInput};
+use crate::extract;
+
+impl InteractionsInput {
+    /// Read the "dispersion" section from the potential configuration.
+    pub(crate) fn read_dispersion(&self, system: &mut System) -> Result<(), Error> {
+        let dispersion = match self.config.get("dispersion") {
+            Some(dispersion) => dispersion,
+            None => return Ok(()),
+        };
+
+        let dispersion = dispersion.as_table().ok_or(
+            Error::from("the 'dispersion' section must be a table")
+        )?;
+
+        let mut potential = DispersionPme::from_toml(dispersion)?;
+        if let Some(restriction) = read_restriction(dispersion)? {
+            if restriction == PairRestriction::IntraMolecular {
+                return Err(Error::from("'intra-molecular' restriction can not be used for dispersion"));
+            }
+            potential.set_restriction(restriction);
+        }
+
+        let c6 = extract::table("c6", dispersion, "dispersion section")?;
+        for (name, value) in c6 {
+            let value = value.as_str().ok_or(
+                Error::from(format!("dispersion coefficient for '{}' must be a string", name))
+            )?;
+            let value = units::from_str(value)?;
+            if value < 0.0 {
+                return Err(Error::from(
+                    format!("dispersion coefficient for '{}' can not be negative", name)
+                ));
+            }
+
+            let count = system.particles().name.iter().filter(|&other| other == name).count();
+            if count == 0 {
+                warn!("No particle with name '{}' was found while setting the dispersion coefficients", name);
+            } else {
+                info!("Dispersion coefficient set to {} for {} {} particles", value, count, name);
+            }
+            potential.set_c6(name, value);
+        }
+
+        system.add_global_potential(Box::new(potential));
+        Ok(())
+    }
+}
+
+impl FromToml for DispersionPme {
+    fn from_toml(table: &Table) -> Result<DispersionPme, Error> {
+        let cutoff = extract::str("cutoff", table, "dispersion section")?;
+        let cutoff = units::from_str(cutoff)?;
+        if cutoff <= 0.0 {
+            return Err(Error::from("'cutoff' must be positive in dispersion section"));
+        }
+
+        let order = if table.contains_key("order") {
+            extract::uint("order", table, "dispersion section")? as usize
+        } else {
+            6
+        };
+        if order < 3 {
+            return Err(Error::from("'order' must be at least 3 in dispersion section"));
+        }
+
+        let grid = if table.get("grid").map_or(false, |grid| grid.is_array()) {
+            let grid = extract::slice("grid", table, "dispersion section")?;
+            let grid = grid.iter().map(|size| {
+                match size.as_integer() {
+                    Some(size) if size > 0 => Ok(size as usize),
+                    _ => Err(Error::from("'grid' values must be positive integers in dispersion section")),
+                }
+            }).collect::<Result<Vec<_>, _>>()?;
+            if grid.len() != 3 {
+                return Err(Error::from("'grid' array must have a size of 3 in dispersion section"));
+            }
+            [grid[0], grid[1], grid[2]]
+        } else {
+            let size = extract::uint("grid", table, "dispersion section")? as usize;
+            [size, size, size]
+        };
+
+        if grid.iter().any(|&size| size < order) {
+            return Err(Error::from("'grid' must contain at least 'order' points in dispersion section"));
+        }
+
+        let alpha = if table.contains_key("alpha") {
+            let alpha = extract::str("alpha", table, "dispersion section")?;
+            let alpha = units::from_str(alpha)?;
+            if alpha <= 0.0 {
+                return Err(Error::from("'alpha' must be positive in dispersion section"));
+            }
+            Some(alpha)
+        } else {
+            None
+        };
+
+        Ok(DispersionPme::new(cutoff, grid, order, alpha))
+    }
+}
@@ -0,0 +1,63 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use lumol_core::energy::Thole;
+use lumol_core::System;
+use lumol_core::units;
+
+use super::read_restriction;
+use crate::{Error, InteractionsInput};
+use crate::extract;
+
+/// Default value of the Thole screening factor, corresponding to the
+/// parametrization of the CHARMM Drude force field.
+const DEFAULT_THOLE: f64 = 1.3;
+
+impl InteractionsInput {
+    /// Read the "drude" section from the potential configuration.
+    pub(crate) fn read_drude(&self, system: &mut System) -> Result<(), Error> {
+        let drude = match self.config.get("drude") {
+            Some(drude) => drude,
+            None => return Ok(()),
+        };
+
+        let drude = drude.as_table().ok_or(
+            Error::from("the 'drude' section must be a table")
+        )?;
+
+        let mut thole = Thole::new();
+        // Use the same restriction as the coulombic solver
+        if let Some(coulomb) = self.config.get("coulomb").and_then(|coulomb| coulomb.as_table()) {
+            if let Some(restriction) = read_restriction(coulomb)? {
+                thole.set_restriction(restriction);
+            }
+        }
+
+        for (name, parameters) in drude {
+            let parameters = parameters.as_table().ok_or(Error::from(
+                format!("Drude parameters associated with {} must be a table", name)
+            ))?;
+
+            let polarizability = extract::str("polarizability", parameters, "Drude particle")?;
+            let polarizability = units::from_str(polarizability)?;
+            if polarizability <= 0.0 {
+                return Err(Error::from(
+                    format!("the polarizability of the Drude particle {} must be positive", name)
+                ));
+            }
+
+            let factor = if parameters.contains_key("thole") {
+                extract::number("thole", parameters, "Drude particle")?
+            } else {
+                DEFAULT_THOLE
+            };
+
+            system.add_drude_particles(name);
+            thole.add_drude(name, polarizability, factor);
+        }
+
+        if !drude.is_empty() {
+            system.add_global_potential(Box::new(thole));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use toml::value::{Table, Value};
+
+use lumol_core::energy::{ElectricField, PositionRestraint, SphericalConfinement};
+use lumol_core::energy::{Wall, WallPotential};
+use lumol_core::{System, Vector3D};
+use lumol_core::units;
+
+use crate::{Error, FromToml, InteractionsInput};
+use crate::extract;
+
+impl InteractionsInput {
+    /// Read the "external" section from the potential configuration.
+    pub(crate) fn read_external(&self, system: &mut System) -> Result<(), Error> {
+        let external = match self.config.get("external") {
+            Some(external) => external,
+            None => return Ok(()),
+        };
+
+        let external = external.as_array().ok_or(
+            Error::from("the 'external' section must be an array of tables")
+        )?;
+
+        for table in external {
+            let table = table.as_table().ok_or(
+                Error::from("the 'external' section must be an array of tables")
+            )?;
+
+            match extract::typ(table, "external potential")? {
+                "electric-field" => {
+                    let field = ElectricField::from_toml(table)?;
+                    system.add_global_potential(Box::new(field));
+                }
+                "wall-9-3" | "wall-10-4-3" => {
+                    let wall = read_wall(table)?;
+                    system.add_global_potential(Box::new(wall));
+                }
+                "restraint" => {
+                    let restraint = read_restraint(table, system)?;
+                    system.add_global_potential(Box::new(restraint));
+                }
+                "spherical-confinement" => {
+                    let confinement = SphericalConfinement::from_toml(table)?;
+                    system.add_global_potential(Box::new(confinement));
+                }
+                other => return Err(Error::from(format!("unknown external potential '{}'", other))),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromToml for ElectricField {
+    fn from_toml(table: &Table) -> Result<ElectricField, Error> {
+        let field = read_vector("field", table, "electric field")?;
+        Ok(ElectricField::new(field))
+    }
+}
+
+impl FromToml for SphericalConfinement {
+    fn from_toml(table: &Table) -> Result<SphericalConfinement, Error> {
+        let center = if table.contains_key("center") {
+            read_vector("center", table, "spherical confinement")?
+        } else {
+            Vector3D::zero()
+        };
+
+        let radius = extract::str("radius", table, "spherical confinement")?;
+        let radius = units::from_str(radius)?;
+        if radius <= 0.0 {
+            return Err(Error::from("'radius' must be positive in spherical confinement"));
+        }
+
+        let k = extract::str("k", table, "spherical confinement")?;
+        Ok(SphericalConfinement::new(center, radius, units::from_str(k)?))
+    }
+}
+
+/// Read a wall potential and its geometry
+fn read_wall(table: &Table) -> Result<Wall, Error> {
+    let sigma = units::from_str(extract::str("sigma", table, "wall")?)?;
+    let epsilon = units::from_str(extract::str("epsilon", table, "wall")?)?;
+    let potential = match extract::typ(table, "wall")? {
+        "wall-9-3" => WallPotential::LennardJones93 { sigma: sigma, epsilon: epsilon },
+        "wall-10-4-3" => WallPotential::LennardJones1043 { sigma: sigma, epsilon: epsilon },
+        _ => unreachable!(),
+    };
+
+    let normal = extract::slice("normal", table, "wall")?;
+    let normal = normal.iter().map(|value| match *value {
+        Value::Integer(value) => Ok(value as f64),
+        Value::Float(value) => Ok(value),
+        _ => Err(Error::from("'normal' must be an array of 3 numbers in wall")),
+    }).collect::<Result<Vec<_>, _>>()?;
+    if normal.len() != 3 {
+        return Err(Error::from("'normal' must be an array of 3 numbers in wall"));
+    }
+    let normal = Vector3D::new(normal[0], normal[1], normal[2]);
+    if normal.norm() == 0.0 {
+        return Err(Error::from("'normal' can not be the null vector in wall"));
+    }
+
+    let position = if table.contains_key("position") {
+        units::from_str(extract::str("position", table, "wall")?)?
+    } else {
+        0.0
+    };
+
+    let cutoff = extract::str("cutoff", table, "wall")?;
+    let cutoff = units::from_str(cutoff)?;
+    if cutoff <= 0.0 {
+        return Err(Error::from("'cutoff' must be positive in wall"));
+    }
+
+    Ok(Wall::new(potential, normal, position, cutoff))
+}
+
+/// Read position restraints, using the current positions of the particles in
+/// the `system` as reference positions.
+fn read_restraint(table: &Table, system: &System) -> Result<PositionRestraint, Error> {
+    let k = extract::str("k", table, "position restraint")?;
+    let k = units::from_str(k)?;
+
+    let mut restraint = if table.contains_key("radius") {
+        let radius = extract::str("radius", table, "position restraint")?;
+        let radius = units::from_str(radius)?;
+        if radius < 0.0 {
+            return Err(Error::from("'radius' can not be negative in position restraint"));
+        }
+        PositionRestraint::flat_bottom(k, radius)
+    } else {
+        PositionRestraint::harmonic(k)
+    };
+
+    let particles = extract::slice("particles", table, "position restraint")?;
+    let names = particles.iter().map(|name| name.as_str().ok_or(
+        Error::from("'particles' must be an array of strings in position restraint")
+    )).collect::<Result<Vec<_>, _>>()?;
+
+    let positions = system.particles().position;
+    for (i, name) in system.particles().name.iter().enumerate() {
+        if names.contains(&name.as_str()) {
+            restraint.add_particle(i, positions[i]);
+        }
+    }
+
+    Ok(restraint)
+}
+
+/// Read a vector of three values with units at the given `key` in `table`,
+/// interpreted as a `context`.
+fn read_vector(key: &str, table: &Table, context: &str) -> Result<Vector3D, Error> {
+    let error = || Error::from(format!("'{}' must be an array of 3 strings in {}", key, context));
+    let values = extract::slice(key, table, context)?;
+    if values.len() != 3 {
+        return Err(error());
+    }
+
+    let mut vector = Vector3D::zero();
+    for (i, value) in values.iter().enumerate() {
+        let value = value.as_str().ok_or_else(error)?;
+        vector[i] = units::from_str(value)?;
+    }
+    Ok(vector)
+}
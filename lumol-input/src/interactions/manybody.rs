@@ -0,0 +1,75 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use toml::Value;
+use toml::value::Table;
+
+use lumol_core::energy::{Eam, StillingerWeber, Tersoff};
+use lumol_core::System;
+
+use super::file_error;
+use crate::{Error, InteractionsInput};
+use crate::extract;
+
+impl InteractionsInput {
+    /// Read the "manybody" section from the potential configuration.
+    pub(crate) fn read_manybody(&self, system: &mut System) -> Result<(), Error> {
+        let manybody = match self.config.get("manybody") {
+            Some(manybody) => manybody,
+            None => return Ok(()),
+        };
+
+        let manybody = manybody.as_table().ok_or(
+            Error::from("the 'manybody' section must be a table")
+        )?;
+
+        for (key, table) in manybody {
+            let table = match *table {
+                Value::Table(ref table) => table,
+                _ => return Err(Error::from(format!("many-body potential '{}' must be a table", key))),
+            };
+
+            match &**key {
+                "eam" => {
+                    let eam = self.read_eam(table)?;
+                    system.add_global_potential(Box::new(eam));
+                }
+                "stillinger_weber" => {
+                    let file = extract::str("file", table, "Stillinger-Weber potential")?;
+                    let path = self.file_path(file);
+                    let sw = StillingerWeber::read(&path).map_err(|error| file_error(error, &path))?;
+                    system.add_global_potential(Box::new(sw));
+                }
+                "tersoff" => {
+                    let file = extract::str("file", table, "Tersoff potential")?;
+                    let path = self.file_path(file);
+                    let tersoff = Tersoff::read(&path).map_err(|error| file_error(error, &path))?;
+                    system.add_global_potential(Box::new(tersoff));
+                }
+                other => return Err(Error::from(format!("unknown many-body potential '{}'", other))),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_eam(&self, table: &Table) -> Result<Eam, Error> {
+        let file = extract::str("file", table, "EAM potential")?;
+        let path = self.file_path(file);
+        let format = extract::str("format", table, "EAM potential")?;
+
+        if format != "funcfl" && table.contains_key("element") {
+            return Err(Error::from("'element' can only be used with funcfl format in EAM potential"));
+        }
+
+        let eam = match format {
+            "setfl" => Eam::read_setfl(&path),
+            "fs" => Eam::read_finnis_sinclair(&path),
+            "funcfl" => {
+                let element = extract::str("element", table, "EAM potential")?;
+                Eam::read_funcfl(&path, element)
+            }
+            other => return Err(Error::from(format!("unknown EAM file format '{}'", other))),
+        };
+
+        return eam.map_err(|error| file_error(error, &path));
+    }
+}
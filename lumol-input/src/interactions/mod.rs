@@ -4,8 +4,9 @@ use toml::de::from_str as parse;
 use toml::value::{Table, Value};
 
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use lumol_core::energy::PairRestriction;
 use lumol_core::System;
@@ -17,11 +18,19 @@ mod potentials;
 mod pairs;
 mod angles;
 mod coulomb;
+mod drude;
+mod manybody;
+mod external;
+mod dispersion;
+mod tabulated;
 
 /// Input file for reading interactions
 pub struct InteractionsInput {
     /// The TOML configuration
     config: Table,
+    /// Path of the input file, used to find other files referenced in the
+    /// configuration
+    path: Option<PathBuf>,
 }
 
 impl InteractionsInput {
@@ -31,7 +40,9 @@ impl InteractionsInput {
         let mut file = try_io!(File::open(&path), path);
         let mut buffer = String::new();
         let _ = try_io!(file.read_to_string(&mut buffer), path);
-        return InteractionsInput::from_str(&buffer);
+        let mut input = InteractionsInput::from_str(&buffer)?;
+        input.path = Some(path);
+        return Ok(input);
     }
 
     /// Read the interactions from a TOML formatted string.
@@ -39,25 +50,44 @@ impl InteractionsInput {
     pub fn from_str(string: &str) -> Result<InteractionsInput, Error> {
         let config = parse(string).map_err(|err| Error::TOML(Box::new(err)))?;
         validate(&config)?;
-        return Ok(InteractionsInput::from_toml(config));
+        return Ok(InteractionsInput::from_toml(config, None));
     }
 
-    /// Read the interactions from a TOML table.
-    pub(crate) fn from_toml(config: Table) -> InteractionsInput {
+    /// Read the interactions from a TOML table, contained in the file at
+    /// `path` if any.
+    pub(crate) fn from_toml(config: Table, path: Option<PathBuf>) -> InteractionsInput {
         InteractionsInput {
-            config: config
+            config: config,
+            path: path,
+        }
+    }
+
+    /// Get the path to a file referenced in this input. Relative paths are
+    /// taken relative to the input file, or to the current directory if the
+    /// input was not read from a file.
+    fn file_path(&self, file: &str) -> PathBuf {
+        let file = Path::new(file);
+        match self.path.as_ref().and_then(|path| path.parent()) {
+            Some(parent) if file.is_relative() => parent.join(file),
+            _ => file.to_path_buf(),
         }
     }
 
     /// Read the interactions from this input into the `system`.
     pub fn read(&self, system: &mut System) -> Result<(), Error> {
         self.read_pairs(system)?;
+        self.read_atoms(system)?;
         self.read_bonds(system)?;
         self.read_angles(system)?;
         self.read_dihedrals(system)?;
+        self.read_impropers(system)?;
         // charges must be read before coulomb
         self.read_charges(system)?;
         self.read_coulomb(system)?;
+        self.read_dispersion(system)?;
+        self.read_drude(system)?;
+        self.read_manybody(system)?;
+        self.read_external(system)?;
         Ok(())
     }
 }
@@ -100,3 +130,11 @@ fn read_restriction(config: &Table) -> Result<Option<PairRestriction>, Error> {
         _ => Err(Error::from("Restriction must be a table or a string")),
     }
 }
+
+/// Convert an error when reading a potential file to an input error
+fn file_error(error: io::Error, path: &Path) -> Error {
+    match error.kind() {
+        io::ErrorKind::InvalidData => Error::from(format!("invalid file at '{}': {}", path.display(), error)),
+        _ => Error::from((error, path.to_path_buf())),
+    }
+}
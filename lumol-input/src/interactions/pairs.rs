@@ -5,10 +5,12 @@ use toml::value::{Table, Value};
 use lumol_core::System;
 use lumol_core::units;
 
-use lumol_core::energy::{BondPotential, PairInteraction, PairPotential};
+use lumol_core::energy::{BondPotential, MixingRule, PairInteraction, PairPotential};
 use lumol_core::energy::{BornMayerHuggins, Buckingham, Gaussian, Morse};
 use lumol_core::energy::{Harmonic, LennardJones, NullPotential, Mie};
-use lumol_core::energy::TableComputation;
+use lumol_core::energy::{CustomPotential, TableComputation};
+use lumol_core::energy::{CosineSquared, Fene, Wca};
+use lumol_core::energy::TabulatedDomain;
 
 use super::read_restriction;
 use crate::{Error, InteractionsInput, FromToml, FromTomlWithData};
@@ -18,6 +20,7 @@ use crate::extract;
 struct GlobalInformation<'a> {
     cutoff: Option<&'a Value>,
     tail: Option<bool>,
+    mixing_rule: Option<&'a str>,
 }
 
 impl GlobalInformation<'_> {
@@ -36,16 +39,25 @@ impl GlobalInformation<'_> {
                         )
                     })
                     .map_or(Ok(None), |tail| tail.map(Some))?;
+                let mixing_rule = global.get("mixing_rule")
+                    .map(|rule| {
+                        rule.as_str().ok_or(
+                            Error::from("the 'mixing_rule' section must be a string")
+                        )
+                    })
+                    .map_or(Ok(None), |rule| rule.map(Some))?;
 
                 Ok(GlobalInformation {
                     cutoff: cutoff,
                     tail: tail,
+                    mixing_rule: mixing_rule,
                 })
             }
             None => {
                 Ok(GlobalInformation {
                     cutoff: None,
                     tail: None,
+                    mixing_rule: None,
                 })
             }
         }
@@ -80,7 +92,7 @@ impl InteractionsInput {
                 ))
             )?;
 
-            let potential = read_pair_potential(table)?;
+            let potential = read_pair_potential(self, table)?;
             let potential = if let Some(computation) = table.get("computation") {
                 let computation = computation.as_table().ok_or(
                     Error::from("'computation' section must be a table")
@@ -99,23 +111,7 @@ impl InteractionsInput {
                 }
             };
 
-            let mut interaction = match *cutoff {
-                Value::String(ref cutoff) => {
-                    let cutoff = units::from_str(cutoff)?;
-                    PairInteraction::new(potential, cutoff)
-                }
-                Value::Table(ref table) => {
-                    let shifted = table.get("shifted").ok_or(
-                        Error::from("'cutoff' table can only contain 'shifted' key")
-                    )?;
-                    let cutoff = shifted.as_str().ok_or(
-                        Error::from("'cutoff.shifted' value must be a string")
-                    )?;
-                    let cutoff = units::from_str(cutoff)?;
-                    PairInteraction::shifted(potential, cutoff)
-                }
-                _ => return Err(Error::from("'cutoff' must be a string or a table")),
-            };
+            let mut interaction = read_cutoff(cutoff, potential)?;
 
             let tail = table.get("tail_correction")
                 .map(|tail| {
@@ -140,6 +136,46 @@ impl InteractionsInput {
         Ok(())
     }
 
+    /// Read the "atoms" section from the potential configuration, and use it
+    /// with the mixing rule to create the missing pair interactions.
+    pub(crate) fn read_atoms(&self, system: &mut System) -> Result<(), Error> {
+        let atoms = match self.config.get("atoms") {
+            Some(atoms) => atoms,
+            None => return Ok(()),
+        };
+
+        let atoms = atoms.as_table().ok_or(
+            Error::from("the 'atoms' section must be a table")
+        )?;
+
+        let global = GlobalInformation::read(&self.config)?;
+        let rule = match global.mixing_rule.unwrap_or("lorentz-berthelot") {
+            "lorentz-berthelot" => MixingRule::LorentzBerthelot,
+            "geometric" => MixingRule::Geometric,
+            "waldman-hagler" | "sixth-power" => MixingRule::WaldmanHagler,
+            other => return Err(Error::from(format!("unknown mixing rule '{}'", other))),
+        };
+
+        let cutoff = global.cutoff.ok_or(
+            Error::from("missing 'cutoff' value in 'global' section for mixing rule")
+        )?;
+        let mut template = read_cutoff(cutoff, Box::new(NullPotential))?;
+        if global.tail == Some(true) {
+            template.enable_tail_corrections();
+        }
+
+        for (name, table) in atoms {
+            let table = table.as_table().ok_or(
+                Error::from(format!("parameters associated with {} must be a table", name))
+            )?;
+            let parameters = LennardJones::from_toml(table)?;
+            system.set_lennard_jones_parameters(name, parameters);
+        }
+
+        system.set_mixing_rule(rule, template);
+        Ok(())
+    }
+
     /// Read the "bonds" section from the potential configuration.
     pub(crate) fn read_bonds(&self, system: &mut System) -> Result<(), Error> {
         let bonds = match self.config.get("bonds") {
@@ -165,14 +201,72 @@ impl InteractionsInput {
                 ))
             )?;
 
-            let potential = read_bond_potential(table)?;
+            let potential = read_bond_potential(self, table)?;
             system.set_bond_potential((atoms[0], atoms[1]), potential);
         }
         Ok(())
     }
 }
 
-fn read_pair_potential(table: &Table) -> Result<Box<dyn PairPotential>, Error> {
+/// Create a pair interaction for `potential` using the given `cutoff`
+/// specification, either a string or a table containing the 'shifted' key.
+fn read_cutoff(cutoff: &Value, potential: Box<dyn PairPotential>) -> Result<PairInteraction, Error> {
+    match *cutoff {
+        Value::String(ref cutoff) => {
+            let cutoff = units::from_str(cutoff)?;
+            Ok(PairInteraction::new(potential, cutoff))
+        }
+        Value::Table(ref table) => {
+            let computations = ["shifted", "energy_switch", "force_switch", "taper"];
+            for key in table.keys() {
+                if key != "switch" && !computations.contains(&key.as_str()) {
+                    return Err(Error::from(format!("unknown key '{}' in 'cutoff' table", key)));
+                }
+            }
+
+            let computation = table.keys().map(String::as_str).filter(|key| *key != "switch").collect::<Vec<_>>();
+            if computation.len() != 1 {
+                return Err(Error::from(
+                    "'cutoff' table must contain one of 'shifted', 'energy_switch', 'force_switch' or 'taper' keys"
+                ));
+            }
+
+            let computation = computation[0];
+            let cutoff = table[computation].as_str().ok_or(
+                Error::from(format!("'cutoff.{}' value must be a string", computation))
+            )?;
+            let cutoff = units::from_str(cutoff)?;
+
+            if computation == "shifted" {
+                if table.contains_key("switch") {
+                    return Err(Error::from("'cutoff.switch' can not be used with 'shifted' cutoff"));
+                }
+                return Ok(PairInteraction::shifted(potential, cutoff));
+            }
+
+            let switch = table.get("switch").ok_or(
+                Error::from(format!("missing 'cutoff.switch' value for '{}' cutoff", computation))
+            )?;
+            let switch = switch.as_str().ok_or(
+                Error::from("'cutoff.switch' value must be a string")
+            )?;
+            let switch = units::from_str(switch)?;
+            if switch < 0.0 || switch >= cutoff {
+                return Err(Error::from("'cutoff.switch' must be positive and smaller than the cutoff"));
+            }
+
+            match computation {
+                "energy_switch" => Ok(PairInteraction::energy_switch(potential, switch, cutoff)),
+                "force_switch" => Ok(PairInteraction::force_switch(potential, switch, cutoff)),
+                "taper" => Ok(PairInteraction::taper(potential, switch, cutoff)),
+                _ => unreachable!(),
+            }
+        }
+        _ => Err(Error::from("'cutoff' must be a string or a table")),
+    }
+}
+
+fn read_pair_potential(input: &InteractionsInput, table: &Table) -> Result<Box<dyn PairPotential>, Error> {
     match extract::typ(table, "pair potential")? {
         "null" => Ok(Box::new(NullPotential::from_toml(table)?)),
         "harmonic" => Ok(Box::new(Harmonic::from_toml(table)?)),
@@ -182,15 +276,22 @@ fn read_pair_potential(table: &Table) -> Result<Box<dyn PairPotential>, Error> {
         "morse" => Ok(Box::new(Morse::from_toml(table)?)),
         "gaussian" => Ok(Box::new(Gaussian::from_toml(table)?)),
         "mie" => Ok(Box::new(Mie::from_toml(table)?)),
+        "wca" => Ok(Box::new(Wca::from_toml(table)?)),
+        "cosine-squared" => Ok(Box::new(CosineSquared::from_toml(table)?)),
+        "custom" => Ok(Box::new(CustomPotential::from_toml(table, "r")?)),
+        "tabulated" => Ok(Box::new(input.read_tabulated(table, "A", TabulatedDomain::Pair)?)),
         other => Err(Error::from(format!("unknown potential type '{}'", other))),
     }
 }
 
-fn read_bond_potential(table: &Table) -> Result<Box<dyn BondPotential>, Error> {
+fn read_bond_potential(input: &InteractionsInput, table: &Table) -> Result<Box<dyn BondPotential>, Error> {
     match extract::typ(table, "bond potential")? {
         "null" => Ok(Box::new(NullPotential::from_toml(table)?)),
         "harmonic" => Ok(Box::new(Harmonic::from_toml(table)?)),
         "morse" => Ok(Box::new(Morse::from_toml(table)?)),
+        "fene" => Ok(Box::new(Fene::from_toml(table)?)),
+        "custom" => Ok(Box::new(CustomPotential::from_toml(table, "r")?)),
+        "tabulated" => Ok(Box::new(input.read_tabulated(table, "A", TabulatedDomain::Bond)?)),
         other => Err(Error::from(format!("unknown potential type '{}'", other))),
     }
 }
@@ -3,7 +3,9 @@
 
 #![allow(clippy::wildcard_imports)]
 
-use toml::value::Table;
+use std::collections::BTreeMap;
+
+use toml::value::{Table, Value};
 use log::warn;
 
 use lumol_core::units;
@@ -82,6 +84,78 @@ impl FromToml for Torsion {
     }
 }
 
+impl FromToml for UreyBradley {
+    fn from_toml(table: &Table) -> Result<UreyBradley, Error> {
+        let k = extract::str("k", table, "Urey-Bradley potential")?;
+        let x0 = extract::str("x0", table, "Urey-Bradley potential")?;
+        let k_ub = extract::str("k_ub", table, "Urey-Bradley potential")?;
+        let r_ub = extract::str("r_ub", table, "Urey-Bradley potential")?;
+        Ok(UreyBradley {
+            k: units::from_str(k)?,
+            x0: units::from_str(x0)?,
+            k_ub: units::from_str(k_ub)?,
+            r_ub: units::from_str(r_ub)?,
+        })
+    }
+}
+
+impl FromToml for BondBond {
+    fn from_toml(table: &Table) -> Result<BondBond, Error> {
+        let k = extract::str("k", table, "bond-bond potential")?;
+        let r1 = extract::str("r1", table, "bond-bond potential")?;
+        let r2 = extract::str("r2", table, "bond-bond potential")?;
+        Ok(BondBond {
+            k: units::from_str(k)?,
+            r1: units::from_str(r1)?,
+            r2: units::from_str(r2)?,
+        })
+    }
+}
+
+impl FromToml for BondAngle {
+    fn from_toml(table: &Table) -> Result<BondAngle, Error> {
+        let k1 = extract::str("k1", table, "bond-angle potential")?;
+        let k2 = extract::str("k2", table, "bond-angle potential")?;
+        let r1 = extract::str("r1", table, "bond-angle potential")?;
+        let r2 = extract::str("r2", table, "bond-angle potential")?;
+        let theta0 = extract::str("theta0", table, "bond-angle potential")?;
+        Ok(BondAngle {
+            k1: units::from_str(k1)?,
+            k2: units::from_str(k2)?,
+            r1: units::from_str(r1)?,
+            r2: units::from_str(r2)?,
+            theta0: units::from_str(theta0)?,
+        })
+    }
+}
+
+impl FromToml for RyckaertBellemans {
+    fn from_toml(table: &Table) -> Result<RyckaertBellemans, Error> {
+        let mut c = [0.0; 6];
+        for (n, value) in c.iter_mut().enumerate() {
+            let key = format!("c{}", n);
+            let cn = extract::str(&key, table, "Ryckaert-Bellemans potential")?;
+            *value = units::from_str(cn)?;
+        }
+        Ok(RyckaertBellemans { c: c })
+    }
+}
+
+impl FromToml for OplsTorsion {
+    fn from_toml(table: &Table) -> Result<OplsTorsion, Error> {
+        let k1 = extract::str("k1", table, "OPLS potential")?;
+        let k2 = extract::str("k2", table, "OPLS potential")?;
+        let k3 = extract::str("k3", table, "OPLS potential")?;
+        let k4 = extract::str("k4", table, "OPLS potential")?;
+        Ok(OplsTorsion {
+            k1: units::from_str(k1)?,
+            k2: units::from_str(k2)?,
+            k3: units::from_str(k3)?,
+            k4: units::from_str(k4)?,
+        })
+    }
+}
+
 impl FromToml for Buckingham {
     fn from_toml(table: &Table) -> Result<Buckingham, Error> {
         let a = extract::str("A", table, "Buckingham potential")?;
@@ -140,6 +214,82 @@ impl FromToml for Gaussian {
     }
 }
 
+impl FromToml for Fene {
+    fn from_toml(table: &Table) -> Result<Fene, Error> {
+        let k = extract::str("k", table, "FENE potential")?;
+        let r0 = units::from_str(extract::str("r0", table, "FENE potential")?)?;
+        if r0 <= 0.0 {
+            return Err(Error::from("'r0' parameter has to be positive in FENE potential"));
+        }
+        Ok(Fene {
+            k: units::from_str(k)?,
+            r0: r0,
+        })
+    }
+}
+
+impl FromToml for Wca {
+    fn from_toml(table: &Table) -> Result<Wca, Error> {
+        let sigma = extract::str("sigma", table, "WCA potential")?;
+        let epsilon = extract::str("epsilon", table, "WCA potential")?;
+        Ok(Wca {
+            sigma: units::from_str(sigma)?,
+            epsilon: units::from_str(epsilon)?,
+        })
+    }
+}
+
+impl FromToml for CosineSquared {
+    fn from_toml(table: &Table) -> Result<CosineSquared, Error> {
+        let epsilon = extract::str("epsilon", table, "cosine-squared potential")?;
+        let r0 = extract::str("r0", table, "cosine-squared potential")?;
+        let width = units::from_str(extract::str("width", table, "cosine-squared potential")?)?;
+        if width <= 0.0 {
+            return Err(Error::from("'width' parameter has to be positive in cosine-squared potential"));
+        }
+        Ok(CosineSquared {
+            epsilon: units::from_str(epsilon)?,
+            r0: units::from_str(r0)?,
+            width: width,
+        })
+    }
+}
+
+impl FromTomlWithData for CustomPotential {
+    // Name of the variable in the expression
+    type Data = &'static str;
+
+    fn from_toml(table: &Table, variable: &'static str) -> Result<CustomPotential, Error> {
+        let expression = extract::str("expression", table, "custom potential")?;
+
+        let mut parameters = BTreeMap::new();
+        if table.contains_key("parameters") {
+            let values = extract::table("parameters", table, "custom potential")?;
+            for (name, value) in values {
+                if name == variable {
+                    return Err(Error::from(format!(
+                        "'{}' can not be used as a parameter name in custom potential", name
+                    )));
+                }
+
+                let value = match *value {
+                    Value::String(ref value) => units::from_str(value)?,
+                    Value::Integer(value) => value as f64,
+                    Value::Float(value) => value,
+                    _ => return Err(Error::from(format!(
+                        "parameter '{}' must be a string or a number in custom potential", name
+                    ))),
+                };
+                let _ = parameters.insert(name.clone(), value);
+            }
+        }
+
+        CustomPotential::new(expression, variable, parameters).map_err(|err| {
+            Error::from(format!("invalid expression in custom potential: {}", err))
+        })
+    }
+}
+
 impl FromTomlWithData for TableComputation {
     type Data = Box<dyn PairPotential>;
 
@@ -161,6 +311,128 @@ impl FromToml for Wolf {
     }
 }
 
+impl FromToml for Polarization {
+    fn from_toml(table: &Table) -> Result<Polarization, Error> {
+        let solver = if table.contains_key("solver") {
+            match extract::str("solver", table, "polarization")? {
+                "iterative" => DipoleSolver::Iterative,
+                "conjugate-gradient" => DipoleSolver::ConjugateGradient,
+                other => return Err(Error::from(format!("unknown solver '{}' in polarization", other))),
+            }
+        } else {
+            DipoleSolver::ConjugateGradient
+        };
+
+        let tolerance = if table.contains_key("tolerance") {
+            extract::number("tolerance", table, "polarization")?
+        } else {
+            1e-6
+        };
+        if tolerance <= 0.0 {
+            return Err(Error::from("'tolerance' must be positive in polarization"));
+        }
+
+        let mut polarization = Polarization::new(solver, tolerance);
+        if table.contains_key("max_iterations") {
+            let max_iterations = extract::uint("max_iterations", table, "polarization")?;
+            if max_iterations == 0 {
+                return Err(Error::from("'max_iterations' can not be 0 in polarization"));
+            }
+            polarization.set_max_iterations(max_iterations as usize);
+        }
+
+        let polarizabilities = extract::table("polarizabilities", table, "polarization")?;
+        for (name, polarizability) in polarizabilities {
+            let polarizability = polarizability.as_str().ok_or(Error::from(
+                format!("the polarizability of {} must be a string in polarization", name)
+            ))?;
+            let polarizability = units::from_str(polarizability)?;
+            if polarizability <= 0.0 {
+                return Err(Error::from(
+                    format!("the polarizability of {} must be positive in polarization", name)
+                ));
+            }
+            polarization.set_polarizability(name, polarizability);
+        }
+
+        Ok(polarization)
+    }
+}
+
+impl FromToml for ReactionField {
+    fn from_toml(table: &Table) -> Result<ReactionField, Error> {
+        let cutoff = extract::str("cutoff", table, "reaction field coulombic potential")?;
+        let dielectric = extract::number("dielectric", table, "reaction field coulombic potential")?;
+        if dielectric < 1.0 {
+            return Err(Error::from(
+                "'dielectric' must be bigger than 1 in reaction field coulombic potential"
+            ));
+        }
+        Ok(ReactionField::new(units::from_str(cutoff)?, dielectric))
+    }
+}
+
+impl FromToml for DampedShiftedForce {
+    fn from_toml(table: &Table) -> Result<DampedShiftedForce, Error> {
+        let cutoff = extract::str("cutoff", table, "DSF coulombic potential")?;
+        let alpha = extract::str("alpha", table, "DSF coulombic potential")?;
+        let alpha = units::from_str(alpha)?;
+        if alpha < 0.0 {
+            return Err(Error::from("'alpha' can not be negative in DSF coulombic potential"));
+        }
+        Ok(DampedShiftedForce::new(units::from_str(cutoff)?, alpha))
+    }
+}
+
+impl FromToml for Yukawa {
+    fn from_toml(table: &Table) -> Result<Yukawa, Error> {
+        let cutoff = extract::str("cutoff", table, "Yukawa coulombic potential")?;
+        let cutoff = units::from_str(cutoff)?;
+        if cutoff <= 0.0 {
+            return Err(Error::from("'cutoff' must be positive in Yukawa coulombic potential"));
+        }
+
+        let permittivity = if table.contains_key("permittivity") {
+            extract::number("permittivity", table, "Yukawa coulombic potential")?
+        } else {
+            1.0
+        };
+        if permittivity <= 0.0 {
+            return Err(Error::from("'permittivity' must be positive in Yukawa coulombic potential"));
+        }
+
+        let kappa = if table.contains_key("kappa") {
+            if table.contains_key("ionic_strength") || table.contains_key("temperature") {
+                return Err(Error::from(
+                    "can not have both kappa and ionic_strength/temperature in Yukawa coulombic potential"
+                ));
+            }
+            let kappa = extract::str("kappa", table, "Yukawa coulombic potential")?;
+            let kappa = units::from_str(kappa)?;
+            if kappa < 0.0 {
+                return Err(Error::from("'kappa' can not be negative in Yukawa coulombic potential"));
+            }
+            kappa
+        } else if table.contains_key("ionic_strength") {
+            let ionic_strength = extract::str("ionic_strength", table, "Yukawa coulombic potential")?;
+            let ionic_strength = units::from_str(ionic_strength)?;
+            if ionic_strength < 0.0 {
+                return Err(Error::from("'ionic_strength' can not be negative in Yukawa coulombic potential"));
+            }
+            let temperature = extract::str("temperature", table, "Yukawa coulombic potential")?;
+            let temperature = units::from_str(temperature)?;
+            if temperature <= 0.0 {
+                return Err(Error::from("'temperature' must be positive in Yukawa coulombic potential"));
+            }
+            Yukawa::debye_kappa(ionic_strength, temperature, permittivity)
+        } else {
+            return Err(Error::from("missing 'kappa' or 'ionic_strength' key in Yukawa coulombic potential"));
+        };
+
+        Ok(Yukawa::new(cutoff, kappa, permittivity))
+    }
+}
+
 impl FromTomlWithRefData for Ewald {
     type Data = Configuration;
 
@@ -169,24 +441,120 @@ impl FromTomlWithRefData for Ewald {
         let cutoff = units::from_str(cutoff)?;
 
         // Check first for the accuracy key
-        if table.contains_key("accuracy") {
+        let mut ewald = if table.contains_key("accuracy") {
             if table.contains_key("kmax") || table.contains_key("alpha") {
                 return Err(Error::from(
                     "can not have both accuracy and kmax/alpha in Ewald coulombic potential"
                 ));
             }
             let accuracy = extract::number("accuracy", table, "Ewald coulombic potential")?;
-            return Ok(Ewald::with_accuracy(cutoff, accuracy, configuration));
+            Ewald::with_accuracy(cutoff, accuracy, configuration)
+        } else {
+            // Else use directly specified parameters
+            let kmax = extract::uint("kmax", table, "Ewald coulombic potential")?;
+            let alpha = if table.contains_key("alpha") {
+                let alpha = extract::str("alpha", table, "Ewald coulombic potential")?;
+                Some(units::from_str(alpha)?)
+            } else {
+                None
+            };
+            Ewald::new(cutoff, kmax as usize, alpha)
+        };
+
+        let slab = match table.get("slab") {
+            Some(slab) => slab.as_bool().ok_or(
+                Error::from("'slab' must be a boolean in Ewald coulombic potential")
+            )?,
+            None => false,
+        };
+
+        if slab {
+            let extension = if table.contains_key("slab_extension") {
+                extract::number("slab_extension", table, "Ewald coulombic potential")?
+            } else {
+                1.0
+            };
+            if extension < 1.0 {
+                return Err(Error::from(
+                    "'slab_extension' must be bigger than 1 in Ewald coulombic potential"
+                ));
+            }
+            ewald.set_slab_correction(Some(extension));
+        } else if table.contains_key("slab_extension") {
+            return Err(Error::from(
+                "'slab_extension' can only be used with 'slab = true' in Ewald coulombic potential"
+            ));
+        }
+
+        if table.contains_key("surface_dielectric") {
+            let dielectric = extract::number("surface_dielectric", table, "Ewald coulombic potential")?;
+            if dielectric < 1.0 {
+                return Err(Error::from(
+                    "'surface_dielectric' must be bigger than 1 in Ewald coulombic potential"
+                ));
+            }
+            ewald.set_surface_dielectric(dielectric);
+        }
+
+        Ok(ewald)
+    }
+}
+
+impl FromTomlWithRefData for Pme {
+    type Data = Configuration;
+
+    fn from_toml(table: &Table, configuration: &Configuration) -> Result<Pme, Error> {
+        let cutoff = extract::str("cutoff", table, "PME coulombic potential")?;
+        let cutoff = units::from_str(cutoff)?;
+
+        let order = if table.contains_key("order") {
+            extract::uint("order", table, "PME coulombic potential")? as usize
+        } else {
+            6
+        };
+        if order < 3 {
+            return Err(Error::from("'order' must be at least 3 in PME coulombic potential"));
+        }
+
+        // Check first for the accuracy key
+        if table.contains_key("accuracy") {
+            if table.contains_key("grid") || table.contains_key("alpha") {
+                return Err(Error::from(
+                    "can not have both accuracy and grid/alpha in PME coulombic potential"
+                ));
+            }
+            let accuracy = extract::number("accuracy", table, "PME coulombic potential")?;
+            return Ok(Pme::with_accuracy(cutoff, accuracy, order, configuration));
         }
 
         // Else use directly specified parameters
-        let kmax = extract::uint("kmax", table, "Ewald coulombic potential")?;
+        let grid = if table.get("grid").map_or(false, |grid| grid.is_array()) {
+            let grid = extract::slice("grid", table, "PME coulombic potential")?;
+            let grid = grid.iter().map(|size| {
+                match size.as_integer() {
+                    Some(size) if size > 0 => Ok(size as usize),
+                    _ => Err(Error::from("'grid' values must be positive integers in PME coulombic potential")),
+                }
+            }).collect::<Result<Vec<_>, _>>()?;
+            if grid.len() != 3 {
+                return Err(Error::from("'grid' array must have a size of 3 in PME coulombic potential"));
+            }
+            [grid[0], grid[1], grid[2]]
+        } else {
+            let size = extract::uint("grid", table, "PME coulombic potential")? as usize;
+            [size, size, size]
+        };
+
+        if grid.iter().any(|&size| size < order) {
+            return Err(Error::from("'grid' must contain at least 'order' points in PME coulombic potential"));
+        }
+
         let alpha = if table.contains_key("alpha") {
-            let alpha = extract::str("alpha", table, "Ewald coulombic potential")?;
+            let alpha = extract::str("alpha", table, "PME coulombic potential")?;
             Some(units::from_str(alpha)?)
         } else {
             None
         };
-        Ok(Ewald::new(cutoff, kmax as usize, alpha))
+        Ok(Pme::new(cutoff, grid, order, alpha))
     }
 }
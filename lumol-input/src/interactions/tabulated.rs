@@ -0,0 +1,54 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use toml::value::Table;
+
+use lumol_core::energy::{TabulatedDomain, TabulatedPotential};
+use lumol_core::units;
+
+use super::file_error;
+use crate::{Error, InteractionsInput};
+use crate::extract;
+
+impl InteractionsInput {
+    /// Read a tabulated potential for the given `domain` from the file given
+    /// in `table`. The values in the first column of the file use `x_unit` if
+    /// the `x_unit` key is not present.
+    pub(crate) fn read_tabulated(
+        &self,
+        table: &Table,
+        x_unit: &str,
+        domain: TabulatedDomain,
+    ) -> Result<TabulatedPotential, Error> {
+        let file = extract::str("table", table, "tabulated potential")?;
+        let path = self.file_path(file);
+
+        let x_unit = if table.contains_key("x_unit") {
+            extract::str("x_unit", table, "tabulated potential")?
+        } else {
+            x_unit
+        };
+        let energy_unit = if table.contains_key("energy_unit") {
+            extract::str("energy_unit", table, "tabulated potential")?
+        } else {
+            "kJ/mol"
+        };
+
+        let x_unit = units::from(1.0, x_unit)?;
+        let energy_unit = units::from(1.0, energy_unit)?;
+        let mut potential = TabulatedPotential::read(&path, x_unit, energy_unit).map_err(|error| file_error(error, &path))?;
+
+        let (first, last) = potential.range();
+        if !domain.covered_by(first, last) {
+            let (kind, range) = match domain {
+                TabulatedDomain::Angle => ("angle", "angles from 0 to 180 degrees"),
+                TabulatedDomain::Dihedral => ("dihedral", "a full period of 360 degrees"),
+                TabulatedDomain::Pair | TabulatedDomain::Bond => unreachable!("pairs and bonds tables cover their domain"),
+            };
+            return Err(Error::from(format!(
+                "tabulated {} potential at '{}' must cover {}", kind, path.display(), range
+            )));
+        }
+        potential.set_domain(domain);
+        return Ok(potential);
+    }
+}
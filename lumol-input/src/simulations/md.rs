@@ -2,17 +2,21 @@
 // Copyright (C) Lumol's contributors — BSD license
 #![allow(clippy::wildcard_imports)]
 
+use std::path::{Path, PathBuf};
 use toml::value::Table;
 
 use lumol_sim::md::*;
+use lumol_core::read_molecule;
 use lumol_core::units;
 
 use crate::alternator::Alternator;
 use crate::{Error, FromToml, FromTomlWithData};
 use crate::extract;
+use crate::simulations::get_input_path;
 
-impl FromToml for MolecularDynamics {
-    fn from_toml(config: &Table) -> Result<MolecularDynamics, Error> {
+impl FromTomlWithData for MolecularDynamics {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, root: PathBuf) -> Result<MolecularDynamics, Error> {
         // Get the timestep of the simulation
         let timestep = extract::str("timestep", config, "molecular dynamics propagator")?;
         let timestep = units::from_str(timestep)?;
@@ -23,16 +27,41 @@ impl FromToml for MolecularDynamics {
                 Error::from("'integrator' must be a table in molecular dynamics")
             )?;
 
-            let integrator: Box<dyn Integrator> = match extract::typ(integrator, "integrator")? {
+            let typ = extract::typ(integrator, "integrator")?;
+            let constrained = ["VelocityVerlet", "BerendsenBarostat", "AnisoBerendsenBarostat"];
+            if integrator.contains_key("constraints") && !constrained.contains(&typ) {
+                return Err(Error::from(format!(
+                    "constraints can not be used with the '{}' integrator", typ
+                )));
+            }
+
+            let constraints = read_constraints(integrator, &root)?;
+            let integrator: Box<dyn Integrator> = match typ {
                 "BerendsenBarostat" => {
-                    Box::new(BerendsenBarostat::from_toml(integrator, timestep)?)
+                    let mut barostat = BerendsenBarostat::from_toml(integrator, timestep)?;
+                    for constraints in constraints {
+                        barostat.add_constraints(constraints);
+                    }
+                    Box::new(barostat)
                 }
                 "AnisoBerendsenBarostat" => {
-                    Box::new(AnisoBerendsenBarostat::from_toml(integrator, timestep)?)
+                    let mut barostat = AnisoBerendsenBarostat::from_toml(integrator, timestep)?;
+                    for constraints in constraints {
+                        barostat.add_constraints(constraints);
+                    }
+                    Box::new(barostat)
                 }
                 "Verlet" => Box::new(Verlet::from_toml(integrator, timestep)?),
-                "VelocityVerlet" => Box::new(VelocityVerlet::from_toml(integrator, timestep)?),
+                "VelocityVerlet" => {
+                    let mut verlet = VelocityVerlet::from_toml(integrator, timestep)?;
+                    for constraints in constraints {
+                        verlet.add_constraints(constraints);
+                    }
+                    Box::new(verlet)
+                }
                 "LeapFrog" => Box::new(LeapFrog::from_toml(integrator, timestep)?),
+                "Drude" => Box::new(DrudeVelocityVerlet::from_toml(integrator, timestep)?),
+                "NoSquish" => Box::new(read_no_squish(integrator, timestep, &root)?),
                 other => return Err(Error::from(format!("unknown integrator '{}'", other))),
             };
 
@@ -124,6 +153,182 @@ impl FromTomlWithData for AnisoBerendsenBarostat {
     }
 }
 
+impl FromTomlWithData for DrudeVelocityVerlet {
+    type Data = f64;
+    fn from_toml(config: &Table, timestep: f64) -> Result<DrudeVelocityVerlet, Error> {
+        let temperature = extract::str("temperature", config, "Drude integrator")?;
+        let temperature = units::from_str(temperature)?;
+        let tau = extract::number("timestep", config, "Drude integrator")?;
+        let drude_temperature = extract::str("drude_temperature", config, "Drude integrator")?;
+        let drude_temperature = units::from_str(drude_temperature)?;
+        let drude_tau = extract::number("drude_timestep", config, "Drude integrator")?;
+        Ok(DrudeVelocityVerlet::new(timestep, temperature, tau, drude_temperature, drude_tau))
+    }
+}
+
+/// Read the constraints algorithms in an integrator `config`
+fn read_constraints(config: &Table, root: &Path) -> Result<Vec<Box<dyn Constraint>>, Error> {
+    let constraints = match config.get("constraints") {
+        Some(constraints) => constraints.as_array().ok_or(
+            Error::from("'constraints' must be an array of tables in integrator")
+        )?,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut algorithms = Vec::new();
+    for config in constraints {
+        let config = config.as_table().ok_or(
+            Error::from("'constraints' must be an array of tables in integrator")
+        )?;
+
+        let algorithm: Box<dyn Constraint> = match extract::typ(config, "constraints")? {
+            "Shake" => {
+                let (constrained, tolerance, max_iterations) = read_distance_constraints(config)?;
+                Box::new(Shake::new(constrained, tolerance, max_iterations))
+            }
+            "Rattle" => {
+                let (constrained, tolerance, max_iterations) = read_distance_constraints(config)?;
+                Box::new(Rattle::new(constrained, tolerance, max_iterations))
+            }
+            "Settle" => Box::new(read_settle(config, root)?),
+            other => return Err(Error::from(format!("unknown constraints type '{}'", other))),
+        };
+        algorithms.push(algorithm);
+    }
+
+    Ok(algorithms)
+}
+
+/// Read the constrained pairs, tolerance and maximal number of iterations for
+/// the SHAKE and RATTLE algorithms
+fn read_distance_constraints(config: &Table) -> Result<(Vec<Constrained>, f64, usize), Error> {
+    for key in config.keys() {
+        match key.as_str() {
+            "type" | "bonds" | "distances" | "tolerance" | "max_iterations" => {}
+            other => return Err(Error::from(format!("unknown key '{}' in constraints", other))),
+        }
+    }
+
+    let mut constrained = Vec::new();
+    if let Some(bonds) = config.get("bonds") {
+        if let Some(bonds) = bonds.as_str() {
+            if bonds != "all" {
+                return Err(Error::from(format!(
+                    "unknown value '{}' for 'bonds' in constraints", bonds
+                )));
+            }
+            constrained.push(Constrained::AllBonds);
+        } else if let Some(bonds) = bonds.as_array() {
+            for bond in bonds {
+                let bond = bond.as_str().ok_or(
+                    Error::from("'bonds' must be \"all\" or an array of strings in constraints")
+                )?;
+                let (first, second) = read_pair(bond)?;
+                constrained.push(Constrained::Bonds(first, second));
+            }
+        } else {
+            return Err(Error::from("'bonds' must be \"all\" or an array of strings in constraints"));
+        }
+    }
+
+    if config.contains_key("distances") {
+        let distances = extract::table("distances", config, "constraints")?;
+        for (pair, distance) in distances {
+            let (first, second) = read_pair(pair)?;
+            let distance = distance.as_str().ok_or(Error::from(format!(
+                "distance for '{}' must be a string in constraints", pair
+            )))?;
+            let distance = units::from_str(distance)?;
+            if distance <= 0.0 {
+                return Err(Error::from(format!(
+                    "distance for '{}' must be positive in constraints", pair
+                )));
+            }
+            constrained.push(Constrained::Distance(first, second, distance));
+        }
+    }
+
+    if constrained.is_empty() {
+        return Err(Error::from("missing 'bonds' or 'distances' key in constraints"));
+    }
+
+    let tolerance = if config.contains_key("tolerance") {
+        let tolerance = extract::number("tolerance", config, "constraints")?;
+        if tolerance <= 0.0 {
+            return Err(Error::from("'tolerance' must be positive in constraints"));
+        }
+        tolerance
+    } else {
+        1e-8
+    };
+
+    let max_iterations = if config.contains_key("max_iterations") {
+        let max_iterations = extract::uint("max_iterations", config, "constraints")?;
+        if max_iterations == 0 {
+            return Err(Error::from("'max_iterations' must be positive in constraints"));
+        }
+        max_iterations as usize
+    } else {
+        500
+    };
+
+    Ok((constrained, tolerance, max_iterations))
+}
+
+/// Read the SETTLE algorithm, using the optional molecule file to select the
+/// constrained molecules
+fn read_settle(config: &Table, root: &Path) -> Result<Settle, Error> {
+    for key in config.keys() {
+        match key.as_str() {
+            "type" | "molecule" => {}
+            other => return Err(Error::from(format!("unknown key '{}' in constraints", other))),
+        }
+    }
+
+    if config.get("molecule").is_some() {
+        let molfile = extract::str("molecule", config, "constraints")?;
+        let molfile = get_input_path(root, molfile);
+        let molecule = read_molecule(molfile)?;
+        if molecule.size() != 3 {
+            return Err(Error::from("SETTLE can only be used with molecules containing three particles"));
+        }
+        Ok(Settle::new(molecule.as_ref().hash()))
+    } else {
+        Ok(Settle::new(None))
+    }
+}
+
+/// Read a pair of particles names, separated by a dash
+fn read_pair(pair: &str) -> Result<(String, String), Error> {
+    let names = pair.split('-').collect::<Vec<_>>();
+    if names.len() != 2 || names[0].is_empty() || names[1].is_empty() {
+        return Err(Error::from(format!(
+            "expected two atoms for '{}' in constraints", pair
+        )));
+    }
+    Ok((names[0].into(), names[1].into()))
+}
+
+/// Read the NO_SQUISH rigid body integrator, using the optional molecule file
+/// to select the rigid molecules
+fn read_no_squish(config: &Table, timestep: f64, root: &Path) -> Result<NoSquish, Error> {
+    for key in config.keys() {
+        match key.as_str() {
+            "type" | "molecule" => {}
+            other => return Err(Error::from(format!("unknown key '{}' in NoSquish integrator", other))),
+        }
+    }
+
+    if config.get("molecule").is_some() {
+        let molfile = extract::str("molecule", config, "NoSquish integrator")?;
+        let molfile = get_input_path(root, molfile);
+        let hash = read_molecule(molfile)?.as_ref().hash();
+        Ok(NoSquish::with_molecule(timestep, hash))
+    } else {
+        Ok(NoSquish::new(timestep))
+    }
+}
+
 impl FromToml for BerendsenThermostat {
     fn from_toml(config: &Table) -> Result<BerendsenThermostat, Error> {
         let temperature = extract::str("temperature", config, "Berendsen thermostat")?;
@@ -6,6 +6,8 @@ use toml::value::Table;
 use lumol_sim::output::Output;
 use lumol_sim::output::{TrajectoryOutput, PropertiesOutput, EnergyOutput};
 use lumol_sim::output::{ForcesOutput, CellOutput, CustomOutput, StressOutput};
+use lumol_sim::output::EnergyDecompositionOutput;
+use lumol_core::sys::compute::EnergyGroups;
 
 use crate::{Input, FromToml, Error};
 use crate::extract;
@@ -41,6 +43,7 @@ impl Input {
                     "trajectory" => Box::new(TrajectoryOutput::from_toml(output)?),
                     "properties" => Box::new(PropertiesOutput::from_toml(output)?),
                     "energy" => Box::new(EnergyOutput::from_toml(output)?),
+                    "energydecomposition" => Box::new(EnergyDecompositionOutput::from_toml(output)?),
                     "stress" => Box::new(StressOutput::from_toml(output)?),
                     "forces" => Box::new(ForcesOutput::from_toml(output)?),
                     "cell" => Box::new(CellOutput::from_toml(output)?),
@@ -89,6 +92,26 @@ impl FromToml for EnergyOutput {
     }
 }
 
+impl FromToml for EnergyDecompositionOutput {
+    fn from_toml(config: &Table) -> Result<EnergyDecompositionOutput, Error> {
+        let path = get_file(config)?;
+        let groups = if config.contains_key("groups") {
+            match extract::str("groups", config, "energy decomposition output")? {
+                "particles" => EnergyGroups::Particles,
+                "molecules" => EnergyGroups::Molecules,
+                other => return Err(Error::from(format!(
+                    "unknown groups '{}' in energy decomposition output, expected 'particles' or 'molecules'",
+                    other
+                ))),
+            }
+        } else {
+            EnergyGroups::Particles
+        };
+        let output = try_io!(EnergyDecompositionOutput::new(path, groups), PathBuf::from(path));
+        Ok(output)
+    }
+}
+
 impl FromToml for PropertiesOutput {
     fn from_toml(config: &Table) -> Result<PropertiesOutput, Error> {
         let path = get_file(config)?;
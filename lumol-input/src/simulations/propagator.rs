@@ -12,7 +12,7 @@ impl Input {
         let config = self.simulation_table()?;
         let propagator = extract::table("propagator", config, "simulation")?;
         match extract::typ(propagator, "propagator")? {
-            "MolecularDynamics" => Ok(Box::new(MolecularDynamics::from_toml(propagator)?)),
+            "MolecularDynamics" => Ok(Box::new(MolecularDynamics::from_toml(propagator, self.path.clone())?)),
             "MonteCarlo" => Ok(Box::new(MonteCarlo::from_toml(propagator, self.path.clone())?)),
             "Minimization" => Ok(Box::new(Minimization::from_toml(propagator)?)),
             other => Err(Error::from(format!("unknown propagator type '{}'", other))),
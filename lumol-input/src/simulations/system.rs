@@ -3,6 +3,7 @@
 use toml::value::{Table, Value};
 
 use lumol_core::{System, UnitCell, TrajectoryBuilder};
+use lumol_core::energy::{AlchemicalCoulomb, SoftCore};
 use lumol_sim::{BoltzmannVelocities, InitVelocities};
 use lumol_core::units;
 
@@ -46,6 +47,7 @@ impl Input {
         };
 
         self.read_potentials(&mut system)?;
+        self.read_alchemical(&mut system)?;
         self.init_velocities(&mut system)?;
 
         if !with_cell && system.cell.is_infinite() {
@@ -144,7 +146,7 @@ impl Input {
                 let input = InteractionsInput::new(path)?;
                 input.read(system)?;
             } else if let Some(potentials) = potentials.as_table() {
-                let input = InteractionsInput::from_toml(potentials.clone());
+                let input = InteractionsInput::from_toml(potentials.clone(), Some(self.path.clone()));
                 input.read(system)?;
             } else {
                 return Err(Error::from("'potentials' must be a string or a table in system"));
@@ -154,6 +156,69 @@ impl Input {
         }
         Ok(())
     }
+
+    fn read_alchemical(&self, system: &mut System) -> Result<(), Error> {
+        let config = self.system_table()?;
+        let alchemical = match config.get("alchemical") {
+            Some(alchemical) => alchemical.as_table().ok_or(
+                Error::from("'alchemical' must be a table in system")
+            )?,
+            None => return Ok(()),
+        };
+
+        let molecule = extract::uint("molecule", alchemical, "alchemical coupling")? as usize;
+        if molecule >= system.molecules().count() {
+            return Err(Error::from(format!(
+                "'molecule' is out of bounds in alchemical coupling: got {} but the system has {} molecules",
+                molecule, system.molecules().count()
+            )));
+        }
+
+        let lambda_vdw = read_lambda("lambda_vdw", alchemical)?;
+        let lambda_coulomb = read_lambda("lambda_coulomb", alchemical)?;
+
+        let alpha = if alchemical.contains_key("alpha") {
+            extract::number("alpha", alchemical, "alchemical coupling")?
+        } else {
+            0.5
+        };
+        if alpha < 0.0 {
+            return Err(Error::from("'alpha' can not be negative in alchemical coupling"));
+        }
+
+        let sigma = if alchemical.contains_key("sigma") {
+            units::from_str(extract::str("sigma", alchemical, "alchemical coupling")?)?
+        } else {
+            units::from(3.0, "A").expect("A is a valid unit")
+        };
+        if sigma <= 0.0 {
+            return Err(Error::from("'sigma' must be positive in alchemical coupling"));
+        }
+
+        system.set_alchemical_molecule(molecule, SoftCore {
+            lambda: lambda_vdw,
+            alpha: alpha,
+            sigma: sigma,
+        });
+
+        let coulomb = system.coulomb_potential().map(|coulomb| coulomb.box_clone_coulombic());
+        if let Some(coulomb) = coulomb {
+            let coulomb = AlchemicalCoulomb::new(coulomb, molecule, lambda_coulomb);
+            system.set_coulomb_potential(Box::new(coulomb));
+        }
+
+        Ok(())
+    }
+}
+
+/// Read a coupling parameter at the given `key` in the alchemical coupling
+/// `table`, checking that it is between 0 and 1.
+fn read_lambda(key: &str, table: &Table) -> Result<f64, Error> {
+    let lambda = extract::number(key, table, "alchemical coupling")?;
+    if !(0.0..=1.0).contains(&lambda) {
+        return Err(Error::from(format!("'{}' must be between 0 and 1 in alchemical coupling", key)));
+    }
+    Ok(lambda)
 }
 
 #[allow(clippy::option_if_let_else)]
@@ -0,0 +1,187 @@
+Synthetic Cu-Ni potential for lumol tests
+This is not a physical potential
+eam/fs format
+2 Cu Ni
+100 0.1 100 0.05 4.95
+29 63.546 3.615 fcc
+0.0000000000e+00 -4.7384164903e-01 -6.6882039325e-01 -8.1708383626e-01 -9.4068329805e-01
+-1.0481601718e+00 -1.1438950039e+00 -1.2304900398e+00 -1.3096407865e+00 -1.3825249471e+00
+-1.4500000000e+00 -1.5127132723e+00 -1.5711676725e+00 -1.6257631376e+00 -1.6768239349e+00
+-1.7246173071e+00 -1.7693665961e+00 -1.8112607216e+00 -1.8504611797e+00 -1.8871073128e+00
+-1.9213203436e+00 -1.9532065119e+00 -1.9828595461e+00 -2.0103626332e+00 -2.0357900077e+00
+-2.0592082451e+00 -2.0806773245e+00 -2.1002515088e+00 -2.1179800796e+00 -2.1339079549e+00
+-2.1480762114e+00 -2.1605225292e+00 -2.1712815730e+00 -2.1803853187e+00 -2.1878633372e+00
+-2.1937430401e+00 -2.1980498942e+00 -2.2008076093e+00 -2.2020383034e+00 -2.2017626487e+00
+-2.2000000000e+00 -2.1967685097e+00 -2.1920852298e+00 -2.1859662030e+00 -2.1784265445e+00
+-2.1694805153e+00 -2.1591415884e+00 -2.1474225083e+00 -2.1343353450e+00 -2.1198915432e+00
+-2.1041019662e+00 -2.0869769372e+00 -2.0685262753e+00 -2.0487593300e+00 -2.0276850116e+00
+-2.0053118199e+00 -1.9816478699e+00 -1.9567009159e+00 -1.9304783736e+00 -1.9029873404e+00
+-1.8742346142e+00 -1.8442267106e+00 -1.8129698794e+00 -1.7804701194e+00 -1.7467331922e+00
+-1.7117646352e+00 -1.6755697736e+00 -1.6381537317e+00 -1.5995214431e+00 -1.5596776610e+00
+-1.5186269666e+00 -1.4763737783e+00 -1.4329223595e+00 -1.3882768258e+00 -1.3424411526e+00
+-1.2954191813e+00 -1.2472146256e+00 -1.1978310777e+00 -1.1472720131e+00 -1.0955407968e+00
+-1.0426406871e+00 -9.8857484123e-01 -9.3334631898e-01 -8.7695808727e-01 -8.1941302386e-01
+-7.6071392113e-01 -7.0086348958e-01 -6.3986436113e-01 -5.7771909226e-01 -5.1443016705e-01
+-4.5000000000e-01 -3.8443093870e-01 -3.1772526643e-01 -2.4988520459e-01 -1.8091291503e-01
+-1.1081050223e-01 -3.9580015449e-02 3.2776549281e-02 1.0625724725e-01 1.8086018323e-01
+1.2128737500e+02 1.1191119056e+02 1.0322758510e+02 9.5187416529e+01 8.7744909928e+01
+8.0857433701e+01 7.4485290192e+01 6.8591519901e+01 6.3141718426e+01 5.8103865317e+01
+5.3448164081e+01 4.9146892647e+01 4.5174263600e+01 4.1506293573e+01 3.8120681211e+01
+3.4996693159e+01 3.2115057551e+01 2.9457864525e+01 2.7008473318e+01 2.4751425501e+01
+2.2672363974e+01 2.0757957340e+01 1.8995829307e+01 1.7374492809e+01 1.5883288519e+01
+1.4512327475e+01 1.3252437566e+01 1.2095113601e+01 1.1032470745e+01 1.0057201102e+01
+9.1625332225e+00 8.3421943633e+00 7.5903753052e+00 6.9016975661e+00 6.2711828491e+00
+5.6942245790e+00 5.1665613879e+00 4.6842524216e+00 4.2436543450e+00 3.8413999328e+00
+3.4743781420e+00 3.1397155636e+00 2.8347591647e+00 2.5570602314e+00 2.3043594341e+00
+2.0745729371e+00 1.8657794846e+00 1.6762083951e+00 1.5042284027e+00 1.3483372891e+00
+1.2071522504e+00 1.0794009508e+00 9.6391321387e-01 8.5961330931e-01 7.6551279355e-01
+6.8070386613e-01 6.0435320614e-01 5.3569625562e-01 4.7403191869e-01 4.1871764759e-01
+3.6916488856e-01 3.2483486240e-01 2.8523465611e-01 2.4991360389e-01 2.1845993695e-01
+1.9049768308e-01 1.6568379836e-01 1.4370551440e-01 1.2427788567e-01 1.0714152278e-01
+9.2060498036e-02 7.8820411211e-02 6.7226603616e-02 5.7102509927e-02 4.8288137637e-02
+4.0638664839e-02 3.4023147647e-02 2.8323329199e-02 2.3432542737e-02 1.9254701775e-02
+1.5703370892e-02 1.2700911112e-02 1.0177694281e-02 8.0713812402e-03 6.3262589711e-03
+4.8926322308e-03 3.7262655187e-03 2.7878715174e-03 2.0426424264e-03 1.4598208713e-03
+1.0123073095e-03 6.7630108057e-04 4.3097245755e-04 2.5816325212e-04 1.4211370471e-04
+6.9213561625e-05 2.7775396290e-05 7.8283775492e-06 9.3082288387e-07 0.0000000000e+00
+1.2128737500e+02 1.1191119056e+02 1.0322758510e+02 9.5187416529e+01 8.7744909928e+01
+8.0857433701e+01 7.4485290192e+01 6.8591519901e+01 6.3141718426e+01 5.8103865317e+01
+5.3448164081e+01 4.9146892647e+01 4.5174263600e+01 4.1506293573e+01 3.8120681211e+01
+3.4996693159e+01 3.2115057551e+01 2.9457864525e+01 2.7008473318e+01 2.4751425501e+01
+2.2672363974e+01 2.0757957340e+01 1.8995829307e+01 1.7374492809e+01 1.5883288519e+01
+1.4512327475e+01 1.3252437566e+01 1.2095113601e+01 1.1032470745e+01 1.0057201102e+01
+9.1625332225e+00 8.3421943633e+00 7.5903753052e+00 6.9016975661e+00 6.2711828491e+00
+5.6942245790e+00 5.1665613879e+00 4.6842524216e+00 4.2436543450e+00 3.8413999328e+00
+3.4743781420e+00 3.1397155636e+00 2.8347591647e+00 2.5570602314e+00 2.3043594341e+00
+2.0745729371e+00 1.8657794846e+00 1.6762083951e+00 1.5042284027e+00 1.3483372891e+00
+1.2071522504e+00 1.0794009508e+00 9.6391321387e-01 8.5961330931e-01 7.6551279355e-01
+6.8070386613e-01 6.0435320614e-01 5.3569625562e-01 4.7403191869e-01 4.1871764759e-01
+3.6916488856e-01 3.2483486240e-01 2.8523465611e-01 2.4991360389e-01 2.1845993695e-01
+1.9049768308e-01 1.6568379836e-01 1.4370551440e-01 1.2427788567e-01 1.0714152278e-01
+9.2060498036e-02 7.8820411211e-02 6.7226603616e-02 5.7102509927e-02 4.8288137637e-02
+4.0638664839e-02 3.4023147647e-02 2.8323329199e-02 2.3432542737e-02 1.9254701775e-02
+1.5703370892e-02 1.2700911112e-02 1.0177694281e-02 8.0713812402e-03 6.3262589711e-03
+4.8926322308e-03 3.7262655187e-03 2.7878715174e-03 2.0426424264e-03 1.4598208713e-03
+1.0123073095e-03 6.7630108057e-04 4.3097245755e-04 2.5816325212e-04 1.4211370471e-04
+6.9213561625e-05 2.7775396290e-05 7.8283775492e-06 9.3082288387e-07 0.0000000000e+00
+28 58.6934 3.52 fcc
+0.0000000000e+00 -6.3195553203e-01 -8.9242719100e-01 -1.0909451150e+00 -1.2569110641e+00
+-1.4017135624e+00 -1.5311933385e+00 -1.6488200531e+00 -1.7568543820e+00 -1.8568665961e+00
+-1.9500000000e+00 -2.0371176963e+00 -2.1188902300e+00 -2.1958508502e+00 -2.2684319132e+00
+-2.3369897428e+00 -2.4018221281e+00 -2.4631809621e+00 -2.5212815730e+00 -2.5763097504e+00
+-2.6284271247e+00 -2.6777753492e+00 -2.7244793948e+00 -2.7686501776e+00 -2.8103866770e+00
+-2.8497776602e+00 -2.8869030993e+00 -2.9218353450e+00 -2.9546401061e+00 -2.9853772732e+00
+-3.0141016151e+00 -3.0408633723e+00 -3.0657087640e+00 -3.0886804249e+00 -3.1098177829e+00
+-3.1291573868e+00 -3.1467331922e+00 -3.1625768123e+00 -3.1767177379e+00 -3.1891835316e+00
+-3.2000000000e+00 -3.2091913463e+00 -3.2167803064e+00 -3.2227882707e+00 -3.2272353927e+00
+-3.2301406871e+00 -3.2315221179e+00 -3.2313966777e+00 -3.2297804600e+00 -3.2266887242e+00
+-3.2221359550e+00 -3.2161359163e+00 -3.2087017004e+00 -3.1998457733e+00 -3.1895800154e+00
+-3.1779157598e+00 -3.1648638265e+00 -3.1504345545e+00 -3.1346378315e+00 -3.1174831206e+00
+-3.0989794856e+00 -3.0791356141e+00 -3.0579598392e+00 -3.0354601592e+00 -3.0116442563e+00
+-2.9865195136e+00 -2.9600930315e+00 -2.9323716422e+00 -2.9033619242e+00 -2.8730702146e+00
+-2.8415026221e+00 -2.8086650378e+00 -2.7745631460e+00 -2.7392024344e+00 -2.7025882035e+00
+-2.6647255751e+00 -2.6256195008e+00 -2.5852747702e+00 -2.5436960175e+00 -2.5008877290e+00
+-2.4568542495e+00 -2.4115997883e+00 -2.3651284253e+00 -2.3174441164e+00 -2.2685506985e+00
+-2.2184518948e+00 -2.1671513194e+00 -2.1146524815e+00 -2.0609587897e+00 -2.0060735561e+00
+-1.9500000000e+00 -1.8927412516e+00 -1.8343003552e+00 -1.7746802728e+00 -1.7138838867e+00
+-1.6519140030e+00 -1.5887733539e+00 -1.5244646010e+00 -1.4589903370e+00 -1.3923530890e+00
+9.7029900000e+01 8.9528952450e+01 8.2582068083e+01 7.6149933223e+01 7.0195927942e+01
+6.4685946961e+01 5.9588232153e+01 5.4873215921e+01 5.0513374741e+01 4.6483092253e+01
+4.2758531265e+01 3.9317514118e+01 3.6139410880e+01 3.3205034858e+01 3.0496544969e+01
+2.7997354528e+01 2.5692046041e+01 2.3566291620e+01 2.1606778654e+01 1.9801140401e+01
+1.8137891180e+01 1.6606365872e+01 1.5196663445e+01 1.3899594247e+01 1.2706630815e+01
+1.1609861980e+01 1.0601950053e+01 9.6760908806e+00 8.8259765961e+00 8.0457608816e+00
+7.3300265780e+00 6.6737554906e+00 6.0723002442e+00 5.5213580529e+00 5.0169462793e+00
+4.5553796632e+00 4.1332491103e+00 3.7474019373e+00 3.3949234760e+00 3.0731199463e+00
+2.7795025136e+00 2.5117724509e+00 2.2678073317e+00 2.0456481852e+00 1.8434875473e+00
+1.6596583496e+00 1.4926235877e+00 1.3409667161e+00 1.2033827222e+00 1.0786698312e+00
+9.6572180031e-01 8.6352076064e-01 7.7113057109e-01 6.8769064744e-01 6.1241023484e-01
+5.4456309290e-01 4.8348256491e-01 4.2855700450e-01 3.7922553495e-01 3.3497411807e-01
+2.9533191085e-01 2.5986788992e-01 2.2818772488e-01 1.9993088311e-01 1.7476794956e-01
+1.5239814646e-01 1.3254703869e-01 1.1496441152e-01 9.9422308539e-02 8.5713218221e-02
+7.3648398429e-02 6.3056328969e-02 5.3781282893e-02 4.5682007942e-02 3.8630510110e-02
+3.2510931871e-02 2.7218518117e-02 2.2658663360e-02 1.8746034190e-02 1.5403761420e-02
+1.2562696714e-02 1.0160728890e-02 8.1421554249e-03 6.4571049922e-03 5.0610071769e-03
+3.9141057846e-03 2.9810124150e-03 2.2302972139e-03 1.6341139411e-03 1.1678566970e-03
+8.0984584764e-04 5.4104086445e-04 3.4477796604e-04 2.0653060170e-04 1.1369096377e-04
+5.5370849300e-05 2.2220317032e-05 6.2627020394e-06 7.4465830709e-07 0.0000000000e+00
+9.7029900000e+01 8.9528952450e+01 8.2582068083e+01 7.6149933223e+01 7.0195927942e+01
+6.4685946961e+01 5.9588232153e+01 5.4873215921e+01 5.0513374741e+01 4.6483092253e+01
+4.2758531265e+01 3.9317514118e+01 3.6139410880e+01 3.3205034858e+01 3.0496544969e+01
+2.7997354528e+01 2.5692046041e+01 2.3566291620e+01 2.1606778654e+01 1.9801140401e+01
+1.8137891180e+01 1.6606365872e+01 1.5196663445e+01 1.3899594247e+01 1.2706630815e+01
+1.1609861980e+01 1.0601950053e+01 9.6760908806e+00 8.8259765961e+00 8.0457608816e+00
+7.3300265780e+00 6.6737554906e+00 6.0723002442e+00 5.5213580529e+00 5.0169462793e+00
+4.5553796632e+00 4.1332491103e+00 3.7474019373e+00 3.3949234760e+00 3.0731199463e+00
+2.7795025136e+00 2.5117724509e+00 2.2678073317e+00 2.0456481852e+00 1.8434875473e+00
+1.6596583496e+00 1.4926235877e+00 1.3409667161e+00 1.2033827222e+00 1.0786698312e+00
+9.6572180031e-01 8.6352076064e-01 7.7113057109e-01 6.8769064744e-01 6.1241023484e-01
+5.4456309290e-01 4.8348256491e-01 4.2855700450e-01 3.7922553495e-01 3.3497411807e-01
+2.9533191085e-01 2.5986788992e-01 2.2818772488e-01 1.9993088311e-01 1.7476794956e-01
+1.5239814646e-01 1.3254703869e-01 1.1496441152e-01 9.9422308539e-02 8.5713218221e-02
+7.3648398429e-02 6.3056328969e-02 5.3781282893e-02 4.5682007942e-02 3.8630510110e-02
+3.2510931871e-02 2.7218518117e-02 2.2658663360e-02 1.8746034190e-02 1.5403761420e-02
+1.2562696714e-02 1.0160728890e-02 8.1421554249e-03 6.4571049922e-03 5.0610071769e-03
+3.9141057846e-03 2.9810124150e-03 2.2302972139e-03 1.6341139411e-03 1.1678566970e-03
+8.0984584764e-04 5.4104086445e-04 3.4477796604e-04 2.0653060170e-04 1.1369096377e-04
+5.5370849300e-05 2.2220317032e-05 6.2627020394e-06 7.4465830709e-07 0.0000000000e+00
+0.0000000000e+00 6.6695630019e+01 1.1698217316e+02 1.5375058503e+02 1.7945298884e+02
+1.9616378265e+02 2.0563270484e+02 2.0933087478e+02 2.0849070063e+02 2.0414043653e+02
+1.9713407481e+02 1.8817717368e+02 1.7784914652e+02 1.6662247279e+02 1.5487923325e+02
+1.4292532130e+02 1.3100263806e+02 1.1929953935e+02 1.0795976904e+02 9.7090082964e+01
+8.6766741202e+01 7.7041023947e+01 6.7943905561e+01 5.9490004149e+01 5.1680908404e+01
+4.4507970092e+01 3.7954638751e+01 3.1998404967e+01 2.6612409595e+01 2.1766768551e+01
+1.7429655968e+01 1.3568182633e+01 1.0149101472e+01 7.1393673953e+00 4.5065749380e+00
+2.2192937725e+00 2.4731927214e-01 -1.4381472260e+00 -2.8643759771e+00 -4.0570336034e+00
+-5.0401461044e+00 -5.8360925150e+00 -6.4656228226e+00 -6.9478947805e+00 -7.3005251403e+00
+-7.5396515759e+00 -7.6800022190e+00 -7.7349702714e+00 -7.7166916256e+00 -7.6361238217e+00
+-7.5031250000e+00 -7.3265317890e+00 -7.1142353078e+00 -6.8732546542e+00 -6.6098074165e+00
+-6.3293768823e+00 -6.0367757273e+00 -5.7362060603e+00 -5.4313157721e+00 -5.1252511967e+00
+-4.8207061381e+00 -4.5199673537e+00 -4.2249566111e+00 -3.9372694578e+00 -3.6582108538e+00
+-3.3888278320e+00 -3.1299393500e+00 -2.8821635053e+00 -2.6459422814e+00 -2.4215639912e+00
+-2.2091835800e+00 -2.0088409444e+00 -1.8204774191e+00 -1.6439505732e+00 -1.4790474539e+00
+-1.3254964068e+00 -1.1829775922e+00 -1.0511323137e+00 -9.2957126232e-01 -8.1788177836e-01
+-7.1563421973e-01 -6.2238752391e-01 -5.3769404106e-01 -4.6110371099e-01 -3.9216765043e-01
+-3.3044121175e-01 -2.7548656880e-01 -2.2687488122e-01 -1.8418808352e-01 -1.4702034140e-01
+-1.1497921388e-01 -8.7686555966e-02 -6.4779193551e-02 -4.5909399055e-02 -3.0745193471e-02
+-1.8970497996e-02 -1.0285155992e-02 -4.4048438977e-03 -1.0608877271e-03 0.0000000000e+00
+0.0000000000e+00 5.3356504016e+01 9.3585738530e+01 1.2300046802e+02 1.4356239107e+02
+1.5693102612e+02 1.6450616387e+02 1.6746469982e+02 1.6679256050e+02 1.6331234923e+02
+1.5770725985e+02 1.5054173894e+02 1.4227931722e+02 1.3329797823e+02 1.2390338660e+02
+1.1434025704e+02 1.0480211045e+02 9.5439631479e+01 8.6367815235e+01 7.7672066371e+01
+6.9413392962e+01 6.1632819157e+01 5.4355124449e+01 4.7592003319e+01 4.1344726723e+01
+3.5606376073e+01 3.0363711001e+01 2.5598723973e+01 2.1289927676e+01 1.7413414841e+01
+1.3943724775e+01 1.0854546106e+01 8.1192811773e+00 5.7114939162e+00 3.6052599504e+00
+1.7754350180e+00 1.9785541771e-01 -1.1505177808e+00 -2.2915007817e+00 -3.2456268828e+00
+-4.0321168835e+00 -4.6688740120e+00 -5.1724982581e+00 -5.5583158244e+00 -5.8404201122e+00
+-6.0317212607e+00 -6.1440017752e+00 -6.1879762171e+00 -6.1733533005e+00 -6.1088990574e+00
+-6.0025000000e+00 -5.8612254312e+00 -5.6913882463e+00 -5.4986037234e+00 -5.2878459332e+00
+-5.0635015058e+00 -4.8294205818e+00 -4.5889648482e+00 -4.3450526177e+00 -4.1002009573e+00
+-3.8565649105e+00 -3.6159738829e+00 -3.3799652889e+00 -3.1498155662e+00 -2.9265686831e+00
+-2.7110622656e+00 -2.5039514800e+00 -2.3057308042e+00 -2.1167538251e+00 -1.9372511930e+00
+-1.7673468640e+00 -1.6070727555e+00 -1.4563819353e+00 -1.3151604585e+00 -1.1832379631e+00
+-1.0603971254e+00 -9.4638207379e-01 -8.4090585094e-01 -7.4365700986e-01 -6.5430542269e-01
+-5.7250737579e-01 -4.9791001913e-01 -4.3015523285e-01 -3.6888296879e-01 -3.1373412035e-01
+-2.6435296940e-01 -2.2038925504e-01 -1.8149990498e-01 -1.4735046681e-01 -1.1761627312e-01
+-9.1983371105e-02 -7.0149244773e-02 -5.1823354841e-02 -3.6727519244e-02 -2.4596154777e-02
+-1.5176398396e-02 -8.2281247935e-03 -3.5238751182e-03 -8.4871018170e-04 0.0000000000e+00
+0.0000000000e+00 4.0017378012e+01 7.0189303898e+01 9.2250351017e+01 1.0767179330e+02
+1.1769826959e+02 1.2337962290e+02 1.2559852487e+02 1.2509442038e+02 1.2248426192e+02
+1.1828044488e+02 1.1290630421e+02 1.0670948791e+02 9.9973483676e+01 9.2927539947e+01
+8.5755192782e+01 7.8601582838e+01 7.1579723609e+01 6.4775861426e+01 5.8254049778e+01
+5.2060044721e+01 4.6224614368e+01 4.0766343337e+01 3.5694002489e+01 3.1008545043e+01
+2.6704782055e+01 2.2772783251e+01 1.9199042980e+01 1.5967445757e+01 1.3060061131e+01
+1.0457793581e+01 8.1409095797e+00 6.0894608829e+00 4.2836204372e+00 2.7039449628e+00
+1.3315762635e+00 1.4839156328e-01 -8.6288833559e-01 -1.7186255863e+00 -2.4342201621e+00
+-3.0240876626e+00 -3.5016555090e+00 -3.8793736935e+00 -4.1687368683e+00 -4.3803150842e+00
+-4.5237909455e+00 -4.6080013314e+00 -4.6409821628e+00 -4.6300149753e+00 -4.5816742930e+00
+-4.5018750000e+00 -4.3959190734e+00 -4.2685411847e+00 -4.1239527925e+00 -3.9658844499e+00
+-3.7976261294e+00 -3.6220654364e+00 -3.4417236362e+00 -3.2587894632e+00 -3.0751507180e+00
+-2.8924236829e+00 -2.7119804122e+00 -2.5349739667e+00 -2.3623616747e+00 -2.1949265123e+00
+-2.0332966992e+00 -1.8779636100e+00 -1.7292981032e+00 -1.5875653688e+00 -1.4529383947e+00
+-1.3255101480e+00 -1.2053045667e+00 -1.0922864515e+00 -9.8637034390e-01 -8.8742847233e-01
+-7.9529784406e-01 -7.0978655534e-01 -6.3067938820e-01 -5.5774275739e-01 -4.9072906702e-01
+-4.2938053184e-01 -3.7343251435e-01 -3.2261642464e-01 -2.7666222659e-01 -2.3530059026e-01
+-1.9826472705e-01 -1.6529194128e-01 -1.3612492873e-01 -1.1051285011e-01 -8.8212204839e-02
+-6.8987528328e-02 -5.2611933579e-02 -3.8867516131e-02 -2.7545639433e-02 -1.8447116082e-02
+-1.1382298797e-02 -6.1710935951e-03 -2.6429063386e-03 -6.3653263627e-04 0.0000000000e+00
@@ -0,0 +1,7 @@
+# Stillinger-Weber parameters for silicon, in LAMMPS format
+# Stillinger and Weber, Phys. Rev. B 31, 5262 (1985)
+#
+# element1 element2 element3
+#     epsilon sigma a lambda gamma costheta0 A B p q tol
+Si Si Si 2.1683 2.0951 1.80 21.0 1.20 -0.333333333333
+         7.049556277 0.6022245584 4.0 0.0 0.0
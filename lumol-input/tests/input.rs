@@ -157,6 +157,7 @@ impl Drop for TestsCleanup {
             "file.log",
             "custom.dat",
             "stress.dat",
+            "decomposition.dat",
             "forces.xyz",
         ];
 
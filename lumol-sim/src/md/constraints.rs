@@ -0,0 +1,987 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use std::collections::BTreeMap;
+
+use lumol_core::{System, Matrix3, MoleculeHash, Vector3D};
+
+/// Pairs of particles whose distance should be constrained during a
+/// simulation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constrained {
+    /// All the bonds in the system, constrained to their length at the
+    /// beginning of the simulation.
+    AllBonds,
+    /// The bonds between particles with the given names, constrained to their
+    /// length at the beginning of the simulation.
+    Bonds(String, String),
+    /// All the pairs of particles with the given names in the same molecule,
+    /// constrained to the given distance. This can be used for pairs which
+    /// are not bonded, such as the hydrogen atoms in rigid water models.
+    Distance(String, String, f64),
+}
+
+impl Constrained {
+    /// Does the pair of particles with names `name_i` and `name_j` match the
+    /// given names, in any order?
+    fn matches(first: &str, second: &str, name_i: &str, name_j: &str) -> bool {
+        (first == name_i && second == name_j) || (first == name_j && second == name_i)
+    }
+}
+
+/// The `Constraint` trait define the interface for algorithms enforcing
+/// distance constraints in molecular dynamics. These algorithms are used by
+/// the integrators, and each constraint remove one degree of freedom from the
+/// system.
+pub trait Constraint {
+    /// Get the number of constrained pairs in the `system`, *i.e.* the number
+    /// of frozen degrees of freedom.
+    fn count(&self, system: &System) -> usize;
+
+    /// Setup the constraints for a simulation of the `system`. This function
+    /// is called once by every simulation run.
+    fn setup(&mut self, system: &System);
+
+    /// Save the positions of the particles at the beginning of an integration
+    /// step, before any update by the integrator.
+    fn save_positions(&mut self, system: &System);
+
+    /// Enforce the constraints on the positions after an update with a
+    /// timestep of `dt`. The velocities are corrected accordingly. This
+    /// function returns the virial of the constraint forces, averaged over the
+    /// whole timestep.
+    fn constrain_positions(&mut self, system: &mut System, dt: f64) -> Matrix3;
+
+    /// Enforce the constraints on the velocities at the end of an
+    /// integration step with a timestep of `dt`. This function returns the
+    /// virial of the constraint forces, averaged over the whole timestep.
+    fn constrain_velocities(&mut self, system: &mut System, dt: f64) -> Matrix3;
+}
+
+/// A single distance constraint between the particles `i` and `j`
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct DistanceConstraint {
+    i: usize,
+    j: usize,
+    distance: f64,
+}
+
+/// Shared implementation of the constraints algorithms
+#[derive(Clone, Debug)]
+struct DistanceConstraints {
+    /// Constrained pairs specification
+    constrained: Vec<Constrained>,
+    /// Relative tolerance on the constrained distances
+    tolerance: f64,
+    /// Maximal number of iterations
+    max_iterations: usize,
+    /// Constraints for the current simulation
+    constraints: Vec<DistanceConstraint>,
+    /// Positions at the beginning of the current step
+    positions: Vec<Vector3D>,
+}
+
+impl DistanceConstraints {
+    fn new(constrained: Vec<Constrained>, tolerance: f64, max_iterations: usize) -> DistanceConstraints {
+        assert!(tolerance > 0.0, "the tolerance must be positive in constraints");
+        assert!(max_iterations > 0, "the maximal number of iterations must be positive in constraints");
+        DistanceConstraints {
+            constrained: constrained,
+            tolerance: tolerance,
+            max_iterations: max_iterations,
+            constraints: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    /// Get the list of constraints for the `system`, using the current
+    /// configuration for the bonds lengths. When a pair is specified more than
+    /// once, the last specification is used.
+    fn constraints(&self, system: &System) -> Vec<DistanceConstraint> {
+        let names = system.particles().name;
+        let mut constraints = BTreeMap::new();
+        for constrained in &self.constrained {
+            for molecule in system.molecules() {
+                match *constrained {
+                    Constrained::AllBonds => {
+                        for bond in molecule.bonds() {
+                            let (i, j) = (bond.i(), bond.j());
+                            let _ = constraints.insert((i, j), system.distance(i, j));
+                        }
+                    }
+                    Constrained::Bonds(ref first, ref second) => {
+                        for bond in molecule.bonds() {
+                            let (i, j) = (bond.i(), bond.j());
+                            if Constrained::matches(first, second, &names[i], &names[j]) {
+                                let _ = constraints.insert((i, j), system.distance(i, j));
+                            }
+                        }
+                    }
+                    Constrained::Distance(ref first, ref second, distance) => {
+                        for i in molecule.indexes() {
+                            for j in (i + 1)..molecule.indexes().end {
+                                if Constrained::matches(first, second, &names[i], &names[j]) {
+                                    let _ = constraints.insert((i, j), distance);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        return constraints.into_iter().map(|((i, j), distance)| {
+            DistanceConstraint { i: i, j: j, distance: distance }
+        }).collect();
+    }
+
+    fn setup(&mut self, system: &System) {
+        self.constraints = self.constraints(system);
+        self.positions = vec![Vector3D::zero(); system.size()];
+    }
+
+    fn save_positions(&mut self, system: &System) {
+        self.positions.clear();
+        self.positions.extend_from_slice(system.particles().position);
+    }
+
+    /// Iteratively correct the positions to satisfy the constraints, using
+    /// the SHAKE algorithm [Ryckaert1977]. The correction uses the direction
+    /// of the constraints at the beginning of the step.
+    fn shake(&mut self, system: &mut System, dt: f64) -> Matrix3 {
+        let cell = system.cell;
+        let mut virial = Matrix3::zero();
+
+        let references = self.constraints.iter().map(|constraint| {
+            let mut reference = self.positions[constraint.i] - self.positions[constraint.j];
+            cell.vector_image(&mut reference);
+            reference
+        }).collect::<Vec<_>>();
+
+        let particles = system.particles_mut();
+        let (positions, velocities, masses) = (particles.position, particles.velocity, particles.mass);
+        let mut converged = false;
+        for _ in 0..self.max_iterations {
+            converged = true;
+            for (constraint, &reference) in self.constraints.iter().zip(&references) {
+                let (i, j) = (constraint.i, constraint.j);
+                let mut rij = positions[i] - positions[j];
+                cell.vector_image(&mut rij);
+
+                let d2 = constraint.distance * constraint.distance;
+                let difference = d2 - rij.norm2();
+                if f64::abs(difference) <= 2.0 * self.tolerance * d2 {
+                    continue;
+                }
+                converged = false;
+
+                let inv_mi = 1.0 / masses[i];
+                let inv_mj = 1.0 / masses[j];
+                let projection = rij * reference;
+                assert!(
+                    projection > 0.0,
+                    "the constrained pair {}-{} was deformed too much in a single step in SHAKE", i, j
+                );
+                let g = difference / (2.0 * projection * (inv_mi + inv_mj));
+
+                let correction = g * reference;
+                positions[i] += inv_mi * correction;
+                positions[j] -= inv_mj * correction;
+                velocities[i] += inv_mi * correction / dt;
+                velocities[j] -= inv_mj * correction / dt;
+
+                // The velocity of the particle i changes by g r_ij / (m_i dt),
+                // which corresponds to a constraint force g r_ij / dt^2 on
+                // average over the step
+                let force = g / (dt * dt) * reference;
+                virial += force.tensorial(&reference);
+            }
+
+            if converged {
+                break;
+            }
+        }
+
+        if !converged {
+            panic!(
+                "SHAKE did not converge after {} iterations. You can try to \
+                 reduce the timestep or to increase the maximal number of \
+                 iterations.", self.max_iterations
+            );
+        }
+
+        return virial;
+    }
+
+    /// Iteratively correct the velocities to remove the components along the
+    /// constraints, using the second stage of the RATTLE algorithm
+    /// [Andersen1983].
+    fn rattle(&mut self, system: &mut System, dt: f64) -> Matrix3 {
+        let cell = system.cell;
+        let mut virial = Matrix3::zero();
+
+        let particles = system.particles_mut();
+        let (positions, velocities, masses) = (particles.position, particles.velocity, particles.mass);
+        let mut converged = false;
+        for _ in 0..self.max_iterations {
+            converged = true;
+            for constraint in &self.constraints {
+                let (i, j) = (constraint.i, constraint.j);
+                let mut rij = positions[i] - positions[j];
+                cell.vector_image(&mut rij);
+                let vij = velocities[i] - velocities[j];
+
+                let d2 = constraint.distance * constraint.distance;
+                let projection = rij * vij;
+                if f64::abs(projection) * dt <= self.tolerance * d2 {
+                    continue;
+                }
+                converged = false;
+
+                let inv_mi = 1.0 / masses[i];
+                let inv_mj = 1.0 / masses[j];
+                let k = projection / (d2 * (inv_mi + inv_mj));
+                velocities[i] -= inv_mi * k * rij;
+                velocities[j] += inv_mj * k * rij;
+
+                // The velocity correction corresponds to a constraint force
+                // -k r_ij / dt on the particle i, on average over the step
+                let force = -k / dt * rij;
+                virial += force.tensorial(&rij);
+            }
+
+            if converged {
+                break;
+            }
+        }
+
+        if !converged {
+            panic!(
+                "RATTLE did not converge after {} iterations. You can try to \
+                 reduce the timestep or to increase the maximal number of \
+                 iterations.", self.max_iterations
+            );
+        }
+
+        return virial;
+    }
+}
+
+/// SHAKE algorithm for distance constraints [Ryckaert1977].
+///
+/// The positions are iteratively corrected after each update until all the
+/// constrained distances are within the relative `tolerance` of their target
+/// value. The velocities are corrected consistently with the positions, but
+/// are not constrained at the end of the step, so the velocities can have
+/// small components along the constraints.
+///
+/// [Ryckaert1977]: Ryckaert, J.-P., Ciccotti, G. & Berendsen, H. J. C. J. Comput. Phys. 23, 327 (1977).
+pub struct Shake {
+    constraints: DistanceConstraints,
+}
+
+impl Shake {
+    /// Create a new `Shake` algorithm constraining the pairs specified in
+    /// `constrained`, with the given relative `tolerance` on the distances
+    /// and maximal number of iterations `max_iterations`.
+    ///
+    /// # Panics
+    ///
+    /// If `tolerance` or `max_iterations` is not positive.
+    pub fn new(constrained: Vec<Constrained>, tolerance: f64, max_iterations: usize) -> Shake {
+        Shake {
+            constraints: DistanceConstraints::new(constrained, tolerance, max_iterations),
+        }
+    }
+}
+
+impl Constraint for Shake {
+    fn count(&self, system: &System) -> usize {
+        self.constraints.constraints(system).len()
+    }
+
+    fn setup(&mut self, system: &System) {
+        self.constraints.setup(system);
+    }
+
+    fn save_positions(&mut self, system: &System) {
+        self.constraints.save_positions(system);
+    }
+
+    fn constrain_positions(&mut self, system: &mut System, dt: f64) -> Matrix3 {
+        self.constraints.shake(system, dt)
+    }
+
+    fn constrain_velocities(&mut self, _: &mut System, _: f64) -> Matrix3 {
+        Matrix3::zero()
+    }
+}
+
+/// RATTLE algorithm for distance constraints [Andersen1983].
+///
+/// This is an extension of the [`Shake`](struct.Shake.html) algorithm for
+/// velocity-Verlet integrators. The positions are corrected as in SHAKE, and
+/// the velocities are additionally corrected at the end of each step to
+/// remove any component along the constraints.
+///
+/// [Andersen1983]: Andersen, H. C. J. Comput. Phys. 52, 24 (1983).
+pub struct Rattle {
+    constraints: DistanceConstraints,
+}
+
+impl Rattle {
+    /// Create a new `Rattle` algorithm constraining the pairs specified in
+    /// `constrained`, with the given relative `tolerance` on the distances
+    /// and the velocities, and maximal number of iterations `max_iterations`.
+    ///
+    /// # Panics
+    ///
+    /// If `tolerance` or `max_iterations` is not positive.
+    pub fn new(constrained: Vec<Constrained>, tolerance: f64, max_iterations: usize) -> Rattle {
+        Rattle {
+            constraints: DistanceConstraints::new(constrained, tolerance, max_iterations),
+        }
+    }
+}
+
+impl Constraint for Rattle {
+    fn count(&self, system: &System) -> usize {
+        self.constraints.constraints(system).len()
+    }
+
+    fn setup(&mut self, system: &System) {
+        self.constraints.setup(system);
+    }
+
+    fn save_positions(&mut self, system: &System) {
+        self.constraints.save_positions(system);
+    }
+
+    fn constrain_positions(&mut self, system: &mut System, dt: f64) -> Matrix3 {
+        self.constraints.shake(system, dt)
+    }
+
+    fn constrain_velocities(&mut self, system: &mut System, dt: f64) -> Matrix3 {
+        self.constraints.rattle(system, dt)
+    }
+}
+
+/// Rigid geometry of a three-sites molecule used by SETTLE
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SettleGeometry {
+    /// Mass of the apex particle
+    mass_a: f64,
+    /// Mass of the two other particles
+    mass_b: f64,
+    /// Distance between the center of mass and the apex particle
+    ra: f64,
+    /// Distance between the center of mass and the base of the triangle
+    rb: f64,
+    /// Half the distance between the two other particles
+    rc: f64,
+}
+
+impl SettleGeometry {
+    /// Get the geometry of the molecule made of the particles `a`, `b` and
+    /// `c` in the `system`, using `a` as the apex of the triangle.
+    #[allow(clippy::float_cmp)]  // checking the masses of b and c
+    fn new(system: &System, a: usize, b: usize, c: usize) -> SettleGeometry {
+        let masses = system.particles().mass;
+        let (mass_a, mass_b) = (masses[a], masses[b]);
+        assert_eq!(
+            masses[b], masses[c],
+            "the two particles at the base of the triangle must have the same mass in SETTLE"
+        );
+
+        let d_ab = 0.5 * (system.distance(a, b) + system.distance(a, c));
+        let rc = 0.5 * system.distance(b, c);
+        let height = f64::sqrt(d_ab * d_ab - rc * rc);
+        let ra = 2.0 * mass_b * height / (mass_a + 2.0 * mass_b);
+        SettleGeometry {
+            mass_a: mass_a,
+            mass_b: mass_b,
+            ra: ra,
+            rb: height - ra,
+            rc: rc,
+        }
+    }
+}
+
+/// Molecules with a sine of the angle at the apex smaller than this value are
+/// considered linear, and can not be used with SETTLE
+const LINEAR_TOLERANCE: f64 = 1e-6;
+
+/// A rigid molecule constrained by SETTLE
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SettleMolecule {
+    /// Index of the apex particle
+    a: usize,
+    /// Index of the second particle
+    b: usize,
+    /// Index of the third particle
+    c: usize,
+    /// Geometry of the molecule
+    geometry: SettleGeometry,
+}
+
+/// SETTLE algorithm for rigid three-sites molecules [Miyamoto1992].
+///
+/// This algorithm solves analytically the SHAKE and RATTLE equations for
+/// molecules made of three particles with fixed distances, such as the
+/// SPC/E or TIP3P water models. The molecules are kept exactly rigid, and this
+/// algorithm is faster than the iterative ones.
+///
+/// Two of the particles in the molecules must have the same name and mass,
+/// and are placed at the base of the isosceles triangle formed by the
+/// molecule. Linear molecules such as CO2 can not be constrained with this
+/// algorithm. The geometry of each molecule type is taken from the first
+/// molecule of this type at the beginning of the simulation.
+///
+/// [Miyamoto1992]: Miyamoto, S. & Kollman, P. A. J. Comput. Chem. 13, 952 (1992).
+pub struct Settle {
+    /// Hash of the molecules to constrain. `None` means all molecules.
+    hash: Option<MoleculeHash>,
+    /// Molecules constrained in the current simulation
+    molecules: Vec<SettleMolecule>,
+    /// Positions at the beginning of the current step
+    positions: Vec<Vector3D>,
+}
+
+impl Settle {
+    /// Create a new `Settle` algorithm, constraining the molecules with the
+    /// given `hash`. If `hash` is `None`, all the non-linear molecules
+    /// containing three particles, two of them with the same name, are
+    /// constrained, and the other molecules are left unconstrained.
+    ///
+    /// The simulation will panic if the molecules with the given `hash` can
+    /// not be constrained with SETTLE.
+    pub fn new<H: Into<Option<MoleculeHash>>>(hash: H) -> Settle {
+        Settle {
+            hash: hash.into(),
+            molecules: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    /// Get the list of constrained molecules in the `system`
+    fn molecules(&self, system: &System) -> Vec<SettleMolecule> {
+        let mut geometries = BTreeMap::new();
+        let mut molecules = Vec::new();
+        for molecule in system.molecules() {
+            let hash = molecule.hash();
+            if let Some(selected) = self.hash {
+                if hash != selected {
+                    continue;
+                }
+            }
+
+            let start = molecule.start();
+            let entry = *geometries.entry(hash).or_insert_with(|| {
+                match settle_apex(system, start, molecule.size()) {
+                    Ok(apex) => {
+                        let (a, b, c) = (start + apex, start + (apex + 1) % 3, start + (apex + 2) % 3);
+                        Some((apex, SettleGeometry::new(system, a, b, c)))
+                    }
+                    Err(message) => {
+                        // Molecules explicitly selected must be usable with
+                        // SETTLE, the others are ignored
+                        assert!(self.hash.is_none(), "{}", message);
+                        None
+                    }
+                }
+            });
+
+            if let Some((apex, geometry)) = entry {
+                molecules.push(SettleMolecule {
+                    a: start + apex,
+                    b: start + (apex + 1) % 3,
+                    c: start + (apex + 2) % 3,
+                    geometry: geometry,
+                });
+            }
+        }
+        return molecules;
+    }
+}
+
+/// Get the index of the apex in the molecule with `size` particles starting
+/// at `start` in the `system`, *i.e.* the particle for which the two other
+/// particles have the same name. This returns an error if the molecule can not
+/// be constrained with SETTLE.
+fn settle_apex(system: &System, start: usize, size: usize) -> Result<usize, &'static str> {
+    if size != 3 {
+        return Err("SETTLE can only be used with molecules containing three particles");
+    }
+
+    let names = system.particles().name;
+    let apex = (0..3).find(|&apex| {
+        names[start + (apex + 1) % 3] == names[start + (apex + 2) % 3]
+    }).ok_or("SETTLE can only be used with molecules containing two identical particles")?;
+
+    let a = start + apex;
+    let ab = system.nearest_image(a, start + (apex + 1) % 3);
+    let ac = system.nearest_image(a, start + (apex + 2) % 3);
+    if (ab ^ ac).norm() < LINEAR_TOLERANCE * ab.norm() * ac.norm() {
+        return Err("SETTLE can not be used with linear molecules");
+    }
+
+    return Ok(apex);
+}
+
+impl Constraint for Settle {
+    fn count(&self, system: &System) -> usize {
+        3 * self.molecules(system).len()
+    }
+
+    fn setup(&mut self, system: &System) {
+        self.molecules = self.molecules(system);
+        self.positions = vec![Vector3D::zero(); system.size()];
+    }
+
+    fn save_positions(&mut self, system: &System) {
+        self.positions.clear();
+        self.positions.extend_from_slice(system.particles().position);
+    }
+
+    #[allow(clippy::many_single_char_names, clippy::similar_names)]
+    fn constrain_positions(&mut self, system: &mut System, dt: f64) -> Matrix3 {
+        let cell = system.cell;
+        let mut virial = Matrix3::zero();
+
+        let particles = system.particles_mut();
+        let (positions, velocities) = (particles.position, particles.velocity);
+        for molecule in &self.molecules {
+            let (a, b, c) = (molecule.a, molecule.b, molecule.c);
+            let SettleGeometry { mass_a, mass_b, ra, rb, rc } = molecule.geometry;
+
+            // Positions at the beginning of the step, relative to a
+            let mut b0 = self.positions[b] - self.positions[a];
+            cell.vector_image(&mut b0);
+            let mut c0 = self.positions[c] - self.positions[a];
+            cell.vector_image(&mut c0);
+
+            // Unconstrained positions, relative to the center of mass
+            let mut b1 = positions[b] - positions[a];
+            cell.vector_image(&mut b1);
+            let mut c1 = positions[c] - positions[a];
+            cell.vector_image(&mut c1);
+            let shift = mass_b * (b1 + c1) / (mass_a + 2.0 * mass_b);
+            let a1 = -shift;
+            let b1 = b1 - shift;
+            let c1 = c1 - shift;
+
+            // Build a frame with the z axis normal to the initial plane of
+            // the molecule, and a1 in the (y, z) plane.
+            let ez = (b0 ^ c0).normalized();
+            let ex = (a1 ^ ez).normalized();
+            let ey = ez ^ ex;
+
+            let (xb0, yb0) = (b0 * ex, b0 * ey);
+            let (xc0, yc0) = (c0 * ex, c0 * ey);
+            let (ya1, za1) = (a1 * ey, a1 * ez);
+            let (xb1, yb1, zb1) = (b1 * ex, b1 * ey, b1 * ez);
+            let (xc1, yc1, zc1) = (c1 * ex, c1 * ey, c1 * ez);
+
+            // Canonical position of the molecule, rotated out of the plane
+            let sin_phi = za1 / ra;
+            let cos_phi = f64::sqrt(1.0 - sin_phi * sin_phi);
+            let sin_psi = (zb1 - zc1) / (2.0 * rc * cos_phi);
+            let cos_psi = f64::sqrt(1.0 - sin_psi * sin_psi);
+
+            let ya2 = ra * cos_phi;
+            let xb2 = -rc * cos_psi;
+            let yb2 = -rb * cos_phi - rc * sin_psi * sin_phi;
+            let yc2 = -rb * cos_phi + rc * sin_psi * sin_phi;
+
+            // Rotation around the z axis
+            let alpha = xb2 * (xb0 - xc0) + yb0 * yb2 + yc0 * yc2;
+            let beta = xb2 * (yc0 - yb0) + xb0 * yb2 + xc0 * yc2;
+            let gamma = xb0 * yb1 - xb1 * yb0 + xc0 * yc1 - xc1 * yc0;
+            let alpha_beta = alpha * alpha + beta * beta;
+            let sin_theta = (alpha * gamma - beta * f64::sqrt(alpha_beta - gamma * gamma)) / alpha_beta;
+            let cos_theta = f64::sqrt(1.0 - sin_theta * sin_theta);
+            assert!(
+                !cos_theta.is_nan(),
+                "a molecule was deformed too much in a single step in SETTLE"
+            );
+
+            // The constrained positions only differ from the unconstrained
+            // ones in the (x, y) plane
+            let delta_a = (-ya2 * sin_theta) * ex + (ya2 * cos_theta - ya1) * ey;
+            let delta_b = (xb2 * cos_theta - yb2 * sin_theta - xb1) * ex
+                        + (xb2 * sin_theta + yb2 * cos_theta - yb1) * ey;
+            let delta_c = (-xb2 * cos_theta - yc2 * sin_theta - xc1) * ex
+                        + (-xb2 * sin_theta + yc2 * cos_theta - yc1) * ey;
+
+            let origin = Vector3D::zero();
+            for &(i, mass, delta, reference) in &[
+                (a, mass_a, delta_a, origin), (b, mass_b, delta_b, b0), (c, mass_b, delta_c, c0)
+            ] {
+                positions[i] += delta;
+                velocities[i] += delta / dt;
+                // The velocity correction corresponds to a constraint force
+                // m delta / dt^2 on average over the step
+                let force = mass * delta / (dt * dt);
+                virial += force.tensorial(&reference);
+            }
+        }
+
+        return virial;
+    }
+
+    fn constrain_velocities(&mut self, system: &mut System, dt: f64) -> Matrix3 {
+        let cell = system.cell;
+        let mut virial = Matrix3::zero();
+
+        let particles = system.particles_mut();
+        let (positions, velocities, masses) = (particles.position, particles.velocity, particles.mass);
+        for molecule in &self.molecules {
+            let pairs = [(molecule.a, molecule.b), (molecule.a, molecule.c), (molecule.b, molecule.c)];
+            let sign = |particle: usize, (i, j): (usize, usize)| {
+                if particle == i {
+                    1.0
+                } else if particle == j {
+                    -1.0
+                } else {
+                    0.0
+                }
+            };
+
+            let mut vectors = [Vector3D::zero(); 3];
+            let mut directions = [Vector3D::zero(); 3];
+            for (k, &(i, j)) in pairs.iter().enumerate() {
+                let mut rij = positions[i] - positions[j];
+                cell.vector_image(&mut rij);
+                vectors[k] = rij;
+                directions[k] = rij.normalized();
+            }
+
+            // Solve the linear system giving the velocities corrections
+            // along each constraint, such that the relative velocities are
+            // orthogonal to the constraints.
+            let mut matrix = Matrix3::zero();
+            let mut projections = Vector3D::zero();
+            for (k, &(i, j)) in pairs.iter().enumerate() {
+                projections[k] = (velocities[i] - velocities[j]) * directions[k];
+                for (l, &pair) in pairs.iter().enumerate() {
+                    let coupling = sign(i, pair) / masses[i] - sign(j, pair) / masses[j];
+                    matrix[k][l] = coupling * (directions[k] * directions[l]);
+                }
+            }
+            let corrections = matrix.inverse() * projections;
+
+            for (k, &(i, j)) in pairs.iter().enumerate() {
+                velocities[i] -= corrections[k] * directions[k] / masses[i];
+                velocities[j] += corrections[k] * directions[k] / masses[j];
+
+                // The velocity correction corresponds to a constraint force
+                // on the particle i, on average over the step
+                let force = -corrections[k] / dt * directions[k];
+                virial += force.tensorial(&vectors[k]);
+            }
+        }
+
+        return virial;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md::{Integrator, VelocityVerlet};
+    use crate::md::{AnisoBerendsenBarostat, BerendsenBarostat};
+    use lumol_core::{units, DegreesOfFreedom, Molecule, Particle, UnitCell};
+    use lumol_core::energy::Harmonic;
+
+    use approx::{assert_relative_eq, assert_ulps_eq};
+
+    fn water() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        let mut molecule = Molecule::new(Particle::with_position("O", [0.0, 0.0, 0.0].into()));
+        molecule.add_particle_bonded_to(0, Particle::with_position("H", [0.757, 0.586, 0.0].into()));
+        molecule.add_particle_bonded_to(0, Particle::with_position("H", [-0.757, 0.586, 0.0].into()));
+        system.add_molecule(molecule);
+
+        system.particles_mut().velocity[0] = [0.001, -0.002, 0.003].into();
+        system.particles_mut().velocity[1] = [-0.02, 0.01, 0.03].into();
+        system.particles_mut().velocity[2] = [0.01, 0.03, -0.02].into();
+        return system;
+    }
+
+    #[test]
+    fn count() {
+        let system = water();
+
+        let shake = Shake::new(vec![Constrained::AllBonds], 1e-8, 100);
+        assert_eq!(shake.count(&system), 2);
+
+        let shake = Shake::new(vec![Constrained::Bonds("H".into(), "O".into())], 1e-8, 100);
+        assert_eq!(shake.count(&system), 2);
+
+        let shake = Shake::new(vec![Constrained::Bonds("H".into(), "H".into())], 1e-8, 100);
+        assert_eq!(shake.count(&system), 0);
+
+        let rattle = Rattle::new(vec![
+            Constrained::AllBonds,
+            Constrained::Bonds("O".into(), "H".into()),
+            Constrained::Distance("H".into(), "H".into(), 1.514),
+        ], 1e-8, 100);
+        assert_eq!(rattle.count(&system), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "the tolerance must be positive in constraints")]
+    fn negative_tolerance() {
+        let _ = Rattle::new(vec![Constrained::AllBonds], -1e-8, 100);
+    }
+
+    #[test]
+    fn rattle() {
+        let mut system = water();
+        let oh = system.distance(0, 1);
+        let hh = system.distance(1, 2);
+
+        let mut integrator = VelocityVerlet::new(1.0);
+        integrator.add_constraints(Box::new(Rattle::new(vec![
+            Constrained::AllBonds,
+            Constrained::Distance("H".into(), "H".into(), hh),
+        ], 1e-10, 100)));
+        assert_eq!(integrator.degrees_of_freedom(&system), DegreesOfFreedom::Frozen(3));
+
+        integrator.setup(&system);
+        for _ in 0..100 {
+            integrator.integrate(&mut system);
+        }
+
+        assert_ulps_eq!(system.distance(0, 1), oh, epsilon = 1e-8);
+        assert_ulps_eq!(system.distance(0, 2), oh, epsilon = 1e-8);
+        assert_ulps_eq!(system.distance(1, 2), hh, epsilon = 1e-8);
+
+        // The velocities have no component along the constraints
+        let positions = system.particles().position;
+        let velocities = system.particles().velocity;
+        for &(i, j) in &[(0, 1), (0, 2), (1, 2)] {
+            let rij = positions[i] - positions[j];
+            let vij = velocities[i] - velocities[j];
+            assert!(f64::abs(rij * vij) < 1e-8);
+        }
+    }
+
+    /// A rotating and translating diatomic molecule, with a bond length of
+    /// 1.2 A
+    fn diatomic() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        let mut molecule = Molecule::new(Particle::with_position("O", [0.0, 0.0, 0.0].into()));
+        molecule.add_particle_bonded_to(0, Particle::with_position("O", [1.2, 0.0, 0.0].into()));
+        system.add_molecule(molecule);
+
+        system.particles_mut().velocity[0] = [0.005, -0.005, 0.0].into();
+        system.particles_mut().velocity[1] = [0.005, 0.005, 0.0].into();
+        return system;
+    }
+
+    /// Get the average pressure over `nsteps` integration steps
+    fn average_pressure(system: &mut System, integrator: &mut dyn Integrator, nsteps: usize) -> f64 {
+        system.simulated_degrees_of_freedom = integrator.degrees_of_freedom(system);
+        integrator.setup(system);
+        let mut pressure = 0.0;
+        for _ in 0..nsteps {
+            integrator.integrate(system);
+            pressure += system.pressure();
+        }
+        return pressure / nsteps as f64;
+    }
+
+    #[test]
+    fn pressure() {
+        // Reference pressure with a stiff bond, at equilibrium between the
+        // spring force and the centrifugal force
+        let mut system = diatomic();
+        let k = 1.0;
+        let reduced_mass = 0.5 * system.particles().mass[0];
+        let x0 = 1.2 - reduced_mass * 0.01 * 0.01 / (k * 1.2);
+        system.set_bond_potential(("O", "O"), Box::new(Harmonic { k: k, x0: x0 }));
+        let mut integrator = VelocityVerlet::new(0.5);
+        let expected = average_pressure(&mut system, &mut integrator, 1000);
+
+        let mut system = diatomic();
+        let mut integrator = VelocityVerlet::new(0.5);
+        integrator.add_constraints(Box::new(Rattle::new(vec![Constrained::AllBonds], 1e-10, 100)));
+        let pressure = average_pressure(&mut system, &mut integrator, 1000);
+        assert_relative_eq!(pressure, expected, max_relative = 1e-6);
+
+        let mut system = diatomic();
+        let mut integrator = VelocityVerlet::new(0.5);
+        integrator.add_constraints(Box::new(Shake::new(vec![Constrained::AllBonds], 1e-10, 100)));
+        let pressure = average_pressure(&mut system, &mut integrator, 1000);
+        assert_relative_eq!(pressure, expected, max_relative = 1e-3);
+    }
+
+    /// Run a few barostat steps on a constrained molecule at rest, and check
+    /// that the volume changes while the molecule stays rigid and at rest
+    fn check_barostat(integrator: &mut dyn Integrator) {
+        let mut system = diatomic();
+        system.particles_mut().velocity[0] = Vector3D::zero();
+        system.particles_mut().velocity[1] = Vector3D::zero();
+        let volume = system.volume();
+
+        system.simulated_degrees_of_freedom = integrator.degrees_of_freedom(&system);
+        integrator.setup(&system);
+        for _ in 0..10 {
+            integrator.integrate(&mut system);
+
+            assert_ulps_eq!(system.distance(0, 1), 1.2, epsilon = 1e-9);
+            for velocity in system.particles().velocity {
+                assert_ulps_eq!(velocity.norm(), 0.0, epsilon = 1e-12);
+            }
+            assert_ulps_eq!(system.constraints_virial.norm(), 0.0, epsilon = 1e-12);
+        }
+
+        assert!(system.volume() < volume);
+        // The center of mass was scaled with the cell
+        assert!(system.molecule(0).center_of_mass()[0] < 0.6);
+    }
+
+    #[test]
+    fn barostats() {
+        let pressure = units::from(1000.0, "bar").unwrap();
+
+        let mut barostat = BerendsenBarostat::new(1.0, pressure, 100.0);
+        barostat.add_constraints(Box::new(Rattle::new(vec![Constrained::AllBonds], 1e-12, 100)));
+        check_barostat(&mut barostat);
+
+        let mut barostat = AnisoBerendsenBarostat::hydrostatic(1.0, pressure, 100.0);
+        barostat.add_constraints(Box::new(Shake::new(vec![Constrained::AllBonds], 1e-12, 100)));
+        check_barostat(&mut barostat);
+    }
+
+    #[test]
+    fn shake() {
+        let mut system = water();
+        let oh = system.distance(0, 1);
+
+        let mut integrator = VelocityVerlet::new(1.0);
+        integrator.add_constraints(Box::new(Shake::new(vec![Constrained::AllBonds], 1e-10, 100)));
+        assert_eq!(integrator.degrees_of_freedom(&system), DegreesOfFreedom::Frozen(2));
+
+        integrator.setup(&system);
+        for _ in 0..100 {
+            integrator.integrate(&mut system);
+        }
+
+        assert_ulps_eq!(system.distance(0, 1), oh, epsilon = 1e-8);
+        assert_ulps_eq!(system.distance(0, 2), oh, epsilon = 1e-8);
+    }
+
+    #[test]
+    fn settle() {
+        let mut system = water();
+        let oh = system.distance(0, 1);
+        let hh = system.distance(1, 2);
+        let hash = system.molecule(0).hash();
+
+        let mut integrator = VelocityVerlet::new(1.0);
+        integrator.add_constraints(Box::new(Settle::new(hash)));
+        assert_eq!(integrator.degrees_of_freedom(&system), DegreesOfFreedom::Frozen(3));
+
+        integrator.setup(&system);
+        for _ in 0..100 {
+            integrator.integrate(&mut system);
+        }
+
+        assert_ulps_eq!(system.distance(0, 1), oh, epsilon = 1e-10);
+        assert_ulps_eq!(system.distance(0, 2), oh, epsilon = 1e-10);
+        assert_ulps_eq!(system.distance(1, 2), hh, epsilon = 1e-10);
+
+        let positions = system.particles().position;
+        let velocities = system.particles().velocity;
+        for &(i, j) in &[(0, 1), (0, 2), (1, 2)] {
+            let rij = positions[i] - positions[j];
+            let vij = velocities[i] - velocities[j];
+            assert!(f64::abs(rij * vij) < 1e-10);
+        }
+
+        // SETTLE gives the same trajectory as a converged RATTLE
+        let mut rattle_system = water();
+        let mut integrator = VelocityVerlet::new(1.0);
+        integrator.add_constraints(Box::new(Rattle::new(vec![
+            Constrained::AllBonds,
+            Constrained::Distance("H".into(), "H".into(), hh),
+        ], 1e-14, 1000)));
+
+        integrator.setup(&rattle_system);
+        for _ in 0..100 {
+            integrator.integrate(&mut rattle_system);
+        }
+
+        for i in 0..3 {
+            let delta = system.particles().position[i] - rattle_system.particles().position[i];
+            assert!(delta.norm() < 1e-8);
+        }
+
+        // And the same virial for the constraint forces
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_relative_eq!(
+                    system.constraints_virial[i][j],
+                    rattle_system.constraints_virial[i][j],
+                    epsilon = 1e-12, max_relative = 1e-5
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn settle_selection() {
+        let mut system = water();
+        // Add a water molecule with the apex in the middle, and a CO2
+        // molecule
+        let mut molecule = Molecule::new(Particle::with_position("H", [5.757, 0.586, 0.0].into()));
+        molecule.add_particle_bonded_to(0, Particle::with_position("O", [5.0, 0.0, 0.0].into()));
+        molecule.add_particle_bonded_to(1, Particle::with_position("H", [4.243, 0.586, 0.0].into()));
+        system.add_molecule(molecule);
+
+        let mut molecule = Molecule::new(Particle::with_position("O", [0.0, 5.0, 0.0].into()));
+        molecule.add_particle_bonded_to(0, Particle::with_position("C", [1.2, 5.0, 0.0].into()));
+        molecule.add_particle_bonded_to(1, Particle::with_position("O", [2.4, 5.0, 0.0].into()));
+        system.add_molecule(molecule);
+
+        let water = Settle::new(system.molecule(0).hash());
+        assert_eq!(water.count(&system), 3);
+
+        let molecules = water.molecules(&system);
+        assert_eq!(molecules.len(), 1);
+        assert_eq!((molecules[0].a, molecules[0].b, molecules[0].c), (0, 1, 2));
+
+        let other = Settle::new(system.molecule(1).hash());
+        let molecules = other.molecules(&system);
+        assert_eq!(molecules.len(), 1);
+        assert_eq!((molecules[0].a, molecules[0].b, molecules[0].c), (4, 5, 3));
+
+        // The CO2 molecule is linear and is not constrained
+        let all = Settle::new(None);
+        assert_eq!(all.count(&system), 6);
+        let molecules = all.molecules(&system);
+        assert_eq!(molecules.len(), 2);
+        assert_eq!((molecules[0].a, molecules[0].b, molecules[0].c), (0, 1, 2));
+        assert_eq!((molecules[1].a, molecules[1].b, molecules[1].c), (4, 5, 3));
+
+        // Molecules with a different number of particles are not constrained
+        let mut molecule = Molecule::new(Particle::with_position("Ar", [0.0, 10.0, 0.0].into()));
+        molecule.add_particle_bonded_to(0, Particle::with_position("Ar", [1.0, 10.0, 0.0].into()));
+        system.add_molecule(molecule);
+        assert_eq!(all.count(&system), 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "SETTLE can not be used with linear molecules")]
+    fn settle_linear() {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        let mut molecule = Molecule::new(Particle::with_position("O", [0.0, 0.0, 0.0].into()));
+        molecule.add_particle_bonded_to(0, Particle::with_position("C", [1.2, 0.0, 0.0].into()));
+        molecule.add_particle_bonded_to(1, Particle::with_position("O", [2.4, 0.0, 0.0].into()));
+        system.add_molecule(molecule);
+
+        let settle = Settle::new(system.molecule(0).hash());
+        let _ = settle.count(&system);
+    }
+}
@@ -2,7 +2,10 @@
 // Copyright (C) Lumol's contributors — BSD license
 use soa_derive::soa_zip;
 
-use lumol_core::{System, Matrix3, Vector3D};
+use lumol_core::{System, DegreesOfFreedom, Matrix3, Vector3D};
+use lumol_core::consts::K_BOLTZMANN;
+
+use super::Constraint;
 
 /// The `Integrator` trait define integrator interface for molecular dynamics.
 /// An integrator is an algorithm responsible for propagating the equations of
@@ -14,16 +17,103 @@ pub trait Integrator {
     /// Integrate the equations of motion. This is called at every step of the
     /// simulation.
     fn integrate(&mut self, system: &mut System);
+    /// Get the number of degrees of freedom simulated by this integrator. The
+    /// default is to simulate all the particles.
+    fn degrees_of_freedom(&self, _: &System) -> DegreesOfFreedom {
+        DegreesOfFreedom::Particles
+    }
+}
+
+/// Constraints algorithms used by an integrator. The virial of the constraint
+/// forces is accumulated separately for the positions and the velocities
+/// stages, and the sum of both is stored in the system.
+struct Constraints {
+    /// All the constraints algorithms, each acting on different particles
+    algorithms: Vec<Box<dyn Constraint>>,
+    /// Virial from the last positions stage
+    positions_virial: Matrix3,
+    /// Virial from the last velocities stage
+    velocities_virial: Matrix3,
+}
+
+impl Constraints {
+    fn new() -> Constraints {
+        Constraints {
+            algorithms: Vec::new(),
+            positions_virial: Matrix3::zero(),
+            velocities_virial: Matrix3::zero(),
+        }
+    }
+
+    fn push(&mut self, constraints: Box<dyn Constraint>) {
+        self.algorithms.push(constraints);
+    }
+
+    fn setup(&mut self, system: &System) {
+        self.positions_virial = Matrix3::zero();
+        self.velocities_virial = Matrix3::zero();
+        for constraints in &mut self.algorithms {
+            constraints.setup(system);
+        }
+    }
+
+    fn save_positions(&mut self, system: &System) {
+        for constraints in &mut self.algorithms {
+            constraints.save_positions(system);
+        }
+    }
+
+    fn constrain_positions(&mut self, system: &mut System, dt: f64) {
+        if self.algorithms.is_empty() {
+            return;
+        }
+
+        self.positions_virial = Matrix3::zero();
+        for constraints in &mut self.algorithms {
+            self.positions_virial += constraints.constrain_positions(system, dt);
+        }
+        system.constraints_virial = self.positions_virial + self.velocities_virial;
+    }
+
+    fn constrain_velocities(&mut self, system: &mut System, dt: f64) {
+        if self.algorithms.is_empty() {
+            return;
+        }
+
+        self.velocities_virial = Matrix3::zero();
+        for constraints in &mut self.algorithms {
+            self.velocities_virial += constraints.constrain_velocities(system, dt);
+        }
+        system.constraints_virial = self.positions_virial + self.velocities_virial;
+    }
+
+    /// Check if there are no constraints algorithms
+    fn is_empty(&self) -> bool {
+        self.algorithms.is_empty()
+    }
+
+    /// Get the degrees of freedom simulated with these constraints
+    fn degrees_of_freedom(&self, system: &System) -> DegreesOfFreedom {
+        if self.algorithms.is_empty() {
+            DegreesOfFreedom::Particles
+        } else {
+            DegreesOfFreedom::Frozen(self.algorithms.iter().map(|constraints| constraints.count(system)).sum())
+        }
+    }
 }
 
 /// Velocity-Verlet integrator.
 ///
 /// This integrator is time-reversible and symplectic (volume preserving).
+/// Distance constraints can be enforced during the integration with the
+/// [`Shake`](struct.Shake.html) or [`Rattle`](struct.Rattle.html) algorithms.
 pub struct VelocityVerlet {
     /// Timestep for the integrator
     timestep: f64,
     /// Storing the accelerations
     accelerations: Vec<Vector3D>,
+    /// Constraints algorithms
+    constraints: Constraints,
 }
 
 impl VelocityVerlet {
@@ -32,18 +122,28 @@ impl VelocityVerlet {
         VelocityVerlet {
             timestep: timestep,
             accelerations: Vec::new(),
+            constraints: Constraints::new(),
         }
     }
+
+    /// Add a constraints algorithm to use with this integrator. Different
+    /// algorithms should act on different particles.
+    pub fn add_constraints(&mut self, constraints: Box<dyn Constraint>) {
+        self.constraints.push(constraints);
+    }
 }
 
 impl Integrator for VelocityVerlet {
     fn setup(&mut self, system: &System) {
         self.accelerations = vec![Vector3D::zero(); system.size()];
+        self.constraints.setup(system);
     }
 
     fn integrate(&mut self, system: &mut System) {
         let dt = self.timestep;
 
+        self.constraints.save_positions(system);
+
         // Update velocities at t + ∆t/2 and positions at t + ∆t
         for (position, velocity, acceleration) in soa_zip!(
             system.particles_mut(), [mut position, mut velocity], &self.accelerations
@@ -52,6 +152,8 @@ impl Integrator for VelocityVerlet {
             *position += velocity * dt;
         }
 
+        self.constraints.constrain_positions(system, dt);
+
         let forces = system.forces();
         // Update accelerations at t + ∆t
         for (&mass, acceleration, force) in soa_zip!(
@@ -66,6 +168,12 @@ impl Integrator for VelocityVerlet {
         ) {
             *velocity += 0.5 * dt * acceleration;
         }
+
+        self.constraints.constrain_velocities(system, dt);
+    }
+
+    fn degrees_of_freedom(&self, system: &System) -> DegreesOfFreedom {
+        self.constraints.degrees_of_freedom(system)
     }
 }
 
@@ -172,9 +280,36 @@ impl Integrator for LeapFrog {
 /// from the DL_POLY source code.
 const WATER_COMPRESSIBILITY: f64 = 7372.0;
 
+/// Scale the positions in the `system` by the `scaling` matrix, as needed by
+/// the barostats. When using `constraints`, only the molecular centers of mass
+/// are scaled, and the molecules are translated as rigid bodies. Scaling all
+/// the positions would stretch the constrained distances, and restoring them
+/// would then appear as a change in the velocities and constraints virial.
+fn scale_positions(system: &mut System, scaling: Matrix3, constraints: &Constraints) {
+    if constraints.is_empty() {
+        for position in system.particles_mut().position {
+            *position = scaling * (*position);
+        }
+    } else {
+        let translations = system.molecules().map(|molecule| {
+            let com = molecule.center_of_mass();
+            (molecule.indexes(), scaling * com - com)
+        }).collect::<Vec<_>>();
+
+        let positions = system.particles_mut().position;
+        for (indexes, delta) in translations {
+            for i in indexes {
+                positions[i] += delta;
+            }
+        }
+    }
+}
+
 /// Berendsen barostat integrator based on velocity-Verlet.
 ///
-/// This integrator is **neither** time-reversible nor symplectic.
+/// This integrator is **neither** time-reversible nor symplectic. When using
+/// constraints, the molecular centers of mass are scaled instead of the
+/// positions of all the particles.
 pub struct BerendsenBarostat {
     /// Timestep for the integrator
     timestep: f64,
@@ -186,6 +321,8 @@ pub struct BerendsenBarostat {
     accelerations: Vec<Vector3D>,
     /// Storing the scaling factor
     eta: f64,
+    /// Constraints algorithms
+    constraints: Constraints,
 }
 
 impl BerendsenBarostat {
@@ -199,28 +336,44 @@ impl BerendsenBarostat {
             tau: tau,
             accelerations: Vec::new(),
             eta: 1.0,
+            constraints: Constraints::new(),
         }
     }
+
+    /// Add a constraints algorithm to use with this integrator. Different
+    /// algorithms should act on different particles.
+    pub fn add_constraints(&mut self, constraints: Box<dyn Constraint>) {
+        self.constraints.push(constraints);
+    }
 }
 
 impl Integrator for BerendsenBarostat {
     fn setup(&mut self, system: &System) {
         self.accelerations = vec![Vector3D::zero(); system.size()];
+        self.constraints.setup(system);
     }
 
     fn integrate(&mut self, system: &mut System) {
         let dt = self.timestep;
 
-        // Update velocities at t + ∆t/2 and positions at t + ∆t
-        for (position, velocity, acceleration) in soa_zip!(
-            system.particles_mut(), [mut position, mut velocity], &self.accelerations
+        // Update velocities at t + ∆t/2
+        for (velocity, acceleration) in soa_zip!(
+            system.particles_mut(), [mut velocity], &self.accelerations
         ) {
             *velocity += 0.5 * dt * acceleration;
-            // Scale all positions
-            *position *= self.eta;
+        }
+
+        scale_positions(system, self.eta * Matrix3::one(), &self.constraints);
+
+        self.constraints.save_positions(system);
+
+        // Update positions at t + ∆t
+        for (position, velocity) in soa_zip!(system.particles_mut(), [mut position, velocity]) {
             *position += velocity * dt;
         }
 
+        self.constraints.constrain_positions(system, dt);
+
         system.cell.scale_mut(self.eta * self.eta * self.eta * Matrix3::one());
 
         if let Some(maximum_cutoff) = system.maximum_cutoff() {
@@ -245,12 +398,20 @@ impl Integrator for BerendsenBarostat {
             *acceleration = force / mass;
             *velocity += 0.5 * dt * acceleration;
         }
+
+        self.constraints.constrain_velocities(system, dt);
+    }
+
+    fn degrees_of_freedom(&self, system: &System) -> DegreesOfFreedom {
+        self.constraints.degrees_of_freedom(system)
     }
 }
 
 /// Anisotropic Berendsen barostat integrator based on velocity-Verlet.
 ///
-/// This integrator is **neither** time-reversible nor symplectic.
+/// This integrator is **neither** time-reversible nor symplectic. When using
+/// constraints, the molecular centers of mass are scaled instead of the
+/// positions of all the particles.
 pub struct AnisoBerendsenBarostat {
     /// Timestep for the integrator
     timestep: f64,
@@ -262,6 +423,8 @@ pub struct AnisoBerendsenBarostat {
     accelerations: Vec<Vector3D>,
     /// Storing the scaling factor
     eta: Matrix3,
+    /// Constraints algorithms
+    constraints: Constraints,
 }
 
 impl AnisoBerendsenBarostat {
@@ -275,9 +438,16 @@ impl AnisoBerendsenBarostat {
             tau: tau,
             accelerations: Vec::new(),
             eta: Matrix3::one(),
+            constraints: Constraints::new(),
         }
     }
 
+    /// Add a constraints algorithm to use with this integrator. Different
+    /// algorithms should act on different particles.
+    pub fn add_constraints(&mut self, constraints: Box<dyn Constraint>) {
+        self.constraints.push(constraints);
+    }
+
     /// Create a new anisotropic Berendsen barostat with an integration timestep
     /// of `timestep`, using an hydrostatic stress matrix corresponding to the
     /// pressure `pressure` and the barostat time scale `tau`.
@@ -289,21 +459,30 @@ impl AnisoBerendsenBarostat {
 impl Integrator for AnisoBerendsenBarostat {
     fn setup(&mut self, system: &System) {
         self.accelerations = vec![Vector3D::zero(); system.size()];
+        self.constraints.setup(system);
     }
 
     fn integrate(&mut self, system: &mut System) {
         let dt = self.timestep;
 
-        // Update velocities at t + ∆t/2 and positions at t + ∆t
-        for (position, velocity, acceleration) in soa_zip!(
-            system.particles_mut(), [mut position, mut velocity], &self.accelerations
+        // Update velocities at t + ∆t/2
+        for (velocity, acceleration) in soa_zip!(
+            system.particles_mut(), [mut velocity], &self.accelerations
         ) {
             *velocity += 0.5 * dt * acceleration;
-            // Scale all positions
-            *position = self.eta * (*position);
+        }
+
+        scale_positions(system, self.eta, &self.constraints);
+
+        self.constraints.save_positions(system);
+
+        // Update positions at t + ∆t
+        for (position, velocity) in soa_zip!(system.particles_mut(), [mut position, velocity]) {
             *position += velocity * dt;
         }
 
+        self.constraints.constrain_positions(system, dt);
+
         system.cell.scale_mut(self.eta);
 
         if let Some(maximum_cutoff) = system.maximum_cutoff() {
@@ -336,5 +515,194 @@ impl Integrator for AnisoBerendsenBarostat {
             *acceleration = force / mass;
             *velocity += 0.5 * dt * acceleration;
         }
+
+        self.constraints.constrain_velocities(system, dt);
+    }
+
+    fn degrees_of_freedom(&self, system: &System) -> DegreesOfFreedom {
+        self.constraints.degrees_of_freedom(system)
+    }
+}
+
+/// A Nosé-Hoover thermostat acting on a group of degrees of freedom, used in
+/// the `DrudeVelocityVerlet` integrator.
+struct NoseHoover {
+    /// Target temperature
+    temperature: f64,
+    /// Thermostat time scale, expressed in units of the timestep
+    tau: f64,
+    /// Number of degrees of freedom coupled to this thermostat
+    dof: usize,
+    /// Thermostat mass
+    mass: f64,
+    /// Thermostat friction coefficient
+    xi: f64,
+}
+
+impl NoseHoover {
+    fn new(temperature: f64, tau: f64) -> NoseHoover {
+        assert!(temperature > 0.0, "The temperature must be positive in Drude integrator.");
+        assert!(tau >= 1.0, "The time scale tau must be at least one timestep in Drude integrator.");
+        NoseHoover {
+            temperature: temperature,
+            tau: tau,
+            dof: 0,
+            mass: 0.0,
+            xi: 0.0,
+        }
+    }
+
+    /// Setup the thermostat for `dof` degrees of freedom, integrated with the
+    /// timestep `dt`
+    fn setup(&mut self, dof: usize, dt: f64) {
+        self.dof = dof;
+        let tau = self.tau * dt;
+        self.mass = dof as f64 * K_BOLTZMANN * self.temperature * tau * tau;
+        self.xi = 0.0;
+    }
+
+    /// Propagate the thermostat by half a timestep `dt`, given the current
+    /// `kinetic` energy of the coupled degrees of freedom. This returns the
+    /// scaling factor for the corresponding velocities.
+    fn half_step(&mut self, kinetic: f64, dt: f64) -> f64 {
+        if self.dof == 0 {
+            return 1.0;
+        }
+        let target = self.dof as f64 * K_BOLTZMANN * self.temperature;
+
+        self.xi += 0.25 * dt * (2.0 * kinetic - target) / self.mass;
+        let scaling = f64::exp(-0.5 * dt * self.xi);
+        let kinetic = kinetic * scaling * scaling;
+        self.xi += 0.25 * dt * (2.0 * kinetic - target) / self.mass;
+
+        return scaling;
+    }
+}
+
+/// Velocity-Verlet integrator for Drude oscillators, with two temperature
+/// Nosé-Hoover thermostats [Lamoureux2003].
+///
+/// The motion of the centers of mass of the core-Drude pairs and of the
+/// other particles is coupled to a thermostat at the physical temperature,
+/// and the relative motion of the Drude particles around their cores is
+/// coupled to a thermostat at a low temperature. This keeps the Drude
+/// particles close to their self-consistent positions, while being much
+/// cheaper than solving the self-consistent field at each step.
+///
+/// This integrator is time-reversible but not symplectic.
+///
+/// [Lamoureux2003]: Lamoureux, G. & Roux, B. J. Chem. Phys. 119, 3025 (2003).
+pub struct DrudeVelocityVerlet {
+    /// Timestep for the integrator
+    timestep: f64,
+    /// Thermostat for the centers of mass and the other particles
+    thermostat: NoseHoover,
+    /// Thermostat for the relative motion of the Drude particles
+    drude_thermostat: NoseHoover,
+    /// Core-Drude pairs in the system
+    pairs: Vec<(usize, usize)>,
+    /// Is the particle at a given index part of a core-Drude pair?
+    in_pair: Vec<bool>,
+    /// Storing the accelerations
+    accelerations: Vec<Vector3D>,
+}
+
+impl DrudeVelocityVerlet {
+    /// Create a new Drude integrator with a timestep of `timestep`. The
+    /// centers of mass are kept at `temperature` with a time scale of `tau`,
+    /// and the relative motion of the Drude particles is kept at
+    /// `drude_temperature` with a time scale of `drude_tau`. The time scales
+    /// are expressed in units of the timestep.
+    pub fn new(timestep: f64, temperature: f64, tau: f64, drude_temperature: f64, drude_tau: f64) -> DrudeVelocityVerlet {
+        DrudeVelocityVerlet {
+            timestep: timestep,
+            thermostat: NoseHoover::new(temperature, tau),
+            drude_thermostat: NoseHoover::new(drude_temperature, drude_tau),
+            pairs: Vec::new(),
+            in_pair: Vec::new(),
+            accelerations: Vec::new(),
+        }
+    }
+
+    /// Apply both thermostats to the velocities for half a timestep
+    fn thermostat_half_step(&mut self, system: &mut System) {
+        let dt = self.timestep;
+        let masses = system.particles().mass;
+        let velocities = system.particles().velocity;
+
+        let mut kinetic = 0.0;
+        let mut drude_kinetic = 0.0;
+        for &(core, drude) in &self.pairs {
+            let (m_core, m_drude) = (masses[core], masses[drude]);
+            let total_mass = m_core + m_drude;
+            let com_velocity = (m_core * velocities[core] + m_drude * velocities[drude]) / total_mass;
+            let relative = velocities[drude] - velocities[core];
+            kinetic += 0.5 * total_mass * com_velocity.norm2();
+            drude_kinetic += 0.5 * m_core * m_drude / total_mass * relative.norm2();
+        }
+        for (i, velocity) in velocities.iter().enumerate() {
+            if !self.in_pair[i] {
+                kinetic += 0.5 * masses[i] * velocity.norm2();
+            }
+        }
+
+        let scaling = self.thermostat.half_step(kinetic, dt);
+        let drude_scaling = self.drude_thermostat.half_step(drude_kinetic, dt);
+
+        for &(core, drude) in &self.pairs {
+            let (m_core, m_drude) = (system.particles().mass[core], system.particles().mass[drude]);
+            let total_mass = m_core + m_drude;
+            let velocities = system.particles_mut().velocity;
+            let com_velocity = (m_core * velocities[core] + m_drude * velocities[drude]) / total_mass;
+            let com_velocity = scaling * com_velocity;
+            let relative = drude_scaling * (velocities[drude] - velocities[core]);
+            velocities[core] = com_velocity - m_drude / total_mass * relative;
+            velocities[drude] = com_velocity + m_core / total_mass * relative;
+        }
+        for (i, velocity) in system.particles_mut().velocity.iter_mut().enumerate() {
+            if !self.in_pair[i] {
+                *velocity *= scaling;
+            }
+        }
+    }
+}
+
+impl Integrator for DrudeVelocityVerlet {
+    fn setup(&mut self, system: &System) {
+        self.accelerations = vec![Vector3D::zero(); system.size()];
+        self.pairs = system.drude_pairs();
+        self.in_pair = vec![false; system.size()];
+        for &(core, drude) in &self.pairs {
+            self.in_pair[core] = true;
+            self.in_pair[drude] = true;
+        }
+
+        let drude_dof = 3 * self.pairs.len();
+        self.thermostat.setup(system.degrees_of_freedom(), self.timestep);
+        self.drude_thermostat.setup(drude_dof, self.timestep);
+    }
+
+    fn integrate(&mut self, system: &mut System) {
+        let dt = self.timestep;
+        self.thermostat_half_step(system);
+
+        // Update velocities at t + ∆t/2 and positions at t + ∆t
+        for (position, velocity, acceleration) in soa_zip!(
+            system.particles_mut(), [mut position, mut velocity], &self.accelerations
+        ) {
+            *velocity += 0.5 * dt * acceleration;
+            *position += velocity * dt;
+        }
+
+        let forces = system.forces();
+        // Update accelerations at t + ∆t and velocities at t + ∆t
+        for (velocity, &mass, acceleration, force) in soa_zip!(
+            system.particles_mut(), [mut velocity, mass], &mut self.accelerations, &forces
+        ) {
+            *acceleration = force / mass;
+            *velocity += 0.5 * dt * acceleration;
+        }
+
+        self.thermostat_half_step(system);
     }
 }
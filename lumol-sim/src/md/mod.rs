@@ -23,7 +23,13 @@
 //! - [`BerendsenBarostat`](struct.BerendsenBarostat.html): isotropic Berendsen
 //!   barostat coupled to a velocity-Verlet integrator;
 //! - [`AnisoBerendsenBarostat`](struct.AnisoBerendsenBarostat.html) anisotropic
-//!   Berendsen barostat coupled to a velocity-Verlet integrator
+//!   Berendsen barostat coupled to a velocity-Verlet integrator;
+//! - [`DrudeVelocityVerlet`](struct.DrudeVelocityVerlet.html): velocity-Verlet
+//!   integrator for Drude oscillators, with separate thermostats for the
+//!   centers of mass and the relative motion of the Drude particles;
+//! - [`NoSquish`](struct.NoSquish.html): symplectic integrator for rigid
+//!   molecules, propagating the centers of mass and the orientations of the
+//!   molecules.
 //!
 //! # Themostats
 //!
@@ -50,16 +56,40 @@
 //!   rotational momentum of a system;
 //! - [`Rewrap`](struct.Rewrap.html): wrap all atoms from a system inside the
 //!   unit cell;
+//!
+//! # Constraints
+//!
+//! [`Constraint`](trait.Constraint.html) algorithms enforce fixed distances
+//! between pairs of particles, and are used by the integrators. The pairs to
+//! constrain are specified with the [`Constrained`](enum.Constrained.html)
+//! enum.
+//!
+//! - [`Shake`](struct.Shake.html): SHAKE algorithm, constraining the positions;
+//! - [`Rattle`](struct.Rattle.html): RATTLE algorithm, constraining both the
+//!   positions and the velocities;
+//! - [`Settle`](struct.Settle.html): analytical SETTLE algorithm for rigid
+//!   three-sites molecules such as water.
 
 mod integrators;
 pub use self::integrators::Integrator;
 
 pub use self::integrators::AnisoBerendsenBarostat;
 pub use self::integrators::BerendsenBarostat;
+pub use self::integrators::DrudeVelocityVerlet;
 pub use self::integrators::LeapFrog;
 pub use self::integrators::VelocityVerlet;
 pub use self::integrators::Verlet;
 
+mod rigid;
+pub use self::rigid::NoSquish;
+
+mod constraints;
+pub use self::constraints::{Constrained, Constraint};
+
+pub use self::constraints::Rattle;
+pub use self::constraints::Settle;
+pub use self::constraints::Shake;
+
 mod controls;
 pub use self::controls::Control;
 
@@ -50,10 +50,8 @@ impl Propagator for MolecularDynamics {
         TemperatureStrategy::Velocities
     }
 
-    fn degrees_of_freedom(&self, _: &System) -> DegreesOfFreedom {
-        // default to particles for now. change this if/when constrains are
-        // implemented
-        DegreesOfFreedom::Particles
+    fn degrees_of_freedom(&self, system: &System) -> DegreesOfFreedom {
+        self.integrator.degrees_of_freedom(system)
     }
 
     fn setup(&mut self, system: &System) {
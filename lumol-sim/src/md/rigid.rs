@@ -0,0 +1,722 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use lumol_core::{System, DegreesOfFreedom, Matrix3, MoleculeHash, Vector3D};
+
+use super::Integrator;
+
+/// Relative threshold under which a principal moment of inertia is set to
+/// zero. This happens for linear molecules and single particles.
+const ZERO_MOMENT: f64 = 1e-10;
+
+/// Quaternion stored as `[w, x, y, z]`
+type Quaternion = [f64; 4];
+
+fn dot4(a: &Quaternion, b: &Quaternion) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
+}
+
+/// Apply the permutation `P_k` from the NO_SQUISH algorithm to the
+/// quaternion `q`.
+#[allow(clippy::many_single_char_names)]
+fn permutation(k: usize, q: &Quaternion) -> Quaternion {
+    let [w, x, y, z] = *q;
+    match k {
+        0 => [-x, w, z, -y],
+        1 => [-y, -z, w, x],
+        2 => [-z, y, -x, w],
+        _ => unreachable!(),
+    }
+}
+
+/// Get the quaternion product `q (0, v)` of `q` with the pure quaternion
+/// `(0, v)`.
+#[allow(clippy::many_single_char_names)]
+fn pure_product(q: &Quaternion, v: &Vector3D) -> Quaternion {
+    let [w, x, y, z] = *q;
+    [
+        -x * v[0] - y * v[1] - z * v[2],
+        w * v[0] - z * v[1] + y * v[2],
+        z * v[0] + w * v[1] - x * v[2],
+        -y * v[0] + x * v[1] + w * v[2],
+    ]
+}
+
+/// Get the rotation matrix associated with the unit quaternion `q`
+#[allow(clippy::many_single_char_names)]
+fn rotation_matrix(q: &Quaternion) -> Matrix3 {
+    let [w, x, y, z] = *q;
+    Matrix3::new([
+        [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)],
+        [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x)],
+        [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y)],
+    ])
+}
+
+/// Get the unit quaternion associated with the rotation matrix `matrix`
+fn quaternion(matrix: &Matrix3) -> Quaternion {
+    let m = matrix;
+    let trace = m.trace();
+    if trace > 0.0 {
+        let s = 2.0 * f64::sqrt(trace + 1.0);
+        [0.25 * s, (m[2][1] - m[1][2]) / s, (m[0][2] - m[2][0]) / s, (m[1][0] - m[0][1]) / s]
+    } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+        let s = 2.0 * f64::sqrt(1.0 + m[0][0] - m[1][1] - m[2][2]);
+        [(m[2][1] - m[1][2]) / s, 0.25 * s, (m[0][1] + m[1][0]) / s, (m[0][2] + m[2][0]) / s]
+    } else if m[1][1] > m[2][2] {
+        let s = 2.0 * f64::sqrt(1.0 + m[1][1] - m[0][0] - m[2][2]);
+        [(m[0][2] - m[2][0]) / s, (m[0][1] + m[1][0]) / s, 0.25 * s, (m[1][2] + m[2][1]) / s]
+    } else {
+        let s = 2.0 * f64::sqrt(1.0 + m[2][2] - m[0][0] - m[1][1]);
+        [(m[1][0] - m[0][1]) / s, (m[0][2] + m[2][0]) / s, (m[1][2] + m[2][1]) / s, 0.25 * s]
+    }
+}
+
+/// Diagonalize the symmetric `matrix` with the Jacobi eigenvalue algorithm,
+/// returning the eigenvalues and a matrix containing the eigenvectors as
+/// columns.
+#[allow(clippy::many_single_char_names, clippy::similar_names)]
+fn principal_axes(matrix: &Matrix3) -> (Vector3D, Matrix3) {
+    let mut a = *matrix;
+    let mut vectors = Matrix3::one();
+    for _ in 0..50 {
+        let off_diagonal = a[0][1] * a[0][1] + a[0][2] * a[0][2] + a[1][2] * a[1][2];
+        if off_diagonal <= 1e-30 * a.norm() * a.norm() {
+            break;
+        }
+
+        for &(p, q) in &[(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = f64::signum(theta) / (f64::abs(theta) + f64::sqrt(theta * theta + 1.0));
+            let c = 1.0 / f64::sqrt(t * t + 1.0);
+            let s = t * c;
+            for k in 0..3 {
+                let (akp, akq) = (a[k][p], a[k][q]);
+                a[k][p] = c * akp - s * akq;
+                a[k][q] = s * akp + c * akq;
+            }
+            for k in 0..3 {
+                let (apk, aqk) = (a[p][k], a[q][k]);
+                a[p][k] = c * apk - s * aqk;
+                a[q][k] = s * apk + c * aqk;
+            }
+            for k in 0..3 {
+                let (vkp, vkq) = (vectors[k][p], vectors[k][q]);
+                vectors[k][p] = c * vkp - s * vkq;
+                vectors[k][q] = s * vkp + c * vkq;
+            }
+        }
+    }
+    return (Vector3D::new(a[0][0], a[1][1], a[2][2]), vectors);
+}
+
+/// A rigid molecule integrated by NO_SQUISH
+#[derive(Clone, Debug)]
+struct RigidBody {
+    /// Index of the first particle in the molecule
+    start: usize,
+    /// Total mass of the molecule
+    mass: f64,
+    /// Principal moments of inertia of the molecule. The moments are zero
+    /// for the axes without rotation, in linear molecules and single
+    /// particles.
+    inertia: Vector3D,
+    /// Positions of the particles relative to the center of mass, in the
+    /// principal axes frame
+    positions: Vec<Vector3D>,
+    /// Orientation of the molecule, rotating the principal axes frame to the
+    /// laboratory frame
+    orientation: Quaternion,
+}
+
+impl RigidBody {
+    /// Create a rigid body from the `size` particles starting at `start` in
+    /// the `system`, using the current configuration as the rigid geometry.
+    fn new(system: &System, start: usize, size: usize) -> RigidBody {
+        let positions = system.particles().position;
+        let masses = system.particles().mass;
+
+        let mut relative = Vec::with_capacity(size);
+        let mut mass = 0.0;
+        let mut com = Vector3D::zero();
+        for i in start..(start + size) {
+            let mut r = positions[i] - positions[start];
+            system.cell.vector_image(&mut r);
+            relative.push(r);
+            mass += masses[i];
+            com += masses[i] * r;
+        }
+        let com = com / mass;
+
+        let mut inertia = Matrix3::zero();
+        for (i, r) in relative.iter_mut().enumerate() {
+            *r -= com;
+            inertia += masses[start + i] * (r.norm2() * Matrix3::one() - r.tensorial(r));
+        }
+
+        let (mut moments, mut axes) = principal_axes(&inertia);
+        if axes.determinant() < 0.0 {
+            for k in 0..3 {
+                axes[k][2] = -axes[k][2];
+            }
+        }
+
+        let max = moments.max();
+        for k in 0..3 {
+            if moments[k] <= ZERO_MOMENT * max {
+                moments[k] = 0.0;
+            }
+        }
+
+        let to_body = axes.transposed();
+        RigidBody {
+            start: start,
+            mass: mass,
+            inertia: moments,
+            positions: relative.iter().map(|&r| to_body * r).collect(),
+            orientation: quaternion(&axes),
+        }
+    }
+
+    /// Get the number of rotational degrees of freedom of this body
+    fn rotational_degrees_of_freedom(&self) -> usize {
+        self.inertia.iter().filter(|&&moment| moment > 0.0).count()
+    }
+
+    /// Get the number of degrees of freedom removed by treating this
+    /// molecule as a rigid body
+    fn frozen_degrees_of_freedom(&self) -> usize {
+        3 * self.positions.len() - 3 - self.rotational_degrees_of_freedom()
+    }
+
+    /// Get the center of mass, the velocity of the center of mass and the
+    /// angular momentum conjugated to the orientation of this body, from the
+    /// particles velocities in the `system`. Any velocity not compatible with
+    /// the rigid body motion is discarded.
+    fn state(&self, system: &System) -> (Vector3D, Vector3D, Quaternion) {
+        let positions = system.particles().position;
+        let velocities = system.particles().velocity;
+        let masses = system.particles().mass;
+        let range = self.start..(self.start + self.positions.len());
+
+        let mut com = Vector3D::zero();
+        let mut velocity = Vector3D::zero();
+        for i in range.clone() {
+            let mut r = positions[i] - positions[self.start];
+            system.cell.vector_image(&mut r);
+            com += masses[i] * r;
+            velocity += masses[i] * velocities[i];
+        }
+        let com = positions[self.start] + com / self.mass;
+        let velocity = velocity / self.mass;
+
+        let rotation = rotation_matrix(&self.orientation);
+        let mut angular = Vector3D::zero();
+        for (i, body) in range.zip(&self.positions) {
+            angular += masses[i] * ((rotation * body) ^ (velocities[i] - velocity));
+        }
+
+        let momentum = self.momentum(&(rotation.transposed() * angular));
+        return (com, velocity, momentum);
+    }
+
+    /// Get the conjugated angular momentum corresponding to the angular
+    /// momentum `angular` in the principal axes frame.
+    fn momentum(&self, angular: &Vector3D) -> Quaternion {
+        let mut angular = *angular;
+        for k in 0..3 {
+            if self.inertia[k] == 0.0 {
+                angular[k] = 0.0;
+            }
+        }
+        let momentum = pure_product(&self.orientation, &angular);
+        return [2.0 * momentum[0], 2.0 * momentum[1], 2.0 * momentum[2], 2.0 * momentum[3]];
+    }
+
+    /// Update the center of mass `velocity` and the conjugated angular
+    /// `momentum` for a time `dt`, using the given `forces` acting on the
+    /// particles.
+    fn kick(&self, forces: &[Vector3D], velocity: &mut Vector3D, momentum: &mut Quaternion, dt: f64) {
+        let rotation = rotation_matrix(&self.orientation);
+        let mut force = Vector3D::zero();
+        let mut torque = Vector3D::zero();
+        for (i, body) in self.positions.iter().enumerate() {
+            let f = forces[self.start + i];
+            force += f;
+            torque += (rotation * body) ^ f;
+        }
+
+        *velocity += dt * force / self.mass;
+        let torque = self.momentum(&(rotation.transposed() * torque));
+        for k in 0..4 {
+            momentum[k] += dt * torque[k];
+        }
+    }
+
+    /// Free rotation around the principal axis `k` for a time `dt`
+    #[allow(clippy::similar_names)]
+    fn free_rotation(&mut self, k: usize, momentum: &mut Quaternion, dt: f64) {
+        if self.inertia[k] == 0.0 {
+            return;
+        }
+
+        let q = self.orientation;
+        let pq = permutation(k, &q);
+        let pp = permutation(k, momentum);
+        let zeta = dot4(momentum, &pq) / (4.0 * self.inertia[k]);
+        let (sin, cos) = f64::sin_cos(zeta * dt);
+        for i in 0..4 {
+            self.orientation[i] = cos * q[i] + sin * pq[i];
+            momentum[i] = cos * momentum[i] + sin * pp[i];
+        }
+    }
+
+    /// Rotate the body for a time `dt`, using the symmetric splitting of the
+    /// free rotation from NO_SQUISH.
+    fn rotate(&mut self, momentum: &mut Quaternion, dt: f64) {
+        self.free_rotation(2, momentum, 0.5 * dt);
+        self.free_rotation(1, momentum, 0.5 * dt);
+        self.free_rotation(0, momentum, dt);
+        self.free_rotation(1, momentum, 0.5 * dt);
+        self.free_rotation(2, momentum, 0.5 * dt);
+
+        let norm = f64::sqrt(dot4(&self.orientation, &self.orientation));
+        for i in 0..4 {
+            self.orientation[i] /= norm;
+        }
+    }
+
+    /// Set the positions of the particles in the `system` from the center of
+    /// mass position `com` and the orientation of the body.
+    fn set_positions(&self, system: &mut System, com: &Vector3D) {
+        let rotation = rotation_matrix(&self.orientation);
+        let positions = system.particles_mut().position;
+        for (i, body) in self.positions.iter().enumerate() {
+            positions[self.start + i] = com + rotation * body;
+        }
+    }
+
+    /// Set the velocities of the particles in the `system` from the center of
+    /// mass `velocity` and the conjugated angular `momentum`. This function
+    /// returns the virial of the constraint forces keeping the body rigid,
+    /// using the `forces` acting on the particles.
+    fn set_velocities(
+        &self,
+        system: &mut System,
+        velocity: &Vector3D,
+        momentum: &Quaternion,
+        forces: &[Vector3D],
+    ) -> Matrix3 {
+        let mut omega = Vector3D::zero();
+        for k in 0..3 {
+            if self.inertia[k] != 0.0 {
+                omega[k] = dot4(&permutation(k, &self.orientation), momentum) / (2.0 * self.inertia[k]);
+            }
+        }
+
+        let rotation = rotation_matrix(&self.orientation);
+        let mut virial = Matrix3::zero();
+        let particles = system.particles_mut();
+        let (velocities, masses) = (particles.velocity, particles.mass);
+        for (i, body) in self.positions.iter().enumerate() {
+            let i = self.start + i;
+            let position = rotation * body;
+            let rotational = rotation * (omega ^ body);
+            velocities[i] = velocity + rotational;
+            // The constraint forces cancel the contribution of the forces
+            // and of the rotational motion relative to the center of mass
+            virial -= forces[i].tensorial(&position) + masses[i] * rotational.tensorial(&rotational);
+        }
+        return virial;
+    }
+}
+
+/// NO_SQUISH integrator for rigid molecules [Miller2002].
+///
+/// This symplectic and time-reversible integrator propagates the center of
+/// mass and the orientation of rigid molecules, using a quaternion
+/// representation of the orientations. The forces on the particles are used
+/// to compute the total forces and torques acting on the molecules, and the
+/// velocities of the particles are set from the rigid body motion, so that
+/// the kinetic energy, the temperature and the thermostats work directly with
+/// the particles velocities. The geometry of each molecule is taken from its
+/// configuration at the beginning of the simulation.
+///
+/// Only the molecules matching a given hash can be treated as rigid, the other
+/// ones are then integrated with a velocity-Verlet integrator.
+///
+/// [Miller2002]: Miller, T. F. et al. J. Chem. Phys. 116, 8649 (2002).
+pub struct NoSquish {
+    /// Timestep for the integrator
+    timestep: f64,
+    /// Hash of the rigid molecules. `None` means all molecules.
+    hash: Option<MoleculeHash>,
+    /// Rigid bodies in the current simulation
+    bodies: Vec<RigidBody>,
+    /// Is each particle part of a rigid body?
+    rigid: Vec<bool>,
+    /// Storing the forces acting on the particles
+    forces: Vec<Vector3D>,
+}
+
+impl NoSquish {
+    /// Create a new integrator with a timestep of `timestep`, treating all
+    /// the molecules as rigid bodies.
+    pub fn new(timestep: f64) -> NoSquish {
+        NoSquish::with_molecule(timestep, None)
+    }
+
+    /// Create a new integrator with a timestep of `timestep`, treating the
+    /// molecules with the given `hash` as rigid bodies, or all the molecules
+    /// if `hash` is `None`.
+    pub fn with_molecule<H: Into<Option<MoleculeHash>>>(timestep: f64, hash: H) -> NoSquish {
+        NoSquish {
+            timestep: timestep,
+            hash: hash.into(),
+            bodies: Vec::new(),
+            rigid: Vec::new(),
+            forces: Vec::new(),
+        }
+    }
+
+    /// Get the list of rigid bodies in the `system`
+    fn bodies(&self, system: &System) -> Vec<RigidBody> {
+        let mut bodies = Vec::new();
+        for molecule in system.molecules() {
+            if let Some(hash) = self.hash {
+                if molecule.hash() != hash {
+                    continue;
+                }
+            }
+            bodies.push(RigidBody::new(system, molecule.start(), molecule.size()));
+        }
+        return bodies;
+    }
+}
+
+impl Integrator for NoSquish {
+    fn setup(&mut self, system: &System) {
+        self.bodies = self.bodies(system);
+        self.rigid = vec![false; system.size()];
+        for body in &self.bodies {
+            for i in 0..body.positions.len() {
+                self.rigid[body.start + i] = true;
+            }
+        }
+        self.forces = vec![Vector3D::zero(); system.size()];
+    }
+
+    fn integrate(&mut self, system: &mut System) {
+        let dt = self.timestep;
+
+        // Update velocities at t + ∆t/2 and positions at t + ∆t of the
+        // particles outside of rigid bodies
+        {
+            let particles = system.particles_mut();
+            let (positions, velocities, masses) = (particles.position, particles.velocity, particles.mass);
+            for i in 0..positions.len() {
+                if !self.rigid[i] {
+                    velocities[i] += 0.5 * dt * self.forces[i] / masses[i];
+                    positions[i] += velocities[i] * dt;
+                }
+            }
+        }
+
+        // Update the rigid bodies velocities and angular momenta at
+        // t + ∆t/2, and positions and orientations at t + ∆t
+        let mut momenta = Vec::with_capacity(self.bodies.len());
+        for body in &mut self.bodies {
+            let (com, mut velocity, mut momentum) = body.state(system);
+            body.kick(&self.forces, &mut velocity, &mut momentum, 0.5 * dt);
+            body.rotate(&mut momentum, dt);
+            body.set_positions(system, &(com + velocity * dt));
+            momenta.push((velocity, momentum));
+        }
+
+        self.forces = system.forces();
+
+        // Update velocities at t + ∆t
+        {
+            let particles = system.particles_mut();
+            let (velocities, masses) = (particles.velocity, particles.mass);
+            for i in 0..velocities.len() {
+                if !self.rigid[i] {
+                    velocities[i] += 0.5 * dt * self.forces[i] / masses[i];
+                }
+            }
+        }
+
+        let mut virial = Matrix3::zero();
+        for (body, (mut velocity, mut momentum)) in self.bodies.iter().zip(momenta) {
+            body.kick(&self.forces, &mut velocity, &mut momentum, 0.5 * dt);
+            virial += body.set_velocities(system, &velocity, &momentum, &self.forces);
+        }
+        system.constraints_virial = virial;
+    }
+
+    fn degrees_of_freedom(&self, system: &System) -> DegreesOfFreedom {
+        let frozen = self.bodies(system).iter().map(RigidBody::frozen_degrees_of_freedom).sum();
+        DegreesOfFreedom::Frozen(frozen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Propagator;
+    use crate::md::{MolecularDynamics, RescaleThermostat};
+    use lumol_core::{Molecule, Particle, UnitCell};
+    use lumol_core::{CoulombicPotential, PairRestriction};
+    use lumol_core::energy::{DampedShiftedForce, LennardJones, PairInteraction};
+    use lumol_core::sys::compute::{Compute, MolecularVirial};
+    use lumol_core::consts::K_BOLTZMANN;
+    use lumol_core::units;
+
+    use approx::{assert_relative_eq, assert_ulps_eq};
+    use soa_derive::soa_zip;
+
+    fn water() -> Molecule {
+        let mut molecule = Molecule::new(Particle::with_position("O", [0.0, 0.0, 0.0].into()));
+        molecule.add_particle_bonded_to(0, Particle::with_position("H", [0.757, 0.586, 0.0].into()));
+        molecule.add_particle_bonded_to(0, Particle::with_position("H", [-0.757, 0.586, 0.0].into()));
+        return molecule;
+    }
+
+    /// Two interacting SPC/E water molecules
+    fn water_dimer() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_molecule(water());
+        system.add_molecule(water());
+        for i in 3..6 {
+            system.particles_mut().position[i] += Vector3D::new(0.3, 2.9, 0.4);
+        }
+
+        for particle in system.particles_mut() {
+            if particle.name == "O" {
+                *particle.charge = -0.8476;
+            } else {
+                *particle.charge = 0.4238;
+            }
+        }
+
+        let lj = Box::new(LennardJones {
+            sigma: units::from(3.166, "A").unwrap(),
+            epsilon: units::from(0.6502, "kJ/mol").unwrap(),
+        });
+        system.set_pair_potential(("O", "O"), PairInteraction::new(lj, 8.0));
+
+        let mut dsf = DampedShiftedForce::new(8.0, 0.2);
+        dsf.set_restriction(PairRestriction::InterMolecular);
+        system.set_coulomb_potential(Box::new(dsf));
+
+        let velocities = system.particles_mut().velocity;
+        velocities[0] = [0.001, -0.002, 0.003].into();
+        velocities[1] = [-0.02, 0.01, 0.03].into();
+        velocities[2] = [0.01, 0.03, -0.02].into();
+        velocities[3] = [-0.003, 0.001, 0.002].into();
+        velocities[4] = [0.01, -0.02, 0.01].into();
+        velocities[5] = [0.02, 0.01, 0.01].into();
+        return system;
+    }
+
+    /// Check that the water molecules in the `system` have the geometry from
+    /// `water()`, and velocities compatible with rigid bodies motion
+    fn check_rigid(system: &System) {
+        let oh = Vector3D::new(0.757, 0.586, 0.0).norm();
+        let hh = 1.514;
+        for molecule in system.molecules() {
+            let start = molecule.start();
+            for &(i, j, expected) in &[(0, 1, oh), (0, 2, oh), (1, 2, hh)] {
+                let (i, j) = (start + i, start + j);
+                assert_ulps_eq!(system.distance(i, j), expected, epsilon = 1e-10);
+
+                let rij = system.nearest_image(i, j);
+                let vij = system.particles().velocity[i] - system.particles().velocity[j];
+                assert!(f64::abs(rij * vij) < 1e-12);
+            }
+        }
+    }
+
+    fn com_velocity(system: &System) -> Vector3D {
+        let mut total_mass = 0.0;
+        let mut velocity = Vector3D::zero();
+        for (&mass, com_velocity) in soa_zip!(system.particles(), [mass, velocity]) {
+            total_mass += mass;
+            velocity += mass * com_velocity;
+        }
+        return velocity / total_mass;
+    }
+
+    fn angular_momentum(system: &System) -> Vector3D {
+        let com = system.molecule(0).center_of_mass();
+        let mut angular = Vector3D::zero();
+        for (&mass, position, velocity) in soa_zip!(system.particles(), [mass, position, velocity]) {
+            angular += mass * ((position - com) ^ velocity);
+        }
+        return angular;
+    }
+
+    #[test]
+    fn quaternions() {
+        let rotation = Matrix3::rotation(&Vector3D::new(1.0, -2.0, 0.5), 2.4);
+        let q = quaternion(&rotation);
+        assert_ulps_eq!(dot4(&q, &q), 1.0, epsilon = 1e-12);
+        let matrix = rotation_matrix(&q);
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_ulps_eq!(matrix[i][j], rotation[i][j], epsilon = 1e-12);
+            }
+        }
+
+        let v = Vector3D::new(0.3, 1.2, -0.7);
+        let product = pure_product(&q, &v);
+        for k in 0..3 {
+            assert_ulps_eq!(dot4(&permutation(k, &q), &product), v[k], epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn principal_axes() {
+        let matrix = Matrix3::new([
+            [2.0, -1.0, 0.5],
+            [-1.0, 3.0, 0.2],
+            [0.5, 0.2, 1.0],
+        ]);
+        let (values, vectors) = super::principal_axes(&matrix);
+        for k in 0..3 {
+            let vector = Vector3D::new(vectors[0][k], vectors[1][k], vectors[2][k]);
+            assert_ulps_eq!(vector.norm(), 1.0, epsilon = 1e-12);
+            assert!((matrix * vector - values[k] * vector).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn degrees_of_freedom() {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_molecule(water());
+
+        let mut co2 = Molecule::new(Particle::with_position("O", [0.0, 5.0, 0.0].into()));
+        co2.add_particle_bonded_to(0, Particle::with_position("C", [1.16, 5.0, 0.0].into()));
+        co2.add_particle_bonded_to(1, Particle::with_position("O", [2.32, 5.0, 0.0].into()));
+        system.add_molecule(co2);
+
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", [5.0, 0.0, 0.0].into())));
+
+        // 3 for water, 4 for CO2, 0 for argon
+        let integrator = NoSquish::new(1.0);
+        assert_eq!(integrator.degrees_of_freedom(&system), DegreesOfFreedom::Frozen(7));
+
+        let integrator = NoSquish::with_molecule(1.0, system.molecule(1).hash());
+        assert_eq!(integrator.degrees_of_freedom(&system), DegreesOfFreedom::Frozen(4));
+    }
+
+    #[test]
+    fn free_rotation() {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_molecule(water());
+        system.particles_mut().velocity[0] = [0.001, -0.002, 0.003].into();
+        system.particles_mut().velocity[1] = [-0.02, 0.01, 0.03].into();
+        system.particles_mut().velocity[2] = [0.01, 0.03, -0.02].into();
+
+        let mut integrator = NoSquish::new(1.0);
+        integrator.setup(&system);
+        // Remove the non-rigid part of the velocities
+        integrator.integrate(&mut system);
+
+        let oh = system.distance(0, 1);
+        let hh = system.distance(1, 2);
+        let com = system.molecule(0).center_of_mass();
+        let velocity = com_velocity(&system);
+        let kinetic = system.kinetic_energy();
+        let angular = angular_momentum(&system);
+
+        for _ in 0..100 {
+            integrator.integrate(&mut system);
+        }
+
+        assert_ulps_eq!(system.distance(0, 1), oh, epsilon = 1e-10);
+        assert_ulps_eq!(system.distance(0, 2), oh, epsilon = 1e-10);
+        assert_ulps_eq!(system.distance(1, 2), hh, epsilon = 1e-10);
+
+        assert!((system.molecule(0).center_of_mass() - com - 100.0 * velocity).norm() < 1e-10);
+        assert!((angular_momentum(&system) - angular).norm() < 1e-10);
+        assert!(f64::abs(system.kinetic_energy() - kinetic) / kinetic < 1e-4);
+    }
+
+    #[test]
+    fn energy_conservation() {
+        let mut system = water_dimer();
+        let mut integrator = NoSquish::new(1.0);
+        integrator.setup(&system);
+        // Remove the non-rigid part of the velocities
+        integrator.integrate(&mut system);
+
+        let energy = system.total_energy();
+        let kinetic = system.kinetic_energy();
+        let mut kinetic_range = (kinetic, kinetic);
+        for _ in 0..1000 {
+            integrator.integrate(&mut system);
+            assert!(f64::abs(system.total_energy() - energy) < 1e-2 * kinetic);
+
+            let current = system.kinetic_energy();
+            kinetic_range.0 = f64::min(kinetic_range.0, current);
+            kinetic_range.1 = f64::max(kinetic_range.1, current);
+        }
+
+        // The torques exchange a significant amount of energy between the
+        // kinetic and potential energy
+        assert!(kinetic_range.1 - kinetic_range.0 > 0.5 * kinetic);
+        check_rigid(&system);
+    }
+
+    #[test]
+    fn pressure() {
+        let mut system = water_dimer();
+        let mut integrator = NoSquish::new(1.0);
+        system.simulated_degrees_of_freedom = integrator.degrees_of_freedom(&system);
+        integrator.setup(&system);
+
+        for _ in 0..10 {
+            integrator.integrate(&mut system);
+
+            // With rigid molecules, the pressure only depends on the motion
+            // of the molecules centers of mass and the molecular virial
+            let mut kinetic = 0.0;
+            for molecule in system.molecules() {
+                let mut mass = 0.0;
+                let mut momentum = Vector3D::zero();
+                for i in molecule.indexes() {
+                    mass += system.particles().mass[i];
+                    momentum += system.particles().mass[i] * system.particles().velocity[i];
+                }
+                kinetic += momentum.norm2() / mass;
+            }
+            let virial = MolecularVirial.compute(&system).trace();
+            let expected = (kinetic + virial) / (3.0 * system.volume());
+            assert_relative_eq!(system.pressure(), expected, max_relative = 1e-8);
+        }
+    }
+
+    #[test]
+    fn thermostat() {
+        let mut system = water_dimer();
+        let mut md = MolecularDynamics::from_integrator(Box::new(NoSquish::new(1.0)));
+        md.set_thermostat(Box::new(RescaleThermostat::with_tolerance(300.0, 0.0)));
+
+        system.simulated_degrees_of_freedom = md.degrees_of_freedom(&system);
+        // 6 degrees of freedom for each rigid molecule
+        assert_eq!(system.degrees_of_freedom(), 12);
+
+        md.setup(&system);
+        for _ in 0..100 {
+            md.propagate(&mut system);
+            assert_ulps_eq!(system.temperature(), 300.0, epsilon = 1e-9);
+            let kinetic = 0.5 * 12.0 * K_BOLTZMANN * 300.0;
+            assert_ulps_eq!(system.kinetic_energy(), kinetic, epsilon = 1e-15);
+        }
+
+        check_rigid(&system);
+    }
+}
@@ -16,6 +16,7 @@ use log_once::{warn_once, error_once};
 
 use super::Output;
 use lumol_core::{units, System};
+use lumol_core::sys::compute::{Compute, LambdaDerivative};
 
 /// Possible causes of error when using a custom output
 #[derive(Debug)]
@@ -192,6 +193,8 @@ fn get_output_context(system: &System) -> Context<'_> {
                     "stress.xy" => Some(system.stress()[0][1]),
                     "stress.xz" => Some(system.stress()[0][2]),
                     "stress.yz" => Some(system.stress()[1][2]),
+                    "dudl.vdw" => Some(LambdaDerivative::Vdw.compute(system)),
+                    "dudl.coulomb" => Some(LambdaDerivative::Coulomb.compute(system)),
                     _ => None,
                 }
             }
@@ -0,0 +1,103 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use log::error;
+
+use super::Output;
+use lumol_core::System;
+use lumol_core::units;
+use lumol_core::sys::compute::{Compute, EnergyDecomposition, EnergyGroups};
+
+/// The `EnergyDecompositionOutput` writes all the components of the potential
+/// energy of the system to a text file, organized as: `step pairs pairs_tail
+/// bonds angles dihedrals impropers coulomb global`, followed by the pair
+/// energy between all the pairs of groups of particles.
+///
+/// Only the pair interactions are decomposed between groups: the energy
+/// between two groups does not include their electrostatic interactions, which
+/// are only part of the total `coulomb` energy.
+///
+/// The groups are taken from the system when the output is set up. Groups
+/// appearing later in the simulation are not written, and groups disappearing
+/// from the system have a zero energy.
+pub struct EnergyDecompositionOutput {
+    file: BufWriter<File>,
+    path: PathBuf,
+    decomposition: EnergyDecomposition,
+    pairs: Vec<(String, String)>,
+}
+
+impl EnergyDecompositionOutput {
+    /// Create a new `EnergyDecompositionOutput` writing to `filename`, and
+    /// decomposing the pair energy between the given `groups`. The file is
+    /// replaced if it already exists.
+    pub fn new<P: AsRef<Path>>(filename: P, groups: EnergyGroups) -> Result<EnergyDecompositionOutput, io::Error> {
+        Ok(EnergyDecompositionOutput {
+            file: BufWriter::new(File::create(filename.as_ref())?),
+            path: filename.as_ref().to_owned(),
+            decomposition: EnergyDecomposition { groups: groups },
+            pairs: Vec::new(),
+        })
+    }
+}
+
+impl Output for EnergyDecompositionOutput {
+    fn setup(&mut self, system: &System) {
+        let groups = self.decomposition.compute(system).groups;
+        self.pairs.clear();
+        for (i, a) in groups.iter().enumerate() {
+            for b in &groups[i..] {
+                self.pairs.push((a.clone(), b.clone()));
+            }
+        }
+
+        let pairs = self.pairs.iter().map(|(a, b)| format!(" {}-{}", a, b)).collect::<String>();
+        writeln_or_log!(self, "# Energy decomposition of the simulation (kJ/mol)");
+        writeln_or_log!(self, "# Step Pairs PairsTail Bonds Angles Dihedrals Impropers Coulomb Global{}", pairs);
+    }
+
+    fn write(&mut self, system: &System) {
+        let components = self.decomposition.compute(system);
+        let to_kj_mol = |energy| units::to(energy, "kJ/mol").expect("bad unit");
+
+        let mut line = format!(
+            "{} {} {} {} {} {} {} {} {}",
+            system.step,
+            to_kj_mol(components.pairs),
+            to_kj_mol(components.pairs_tail),
+            to_kj_mol(components.bonds),
+            to_kj_mol(components.angles),
+            to_kj_mol(components.dihedrals),
+            to_kj_mol(components.impropers),
+            to_kj_mol(components.coulomb),
+            to_kj_mol(components.global),
+        );
+        for (a, b) in &self.pairs {
+            let energy = components.pairs_between_groups(a, b).unwrap_or(0.0);
+            line += &format!(" {}", to_kj_mol(energy));
+        }
+        writeln_or_log!(self, "{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::test_output;
+
+    #[test]
+    fn energy_decomposition() {
+        test_output(
+            |path| Box::new(EnergyDecompositionOutput::new(path, EnergyGroups::Particles).unwrap()),
+            "# Energy decomposition of the simulation (kJ/mol)
+            # Step Pairs PairsTail Bonds Angles Dihedrals Impropers Coulomb Global F-F
+            42 1.5000000000000027 0 0 0 0 0 0 0 1.5000000000000027
+            ",
+        );
+    }
+}
@@ -40,6 +40,9 @@ pub use self::stress::StressOutput;
 mod energy;
 pub use self::energy::EnergyOutput;
 
+mod decomposition;
+pub use self::decomposition::EnergyDecompositionOutput;
+
 mod custom;
 pub use self::custom::{CustomOutput, CustomOutputError};
 
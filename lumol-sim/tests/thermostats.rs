@@ -2,10 +2,12 @@
 // Copyright (C) Lumol's contributors — BSD license
 
 use lumol_core::{Vector3D, Particle, Molecule, System, UnitCell};
+use lumol_core::{Harmonic, NullPotential, PairInteraction};
 use lumol_core::consts::K_BOLTZMANN;
+use lumol_core::units;
 
 use lumol_sim::{BoltzmannVelocities, InitVelocities};
-use lumol_sim::md::{Integrator, VelocityVerlet};
+use lumol_sim::md::{Integrator, VelocityVerlet, DrudeVelocityVerlet};
 use lumol_sim::md::{Thermostat, RescaleThermostat, BerendsenThermostat, CSVRThermostat};
 
 use approx::{assert_ulps_eq, assert_relative_eq};
@@ -115,3 +117,80 @@ fn csvr_thermostat() {
     let expected = dof * (K_BOLTZMANN * temperature) * (K_BOLTZMANN * temperature) / 2.0;
     assert_relative_eq!(variance, expected, epsilon=1e-3);
 }
+
+// An ideal gas of Drude oscillators
+fn drude_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(20.0));
+
+    for i in 0..10 {
+        for j in 0..10 {
+            for k in 0..5 {
+                let position = Vector3D::new(i as f64 * 2.0, j as f64 * 2.0, k as f64 * 4.0);
+                let mut core = Particle::new("Ar");
+                core.position = position;
+                let mut drude = Particle::new("DAr");
+                drude.position = position + Vector3D::new(0.05, 0.0, 0.0);
+                drude.mass = 0.4;
+
+                let mut molecule = Molecule::new(core);
+                molecule.add_particle_bonded_to(0, drude);
+                system.add_molecule(molecule);
+            }
+        }
+    }
+
+    system.add_drude_particles("DAr");
+    system.set_bond_potential(("Ar", "DAr"), Box::new(Harmonic {
+        k: units::from(1000.0, "kcal/mol/A^2").unwrap(),
+        x0: 0.0,
+    }));
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(Box::new(NullPotential), 5.0));
+    system.set_pair_potential(("Ar", "DAr"), PairInteraction::new(Box::new(NullPotential), 5.0));
+    system.set_pair_potential(("DAr", "DAr"), PairInteraction::new(Box::new(NullPotential), 5.0));
+
+    let mut velocities = BoltzmannVelocities::new(300.0);
+    velocities.init(&mut system);
+    return system;
+}
+
+fn drude_temperature(system: &System) -> f64 {
+    let pairs = system.drude_pairs();
+    let masses = system.particles().mass;
+    let velocities = system.particles().velocity;
+    let mut kinetic = 0.0;
+    for &(core, drude) in &pairs {
+        let reduced_mass = masses[core] * masses[drude] / (masses[core] + masses[drude]);
+        kinetic += 0.5 * reduced_mass * (velocities[drude] - velocities[core]).norm2();
+    }
+    return 2.0 * kinetic / (3.0 * pairs.len() as f64 * K_BOLTZMANN);
+}
+
+#[test]
+fn drude_thermostats() {
+    let mut system = drude_system();
+    assert_eq!(system.drude_pairs().len(), 500);
+    assert_eq!(system.degrees_of_freedom(), 1500);
+
+    let timestep = units::from(0.5, "fs").unwrap();
+    let mut integrator = DrudeVelocityVerlet::new(timestep, 250.0, 10.0, 1.0, 20.0);
+    integrator.setup(&system);
+    // equilibrate
+    for _ in 0..1000 {
+        integrator.integrate(&mut system);
+    }
+
+    // accumulate
+    let mut temperatures = Vec::new();
+    let mut drude_temperatures = Vec::new();
+    for _ in 0..2000 {
+        integrator.integrate(&mut system);
+        temperatures.push(system.temperature());
+        drude_temperatures.push(drude_temperature(&system));
+    }
+
+    let mean = temperatures.iter().sum::<f64>() / temperatures.len() as f64;
+    assert_relative_eq!(mean, 250.0, max_relative = 0.05);
+
+    let mean = drude_temperatures.iter().sum::<f64>() / drude_temperatures.len() as f64;
+    assert!(mean < 10.0);
+}